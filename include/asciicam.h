@@ -0,0 +1,55 @@
+/* C interface to the asciicam conversion core, build with `--features ffi`. */
+
+#ifndef ASCIICAM_H
+#define ASCIICAM_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define ASCIICAM_FORMAT_MJPEG 0
+#define ASCIICAM_FORMAT_GRAY8 1
+#define ASCIICAM_FORMAT_RGB8 2
+
+typedef struct {
+    /* size of the output in characters */
+    uint32_t columns;
+    uint32_t rows;
+    /* one of the ASCIICAM_FORMAT_* constants */
+    uint32_t pixel_format;
+    /* flip the image horizontally */
+    bool mirror;
+    /* UTF-8 charset ordered dark to bright, or NULL for the default */
+    const char *charset;
+} asciicam_options;
+
+typedef struct {
+    /* NUL terminated, free with asciicam_free */
+    char *data;
+    size_t len;
+} asciicam_output;
+
+/* 80x24, MJPEG input, mirrored, default charset */
+asciicam_options asciicam_default_options(void);
+
+/* Converts len bytes of image data in buf to ascii art, one line per row
+ * separated by '\n'. width and height are ignored for MJPEG input.
+ * Returns 0 on success, -1 on failure (see asciicam_last_error). */
+int asciicam_convert(const uint8_t *buf, size_t len, uint32_t width,
+                     uint32_t height, const asciicam_options *opts,
+                     asciicam_output *out);
+
+void asciicam_free(asciicam_output *out);
+
+/* Description of the last error on this thread, or NULL */
+const char *asciicam_last_error(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
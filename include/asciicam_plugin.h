@@ -0,0 +1,88 @@
+/* Native filters and sources asciicam loads at startup when built with
+ * `--features plugins`: every shared library in
+ * $XDG_CONFIG_HOME/asciicam/plugins that exports asciicam_plugin_init. */
+
+#ifndef ASCIICAM_PLUGIN_H
+#define ASCIICAM_PLUGIN_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* bumped whenever anything below changes, plugins built against another
+ * version are skipped */
+#define ASCIICAM_PLUGIN_ABI 1
+
+/* the same as in asciicam.h */
+#ifndef ASCIICAM_FORMAT_MJPEG
+#define ASCIICAM_FORMAT_MJPEG 0
+#define ASCIICAM_FORMAT_GRAY8 1
+#define ASCIICAM_FORMAT_RGB8 2
+#endif
+
+typedef struct {
+    /* the brightness the characters are picked from, width * height bytes */
+    uint8_t *luma;
+    /* width * height * 3 bytes of red, green and blue, NULL unless the
+     * picture is shown in color */
+    uint8_t *rgb;
+    uint32_t width;
+    uint32_t height;
+} asciicam_image;
+
+/* A filter, used with --filter plugin=<name>[:<args>]. Its state is only
+ * ever used from one thread at a time, though not always the same one. */
+typedef struct {
+    const char *name;
+    /* state for one filter chain, args is "" when none are given. NULL
+     * when the args are no good */
+    void *(*create)(const char *args);
+    /* filters image in place, returns 0, or -1 to leave the frame as it
+     * was left */
+    int (*apply)(void *state, asciicam_image *image);
+    void (*destroy)(void *state);
+} asciicam_filter_plugin;
+
+typedef struct {
+    /* one of the ASCIICAM_FORMAT_* constants */
+    uint32_t pixel_format;
+    /* ignored for MJPEG */
+    uint32_t width;
+    uint32_t height;
+    /* owned by the plugin, only read until the next call */
+    const uint8_t *data;
+    size_t len;
+} asciicam_frame;
+
+/* A source, used with --input plugin:<name>[:<args>]. Its state is only
+ * ever used from one thread at a time, though not always the same one. */
+typedef struct {
+    const char *name;
+    /* NULL when the source can't be opened */
+    void *(*open)(const char *args);
+    /* blocks until the next frame, returns 0, or -1 when there are no more */
+    int (*next_frame)(void *state, asciicam_frame *frame);
+    void (*close)(void *state);
+} asciicam_source_plugin;
+
+typedef struct {
+    /* ASCIICAM_PLUGIN_ABI */
+    uint32_t abi_version;
+    const asciicam_filter_plugin *filters;
+    size_t filter_count;
+    const asciicam_source_plugin *sources;
+    size_t source_count;
+} asciicam_plugin;
+
+/* What a plugin exports. The plugin is never unloaded, so this can point
+ * to static data. */
+const asciicam_plugin *asciicam_plugin_init(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
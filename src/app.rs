@@ -0,0 +1,1466 @@
+//! The interactive terminal viewer, configured through [`AsciicamBuilder`].
+
+use crate::capture::{
+    default_presets_path, list_devices, CameraControl, CaptureSource, CaptureThread, Captured,
+    Controls, Deinterlace, DeinterlacedSource, Frame, InterpolatedSource, IoMode, Presets,
+    ReconnectingCamera, SyntheticSource, EXPOSURE, FOCUS, GAIN, PAN, TILT, WHITE_BALANCE, ZOOM,
+};
+use crate::convert::{LevelWindow, LumaWeights};
+use crate::exposure::{AutoExposure, MeterRegion};
+use crate::filter::{ChromaKey, Filter, Tint};
+use crate::metrics::Metrics;
+use crate::render::{
+    parse_charset, render_frame_with, ColorMode, FitMode, Grid, RenderBuffers, RenderMode,
+    RenderOptions, Renderer, CHARSETS, DEFAULT_CHARSET, NIGHT_CHARSET,
+};
+#[cfg(feature = "script")]
+use crate::script::Script;
+use crate::sink::{BackgroundSink, Fanout, Sink, TerminalSink, WriterSink};
+use crate::stats::{DropStats, RollingTimings, StageTimings, StatsLog};
+use crossterm::{
+    event::{Event, EventStream, KeyCode, KeyEvent, KeyModifiers},
+    terminal,
+};
+use eyre::{eyre, Report, Result};
+use futures_util::StreamExt;
+use std::mem;
+use std::ops::ControlFlow;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+use tracing::{debug, info};
+
+const ZOOM_STEP: f32 = 1.25;
+const MAX_ZOOM: f32 = 8.0;
+// seconds skipped by the arrow keys when playing back a video
+const SEEK_STEP: f64 = 5.0;
+// the picture in picture inset is this fraction of the screen in each
+// direction
+const PIP_SCALE: u32 = 4;
+// cells between the inset and the edges of the screen
+const PIP_MARGIN: u32 = 1;
+// how often the pace of the terminal is looked at to adapt the quality
+const QUALITY_INTERVAL: Duration = Duration::from_secs(1);
+// the coarsest the picture gets when the terminal can't keep up, a cell
+// stretched over 4 x 4
+const MAX_QUALITY_SCALE: u32 = 4;
+// how long messages like a changed camera control stay on screen
+const OSD_DURATION: Duration = Duration::from_secs(2);
+
+/// Which corner the picture in picture inset sits in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    #[default]
+    BottomRight,
+}
+
+impl Corner {
+    /// The next corner clockwise.
+    pub fn next(self) -> Self {
+        match self {
+            Corner::TopLeft => Corner::TopRight,
+            Corner::TopRight => Corner::BottomRight,
+            Corner::BottomRight => Corner::BottomLeft,
+            Corner::BottomLeft => Corner::TopLeft,
+        }
+    }
+
+    /// Top left cell of an inset of `inset` cells in this corner of a grid
+    /// of `size` cells.
+    fn position(self, size: (u32, u32), inset: (u32, u32)) -> (u32, u32) {
+        let right = size.0.saturating_sub(inset.0 + PIP_MARGIN);
+        let bottom = size.1.saturating_sub(inset.1 + PIP_MARGIN);
+
+        match self {
+            Corner::TopLeft => (PIP_MARGIN, PIP_MARGIN),
+            Corner::TopRight => (right, PIP_MARGIN),
+            Corner::BottomLeft => (PIP_MARGIN, bottom),
+            Corner::BottomRight => (right, bottom),
+        }
+    }
+}
+
+impl FromStr for Corner {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "top-left" => Ok(Corner::TopLeft),
+            "top-right" => Ok(Corner::TopRight),
+            "bottom-left" => Ok(Corner::BottomLeft),
+            "bottom-right" => Ok(Corner::BottomRight),
+            _ => Err(eyre!(
+                "Unknown corner '{s}', expected one of top-left, top-right, bottom-left, bottom-right"
+            )),
+        }
+    }
+}
+
+/// The settings for the right half of a side by side comparison, see
+/// [`AsciicamBuilder::compare`]. Whatever is left out is the same as on the
+/// left.
+#[derive(Debug, Clone, Default)]
+pub struct Comparison {
+    pub render_mode: Option<RenderMode>,
+    pub charset: Option<String>,
+    /// The filters, replacing the ones on the left rather than adding to
+    /// them, so no filters compares against the unfiltered picture.
+    pub filters: Vec<Filter>,
+}
+
+/// Collects and validates the settings for an [`App`].
+///
+/// ```no_run
+/// use asciicam::app::AsciicamBuilder;
+/// use asciicam::render::FitMode;
+///
+/// # fn main() -> eyre::Result<()> {
+/// AsciicamBuilder::new()
+///     .device(0)
+///     .resolution(640, 480)
+///     .charset(" .:-=+*#%@")
+///     .fit_mode(FitMode::Fit)
+///     .build()?
+///     .run()
+/// # }
+/// ```
+pub struct AsciicamBuilder {
+    device: usize,
+    resolution: Option<(u32, u32)>,
+    io_mode: IoMode,
+    black_level: u8,
+    window: Option<LevelWindow>,
+    deinterlace: Option<Deinterlace>,
+    interpolate: Option<Duration>,
+    night: bool,
+    charset: Option<String>,
+    render_mode: RenderMode,
+    color_mode: ColorMode,
+    options: RenderOptions,
+    source: Option<Box<dyn CaptureSource + Send>>,
+    open_timeout: Duration,
+    pip: Option<Box<dyn CaptureSource>>,
+    pip_corner: Corner,
+    compare: Option<Comparison>,
+    camera_controls: Vec<(CameraControl, Option<i64>)>,
+    presets: Option<PathBuf>,
+    auto_exposure: Option<u8>,
+    meter_region: Option<MeterRegion>,
+    sinks: Fanout,
+    adaptive_quality: bool,
+    show_stats: bool,
+    metrics: Option<Metrics>,
+    stats_log: Option<StatsLog>,
+    #[cfg(feature = "script")]
+    script: Option<Script>,
+}
+
+impl Default for AsciicamBuilder {
+    fn default() -> Self {
+        Self {
+            device: 0,
+            resolution: None,
+            io_mode: IoMode::default(),
+            black_level: 0,
+            window: None,
+            deinterlace: None,
+            interpolate: None,
+            night: false,
+            charset: None,
+            render_mode: RenderMode::Ascii,
+            color_mode: ColorMode::default(),
+            options: RenderOptions::default(),
+            source: None,
+            open_timeout: Duration::ZERO,
+            pip: None,
+            pip_corner: Corner::default(),
+            compare: None,
+            camera_controls: Vec::new(),
+            presets: None,
+            auto_exposure: None,
+            meter_region: None,
+            sinks: Fanout::new(),
+            adaptive_quality: true,
+            show_stats: false,
+            metrics: None,
+            stats_log: None,
+            #[cfg(feature = "script")]
+            script: None,
+        }
+    }
+}
+
+impl AsciicamBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The v4l device to capture from, `/dev/video{index}`. Defaults to 0.
+    pub fn device(mut self, index: usize) -> Self {
+        self.device = index;
+        self
+    }
+
+    /// The resolution to ask the device for. Defaults to whatever the
+    /// device is currently set to.
+    pub fn resolution(mut self, width: u32, height: u32) -> Self {
+        self.resolution = Some((width, height));
+        self
+    }
+
+    /// How the device hands over its buffers. Defaults to
+    /// [`IoMode::Mmap`], which every driver supports.
+    pub fn io_mode(mut self, io_mode: IoMode) -> Self {
+        self.io_mode = io_mode;
+        self
+    }
+
+    /// What a raw sensor reads in the dark, see
+    /// [`Camera::set_black_level`](crate::Camera::set_black_level).
+    /// Defaults to 0.
+    pub fn black_level(mut self, black_level: u8) -> Self {
+        self.black_level = black_level;
+        self
+    }
+
+    /// The sensor values mapped onto black to white for sensors with more
+    /// than 8 bits per pixel, see
+    /// [`Camera::set_window`](crate::Camera::set_window). Defaults to the
+    /// whole range of the sensor.
+    pub fn window(mut self, window: LevelWindow) -> Self {
+        self.window = Some(window);
+        self
+    }
+
+    /// Deinterlaces the frames of the main source, the camera or the one
+    /// given with [`source`](AsciicamBuilder::source).
+    pub fn deinterlace(mut self, mode: Deinterlace) -> Self {
+        self.deinterlace = Some(mode);
+        self
+    }
+
+    /// Fades from one frame of the main source into the next over `window`,
+    /// with frames made up in between, for cameras that only manage a few
+    /// frames a second in low light. See [`InterpolatedSource`].
+    pub fn interpolate(mut self, window: Duration) -> Self {
+        self.interpolate = Some(window);
+        self
+    }
+
+    /// The characters to map pixels onto, ordered from dark to bright.
+    pub fn charset(mut self, charset: &str) -> Self {
+        self.charset = Some(charset.to_string());
+        self
+    }
+
+    pub fn render_mode(mut self, render_mode: RenderMode) -> Self {
+        self.render_mode = render_mode;
+        self
+    }
+
+    pub fn color_mode(mut self, color_mode: ColorMode) -> Self {
+        self.color_mode = color_mode;
+        self
+    }
+
+    pub fn fit_mode(mut self, fit: FitMode) -> Self {
+        self.options.fit = fit;
+        self
+    }
+
+    /// Adds a filter, filters are applied in the order they are added and
+    /// can be rotated through at runtime with `r`, see
+    /// [`FilterChain`](crate::filter::FilterChain).
+    pub fn filter(mut self, filter: Filter) -> Self {
+        self.options.filters.push(filter);
+        self
+    }
+
+    /// Makes a dark room usable without touching the camera's controls:
+    /// every pixel is multiplied by `gain` before the other filters, and
+    /// unless a charset is given the darkest levels are left blank with
+    /// [`NIGHT_CHARSET`], so the noise that comes up with them doesn't show.
+    pub fn night(mut self, gain: f32) -> Self {
+        self.options.filters.insert(0, Filter::Gain(gain));
+        self.night = true;
+        self
+    }
+
+    /// Whether to flip the image horizontally. Defaults to true.
+    pub fn mirror(mut self, mirror: bool) -> Self {
+        self.options.mirror = mirror;
+        self
+    }
+
+    /// How long to keep retrying when the v4l device is busy or not there
+    /// yet. Defaults to giving up right away.
+    pub fn open_timeout(mut self, timeout: Duration) -> Self {
+        self.open_timeout = timeout;
+        self
+    }
+
+    /// Captures from `source` instead of a v4l device, `device` and
+    /// `resolution` are ignored. It is captured from on a thread of its
+    /// own.
+    pub fn source(mut self, source: impl CaptureSource + Send + 'static) -> Self {
+        self.source = Some(Box::new(source));
+        self
+    }
+
+    /// Draws `source` small in a corner over the main picture. It is asked
+    /// for a frame every time the main source delivers one, so a live source
+    /// should be wrapped in a [`BackgroundSource`](crate::capture::BackgroundSource)
+    /// to not hold up the main one.
+    pub fn pip(mut self, source: impl CaptureSource + 'static) -> Self {
+        self.pip = Some(Box::new(source));
+        self
+    }
+
+    /// The corner the picture in picture inset starts in. Defaults to the
+    /// bottom right.
+    pub fn pip_corner(mut self, corner: Corner) -> Self {
+        self.pip_corner = corner;
+        self
+    }
+
+    /// Splits the picture in two, the left half rendered with the regular
+    /// settings and the right half with `comparison`, to tune settings by
+    /// looking at both.
+    pub fn compare(mut self, comparison: Comparison) -> Self {
+        self.compare = Some(comparison);
+        self
+    }
+
+    /// Sets a control of the camera, like [`FOCUS`], once it is open, or
+    /// turns its automatic mode back on for `None`. Fails when the camera
+    /// doesn't have the control, and does nothing for other sources.
+    ///
+    /// A camera without [`WHITE_BALANCE`] gets the colors balanced in
+    /// software instead, like [`gray_world`](AsciicamBuilder::gray_world).
+    pub fn camera_control(mut self, control: CameraControl, value: Option<i64>) -> Self {
+        self.camera_controls.push((control, value));
+        self
+    }
+
+    /// The file camera position presets are saved to and recalled from,
+    /// see [`Presets`]. Defaults to [`default_presets_path`].
+    pub fn presets(mut self, path: impl Into<PathBuf>) -> Self {
+        self.presets = Some(path.into());
+        self
+    }
+
+    /// Balances the colors in software, so they don't come out orange under
+    /// warm lights, see [`gray_world`](crate::filter::gray_world).
+    pub fn gray_world(mut self, gray_world: bool) -> Self {
+        self.options.gray_world = gray_world;
+        self
+    }
+
+    /// Weights to turn the colors of a color source into brightness with,
+    /// instead of the decoder's.
+    pub fn luma(mut self, weights: LumaWeights) -> Self {
+        self.options.luma = Some(weights);
+        self
+    }
+
+    /// Keeps the character of a cell until its brightness is more than
+    /// `margin`, out of 255, past it, so cells on the edge between two
+    /// characters don't flicker, see [`CharLut::hold`](crate::CharLut::hold).
+    pub fn hysteresis(mut self, margin: u8) -> Self {
+        self.options.hysteresis = Some(margin);
+        self
+    }
+
+    /// Holds the picture of a handheld or clip-on camera still, cropping a
+    /// little off every side to have room to move it, see
+    /// [`Stabilizer`](crate::stabilize::Stabilizer).
+    pub fn stabilize(mut self, stabilize: bool) -> Self {
+        self.options.stabilize = stabilize;
+        self
+    }
+
+    /// Replaces everything of about one color, like a green screen, with a
+    /// background, see [`ChromaKey`].
+    pub fn chroma_key(mut self, key: ChromaKey) -> Self {
+        self.options.chroma_key = Some(key);
+        self
+    }
+
+    /// Lays a color cast over the picture in the color modes, cycled
+    /// through at runtime with `h`.
+    pub fn tint(mut self, tint: Tint) -> Self {
+        self.options.tint = Some(tint);
+        self
+    }
+
+    /// Shows the picture in the green of an old monochrome monitor.
+    pub fn phosphor(mut self, phosphor: bool) -> Self {
+        self.options.phosphor = phosphor;
+        self
+    }
+
+    /// Averages the pixels under every cell instead of picking one, which
+    /// keeps edges from shimmering, see
+    /// [`Resizer::resize_area`](crate::convert::Resizer::resize_area).
+    pub fn area(mut self, area: bool) -> Self {
+        self.options.area = area;
+        self
+    }
+
+    /// Averages the pixels under every cell in linear light instead of
+    /// picking one, slower but keeping thin things like hair and text, see
+    /// [`Resizer::resize_linear`](crate::convert::Resizer::resize_linear).
+    pub fn linear(mut self, linear: bool) -> Self {
+        self.options.linear = linear;
+        self
+    }
+
+    /// Draws the picture coarser while the terminal can't keep up with the
+    /// camera, like over a slow ssh connection, instead of falling further
+    /// and further behind it, and fine again once it can. Defaults to true.
+    pub fn adaptive_quality(mut self, adaptive: bool) -> Self {
+        self.adaptive_quality = adaptive;
+        self
+    }
+
+    /// Shows how many frames were dropped or late in the status bar, see
+    /// [`App::drop_stats`].
+    pub fn show_stats(mut self, show: bool) -> Self {
+        self.show_stats = show;
+        self
+    }
+
+    /// Reports every frame, the clients of the sinks, dropped frames and
+    /// reconnects of the camera to a Prometheus endpoint.
+    pub fn metrics(mut self, metrics: Metrics) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Appends the frame rate, CPU time, bytes written to the terminal and
+    /// motion to `log` every second.
+    pub fn stats_log(mut self, log: StatsLog) -> Self {
+        self.stats_log = Some(log);
+        self
+    }
+
+    /// Adjusts the camera's exposure and gain to keep the mean brightness
+    /// of the picture at `target`, for cameras whose own automatic exposure
+    /// is missing or no good, see [`AutoExposure`]. Does nothing for other
+    /// sources.
+    pub fn auto_exposure(mut self, target: u8) -> Self {
+        self.auto_exposure = Some(target);
+        self
+    }
+
+    /// Has the [`auto_exposure`](AsciicamBuilder::auto_exposure) measure
+    /// the brightness in `region` of the picture as it is shown, mirrored or
+    /// not, rather than all of it.
+    pub fn meter_region(mut self, region: MeterRegion) -> Self {
+        self.meter_region = Some(region);
+        self
+    }
+
+    /// Also sends every frame to `sink`, next to drawing it in the terminal
+    /// or handing it to [`App::run_with`].
+    pub fn sink(mut self, sink: impl Sink + 'static) -> Self {
+        self.sinks.push(sink);
+        self
+    }
+
+    /// Runs `script` on every frame, after the picture is mapped onto the
+    /// cells and before the inset, the comparison and the OSD are drawn.
+    #[cfg(feature = "script")]
+    pub fn script(mut self, script: Script) -> Self {
+        self.script = Some(script);
+        self
+    }
+
+    /// Validates the settings and opens the capture source.
+    pub fn build(mut self) -> Result<App> {
+        let charset = match &self.charset {
+            None if self.night => NIGHT_CHARSET.to_vec(),
+            None => DEFAULT_CHARSET.to_vec(),
+            Some(charset) => parse_charset(charset)?,
+        };
+
+        if let Some((width, height)) = self.resolution {
+            if width == 0 || height == 0 {
+                return Err(eyre!("Invalid resolution {width}x{height}"));
+            }
+        }
+
+        for filter in &self.options.filters {
+            filter.validate()?;
+        }
+
+        let (source, device): (Box<dyn CaptureSource + Send>, _) = match self.source {
+            Some(source) => (source, None),
+            None => {
+                let mut camera = ReconnectingCamera::open_retrying(
+                    self.device,
+                    self.resolution,
+                    self.io_mode,
+                    self.open_timeout,
+                )?;
+                camera.set_black_level(self.black_level);
+                camera.set_window(self.window);
+                (Box::new(camera), Some(self.device))
+            }
+        };
+        let source = interpolated(deinterlaced(source, self.deinterlace), self.interpolate);
+
+        // a camera without controls is fine unless some were asked for,
+        // white balance is done in software then
+        let wants_white_balance = self
+            .camera_controls
+            .iter()
+            .any(|(control, _)| control.label == WHITE_BALANCE.label);
+        let required = self.auto_exposure.is_some()
+            || self
+                .camera_controls
+                .iter()
+                .any(|(control, _)| control.label != WHITE_BALANCE.label);
+        let controls = match device {
+            Some(device) => Controls::open_optional(device, required)?,
+            None => None,
+        };
+        let controls = match controls {
+            Some(controls) => {
+                // not every driver sends events, the values shown can go
+                // stale then but nothing else is lost
+                let _ = controls.subscribe();
+                for (control, value) in &self.camera_controls {
+                    let result = match value {
+                        Some(value) => controls.set_to(control, *value),
+                        None => controls.set_auto(control),
+                    };
+                    // cameras without white balance get it done in software
+                    match result {
+                        Err(_)
+                            if control.label == WHITE_BALANCE.label
+                                && controls.lookup(&WHITE_BALANCE).is_err() =>
+                        {
+                            self.options.gray_world = true
+                        }
+                        result => result?,
+                    }
+                }
+                Some(controls)
+            }
+            None => {
+                self.options.gray_world |= device.is_some() && wants_white_balance;
+                None
+            }
+        };
+        let has_controls = controls.is_some();
+
+        let auto_exposure = match (device, self.auto_exposure) {
+            (Some(_), Some(target)) => {
+                let auto_exposure = AutoExposure::new(target);
+                // frames are measured before they are mirrored
+                match self.meter_region {
+                    Some(region) if self.options.mirror => {
+                        Some(auto_exposure.region(region.mirrored()))
+                    }
+                    Some(region) => Some(auto_exposure.region(region)),
+                    None => Some(auto_exposure),
+                }
+            }
+            _ => None,
+        };
+
+        let mut options = self.options;
+        options.color = self.color_mode != ColorMode::Mono;
+
+        let compare = match self.compare {
+            Some(comparison) => {
+                for filter in &comparison.filters {
+                    filter.validate()?;
+                }
+                let charset = match &comparison.charset {
+                    Some(charset) => parse_charset(charset)?,
+                    None => charset.clone(),
+                };
+                let render_mode = comparison.render_mode.unwrap_or(self.render_mode);
+
+                Some(Compare {
+                    renderer: render_mode.renderer(&charset),
+                    filters: comparison.filters,
+                    buffers: RenderBuffers::default(),
+                })
+            }
+            None => None,
+        };
+
+        // the configured charset comes first, the presets are there to cycle
+        // through at runtime
+        let mut charsets = vec![charset];
+        for preset in CHARSETS {
+            charsets.push(parse_charset(preset)?);
+        }
+
+        Ok(App {
+            source: CaptureThread::spawn(source, options.color),
+            status: None,
+            device,
+            resolution: self.resolution,
+            io_mode: self.io_mode,
+            black_level: self.black_level,
+            window: self.window,
+            deinterlace: self.deinterlace,
+            interpolate: self.interpolate,
+            renderer: self.render_mode.renderer(&charsets[0]),
+            buffers: RenderBuffers::default(),
+            charsets,
+            charset: 0,
+            render_mode: self.render_mode,
+            color_mode: self.color_mode,
+            options,
+            pip: self.pip.map(|source| Pip {
+                source,
+                corner: self.pip_corner,
+                swapped: false,
+                buffers: RenderBuffers::default(),
+            }),
+            compare,
+            controls,
+            has_controls,
+            presets_path: self.presets.unwrap_or_else(default_presets_path),
+            presets: None,
+            auto_exposure,
+            osd: None,
+            sinks: self.sinks,
+            quality: self.adaptive_quality.then(AdaptiveQuality::new),
+            drops: DropStats::new(),
+            show_stats: self.show_stats,
+            metrics: self.metrics,
+            stats_log: self.stats_log,
+            timings: RollingTimings::new(),
+            show_timings: false,
+            #[cfg(feature = "script")]
+            script: self.script,
+        })
+    }
+}
+
+/// Timings for a frame handed to [`App::run_with`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FrameStats {
+    /// Number of frames rendered so far, including this one.
+    pub frames: u64,
+    /// Time spent waiting for the source to deliver the frame.
+    pub capture: Duration,
+    /// Time spent decoding, resizing and rendering the frame.
+    pub render: Duration,
+    /// Average frames per second since `run_with` was called.
+    pub fps: f64,
+}
+
+/// The interactive viewer, drawing frames to the terminal until `q` is
+/// pressed.
+pub struct App {
+    source: CaptureThread,
+    // what the source had to say with its last frame
+    status: Option<String>,
+    // the v4l device the source was opened from, when it is a camera
+    device: Option<usize>,
+    resolution: Option<(u32, u32)>,
+    io_mode: IoMode,
+    black_level: u8,
+    window: Option<LevelWindow>,
+    deinterlace: Option<Deinterlace>,
+    interpolate: Option<Duration>,
+    renderer: Box<dyn Renderer>,
+    // the main picture is resized into these every frame
+    buffers: RenderBuffers,
+    charsets: Vec<Vec<char>>,
+    charset: usize,
+    render_mode: RenderMode,
+    color_mode: ColorMode,
+    options: RenderOptions,
+    pip: Option<Pip>,
+    compare: Option<Compare>,
+    // controls of the device, opened again the first time one is adjusted
+    // after switching devices
+    controls: Option<Controls>,
+    // false once the camera turned out to have no controls, the keys for
+    // them do nothing but say so then
+    has_controls: bool,
+    presets_path: PathBuf,
+    // loaded the first time a preset is saved or recalled
+    presets: Option<Presets>,
+    auto_exposure: Option<AutoExposure>,
+    // a message on the top row and when it was shown
+    osd: Option<(String, Instant)>,
+    sinks: Fanout,
+    // None when the picture is always drawn at full quality
+    quality: Option<AdaptiveQuality>,
+    drops: DropStats,
+    show_stats: bool,
+    metrics: Option<Metrics>,
+    stats_log: Option<StatsLog>,
+    timings: RollingTimings,
+    // whether the timings are drawn over the picture
+    show_timings: bool,
+    #[cfg(feature = "script")]
+    script: Option<Script>,
+}
+
+/// Keeps the terminal in raw mode until dropped, so it is restored however
+/// the viewer exits, panics included.
+struct RawMode;
+
+impl RawMode {
+    fn enable() -> Result<Self> {
+        terminal::enable_raw_mode()?;
+        Ok(Self)
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// The right half of a side by side comparison.
+struct Compare {
+    renderer: Box<dyn Renderer>,
+    // everything else, like the zoom, follows the left half
+    filters: Vec<Filter>,
+    // the right half is resized into these, apart from the left half's
+    buffers: RenderBuffers,
+}
+
+/// The picture in picture inset of an [`App`].
+struct Pip {
+    source: Box<dyn CaptureSource>,
+    corner: Corner,
+    // whether the inset source is drawn full screen and the main one small
+    swapped: bool,
+    // the inset is resized into these, apart from the main picture's
+    buffers: RenderBuffers,
+}
+
+/// Stretches every cell of the picture over `scale` x `scale` while writing
+/// frames to the terminal falls behind the camera, so fewer colors have to
+/// be sent, and shrinks it back once there is room again.
+struct AdaptiveQuality {
+    scale: u32,
+    since: Instant,
+    // frames and the terminal's count of dropped ones since `since`
+    frames: u32,
+    dropped: u64,
+}
+
+impl AdaptiveQuality {
+    fn new() -> Self {
+        Self {
+            scale: 1,
+            since: Instant::now(),
+            frames: 0,
+            dropped: 0,
+        }
+    }
+
+    /// Counts a frame handed to `terminal`, returning the new scale when it
+    /// changed.
+    fn update(&mut self, terminal: &BackgroundSink) -> Option<u32> {
+        self.frames += 1;
+        let elapsed = self.since.elapsed();
+        if elapsed < QUALITY_INTERVAL {
+            return None;
+        }
+
+        let dropped = terminal.dropped() - self.dropped;
+        let frame_time = elapsed / self.frames;
+        // more than a quarter of the frames never made it out
+        let scale = if dropped * 4 > self.frames as u64 {
+            (self.scale + 1).min(MAX_QUALITY_SCALE)
+        // twice as fast as needed leaves room for the next finer step
+        } else if dropped == 0 && terminal.write_time() * 2 < frame_time {
+            self.scale.saturating_sub(1).max(1)
+        } else {
+            self.scale
+        };
+
+        self.since = Instant::now();
+        self.frames = 0;
+        self.dropped = terminal.dropped();
+
+        if scale == self.scale {
+            return None;
+        }
+        self.scale = scale;
+        Some(scale)
+    }
+}
+
+impl App {
+    /// Runs the viewer, putting the terminal in raw mode for the duration.
+    pub fn run(&mut self) -> Result<()> {
+        let _raw_mode = RawMode::enable()?;
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?
+            .block_on(self.run_loop())
+    }
+
+    /// The frames dropped and late so far.
+    pub fn drop_stats(&self) -> DropStats {
+        self.drops
+    }
+
+    /// Captures and renders frames of `columns` x `rows` cells, handing each
+    /// one to `on_frame` until it returns [`ControlFlow::Break`].
+    ///
+    /// Unlike [`run`](App::run) this doesn't touch the terminal, so it can
+    /// be driven from an application that owns its own event loop. Frames
+    /// still go to the sinks added with [`AsciicamBuilder::sink`].
+    ///
+    /// ```no_run
+    /// use std::ops::ControlFlow;
+    ///
+    /// # fn main() -> eyre::Result<()> {
+    /// let mut app = asciicam::AsciicamBuilder::new().build()?;
+    /// app.run_with(80, 24, |grid, stats| {
+    ///     println!("frame {} took {:?}", stats.frames, stats.render);
+    ///     if stats.frames == 100 {
+    ///         ControlFlow::Break(())
+    ///     } else {
+    ///         ControlFlow::Continue(())
+    ///     }
+    /// })
+    /// # }
+    /// ```
+    pub fn run_with<F>(&mut self, columns: u32, rows: u32, mut on_frame: F) -> Result<()>
+    where
+        F: FnMut(&Grid, &FrameStats) -> ControlFlow<()>,
+    {
+        let start = Instant::now();
+        let mut stats = FrameStats::default();
+
+        loop {
+            let captured_at = Instant::now();
+            let Captured {
+                frame: captured,
+                status,
+                decode,
+                reconnects,
+                ..
+            } = self.source.next_blocking()?;
+            let rendered_at = Instant::now();
+            self.status = status;
+            self.drops.captured(&captured.meta);
+            self.sinks.write_captured(&captured)?;
+            self.expose(&captured);
+            self.show_control_changes();
+
+            let grid = self.render(&captured, columns, rows)?;
+            self.drops.shown(&captured.meta);
+            self.drops.skipped = self.source.skipped();
+            // nothing is written to a terminal here
+            self.report(&captured, decode, reconnects, 0)?;
+            self.source.recycle(captured);
+
+            stats.frames += 1;
+            stats.capture = rendered_at - captured_at;
+            stats.render = rendered_at.elapsed();
+            stats.fps = stats.frames as f64 / start.elapsed().as_secs_f64();
+
+            self.sinks.write_frame(&grid)?;
+            if on_frame(&grid, &stats).is_break() {
+                return Ok(());
+            }
+            self.buffers.recycle(grid);
+        }
+    }
+
+    /// Hands the numbers of the frame just rendered to the metrics endpoint
+    /// and the stats log, `decode` being how long the capture thread took to
+    /// decode it.
+    fn report(
+        &mut self,
+        frame: &Frame,
+        decode: Duration,
+        reconnects: u64,
+        terminal_bytes: u64,
+    ) -> Result<()> {
+        if let Some(metrics) = &self.metrics {
+            metrics.frame(decode + self.buffers.timings().decode);
+            metrics.update(self.sinks.clients(), &self.drops, reconnects);
+        }
+        if let Some(log) = &mut self.stats_log {
+            log.frame(frame, terminal_bytes)?;
+        }
+
+        Ok(())
+    }
+
+    async fn run_loop(&mut self) -> Result<()> {
+        // capturing and drawing each run on a thread of their own, the next
+        // frame is captured and the last one drawn while this one renders
+        let sink = TerminalSink::with_color_mode(self.color_mode);
+        let written = sink.written();
+        let mut terminal = BackgroundSink::spawn(sink);
+        let mut events = EventStream::new();
+        let (mut term_width, mut term_height) = terminal::size()?;
+        // the last frame, drawn again when a key changes how it looks
+        let mut last: Option<Frame> = None;
+        let mut grid: Option<Grid> = None;
+        // how long the capture thread took on the last frame
+        let mut captured_timings = (Duration::ZERO, Duration::ZERO);
+        let mut reconnects = 0;
+
+        loop {
+            // nothing is drawn between frames and keys but the message going
+            // away, the loop sleeps until one of them is due
+            let osd_expires = self
+                .osd
+                .as_ref()
+                .map(|(_, shown)| *shown + OSD_DURATION)
+                .filter(|expires| *expires > Instant::now());
+
+            let new_frame = tokio::select! {
+                captured = self.source.next() => {
+                    let Captured {
+                        frame,
+                        status,
+                        capture,
+                        decode,
+                        reconnects: source_reconnects,
+                    } = captured?;
+                    captured_timings = (capture, decode);
+                    reconnects = source_reconnects;
+                    self.drops.captured(&frame.meta);
+                    // terminals report no size for a moment at times, termux
+                    // while its keyboard slides in or the app is in the
+                    // background, and don't always say when they have one again
+                    if term_width == 0 || term_height == 0 {
+                        (term_width, term_height) = terminal::size()?;
+                    }
+                    self.status = status;
+                    self.sinks.write_captured(&frame)?;
+                    self.expose(&frame);
+                    self.show_control_changes();
+                    if let Some(done) = last.replace(frame) {
+                        self.source.recycle(done);
+                    }
+                    true
+                }
+                event = events.next() => {
+                    match event {
+                        Some(Ok(Event::Resize(width, height))) => {
+                            (term_width, term_height) = (width, height);
+                        }
+                        Some(Ok(Event::Key(KeyEvent { code, modifiers, .. }))) => {
+                            if self.handle_key(code, modifiers, grid.as_ref())?.is_break() {
+                                break;
+                            }
+                        }
+                        Some(Ok(_)) => (),
+                        Some(Err(e)) => return Err(e.into()),
+                        // stdin closed, nothing left to wait for
+                        None => break,
+                    }
+                    false
+                }
+                _ = tokio::time::sleep_until(osd_expires.unwrap_or_else(Instant::now).into()),
+                    if osd_expires.is_some() =>
+                {
+                    self.osd = None;
+                    false
+                }
+            };
+
+            if term_width == 0 || term_height == 0 {
+                continue;
+            }
+            let frame = match &last {
+                Some(frame) => frame,
+                None => continue,
+            };
+            let rendered = self.render(frame, term_width.into(), term_height.into())?;
+
+            terminal.write_frame(&rendered)?;
+            if new_frame {
+                self.sinks.write_frame(&rendered)?;
+                self.drops.shown(&frame.meta);
+                self.drops.skipped = self.source.skipped() + terminal.dropped();
+
+                // the terminal is written on a thread of its own, the last
+                // write it finished is the closest there is
+                let (capture, decode) = captured_timings;
+                let render = self.buffers.timings();
+                self.timings.update(StageTimings {
+                    capture,
+                    decode: decode + render.decode,
+                    write: terminal.write_time(),
+                    ..render
+                });
+                let terminal_bytes = written.load(Ordering::Relaxed);
+                self.report(frame, decode, reconnects, terminal_bytes)?;
+
+                let scale = self.quality.as_mut().and_then(|q| q.update(&terminal));
+                if let Some(scale) = scale {
+                    self.osd = Some((format!("quality 1/{scale}"), Instant::now()));
+                }
+            }
+            if let Some(shown) = grid.replace(rendered) {
+                self.buffers.recycle(shown);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Acts on a key pressed in the viewer, `grid` is the frame on screen.
+    fn handle_key(
+        &mut self,
+        code: KeyCode,
+        modifiers: KeyModifiers,
+        grid: Option<&Grid>,
+    ) -> Result<ControlFlow<()>> {
+        match code {
+            KeyCode::Char('q') => return Ok(ControlFlow::Break(())),
+            KeyCode::Char('s') => {
+                if let Some(grid) = grid {
+                    let dt = chrono::Utc::now();
+                    WriterSink::create(format!("asciicam-{}.txt", dt.format("%Y-%m-%d_%H:%M:%S")))?
+                        .write_frame(grid)?;
+                }
+            }
+            KeyCode::Char('m') => {
+                self.render_mode = self.render_mode.next();
+                self.renderer = self.render_mode.renderer(&self.charsets[self.charset]);
+            }
+            KeyCode::Char('c') => {
+                self.charset = (self.charset + 1) % self.charsets.len();
+                self.renderer = self.render_mode.renderer(&self.charsets[self.charset]);
+            }
+            KeyCode::Char('i') => {
+                let filters = &mut self.options.filters;
+                if filters.contains(&Filter::Invert) {
+                    filters.retain(|f| *f != Filter::Invert);
+                } else {
+                    filters.push(Filter::Invert);
+                }
+            }
+            // the last filter to the front, so every order comes round
+            KeyCode::Char('r') => self.options.filters.rotate_right(1),
+            KeyCode::Char('h') => self.options.tint = Tint::next(self.options.tint),
+            KeyCode::Char('+') | KeyCode::Char('=') => {
+                self.options.zoom = (self.options.zoom * ZOOM_STEP).min(MAX_ZOOM)
+            }
+            KeyCode::Char('-') => self.options.zoom = (self.options.zoom / ZOOM_STEP).max(1.0),
+            KeyCode::Char('0') => self.options.zoom = 1.0,
+            KeyCode::Char('d') => self.next_device()?,
+            KeyCode::Char('e') => self.adjust_control(&EXPOSURE, -1),
+            KeyCode::Char('E') => self.adjust_control(&EXPOSURE, 1),
+            KeyCode::Char('g') => self.adjust_control(&GAIN, -1),
+            KeyCode::Char('G') => self.adjust_control(&GAIN, 1),
+            KeyCode::Char('f') => self.adjust_control(&FOCUS, -1),
+            KeyCode::Char('F') => self.adjust_control(&FOCUS, 1),
+            KeyCode::Char('a') => self.autofocus(),
+            KeyCode::Char('b') => self.adjust_control(&WHITE_BALANCE, -1),
+            KeyCode::Char('B') => self.adjust_control(&WHITE_BALANCE, 1),
+            KeyCode::Char('z') => self.adjust_control(&ZOOM, -1),
+            KeyCode::Char('Z') => self.adjust_control(&ZOOM, 1),
+            KeyCode::Char(slot @ '1'..='9') if modifiers.contains(KeyModifiers::ALT) => {
+                self.preset(slot, true)
+            }
+            KeyCode::Char(slot @ '1'..='9') => self.preset(slot, false),
+            // `d` is taken by the next camera
+            KeyCode::Char('t') => self.show_timings = !self.show_timings,
+            KeyCode::Char('v') => {
+                let message = match self.source.with(|source| source.next_stream())? {
+                    Ok(stream) => stream,
+                    Err(e) => Some(e.to_string()),
+                };
+                if let Some(message) = message {
+                    self.osd = Some((message, Instant::now()));
+                }
+            }
+            KeyCode::Char('p') => {
+                if let Some(pip) = &mut self.pip {
+                    pip.corner = pip.corner.next();
+                }
+            }
+            KeyCode::Char('w') => {
+                if let Some(pip) = &mut self.pip {
+                    pip.swapped = !pip.swapped;
+                }
+            }
+            KeyCode::Char(' ') => {
+                self.source.with(|source| {
+                    if let Some(playback) = source.playback() {
+                        playback.toggle_pause();
+                    }
+                })?;
+            }
+            KeyCode::Left if modifiers.contains(KeyModifiers::CONTROL) => {
+                self.adjust_control(&PAN, -1)
+            }
+            KeyCode::Right if modifiers.contains(KeyModifiers::CONTROL) => {
+                self.adjust_control(&PAN, 1)
+            }
+            KeyCode::Up if modifiers.contains(KeyModifiers::CONTROL) => {
+                self.adjust_control(&TILT, 1)
+            }
+            KeyCode::Down if modifiers.contains(KeyModifiers::CONTROL) => {
+                self.adjust_control(&TILT, -1)
+            }
+            KeyCode::Left => {
+                self.source.with(|source| match source.playback() {
+                    Some(playback) => playback.seek(-SEEK_STEP),
+                    None => Ok(()),
+                })??;
+            }
+            KeyCode::Right => {
+                self.source.with(|source| match source.playback() {
+                    Some(playback) => playback.seek(SEEK_STEP),
+                    None => Ok(()),
+                })??;
+            }
+            _ => (),
+        }
+
+        Ok(ControlFlow::Continue(()))
+    }
+
+    /// Switches to the next v4l device that can be opened, wrapping around
+    /// to the first. Does nothing when the source isn't a camera.
+    fn next_device(&mut self) -> Result<()> {
+        let current = match self.device {
+            Some(device) => device,
+            None => return Ok(()),
+        };
+
+        let mut indices: Vec<usize> = list_devices().iter().map(|d| d.index).collect();
+        indices.sort_unstable();
+        // the devices after the current one, then from the first around to
+        // the current one again in case no other one works. not every node
+        // is a camera, some are only metadata of another one
+        let (after, before): (Vec<usize>, Vec<usize>) =
+            indices.into_iter().partition(|&index| index > current);
+
+        // close the camera before opening the next, some drivers can't
+        // stream from two at once
+        self.source
+            .replace(Box::new(SyntheticSource::new(1, 1, 0)))?;
+        self.controls = None;
+
+        for index in after.into_iter().chain(before) {
+            match ReconnectingCamera::open_io(index, self.resolution, self.io_mode) {
+                Ok(mut camera) => {
+                    camera.set_black_level(self.black_level);
+                    camera.set_window(self.window);
+                    info!("switched to /dev/video{index}");
+                    let source = deinterlaced(Box::new(camera), self.deinterlace);
+                    self.source
+                        .replace(interpolated(source, self.interpolate))?;
+                    self.device = Some(index);
+                    // looked for the first time one is adjusted
+                    self.has_controls = true;
+                    return Ok(());
+                }
+                Err(e) => debug!("skipping /dev/video{index}: {e}"),
+            }
+        }
+
+        Err(eyre!("Could not open /dev/video{current} again"))
+    }
+
+    /// Moves a camera control by `steps` and shows its new value, or why it
+    /// couldn't be changed.
+    fn adjust_control(&mut self, control: &CameraControl, steps: i64) {
+        // the software auto exposure would undo it right away
+        if control.label == EXPOSURE.label || control.label == GAIN.label {
+            self.auto_exposure = None;
+        }
+
+        let message = match self.controls().and_then(|c| c.adjust(control, steps)) {
+            Ok(info) => format!(
+                "{} {} ({}..{})",
+                control.label,
+                info.value.unwrap_or(info.default),
+                info.minimum,
+                info.maximum
+            ),
+            Err(e) => e.to_string(),
+        };
+        self.osd = Some((message, Instant::now()));
+    }
+
+    /// Runs a step of the software auto exposure, turning it off with a
+    /// message when the camera can't be adjusted.
+    fn expose(&mut self, captured: &Frame) {
+        let mut auto_exposure = match self.auto_exposure.take() {
+            Some(auto_exposure) => auto_exposure,
+            None => return,
+        };
+
+        match self
+            .controls()
+            .and_then(|c| auto_exposure.update(captured, c))
+        {
+            Ok(()) => self.auto_exposure = Some(auto_exposure),
+            Err(e) => self.osd = Some((format!("auto exposure off: {e}"), Instant::now())),
+        }
+    }
+
+    /// Shows the value of a control another program changed, like
+    /// `v4l2-ctl`, so what is on screen isn't stale.
+    fn show_control_changes(&mut self) {
+        let changed = match self.controls.as_ref().map(Controls::changes) {
+            Some(Ok(changed)) => changed,
+            _ => return,
+        };
+
+        if let Some(info) = changed.last() {
+            let message = match info.value {
+                Some(value) => format!("{} {value} ({}..{})", info.key, info.minimum, info.maximum),
+                None => info.key.clone(),
+            };
+            self.osd = Some((message, Instant::now()));
+        }
+    }
+
+    /// Turns autofocus back on after focusing by hand.
+    fn autofocus(&mut self) {
+        let message = match self.controls().and_then(|c| c.set_auto(&FOCUS)) {
+            Ok(()) => "autofocus".to_string(),
+            Err(e) => e.to_string(),
+        };
+        self.osd = Some((message, Instant::now()));
+    }
+
+    /// Saves where the camera is pointing to a preset slot, or points it
+    /// there again.
+    fn preset(&mut self, slot: char, save: bool) {
+        let slot = slot as u8 - b'0';
+        let message = match self.try_preset(slot, save) {
+            Ok(()) if save => format!("saved preset {slot}"),
+            Ok(()) => format!("preset {slot}"),
+            Err(e) => e.to_string(),
+        };
+        self.osd = Some((message, Instant::now()));
+    }
+
+    fn try_preset(&mut self, slot: u8, save: bool) -> Result<()> {
+        let mut presets = match self.presets.take() {
+            Some(presets) => presets,
+            None => Presets::load(&self.presets_path)?,
+        };
+
+        let result = if save {
+            self.controls().and_then(|c| presets.save(slot, c))
+        } else {
+            self.controls().and_then(|c| presets.recall(slot, c))
+        };
+        self.presets = Some(presets);
+
+        result
+    }
+
+    fn controls(&mut self) -> Result<&Controls> {
+        let device = match self.device {
+            Some(device) => device,
+            None => return Err(eyre!("Camera controls need a camera")),
+        };
+        if !self.has_controls {
+            return Err(eyre!("This camera has no controls"));
+        }
+
+        let controls = match self.controls.take() {
+            Some(controls) => controls,
+            None => match Controls::open(device) {
+                Ok(controls) => {
+                    let _ = controls.subscribe();
+                    controls
+                }
+                Err(e) => {
+                    self.has_controls = false;
+                    return Err(e);
+                }
+            },
+        };
+        Ok(self.controls.insert(controls))
+    }
+
+    /// Renders `captured` into a grid of `columns` x `rows`, with the
+    /// picture in picture inset, the comparison, the OSD and the status bar
+    /// drawn over it.
+    fn render(&mut self, captured: &Frame, columns: u32, rows: u32) -> Result<Grid> {
+        // the comparison gets the right half, past a divider
+        let left = match self.compare {
+            Some(_) => (columns.saturating_sub(1) / 2).max(1),
+            None => columns,
+        };
+
+        // the main picture is drawn coarser when the terminal can't keep up,
+        // one cell stretched over `scale` x `scale`
+        let scale = self.quality.as_ref().map_or(1, |quality| quality.scale);
+        let (main_columns, main_rows) = ((left / scale).max(1), (rows / scale).max(1));
+
+        // an inset source that stopped, like an unplugged camera or a video
+        // that ended, is dropped and the main picture drawn on its own
+        let inset_frame = match self.pip.as_mut().map(|pip| pip.source.next_shared()) {
+            Some(Ok(frame)) => Some(frame),
+            Some(Err(e)) => {
+                info!("picture in picture stopped: {e}");
+                self.osd = Some((format!("inset stopped: {e}"), Instant::now()));
+                self.pip = None;
+                None
+            }
+            None => None,
+        };
+
+        let mut grid = match (&mut self.pip, inset_frame) {
+            (Some(pip), Some(inset_frame)) => {
+                let (main, small) = if pip.swapped {
+                    (&*inset_frame, captured)
+                } else {
+                    (captured, &*inset_frame)
+                };
+
+                let mut grid = render_frame_with(
+                    main,
+                    &*self.renderer,
+                    main_columns,
+                    main_rows,
+                    &self.options,
+                    &mut self.buffers,
+                )?;
+                if scale > 1 {
+                    grid = grid.resized(left, rows);
+                }
+                let size = ((left / PIP_SCALE).max(1), (rows / PIP_SCALE).max(1));
+                let inset = render_frame_with(
+                    small,
+                    &*self.renderer,
+                    size.0,
+                    size.1,
+                    &self.options,
+                    &mut pip.buffers,
+                )?;
+
+                let (x, y) = pip.corner.position((left, rows), size);
+                grid.blit(&inset, x, y);
+                pip.buffers.recycle(inset);
+                // the grid stays tied to the frame it was captured from
+                grid.set_meta(captured.meta);
+                grid
+            }
+            _ => {
+                let grid = render_frame_with(
+                    captured,
+                    &*self.renderer,
+                    main_columns,
+                    main_rows,
+                    &self.options,
+                    &mut self.buffers,
+                )?;
+                if scale > 1 {
+                    grid.resized(left, rows)
+                } else {
+                    grid
+                }
+            }
+        };
+
+        #[cfg(feature = "script")]
+        if let Some(script) = &mut self.script {
+            // a script that fails on a frame is shown on the OSD and kept,
+            // it may well do fine on the next one
+            match script.frame(self.buffers.luma(), grid.clone()) {
+                Ok(scripted) => grid = scripted,
+                Err(e) => {
+                    debug!("{e}");
+                    self.osd = Some((e.to_string(), Instant::now()));
+                }
+            }
+            if let Some(message) = script.take_osd() {
+                self.osd = Some((message, Instant::now()));
+            }
+        }
+
+        if let Some(compare) = &mut self.compare {
+            let right = columns.saturating_sub(left + 1).max(1);
+            // borrowing the filters for the right half instead of cloning
+            // the options for it every frame
+            mem::swap(&mut self.options.filters, &mut compare.filters);
+            let other = render_frame_with(
+                captured,
+                &*compare.renderer,
+                right,
+                rows,
+                &self.options,
+                &mut compare.buffers,
+            );
+            mem::swap(&mut self.options.filters, &mut compare.filters);
+            let other = other?;
+
+            let mut split = Grid::new(columns, rows);
+            split.blit(&grid, 0, 0);
+            for y in 0..rows {
+                split.print(left, y, "│");
+            }
+            split.blit(&other, left + 1, 0);
+            split.set_meta(captured.meta);
+            grid = split;
+        }
+
+        if let Some((message, shown)) = &self.osd {
+            if shown.elapsed() < OSD_DURATION {
+                let bar = format!(" {message:<width$}", width = columns as usize);
+                grid.print(0, 0, &bar);
+            }
+        }
+        if self.show_timings {
+            // below the message line
+            for (y, line) in (1..rows).zip(self.timings.lines()) {
+                grid.print(0, y, &format!(" {line} "));
+            }
+        }
+        let status = match (&self.status, self.show_stats) {
+            (Some(status), true) => Some(format!("{status} · {}", self.drops)),
+            (Some(status), false) => Some(status.clone()),
+            (None, true) => Some(self.drops.to_string()),
+            (None, false) => None,
+        };
+        if let Some(status) = status {
+            let bar = format!(" {status:<width$}", width = columns as usize);
+            grid.print(0, rows.saturating_sub(1), &bar);
+        }
+
+        Ok(grid)
+    }
+}
+
+/// `source` deinterlaced with `mode`, when there is one.
+fn deinterlaced(
+    source: Box<dyn CaptureSource + Send>,
+    mode: Option<Deinterlace>,
+) -> Box<dyn CaptureSource + Send> {
+    match mode {
+        Some(mode) => Box::new(DeinterlacedSource::new(source, mode)),
+        None => source,
+    }
+}
+
+/// `source` faded from frame to frame over `window`, when there is one.
+fn interpolated(
+    source: Box<dyn CaptureSource + Send>,
+    window: Option<Duration>,
+) -> Box<dyn CaptureSource + Send> {
+    match window {
+        Some(window) => Box::new(InterpolatedSource::new(source, window)),
+        None => source,
+    }
+}
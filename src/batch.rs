@@ -0,0 +1,178 @@
+//! Converting image files to text without a terminal, one off or by watching
+//! a folder.
+
+use crate::capture::{CaptureSource, ImageSource};
+use crate::render::{render_frame, write_grid_with, ColorMode, RenderOptions, Renderer};
+use eyre::{eyre, Result};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+/// File extensions picked up by [`Converter::watch`].
+pub const IMAGE_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "webp", "bmp", "gif", "tif", "tiff"];
+
+/// Renders image files into text files of `columns` x `rows` cells.
+pub struct Converter {
+    renderer: Box<dyn Renderer>,
+    columns: u32,
+    rows: u32,
+    color_mode: ColorMode,
+    options: RenderOptions,
+}
+
+impl Converter {
+    pub fn new(
+        renderer: Box<dyn Renderer>,
+        columns: u32,
+        rows: u32,
+        color_mode: ColorMode,
+        options: RenderOptions,
+    ) -> Self {
+        Self {
+            renderer,
+            columns,
+            rows,
+            color_mode,
+            options,
+        }
+    }
+
+    /// Converts `input` into `<out_dir>/<file stem>.txt` and returns the path
+    /// written to.
+    ///
+    /// Mono output is plain text, colored output contains the SGR escape
+    /// codes for the colors and is meant to be `cat` to a terminal.
+    pub fn convert_file(&self, input: &Path, out_dir: &Path) -> Result<PathBuf> {
+        let stem = match input.file_stem() {
+            Some(stem) => stem,
+            None => return Err(eyre!("'{}' is not a file", input.display())),
+        };
+        let output = out_dir.join(stem).with_extension("txt");
+
+        let frame = ImageSource::open(input)
+            .map_err(|e| eyre!("Could not read '{}': {e}", input.display()))?
+            .next_frame()?;
+        let grid = render_frame(
+            &frame,
+            &*self.renderer,
+            self.columns,
+            self.rows,
+            &self.options,
+        )?;
+
+        let mut out = BufWriter::new(File::create(&output)?);
+        match self.color_mode {
+            ColorMode::Mono => out.write_all(grid.to_text().as_bytes())?,
+            color_mode => write_grid_with(&grid, color_mode, &mut out)?,
+        }
+        out.flush()?;
+
+        Ok(output)
+    }
+
+    /// Converts every image that shows up in `dir`, including the ones
+    /// already there. Only returns when `dir` can't be read.
+    ///
+    /// Files are only picked up once their size and modification time have
+    /// stayed the same for one `interval`, so images still being copied in
+    /// aren't converted half written. A file that changes later is
+    /// converted again. `on_converted` is called with the result of every
+    /// conversion, a failed one doesn't stop the watch.
+    pub fn watch(
+        &self,
+        dir: &Path,
+        out_dir: &Path,
+        interval: Duration,
+        mut on_converted: impl FnMut(&Path, Result<PathBuf>),
+    ) -> Result<()> {
+        // what each file looked like at the last poll, and whether that
+        // version has been converted already
+        let mut seen: HashMap<PathBuf, ((u64, SystemTime), bool)> = HashMap::new();
+
+        loop {
+            for entry in fs::read_dir(dir)? {
+                let path = entry?.path();
+                if !is_image(&path) {
+                    continue;
+                }
+
+                let metadata = match fs::metadata(&path) {
+                    Ok(metadata) if metadata.is_file() => metadata,
+                    // gone again or not a regular file
+                    _ => continue,
+                };
+                let version = (metadata.len(), metadata.modified()?);
+
+                match seen.get_mut(&path) {
+                    Some((last, converted)) if *last == version => {
+                        if !*converted {
+                            *converted = true;
+                            let result = self.convert_file(&path, out_dir);
+                            on_converted(&path, result);
+                        }
+                    }
+                    Some(entry) => *entry = (version, false),
+                    None => {
+                        seen.insert(path, (version, false));
+                    }
+                }
+            }
+
+            thread::sleep(interval);
+        }
+    }
+}
+
+pub(crate) fn is_image(path: &Path) -> bool {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some(e) => IMAGE_EXTENSIONS.contains(&e.to_ascii_lowercase().as_str()),
+        None => false,
+    }
+}
+
+/// Expands a `*` / `?` pattern in the file name part of `pattern`, e.g.
+/// `photos/*.jpg`, into the matching files sorted by name. Patterns without
+/// wildcards are returned as is.
+///
+/// Shells usually expand these already, this is for quoted patterns and
+/// shells that don't.
+pub fn expand_glob(pattern: &str) -> Result<Vec<PathBuf>> {
+    let path = Path::new(pattern);
+    let name = match path.file_name().and_then(|n| n.to_str()) {
+        Some(name) if name.contains(['*', '?']) => name,
+        _ => return Ok(vec![path.to_path_buf()]),
+    };
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if let Some(file_name) = entry.file_name().to_str() {
+            if wildcard_match(name.as_bytes(), file_name.as_bytes()) {
+                paths.push(entry.path());
+            }
+        }
+    }
+    paths.sort();
+
+    Ok(paths)
+}
+
+fn wildcard_match(pattern: &[u8], name: &[u8]) -> bool {
+    match (pattern.first(), name.first()) {
+        (None, None) => true,
+        (Some(b'*'), _) => {
+            wildcard_match(&pattern[1..], name)
+                || (!name.is_empty() && wildcard_match(pattern, &name[1..]))
+        }
+        (Some(b'?'), Some(_)) => wildcard_match(&pattern[1..], &name[1..]),
+        (Some(p), Some(n)) if p == n => wildcard_match(&pattern[1..], &name[1..]),
+        _ => false,
+    }
+}
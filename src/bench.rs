@@ -0,0 +1,178 @@
+//! Measuring how fast frames get through decoding, resizing, mapping and
+//! writing, without a camera or a terminal, to compare changes that are
+//! meant to speed that up.
+
+use crate::capture::{Frame, FrameMeta, PixelFormat, SyntheticSource};
+use crate::render::{
+    parse_charset, render_frame_with, ColorMode, GridWriter, RenderBuffers, RenderMode,
+    RenderOptions, CHARSETS,
+};
+use crate::stats::{StageTimings, Stopwatch};
+use eyre::{eyre, Result};
+use image::codecs::jpeg::JpegEncoder;
+use image::ColorType;
+use std::fs;
+use std::path::Path;
+
+// the sizes cameras commonly deliver MJPEG at
+const FIXTURE_SIZES: [(u32, u32); 3] = [(640, 480), (1280, 720), (1920, 1080)];
+// what cheap webcams compress at, roughly
+const FIXTURE_QUALITY: u8 = 85;
+
+/// A JPEG frame to benchmark with, as a camera would deliver it.
+pub struct Fixture {
+    pub name: String,
+    pub frame: Frame,
+}
+
+impl Fixture {
+    /// The frames benchmarked without any given, the synthetic test pattern
+    /// in color at 640x480, 1280x720 and 1920x1080. They are generated the
+    /// same every time, so runs on different commits compare.
+    pub fn builtin() -> Result<Vec<Self>> {
+        FIXTURE_SIZES
+            .iter()
+            .map(|&(width, height)| {
+                let gray = SyntheticSource::new(width, height, 0).frame_at(0);
+                // some color, so the color renderer has something to map
+                let rgb: Vec<u8> = gray
+                    .data
+                    .iter()
+                    .flat_map(|&v| [v, v / 2 + 64, 255 - v])
+                    .collect();
+
+                let mut jpeg = Vec::new();
+                JpegEncoder::new_with_quality(&mut jpeg, FIXTURE_QUALITY).encode(
+                    &rgb,
+                    width,
+                    height,
+                    ColorType::Rgb8,
+                )?;
+
+                Ok(Self {
+                    name: format!("{width}x{height}"),
+                    frame: mjpeg_frame(jpeg, width, height),
+                })
+            })
+            .collect()
+    }
+
+    /// A JPEG file, named after the file.
+    pub fn open(path: &Path) -> Result<Self> {
+        let jpeg = fs::read(path).map_err(|e| eyre!("Could not read '{}': {e}", path.display()))?;
+        let (width, height) = image::image_dimensions(path)
+            .map_err(|e| eyre!("'{}' is not an image: {e}", path.display()))?;
+
+        Ok(Self {
+            name: path
+                .file_name()
+                .map_or_else(String::new, |name| name.to_string_lossy().into_owned()),
+            frame: mjpeg_frame(jpeg, width, height),
+        })
+    }
+}
+
+fn mjpeg_frame(jpeg: Vec<u8>, width: u32, height: u32) -> Frame {
+    Frame {
+        meta: FrameMeta {
+            bytes_used: jpeg.len() as u32,
+            ..FrameMeta::default()
+        },
+        data: jpeg,
+        width,
+        height,
+        pixel_format: PixelFormat::Mjpeg,
+    }
+}
+
+/// A render mode and charset to benchmark. Modes that don't map onto a
+/// charset are benchmarked once, with `charset` `None`.
+#[derive(Debug, Clone, Copy)]
+pub struct Combination {
+    pub mode: RenderMode,
+    /// An index into [`CHARSETS`].
+    pub charset: Option<usize>,
+}
+
+impl Combination {
+    /// Every render mode with every charset it uses.
+    pub fn all() -> Vec<Self> {
+        RenderMode::ALL
+            .iter()
+            .flat_map(|&mode| {
+                let charsets: Vec<Option<usize>> = match mode {
+                    RenderMode::Ascii | RenderMode::Color => {
+                        (0..CHARSETS.len()).map(Some).collect()
+                    }
+                    RenderMode::HalfBlock | RenderMode::Braille | RenderMode::Matrix => {
+                        vec![None]
+                    }
+                };
+                charsets
+                    .into_iter()
+                    .map(move |charset| Self { mode, charset })
+            })
+            .collect()
+    }
+}
+
+/// Renders `fixture` `frames` times at `columns` x `rows` the way the
+/// viewer does and returns the average time of each stage, writing to
+/// memory instead of a terminal.
+pub fn measure(
+    fixture: &Fixture,
+    combination: Combination,
+    columns: u32,
+    rows: u32,
+    frames: u32,
+) -> Result<StageTimings> {
+    let charset = parse_charset(CHARSETS[combination.charset.unwrap_or(0)])?;
+    let renderer = combination.mode.renderer(&charset);
+    let (options, color_mode) = match combination.mode {
+        RenderMode::Color => (RenderOptions::default(), ColorMode::TrueColor),
+        _ => (
+            RenderOptions {
+                color: false,
+                ..RenderOptions::default()
+            },
+            ColorMode::Mono,
+        ),
+    };
+    let mut buffers = RenderBuffers::default();
+    let mut writer = GridWriter::default();
+    let mut out = Vec::new();
+
+    let mut total = StageTimings::default();
+    // the first frame sizes the buffers, like the viewer's first one does
+    for frame in 0..=frames {
+        let grid = render_frame_with(
+            &fixture.frame,
+            &*renderer,
+            columns,
+            rows,
+            &options,
+            &mut buffers,
+        )?;
+        out.clear();
+        let mut stopwatch = Stopwatch::start();
+        writer.write(&grid, color_mode, &mut out)?;
+        let write = stopwatch.lap();
+
+        if frame > 0 {
+            let timings = buffers.timings();
+            total.decode += timings.decode;
+            total.resize += timings.resize;
+            total.map += timings.map;
+            total.write += write;
+        }
+    }
+
+    let frames = frames.max(1);
+    Ok(StageTimings {
+        decode: total.decode / frames,
+        resize: total.resize / frames,
+        map: total.map / frames,
+        write: total.write / frames,
+        ..StageTimings::default()
+    })
+}
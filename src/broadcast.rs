@@ -0,0 +1,160 @@
+//! Feeding one capture pipeline to many remote clients.
+//!
+//! A [`Broadcast`] is a [`Sink`] that decodes every captured frame once and
+//! hands it to each [`Subscriber`], which renders it at its own size. Every
+//! subscriber has a queue of a single frame: when a client falls behind its
+//! queue is full and newer frames are dropped for it, so capture and the
+//! other clients never wait on a slow one.
+
+use crate::capture::{Frame, PixelFormat};
+use crate::convert::{decode_frame, decode_frame_rgb};
+use crate::render::{render_frame, write_grid_with, ColorMode, Grid, RenderMode, RenderOptions};
+use crate::sink::Sink;
+use eyre::Result;
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+
+/// How subscribers render frames, shared by all of them.
+#[derive(Debug, Clone)]
+pub struct RenderSettings {
+    pub render_mode: RenderMode,
+    pub charset: Vec<char>,
+    pub color_mode: ColorMode,
+    pub options: RenderOptions,
+}
+
+impl RenderSettings {
+    /// Renders `frame` into a grid of `columns` x `rows`.
+    pub fn render(&self, frame: &Frame, columns: u32, rows: u32) -> Result<Grid> {
+        let renderer = self.render_mode.renderer(&self.charset);
+        render_frame(frame, &*renderer, columns, rows, &self.options)
+    }
+
+    /// Renders `frame` and encodes it as ANSI text that redraws the screen
+    /// from the top left corner.
+    pub fn render_ansi(&self, frame: &Frame, columns: u32, rows: u32) -> Result<Vec<u8>> {
+        let grid = self.render(frame, columns, rows)?;
+
+        let mut buf = b"\x1b[H".to_vec();
+        write_grid_with(&grid, self.color_mode, &mut buf)?;
+        Ok(buf)
+    }
+
+    fn needs_color(&self) -> bool {
+        self.options.color && self.render_mode.renderer(&self.charset).needs_color()
+    }
+}
+
+struct Shared {
+    settings: RenderSettings,
+    subscribers: Mutex<Vec<SyncSender<Arc<Frame>>>>,
+}
+
+/// Fans captured frames out to subscribers. Clones share the same set of
+/// subscribers.
+#[derive(Clone)]
+pub struct Broadcast {
+    shared: Arc<Shared>,
+}
+
+impl Broadcast {
+    pub fn new(settings: RenderSettings) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                settings,
+                subscribers: Mutex::new(Vec::new()),
+            }),
+        }
+    }
+
+    pub fn settings(&self) -> &RenderSettings {
+        &self.shared.settings
+    }
+
+    /// Adds a subscriber that starts receiving the next captured frame.
+    pub fn subscribe(&self) -> Subscriber {
+        let (sender, frames) = sync_channel(1);
+        self.shared.subscribers.lock().unwrap().push(sender);
+
+        Subscriber {
+            frames,
+            shared: Arc::clone(&self.shared),
+        }
+    }
+
+    /// Number of subscribers that haven't been dropped yet.
+    pub fn subscribers(&self) -> usize {
+        self.shared.subscribers.lock().unwrap().len()
+    }
+
+    /// Decodes `frame` once and queues it for every subscriber with room.
+    pub fn send(&self, frame: &Frame) -> Result<()> {
+        let mut subscribers = self.shared.subscribers.lock().unwrap();
+        if subscribers.is_empty() {
+            return Ok(());
+        }
+
+        // subscribers only have to resize and render, decoding a raw frame
+        // is just a copy
+        let decoded = if self.shared.settings.needs_color() {
+            let img = decode_frame_rgb(frame)?;
+            Frame {
+                width: img.width(),
+                height: img.height(),
+                data: img.into_raw(),
+                pixel_format: PixelFormat::Rgb8,
+                meta: frame.meta,
+            }
+        } else {
+            let img = decode_frame(frame)?;
+            Frame {
+                width: img.width(),
+                height: img.height(),
+                data: img.into_raw(),
+                pixel_format: PixelFormat::Gray8,
+                meta: frame.meta,
+            }
+        };
+        let decoded = Arc::new(decoded);
+
+        // a dropped subscriber has dropped its receiver
+        subscribers.retain(|s| match s.try_send(Arc::clone(&decoded)) {
+            Ok(()) | Err(TrySendError::Full(_)) => true,
+            Err(TrySendError::Disconnected(_)) => false,
+        });
+
+        Ok(())
+    }
+}
+
+impl Sink for Broadcast {
+    // subscribers render captured frames themselves
+    fn write_frame(&mut self, _grid: &Grid) -> Result<()> {
+        Ok(())
+    }
+
+    fn write_captured(&mut self, frame: &Frame) -> Result<()> {
+        self.send(frame)
+    }
+
+    fn clients(&self) -> usize {
+        self.subscribers()
+    }
+}
+
+/// One client of a [`Broadcast`]. Dropping it unsubscribes.
+pub struct Subscriber {
+    frames: Receiver<Arc<Frame>>,
+    shared: Arc<Shared>,
+}
+
+impl Subscriber {
+    /// The queue of decoded frames, holding at most one.
+    pub fn frames(&self) -> &Receiver<Arc<Frame>> {
+        &self.frames
+    }
+
+    pub fn settings(&self) -> &RenderSettings {
+        &self.shared.settings
+    }
+}
@@ -0,0 +1,103 @@
+use super::{CaptureSource, Frame, SourceFormat};
+use eyre::{eyre, Result};
+use std::sync::mpsc::sync_channel;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+
+#[derive(Default)]
+struct Latest {
+    frame: Option<Arc<Frame>>,
+    // the error that stopped the capture thread
+    error: Option<String>,
+}
+
+/// Runs another source on its own thread and hands out whatever frame it
+/// delivered last, without waiting for the next one.
+///
+/// Meant for a second source next to the main one, like a picture in
+/// picture inset, which shouldn't slow the main source down to its own
+/// frame rate.
+pub struct BackgroundSource {
+    latest: Arc<(Mutex<Latest>, Condvar)>,
+    format: SourceFormat,
+}
+
+impl BackgroundSource {
+    /// Opens the source with `open` on the capture thread, so the source
+    /// itself doesn't have to be [`Send`], and waits until it is open.
+    pub fn spawn<S, F>(open: F) -> Result<Self>
+    where
+        S: CaptureSource,
+        F: FnOnce() -> Result<S> + Send + 'static,
+    {
+        let latest = Arc::new((Mutex::new(Latest::default()), Condvar::new()));
+        let (opened, format) = sync_channel(1);
+
+        let shared = Arc::clone(&latest);
+        thread::spawn(move || {
+            let mut source = match open() {
+                Ok(source) => source,
+                Err(e) => {
+                    let _ = opened.send(Err(e));
+                    return;
+                }
+            };
+            let _ = opened.send(Ok(source.format()));
+
+            let (latest, ready) = &*shared;
+            loop {
+                let result = source.next_frame();
+                let mut latest = latest.lock().unwrap();
+                match result {
+                    Ok(frame) => latest.frame = Some(Arc::new(frame)),
+                    Err(e) => latest.error = Some(e.to_string()),
+                }
+                ready.notify_all();
+                if latest.error.is_some() {
+                    return;
+                }
+                // nobody is waiting on a dropped source
+                if Arc::strong_count(&shared) == 1 {
+                    return;
+                }
+            }
+        });
+
+        let format = match format.recv() {
+            Ok(format) => format?,
+            Err(_) => return Err(eyre!("The capture thread stopped while opening")),
+        };
+
+        Ok(Self { latest, format })
+    }
+}
+
+impl CaptureSource for BackgroundSource {
+    /// Returns a copy of the latest frame, see
+    /// [`next_shared`](CaptureSource::next_shared).
+    fn next_frame(&mut self) -> Result<Frame> {
+        self.next_shared().map(|frame| (*frame).clone())
+    }
+
+    /// Returns the latest frame, only blocking until the source has
+    /// delivered its first. The same frame is handed out until the source
+    /// delivers the next.
+    fn next_shared(&mut self) -> Result<Arc<Frame>> {
+        let (latest, ready) = &*self.latest;
+        let mut latest = latest.lock().unwrap();
+
+        loop {
+            if let Some(error) = &latest.error {
+                return Err(eyre!("{error}"));
+            }
+            if let Some(frame) = &latest.frame {
+                return Ok(Arc::clone(frame));
+            }
+            latest = ready.wait(latest).unwrap();
+        }
+    }
+
+    fn format(&self) -> SourceFormat {
+        self.format
+    }
+}
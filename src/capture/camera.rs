@@ -0,0 +1,413 @@
+#[cfg(feature = "h264")]
+use super::h264::H264Decoder;
+use super::mplane::MplaneStream;
+use super::{
+    CaptureSource, Frame, FrameMeta, FramePool, Frames, IoMode, PixelFormat, SourceFormat,
+};
+use crate::convert::{
+    black_levels, decode_frame, decode_frame_rgb, demosaic, unpad_rows, window_gray, BayerPattern,
+    HighBitDepth, LevelWindow,
+};
+use eyre::{eyre, Result};
+use image::{GrayImage, RgbImage};
+use std::io;
+use std::time::{Duration, Instant};
+#[cfg(feature = "h264")]
+use tracing::info;
+use v4l::buffer::{Metadata, Type};
+use v4l::capability::Flags;
+use v4l::io::{mmap, traits::CaptureStream, userptr};
+use v4l::{video::Capture, Device, FourCC};
+
+// buffers queued with the driver, enough for it to keep filling one while
+// we read another
+const BUFFERS: u32 = 4;
+// formats starting with a plane of luma, which is all that's shown of them,
+// like the NV12 the ISPs of SoCs put out
+const LUMA_FORMATS: [&[u8; 4]; 4] = [b"GREY", b"NV12", b"NM12", b"YU12"];
+
+/// A v4l device found by [`list_devices`].
+#[derive(Debug, Clone)]
+pub struct DeviceInfo {
+    pub index: usize,
+    pub path: std::path::PathBuf,
+    pub name: Option<String>,
+}
+
+/// Lists the v4l devices on the system, `/dev/video*`.
+pub fn list_devices() -> Vec<DeviceInfo> {
+    v4l::context::enum_devices()
+        .into_iter()
+        .map(|node| DeviceInfo {
+            index: node.index(),
+            path: node.path().to_path_buf(),
+            name: node.name(),
+        })
+        .collect()
+}
+
+/// Whether `/dev/video{index}` is there, which is cheaper than trying to
+/// open a device that isn't.
+pub(crate) fn device_present(index: usize) -> bool {
+    std::path::Path::new(&format!("/dev/video{index}")).exists()
+}
+
+// the stream of whichever io mode the camera was opened with
+enum Stream {
+    Mmap(mmap::Stream<'static>),
+    UserPtr(userptr::Stream),
+    // for devices with only the multi-planar API
+    Mplane(MplaneStream),
+}
+
+impl Stream {
+    fn open(dev: &Device, io_mode: IoMode) -> Result<Self> {
+        match io_mode {
+            IoMode::Mmap => Ok(Stream::Mmap(mmap::Stream::with_buffers(
+                dev,
+                Type::VideoCapture,
+                BUFFERS,
+            )?)),
+            IoMode::UserPtr => {
+                match userptr::Stream::with_buffers(dev, Type::VideoCapture, BUFFERS) {
+                    Ok(stream) => Ok(Stream::UserPtr(stream)),
+                    Err(e) => Err(eyre!(
+                        "The device doesn't stream into user pointers ({e}), try --io-mode mmap"
+                    )),
+                }
+            }
+        }
+    }
+
+    /// The part of the next buffer the driver filled in.
+    fn next(&mut self) -> io::Result<(&[u8], FrameMeta)> {
+        let (buf, meta) = match self {
+            Stream::Mmap(stream) => CaptureStream::next(stream)?,
+            Stream::UserPtr(stream) => CaptureStream::next(stream)?,
+            Stream::Mplane(stream) => return stream.next(),
+        };
+
+        // the buffer is the size of the whole mapping, bytesused is what the
+        // driver actually filled in (0 if it doesn't report it)
+        let buf = match meta.bytesused as usize {
+            0 => buf,
+            n => &buf[..n.min(buf.len())],
+        };
+        Ok((buf, frame_meta(meta)))
+    }
+}
+
+fn frame_meta(meta: &Metadata) -> FrameMeta {
+    FrameMeta {
+        sequence: meta.sequence.into(),
+        timestamp: Duration::from_secs(meta.timestamp.sec as u64)
+            + Duration::from_micros(meta.timestamp.usec as u64),
+        bytes_used: meta.bytesused,
+        received: Some(Instant::now()),
+    }
+}
+
+/// A v4l capture device streaming MJPEG frames.
+///
+/// Raw sensors without MJPEG, like industrial cameras, stream 8 bit Bayer
+/// frames instead, which are demosaiced as they are captured and come as
+/// [`PixelFormat::Rgb8`], see [`set_black_level`](Camera::set_black_level).
+/// Scientific and low light sensors with 10 to 16 bits per pixel come as
+/// [`PixelFormat::Gray8`], see [`set_window`](Camera::set_window).
+///
+/// Devices with only the multi-planar API, like the ISPs of Rockchip and
+/// Amlogic boards, are streamed through that. Their NV12 frames, like any
+/// other format with luma first, come as [`PixelFormat::Gray8`].
+///
+/// Built with the `h264` feature, a camera that only has the resolution
+/// asked for in H.264 streams that instead. Its frames are decoded as they
+/// are captured and come as [`PixelFormat::Rgb8`].
+pub struct Camera {
+    // the stream only holds on to the device handle, so the device has to
+    // stay alive for as long as we are capturing
+    _dev: Device,
+    stream: Stream,
+    width: u32,
+    height: u32,
+    meta: FrameMeta,
+    // the pattern of a raw sensor that has no MJPEG
+    bayer: Option<BayerPattern>,
+    // what its values become with the black level taken off, and the
+    // buffers its frames are demosaiced into
+    levels: [u8; 256],
+    pool: FramePool,
+    // the format of a sensor with more than 8 bits per pixel
+    high_bit_depth: Option<HighBitDepth>,
+    window: Option<LevelWindow>,
+    // a format of which only the luma is shown
+    luma: bool,
+    // bytes from one row to the next of raw frames
+    stride: usize,
+    #[cfg(feature = "h264")]
+    h264: Option<H264Decoder>,
+}
+
+impl Camera {
+    /// Opens `/dev/video{index}` and starts an MJPEG capture stream.
+    pub fn open(index: usize) -> Result<Self> {
+        Self::open_with(index, None)
+    }
+
+    /// Like [`open`](Camera::open), but asks the device for a specific
+    /// `(width, height)`. The device may pick the closest size it supports.
+    pub fn open_with(index: usize, resolution: Option<(u32, u32)>) -> Result<Self> {
+        Self::open_io(index, resolution, IoMode::Mmap)
+    }
+
+    /// Like [`open_with`](Camera::open_with), streaming with `io_mode`.
+    pub fn open_io(index: usize, resolution: Option<(u32, u32)>, io_mode: IoMode) -> Result<Self> {
+        let dev = match Device::new(index) {
+            Ok(dev) => dev,
+            Err(_) => {
+                return Err(eyre!(
+                    "Could not find device '{index}'. Is a webcam available / plugged in?"
+                ))
+            }
+        };
+
+        let capabilities = dev.query_caps()?.capabilities;
+        let (fmt, stream) = if !capabilities.contains(Flags::VIDEO_CAPTURE)
+            && capabilities.contains(Flags::VIDEO_CAPTURE_MPLANE)
+        {
+            if io_mode != IoMode::Mmap {
+                return Err(eyre!(
+                    "The device only has the multi-planar API, which streams with --io-mode mmap"
+                ));
+            }
+            let (stream, fmt) =
+                MplaneStream::open(&dev, FourCC::new(b"MJPG"), resolution, BUFFERS)?;
+            (fmt, Stream::Mplane(stream))
+        } else {
+            let mut fmt = dev.format()?;
+
+            fmt.fourcc = FourCC::new(b"MJPG");
+            if let Some((width, height)) = resolution {
+                fmt.width = width;
+                fmt.height = height;
+            }
+            let fmt = dev.set_format(&fmt)?;
+            #[cfg(feature = "h264")]
+            let fmt = match resolution {
+                Some(size) if (fmt.width, fmt.height) != size => prefer_h264(&dev, fmt, size)?,
+                _ => fmt,
+            };
+
+            (fmt, Stream::open(&dev, io_mode)?)
+        };
+
+        Ok(Self {
+            _dev: dev,
+            stream,
+            width: fmt.width,
+            height: fmt.height,
+            meta: FrameMeta::default(),
+            bayer: BayerPattern::from_fourcc(&fmt.fourcc.repr),
+            levels: black_levels(0),
+            pool: FramePool::new(),
+            high_bit_depth: HighBitDepth::from_fourcc(&fmt.fourcc.repr),
+            window: None,
+            luma: LUMA_FORMATS.contains(&&fmt.fourcc.repr),
+            stride: fmt.stride as usize,
+            #[cfg(feature = "h264")]
+            h264: if fmt.fourcc == FourCC::new(b"H264") {
+                Some(H264Decoder::new()?)
+            } else {
+                None
+            },
+        })
+    }
+
+    /// Decodes buffers from `data` on until one holds a picture.
+    #[cfg(feature = "h264")]
+    fn decode_h264(&mut self, mut data: Vec<u8>) -> Result<Frame> {
+        let mut rgb = Vec::new();
+        while let Some(decoder) = &mut self.h264 {
+            if let Some((width, height)) = decoder.decode(&data, &mut rgb)? {
+                return Ok(Frame {
+                    data: rgb,
+                    width,
+                    height,
+                    pixel_format: PixelFormat::Rgb8,
+                    meta: self.meta,
+                });
+            }
+            data = self.next_buffer()?.to_vec();
+        }
+
+        Err(eyre!("The camera isn't streaming H.264"))
+    }
+
+    fn pixel_format(&self) -> PixelFormat {
+        if self.bayer.is_some() {
+            return PixelFormat::Rgb8;
+        }
+        if self.high_bit_depth.is_some() || self.luma {
+            return PixelFormat::Gray8;
+        }
+        #[cfg(feature = "h264")]
+        if self.h264.is_some() {
+            return PixelFormat::Rgb8;
+        }
+
+        PixelFormat::Mjpeg
+    }
+
+    /// What a raw sensor reads in the dark, out of 255, to take off every
+    /// pixel. Only raw sensors have one, it's 16 on the Raspberry Pi HQ
+    /// camera. Defaults to 0.
+    pub fn set_black_level(&mut self, black_level: u8) {
+        self.levels = black_levels(black_level);
+    }
+
+    /// The sensor values mapped onto black to white for sensors with more
+    /// than 8 bits per pixel, `None` for the whole range of the format,
+    /// which is the default.
+    pub fn set_window(&mut self, window: Option<LevelWindow>) {
+        self.window = window;
+    }
+
+    /// Width of the captured frames in pixels.
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Height of the captured frames in pixels.
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Iterates over decoded grayscale frames.
+    ///
+    /// ```no_run
+    /// # fn main() -> eyre::Result<()> {
+    /// let mut cam = asciicam::Camera::open(0)?;
+    /// for frame in cam.frames().take(100) {
+    ///     let frame = frame?;
+    ///     println!("{:?}: {}x{}", frame.timestamp, frame.image.width(), frame.image.height());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn frames(&mut self) -> Frames<'_, Self, GrayImage> {
+        Frames::new(self, decode_frame)
+    }
+
+    /// Iterates over decoded color frames.
+    pub fn rgb_frames(&mut self) -> Frames<'_, Self, RgbImage> {
+        Frames::new(self, decode_frame_rgb)
+    }
+
+    /// Blocks until the next MJPEG buffer is available.
+    pub fn next_buffer(&mut self) -> Result<&[u8]> {
+        let (buf, meta) = self.stream.next()?;
+        self.meta = meta;
+
+        Ok(buf)
+    }
+
+    /// Metadata of the buffer last returned by
+    /// [`next_buffer`](Camera::next_buffer).
+    pub fn meta(&self) -> FrameMeta {
+        self.meta
+    }
+}
+
+impl CaptureSource for Camera {
+    fn next_frame(&mut self) -> Result<Frame> {
+        if let Some(pattern) = self.bayer {
+            let (width, height, stride, levels) =
+                (self.width, self.height, self.stride, self.levels);
+            let mut data = self.pool.take();
+            let demosaiced = self
+                .next_buffer()
+                .and_then(|raw| demosaic(raw, width, height, stride, pattern, &levels, &mut data));
+            if let Err(e) = demosaiced {
+                self.pool.give(data);
+                return Err(e);
+            }
+
+            return Ok(Frame {
+                data,
+                width,
+                height,
+                pixel_format: PixelFormat::Rgb8,
+                meta: self.meta,
+            });
+        }
+        if let Some(format) = self.high_bit_depth {
+            let (width, height, stride, window) =
+                (self.width, self.height, self.stride, self.window);
+            let data = window_gray(self.next_buffer()?, width, height, stride, format, window);
+
+            return Ok(Frame {
+                data,
+                width,
+                height,
+                pixel_format: PixelFormat::Gray8,
+                meta: self.meta,
+            });
+        }
+
+        if self.luma {
+            let (width, height, stride) = (self.width, self.height, self.stride);
+            let data = unpad_rows(self.next_buffer()?, stride, width as usize, height);
+
+            return Ok(Frame {
+                data,
+                width,
+                height,
+                pixel_format: PixelFormat::Gray8,
+                meta: self.meta,
+            });
+        }
+
+        let data = self.next_buffer()?.to_vec();
+        #[cfg(feature = "h264")]
+        if self.h264.is_some() {
+            return self.decode_h264(data);
+        }
+
+        Ok(Frame {
+            data,
+            width: self.width,
+            height: self.height,
+            pixel_format: PixelFormat::Mjpeg,
+            meta: self.meta,
+        })
+    }
+
+    fn format(&self) -> SourceFormat {
+        SourceFormat {
+            width: self.width,
+            height: self.height,
+            pixel_format: self.pixel_format(),
+        }
+    }
+
+    fn set_pool(&mut self, pool: FramePool) {
+        self.pool = pool;
+    }
+}
+
+/// Switches the device to H.264 when it has `size` in that, but gave
+/// `mjpeg` a smaller one.
+#[cfg(feature = "h264")]
+fn prefer_h264(dev: &Device, mjpeg: v4l::Format, size: (u32, u32)) -> Result<v4l::Format> {
+    let h264 = dev.set_format(&v4l::Format::new(size.0, size.1, FourCC::new(b"H264")))?;
+    let pixels = |fmt: &v4l::Format| fmt.width as u64 * fmt.height as u64;
+    if h264.fourcc == FourCC::new(b"H264") && pixels(&h264) > pixels(&mjpeg) {
+        info!(
+            "streaming H.264 at {}x{}, MJPEG only goes up to {}x{}",
+            h264.width, h264.height, mjpeg.width, mjpeg.height
+        );
+        return Ok(h264);
+    }
+
+    // not H.264, or no bigger in it
+    Ok(dev.set_format(&mjpeg)?)
+}
@@ -0,0 +1,602 @@
+use eyre::{eyre, Result};
+use std::fmt;
+use std::fs;
+#[cfg(target_os = "linux")]
+use std::mem;
+#[cfg(target_os = "linux")]
+use std::os::raw::c_void;
+use std::path::Path;
+#[cfg(target_os = "linux")]
+use v4l::{
+    control::{Control, Description, Flags, MenuItem, Type, Value},
+    v4l2::{self, vidioc},
+    v4l_sys::{v4l2_event, v4l2_event_subscription, V4L2_EVENT_CTRL},
+    Device,
+};
+
+/// What kind of value a [`ControlInfo`] takes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ControlKind {
+    Integer,
+    Boolean,
+    /// One of the listed `(value, label)` choices.
+    Menu(Vec<(i64, String)>),
+    /// Does something when set, has no value.
+    Button,
+}
+
+/// A camera control like brightness or exposure, as listed by
+/// [`Controls::list`].
+#[derive(Debug, Clone)]
+pub struct ControlInfo {
+    pub id: u32,
+    /// Name as the driver reports it, e.g. `Exposure, Auto`.
+    pub name: String,
+    /// [`name`](ControlInfo::name) the way `v4l2-ctl` spells it, e.g.
+    /// `exposure_auto`, which is what [`Controls::get`] and
+    /// [`Controls::set`] take.
+    pub key: String,
+    pub kind: ControlKind,
+    pub minimum: i64,
+    pub maximum: i64,
+    pub step: u64,
+    pub default: i64,
+    /// `None` for buttons and controls that can't be read right now, like
+    /// manual exposure while auto exposure is on.
+    pub value: Option<i64>,
+    /// Whether the control can't be changed at the moment.
+    pub inactive: bool,
+}
+
+impl fmt::Display for ControlInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.kind {
+            ControlKind::Integer => "int",
+            ControlKind::Boolean => "bool",
+            ControlKind::Menu(_) => "menu",
+            ControlKind::Button => "button",
+        };
+        write!(f, "{} ({kind})", self.key)?;
+
+        if let Some(value) = self.value {
+            write!(f, " = {value}")?;
+        }
+        match &self.kind {
+            ControlKind::Integer => write!(
+                f,
+                ", {}..{} step {}, default {}",
+                self.minimum, self.maximum, self.step, self.default
+            )?,
+            ControlKind::Boolean => write!(f, ", default {}", self.default)?,
+            ControlKind::Menu(items) => {
+                write!(f, ", default {}:", self.default)?;
+                for (value, label) in items {
+                    write!(f, " {value}={label}")?;
+                }
+            }
+            ControlKind::Button => (),
+        }
+        if self.inactive {
+            write!(f, " (inactive)")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A control that drivers know under different names, like exposure
+/// which newer kernels call `exposure_time_absolute`, along with the
+/// automatic mode that has to be turned off before it can be set by hand.
+#[derive(Debug, Clone, Copy)]
+pub struct CameraControl {
+    /// What to call it in messages.
+    pub label: &'static str,
+    /// [`ControlInfo::key`]s, the first one the camera has is used.
+    pub keys: &'static [&'static str],
+    /// Keys of the automatic mode control and its value that means manual.
+    pub manual: Option<(&'static [&'static str], i64)>,
+}
+
+pub const EXPOSURE: CameraControl = CameraControl {
+    label: "exposure",
+    keys: &["exposure_time_absolute", "exposure_absolute"],
+    // V4L2_EXPOSURE_MANUAL
+    manual: Some((&["auto_exposure", "exposure_auto"], 1)),
+};
+
+pub const GAIN: CameraControl = CameraControl {
+    label: "gain",
+    keys: &["gain", "analogue_gain"],
+    manual: Some((&["gain_automatic", "autogain"], 0)),
+};
+
+pub const FOCUS: CameraControl = CameraControl {
+    label: "focus",
+    keys: &["focus_absolute"],
+    manual: Some((&["focus_automatic_continuous", "focus_auto"], 0)),
+};
+
+pub const ZOOM: CameraControl = CameraControl {
+    label: "zoom",
+    keys: &["zoom_absolute"],
+    manual: None,
+};
+
+/// White balance as a color temperature in kelvin, for the color modes.
+pub const WHITE_BALANCE: CameraControl = CameraControl {
+    label: "white balance",
+    keys: &["white_balance_temperature"],
+    manual: Some((
+        &["white_balance_automatic", "white_balance_temperature_auto"],
+        0,
+    )),
+};
+
+/// Panning of a PTZ camera, by absolute position where it can, the
+/// relative control moves it by the step taken otherwise.
+pub const PAN: CameraControl = CameraControl {
+    label: "pan",
+    keys: &["pan_absolute", "pan_relative"],
+    manual: None,
+};
+
+/// Tilting of a PTZ camera, like [`PAN`].
+pub const TILT: CameraControl = CameraControl {
+    label: "tilt",
+    keys: &["tilt_absolute", "tilt_relative"],
+    manual: None,
+};
+
+/// Anti-flicker filter for the mains frequency of the lights, a menu of
+/// 0 for off, 1 for 50 Hz, 2 for 60 Hz and 3 for automatic on cameras that
+/// have that.
+pub const POWER_LINE_FREQUENCY: CameraControl = CameraControl {
+    label: "power line frequency",
+    keys: &["power_line_frequency"],
+    manual: None,
+};
+
+// a step of the adjustment keys is this fraction of the control's range
+const ADJUST_STEPS: i64 = 32;
+
+/// The controls of a v4l device, through a handle of its own so they can be
+/// changed while a [`Camera`](super::Camera) is capturing from it.
+///
+/// Other systems have no v4l, [`open`](Controls::open) always fails there.
+pub struct Controls {
+    #[cfg(target_os = "linux")]
+    dev: Device,
+    #[cfg(not(target_os = "linux"))]
+    unsupported: std::convert::Infallible,
+}
+
+impl Controls {
+    /// Opens the controls of `/dev/video{index}` like [`open`](Controls::open),
+    /// `None` when the camera has none, like the ones captured through
+    /// nokhwa or termux off linux. That is only an error when some are
+    /// `required`.
+    pub fn open_optional(index: usize, required: bool) -> Result<Option<Self>> {
+        match Self::open(index) {
+            Ok(controls) => Ok(Some(controls)),
+            Err(e) if required => Err(e),
+            Err(_) => Ok(None),
+        }
+    }
+
+    /// Looks up a control by its [`key`](ControlInfo::key).
+    pub fn find(&self, key: &str) -> Result<ControlInfo> {
+        let controls = self.list()?;
+        let key = control_key(key);
+
+        match controls.into_iter().find(|control| control.key == key) {
+            Some(control) => Ok(control),
+            None => Err(eyre!(
+                "The camera has no control '{key}', see asciicam controls --list"
+            )),
+        }
+    }
+
+    /// Looks up the first of the names of `control` the camera has.
+    pub fn lookup(&self, control: &CameraControl) -> Result<ControlInfo> {
+        let controls = self.list()?;
+
+        for key in control.keys {
+            if let Some(info) = controls.iter().find(|info| info.key == *key) {
+                return Ok(info.clone());
+            }
+        }
+
+        Err(eyre!("The camera has no {} control", control.label))
+    }
+
+    /// Turns off the automatic mode of `control`, if it has one.
+    pub fn set_manual(&self, control: &CameraControl) -> Result<()> {
+        let (keys, manual) = match control.manual {
+            Some(manual) => manual,
+            None => return Ok(()),
+        };
+
+        let controls = self.list()?;
+        for key in keys {
+            if let Some(auto) = controls.iter().find(|info| info.key == *key) {
+                if auto.value != Some(manual) {
+                    self.write(auto, manual)?;
+                }
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Turns the automatic mode of `control` back on, to the driver's
+    /// default automatic mode.
+    pub fn set_auto(&self, control: &CameraControl) -> Result<()> {
+        let keys = match control.manual {
+            Some((keys, _)) => keys,
+            None => {
+                return Err(eyre!(
+                    "The camera's {} has no automatic mode",
+                    control.label
+                ))
+            }
+        };
+
+        let controls = self.list()?;
+        for key in keys {
+            if let Some(auto) = controls.iter().find(|info| info.key == *key) {
+                return self.write(auto, auto.default);
+            }
+        }
+
+        Err(eyre!("The camera has no automatic {}", control.label))
+    }
+
+    /// Switches `control` to manual and sets it to `value`.
+    pub fn set_to(&self, control: &CameraControl, value: i64) -> Result<()> {
+        self.set_manual(control)?;
+        let info = self.lookup(control)?;
+        self.write(&info, value)
+    }
+
+    /// Moves `control` by `steps` steps of a 32nd of its range, switching
+    /// it to manual first, and returns it with its new value.
+    pub fn adjust(&self, control: &CameraControl, steps: i64) -> Result<ControlInfo> {
+        self.set_manual(control)?;
+        let mut info = self.lookup(control)?;
+
+        // whole multiples of the control's own step
+        let unit = (info.step as i64).max(1);
+        let step = ((info.maximum - info.minimum) / ADJUST_STEPS / unit).max(1) * unit;
+        let value =
+            (info.value.unwrap_or(info.default) + steps * step).clamp(info.minimum, info.maximum);
+
+        self.write(&info, value)?;
+        info.value = Some(value);
+
+        Ok(info)
+    }
+
+    /// The current value of the control called `key`.
+    pub fn get(&self, key: &str) -> Result<i64> {
+        let control = self.find(key)?;
+        self.read(control.id)
+    }
+
+    /// Sets the control called `key` to `value`, which has to be in its
+    /// range.
+    pub fn set(&self, key: &str, value: i64) -> Result<()> {
+        let control = self.find(key)?;
+        self.write(&control, value)
+    }
+
+    /// Writes the values of all the controls that can be changed right now
+    /// to a profile at `path`, a toml file of `name = value` lines that
+    /// [`load_profile`](Controls::load_profile) restores.
+    pub fn save_profile(&self, path: impl AsRef<Path>) -> Result<()> {
+        let mut profile = format!("# asciicam controls of {}\n", self.card());
+
+        let mut controls = self.list()?;
+        // automatic modes come first, they decide which of the manual
+        // controls can be set when the profile is loaded
+        controls.sort_by_key(|control| !control.key.contains("auto"));
+        for control in controls {
+            if let (false, Some(value)) = (control.inactive, control.value) {
+                profile.push_str(&format!("{} = {value}\n", control.key));
+            }
+        }
+
+        if let Err(e) = fs::write(path.as_ref(), profile) {
+            return Err(eyre!("Could not write '{}': {e}", path.as_ref().display()));
+        }
+
+        Ok(())
+    }
+
+    /// Sets the controls to the values in a profile written by
+    /// [`save_profile`](Controls::save_profile), in the order they are
+    /// listed.
+    pub fn load_profile(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let profile = match fs::read_to_string(path) {
+            Ok(profile) => profile,
+            Err(e) => return Err(eyre!("Could not read '{}': {e}", path.display())),
+        };
+
+        for (number, line) in profile.lines().enumerate() {
+            let line = line.trim();
+            // tables are allowed for hand written profiles, but don't mean
+            // anything
+            if line.is_empty() || line.starts_with('#') || line.starts_with('[') {
+                continue;
+            }
+
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => {
+                    return Err(eyre!(
+                        "{}:{}: expected name = value",
+                        path.display(),
+                        number + 1
+                    ))
+                }
+            };
+            let value = match value {
+                "true" => 1,
+                "false" => 0,
+                value => match value.parse() {
+                    Ok(value) => value,
+                    Err(_) => {
+                        return Err(eyre!(
+                            "{}:{}: '{value}' is not a number",
+                            path.display(),
+                            number + 1
+                        ))
+                    }
+                },
+            };
+            self.set(key, value)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Controls {
+    /// Opens the controls of `/dev/video{index}`.
+    pub fn open(index: usize) -> Result<Self> {
+        match Device::new(index) {
+            Ok(dev) => Ok(Self { dev }),
+            Err(_) => Err(eyre!(
+                "Could not find device '{index}'. Is a webcam available / plugged in?"
+            )),
+        }
+    }
+
+    /// Lists the controls the device has, with their current values.
+    pub fn list(&self) -> Result<Vec<ControlInfo>> {
+        let mut controls = Vec::new();
+
+        for desc in self.dev.query_controls()? {
+            if desc.flags.contains(Flags::DISABLED) {
+                continue;
+            }
+            let kind = match control_kind(&desc) {
+                Some(kind) => kind,
+                // control class headings, strings and compound controls
+                None => continue,
+            };
+
+            let value = match kind {
+                ControlKind::Button => None,
+                _ => self.read(desc.id).ok(),
+            };
+            controls.push(ControlInfo {
+                id: desc.id,
+                key: control_key(&desc.name),
+                name: desc.name,
+                kind,
+                minimum: desc.minimum,
+                maximum: desc.maximum,
+                step: desc.step,
+                default: desc.default,
+                value,
+                inactive: desc.flags.contains(Flags::INACTIVE)
+                    || desc.flags.contains(Flags::READ_ONLY),
+            });
+        }
+
+        Ok(controls)
+    }
+
+    /// Sets `control` to `value`, without looking it up by name again.
+    pub fn write(&self, control: &ControlInfo, value: i64) -> Result<()> {
+        let in_range = match &control.kind {
+            ControlKind::Menu(items) => items.iter().any(|(item, _)| *item == value),
+            ControlKind::Button => true,
+            _ => (control.minimum..=control.maximum).contains(&value),
+        };
+        if !in_range {
+            return Err(eyre!(
+                "{value} is out of range for '{}', see asciicam controls --list",
+                control.key
+            ));
+        }
+
+        let value = match control.kind {
+            ControlKind::Boolean => Value::Boolean(value != 0),
+            ControlKind::Button => Value::None,
+            _ => Value::Integer(value),
+        };
+        if let Err(e) = self.dev.set_control(Control {
+            id: control.id,
+            value,
+        }) {
+            return Err(eyre!("Could not set '{}': {e}", control.key));
+        }
+
+        Ok(())
+    }
+
+    /// Asks the driver to report changes other programs, like `v4l2-ctl`
+    /// or guvcview, make to the controls, see [`changes`](Controls::changes).
+    /// Changes made through these controls aren't reported back.
+    pub fn subscribe(&self) -> Result<()> {
+        let fd = self.dev.handle().fd();
+
+        for control in self.list()? {
+            // all zeroes is a valid subscription, filled in below, and it
+            // outlives the call
+            let mut subscription: v4l2_event_subscription = unsafe { mem::zeroed() };
+            subscription.type_ = V4L2_EVENT_CTRL;
+            subscription.id = control.id;
+            let result = unsafe {
+                v4l2::ioctl(
+                    fd,
+                    vidioc::VIDIOC_SUBSCRIBE_EVENT,
+                    &mut subscription as *mut _ as *mut c_void,
+                )
+            };
+            if let Err(e) = result {
+                return Err(eyre!("Could not watch '{}' for changes: {e}", control.key));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The controls that changed since the last call, with their new values,
+    /// when [`subscribe`](Controls::subscribe)d. Doesn't wait for changes.
+    pub fn changes(&self) -> Result<Vec<ControlInfo>> {
+        let fd = self.dev.handle().fd();
+        let mut changed = Vec::new();
+
+        // the device is opened non-blocking, so dequeueing fails once there
+        // are no more events
+        loop {
+            // all zeroes is a valid event for the driver to fill in
+            let mut event: v4l2_event = unsafe { mem::zeroed() };
+            let result = unsafe {
+                v4l2::ioctl(
+                    fd,
+                    vidioc::VIDIOC_DQEVENT,
+                    &mut event as *mut _ as *mut c_void,
+                )
+            };
+            if result.is_err() {
+                break;
+            }
+            if event.type_ == V4L2_EVENT_CTRL && !changed.contains(&event.id) {
+                changed.push(event.id);
+            }
+        }
+        if changed.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        // read them again rather than out of the event, which has the flags
+        // and range too but is a union per type
+        let controls = self.list()?;
+        Ok(controls
+            .into_iter()
+            .filter(|control| changed.contains(&control.id))
+            .collect())
+    }
+
+    fn read(&self, id: u32) -> Result<i64> {
+        match self.dev.control(id)?.value {
+            Value::Integer(value) => Ok(value),
+            Value::Boolean(value) => Ok(value as i64),
+            _ => Err(eyre!("Control {id:#x} has no integer value")),
+        }
+    }
+
+    fn card(&self) -> String {
+        match self.dev.query_caps() {
+            Ok(caps) => caps.card,
+            Err(_) => "unknown camera".to_string(),
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+impl Controls {
+    pub fn open(_index: usize) -> Result<Self> {
+        // android is linux underneath, but termux can't get at the v4l nodes
+        if cfg!(target_os = "android") {
+            return Err(eyre!("The termux camera has no controls"));
+        }
+        Err(eyre!("Camera controls need v4l, which is only on Linux"))
+    }
+
+    pub fn list(&self) -> Result<Vec<ControlInfo>> {
+        match self.unsupported {}
+    }
+
+    pub fn write(&self, _control: &ControlInfo, _value: i64) -> Result<()> {
+        match self.unsupported {}
+    }
+
+    pub fn subscribe(&self) -> Result<()> {
+        match self.unsupported {}
+    }
+
+    pub fn changes(&self) -> Result<Vec<ControlInfo>> {
+        match self.unsupported {}
+    }
+
+    fn read(&self, _id: u32) -> Result<i64> {
+        match self.unsupported {}
+    }
+
+    fn card(&self) -> String {
+        match self.unsupported {}
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn control_kind(desc: &Description) -> Option<ControlKind> {
+    let kind = match desc.typ {
+        Type::Integer | Type::Integer64 => ControlKind::Integer,
+        Type::Boolean => ControlKind::Boolean,
+        Type::Button => ControlKind::Button,
+        Type::Menu | Type::IntegerMenu => {
+            let items = desc.items.as_deref().unwrap_or_default();
+            ControlKind::Menu(
+                items
+                    .iter()
+                    .map(|(value, item)| {
+                        let label = match item {
+                            MenuItem::Name(name) => name.clone(),
+                            MenuItem::Value(value) => value.to_string(),
+                        };
+                        (*value as i64, label)
+                    })
+                    .collect(),
+            )
+        }
+        _ => return None,
+    };
+
+    Some(kind)
+}
+
+/// Spells a control name like `v4l2-ctl` does: lowercase, with every run of
+/// other characters than letters and digits turned into one `_`.
+fn control_key(name: &str) -> String {
+    let mut key = String::with_capacity(name.len());
+
+    for c in name.chars() {
+        if c.is_ascii_alphanumeric() {
+            key.push(c.to_ascii_lowercase());
+        } else if !key.is_empty() && !key.ends_with('_') {
+            key.push('_');
+        }
+    }
+    if key.ends_with('_') {
+        key.pop();
+    }
+
+    key
+}
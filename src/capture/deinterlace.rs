@@ -0,0 +1,186 @@
+use super::{CaptureSource, Frame, FrameMeta, PixelFormat, Playback, SourceFormat};
+use crate::convert::decode_frame_rgb;
+use eyre::{eyre, Report, Result};
+use std::str::FromStr;
+
+/// How a [`DeinterlacedSource`] puts fields back together.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Deinterlace {
+    /// For frames of two interlaced fields, which comb wherever something
+    /// moves: each field is shown on its own, with the lines of the other
+    /// filled in between its own, at twice the frame rate.
+    Bob,
+    /// For sources that deliver every field on its own at half the
+    /// height: each two are interleaved into a full frame, at half the
+    /// rate.
+    Weave,
+}
+
+impl FromStr for Deinterlace {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "bob" => Ok(Deinterlace::Bob),
+            "weave" => Ok(Deinterlace::Weave),
+            _ => Err(eyre!("Unknown deinterlacing '{s}', expected bob or weave")),
+        }
+    }
+}
+
+/// Deinterlaces the frames of another source, like an analog capture
+/// dongle with a composite camera or a VCR on it. Frames come as
+/// [`PixelFormat::Rgb8`].
+pub struct DeinterlacedSource<S> {
+    source: S,
+    mode: Deinterlace,
+    // the second field of the frame bob delivered the first of
+    pending: Option<Frame>,
+}
+
+impl<S: CaptureSource> DeinterlacedSource<S> {
+    pub fn new(source: S, mode: Deinterlace) -> Self {
+        Self {
+            source,
+            mode,
+            pending: None,
+        }
+    }
+
+    fn next_rgb(&mut self) -> Result<Frame> {
+        let frame = self.source.next_frame()?;
+        let img = decode_frame_rgb(&frame)?;
+
+        Ok(Frame {
+            width: img.width(),
+            height: img.height(),
+            data: img.into_raw(),
+            pixel_format: PixelFormat::Rgb8,
+            meta: frame.meta,
+        })
+    }
+}
+
+impl<S: CaptureSource> CaptureSource for DeinterlacedSource<S> {
+    fn next_frame(&mut self) -> Result<Frame> {
+        match self.mode {
+            Deinterlace::Bob => {
+                if let Some(frame) = self.pending.take() {
+                    return Ok(frame);
+                }
+
+                let frame = self.next_rgb()?;
+                // sequence numbers stay without gaps at twice the rate
+                let meta = |field: u64| FrameMeta {
+                    sequence: frame.meta.sequence * 2 + field,
+                    ..frame.meta
+                };
+                self.pending = Some(Frame {
+                    data: bob(&frame, 1),
+                    meta: meta(1),
+                    ..frame.clone()
+                });
+                Ok(Frame {
+                    data: bob(&frame, 0),
+                    meta: meta(0),
+                    ..frame
+                })
+            }
+            Deinterlace::Weave => {
+                let first = self.next_rgb()?;
+                let second = self.next_rgb()?;
+
+                Ok(Frame {
+                    data: weave(&first, &second)?,
+                    height: first.height * 2,
+                    meta: FrameMeta {
+                        sequence: first.meta.sequence / 2,
+                        ..first.meta
+                    },
+                    ..first
+                })
+            }
+        }
+    }
+
+    fn format(&self) -> SourceFormat {
+        let format = self.source.format();
+        SourceFormat {
+            height: match self.mode {
+                Deinterlace::Bob => format.height,
+                Deinterlace::Weave => format.height * 2,
+            },
+            pixel_format: PixelFormat::Rgb8,
+            ..format
+        }
+    }
+
+    fn playback(&mut self) -> Option<&mut dyn Playback> {
+        self.source.playback()
+    }
+
+    fn status(&self) -> Option<String> {
+        self.source.status()
+    }
+
+    fn next_stream(&mut self) -> Result<Option<String>> {
+        self.pending = None;
+        self.source.next_stream()
+    }
+
+    fn reconnects(&self) -> u64 {
+        self.source.reconnects()
+    }
+}
+
+/// The field of the rows with `parity`, the lines between them the average
+/// of the ones above and below.
+fn bob(frame: &Frame, parity: usize) -> Vec<u8> {
+    let (row, height) = (frame.width as usize * 3, frame.height as usize);
+    let mut out = frame.data.clone();
+    if height < 2 {
+        return out;
+    }
+
+    for y in (0..height).filter(|y| y % 2 != parity) {
+        // the nearest rows of the field, one of them past the edge at the
+        // top or bottom
+        let above = if y > 0 { y - 1 } else { y + 1 };
+        let below = if y + 1 < height { y + 1 } else { y - 1 };
+        for x in 0..row {
+            let sum = frame.data[above * row + x] as u16 + frame.data[below * row + x] as u16;
+            out[y * row + x] = (sum / 2) as u8;
+        }
+    }
+
+    out
+}
+
+/// `first` on the even rows and `second` on the odd ones.
+fn weave(first: &Frame, second: &Frame) -> Result<Vec<u8>> {
+    if (first.width, first.height) != (second.width, second.height) {
+        return Err(eyre!(
+            "Fields of different sizes, {}x{} and {}x{}, can't be woven",
+            first.width,
+            first.height,
+            second.width,
+            second.height
+        ));
+    }
+
+    let row = first.width as usize * 3;
+    if row == 0 {
+        return Ok(Vec::new());
+    }
+    let mut out = Vec::with_capacity(first.data.len() * 2);
+    for (even, odd) in first
+        .data
+        .chunks_exact(row)
+        .zip(second.data.chunks_exact(row))
+    {
+        out.extend_from_slice(even);
+        out.extend_from_slice(odd);
+    }
+
+    Ok(out)
+}
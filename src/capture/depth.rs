@@ -0,0 +1,304 @@
+use super::{CaptureSource, Frame, FrameMeta, PixelFormat, SourceFormat};
+use crate::convert::{unpad_rows, yuyv_to_rgb};
+use eyre::{eyre, Report, Result};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use v4l::{
+    buffer::Type, io::mmap::Stream, io::traits::CaptureStream, video::Capture, Device, FourCC,
+};
+
+// every how many pixels one is looked at to find the range of a frame
+const RANGE_SAMPLE_STEP: usize = 7;
+// the share of the nearest and the farthest pixels left out of the range,
+// so a few noisy ones don't squash everything else together
+const RANGE_OUTLIERS: f32 = 0.02;
+// how fast the automatic range follows the scene, as the share of the new
+// range mixed into the old one every frame. keeps the picture from pumping
+const RANGE_SMOOTHING: f32 = 0.2;
+
+/// The streams of a depth camera, each a v4l node of its own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DepthStream {
+    /// Distances in millimeters, 16 bits per pixel.
+    Depth,
+    /// The infrared image the depth is computed from.
+    Infrared,
+    Color,
+}
+
+impl DepthStream {
+    fn fourcc(self) -> FourCC {
+        match self {
+            DepthStream::Depth => FourCC::new(b"Z16 "),
+            DepthStream::Infrared => FourCC::new(b"GREY"),
+            DepthStream::Color => FourCC::new(b"YUYV"),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            DepthStream::Depth => "depth",
+            DepthStream::Infrared => "infrared",
+            DepthStream::Color => "color",
+        }
+    }
+}
+
+/// The distances in millimeters mapped onto the charset, anything nearer
+/// than `near` is as dense as it gets and anything past `far` is left
+/// blank.
+///
+/// Parsed from meters, `0.3-2.5`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DepthRange {
+    pub near: f32,
+    pub far: f32,
+}
+
+impl FromStr for DepthRange {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (near, far) = match s.split_once('-') {
+            Some((near, far)) => (near.parse::<f32>()?, far.parse::<f32>()?),
+            None => return Err(eyre!("Expected a range in meters like 0.3-2.5, got '{s}'")),
+        };
+        if !(near >= 0.0 && far > near) {
+            return Err(eyre!("The far end of '{s}' has to be past the near one"));
+        }
+
+        Ok(Self {
+            near: near * 1000.0,
+            far: far * 1000.0,
+        })
+    }
+}
+
+/// A depth camera like an Intel RealSense, whose depth map is rendered with
+/// near things dense and far ones sparse.
+///
+/// Depth cameras show up as several v4l nodes on the same USB device, one
+/// per stream. Opened with the node of the depth stream, the infrared and
+/// color nodes next to it are found too and
+/// [`next_stream`](CaptureSource::next_stream) switches between them.
+pub struct DepthCamera {
+    // the node of each stream the camera has, depth first
+    streams: Vec<(DepthStream, usize)>,
+    current: usize,
+    capture: Capture,
+    // None follows the scene
+    fixed_range: Option<DepthRange>,
+    range: Option<DepthRange>,
+}
+
+/// One stream being captured.
+struct Capture {
+    kind: DepthStream,
+    // the stream only holds on to the device handle, so the device has to
+    // stay alive for as long as we are capturing
+    _dev: Device,
+    stream: Stream<'static>,
+    width: u32,
+    height: u32,
+    stride: usize,
+}
+
+impl DepthCamera {
+    /// Opens the depth stream of `/dev/video{index}`, with the distances in
+    /// `range` or a range that follows the scene.
+    pub fn open(index: usize, range: Option<DepthRange>) -> Result<Self> {
+        let dev = match Device::new(index) {
+            Ok(dev) => dev,
+            Err(_) => {
+                return Err(eyre!(
+                    "Could not find device '{index}'. Is a depth camera plugged in?"
+                ))
+            }
+        };
+        if !has_format(&dev, DepthStream::Depth) {
+            return Err(eyre!("/dev/video{index} has no depth stream (Z16)"));
+        }
+        let bus = dev.query_caps()?.bus;
+        drop(dev);
+
+        // the other streams are on the nodes of the same device
+        let mut streams = vec![(DepthStream::Depth, index)];
+        let mut nodes: Vec<usize> = v4l::context::enum_devices()
+            .iter()
+            .map(|node| node.index())
+            .filter(|&node| node != index)
+            .collect();
+        nodes.sort_unstable();
+        for kind in [DepthStream::Infrared, DepthStream::Color] {
+            let node = nodes.iter().copied().find(|&node| {
+                Device::new(node).map_or(false, |dev| {
+                    dev.query_caps().map_or(false, |caps| caps.bus == bus) && has_format(&dev, kind)
+                })
+            });
+            if let Some(node) = node {
+                streams.push((kind, node));
+            }
+        }
+
+        Ok(Self {
+            streams,
+            current: 0,
+            capture: Capture::open(DepthStream::Depth, index)?,
+            fixed_range: range,
+            range,
+        })
+    }
+
+    /// The stream being captured.
+    pub fn stream(&self) -> DepthStream {
+        self.capture.kind
+    }
+
+    /// Maps the distances onto brightness, near is bright.
+    fn shade(&mut self, depth: &[u16]) -> Vec<u8> {
+        if self.fixed_range.is_none() {
+            if let Some(found) = scene_range(depth) {
+                self.range = Some(match self.range {
+                    Some(range) => DepthRange {
+                        near: range.near + (found.near - range.near) * RANGE_SMOOTHING,
+                        far: range.far + (found.far - range.far) * RANGE_SMOOTHING,
+                    },
+                    None => found,
+                });
+            }
+        }
+        let range = match self.range {
+            Some(range) => range,
+            None => return vec![0; depth.len()],
+        };
+
+        depth
+            .iter()
+            .map(|&distance| {
+                let distance = distance as f32;
+                // 0 is where the camera couldn't tell
+                if distance == 0.0 || distance > range.far {
+                    return 0;
+                }
+                let nearness = (range.far - distance) / (range.far - range.near).max(1.0);
+                // 1 rather than 0 at the far end, which is left blank
+                (nearness.clamp(0.0, 1.0) * 254.0) as u8 + 1
+            })
+            .collect()
+    }
+}
+
+impl Capture {
+    fn open(kind: DepthStream, index: usize) -> Result<Self> {
+        let dev = Device::new(index)?;
+        let mut fmt = dev.format()?;
+        fmt.fourcc = kind.fourcc();
+        let fmt = dev.set_format(&fmt)?;
+        if fmt.fourcc != kind.fourcc() {
+            return Err(eyre!(
+                "/dev/video{index} doesn't deliver {} frames",
+                kind.name()
+            ));
+        }
+
+        let stream = Stream::with_buffers(&dev, Type::VideoCapture, 4)?;
+
+        Ok(Self {
+            kind,
+            _dev: dev,
+            stream,
+            width: fmt.width,
+            height: fmt.height,
+            stride: fmt.stride as usize,
+        })
+    }
+}
+
+impl CaptureSource for DepthCamera {
+    fn next_frame(&mut self) -> Result<Frame> {
+        let capture = &mut self.capture;
+        let (width, height, stride, kind) =
+            (capture.width, capture.height, capture.stride, capture.kind);
+        let (buf, meta) = capture.stream.next()?;
+        let meta = FrameMeta {
+            sequence: meta.sequence.into(),
+            timestamp: Duration::from_secs(meta.timestamp.sec as u64)
+                + Duration::from_micros(meta.timestamp.usec as u64),
+            bytes_used: meta.bytesused,
+            received: Some(Instant::now()),
+        };
+
+        let (data, pixel_format) = match kind {
+            DepthStream::Depth => {
+                let bytes = unpad_rows(buf, stride, width as usize * 2, height);
+                let depth: Vec<u16> = bytes
+                    .chunks_exact(2)
+                    .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+                    .collect();
+                (self.shade(&depth), PixelFormat::Gray8)
+            }
+            DepthStream::Infrared => (
+                unpad_rows(buf, stride, width as usize, height),
+                PixelFormat::Gray8,
+            ),
+            DepthStream::Color => (
+                yuyv_to_rgb(&unpad_rows(buf, stride, width as usize * 2, height)),
+                PixelFormat::Rgb8,
+            ),
+        };
+
+        Ok(Frame {
+            data,
+            width,
+            height,
+            pixel_format,
+            meta,
+        })
+    }
+
+    fn format(&self) -> SourceFormat {
+        SourceFormat {
+            width: self.capture.width,
+            height: self.capture.height,
+            pixel_format: match self.capture.kind {
+                DepthStream::Color => PixelFormat::Rgb8,
+                _ => PixelFormat::Gray8,
+            },
+        }
+    }
+
+    fn next_stream(&mut self) -> Result<Option<String>> {
+        let next = (self.current + 1) % self.streams.len();
+        let (kind, index) = self.streams[next];
+        self.capture = Capture::open(kind, index)?;
+        self.current = next;
+
+        Ok(Some(kind.name().to_string()))
+    }
+}
+
+fn has_format(dev: &Device, kind: DepthStream) -> bool {
+    dev.enum_formats().map_or(false, |formats| {
+        formats.iter().any(|f| f.fourcc == kind.fourcc())
+    })
+}
+
+/// The range the distances in `depth` fall in, without the outliers.
+fn scene_range(depth: &[u16]) -> Option<DepthRange> {
+    let mut sample: Vec<u16> = depth
+        .iter()
+        .step_by(RANGE_SAMPLE_STEP)
+        .copied()
+        .filter(|&distance| distance != 0)
+        .collect();
+    if sample.is_empty() {
+        return None;
+    }
+    sample.sort_unstable();
+
+    let outliers = (sample.len() as f32 * RANGE_OUTLIERS) as usize;
+    let near = sample[outliers] as f32;
+    let far = sample[sample.len() - 1 - outliers] as f32;
+
+    Some(DepthRange { near, far })
+}
@@ -0,0 +1,65 @@
+use super::{CaptureSource, Frame, FrameMeta};
+use eyre::Result;
+use std::time::{Duration, Instant};
+
+/// A decoded frame and when it was captured.
+#[derive(Debug, Clone)]
+pub struct TimedFrame<I> {
+    pub image: I,
+    /// Time since the iterator that produced the frame was created.
+    pub timestamp: Duration,
+    pub meta: FrameMeta,
+}
+
+/// An iterator decoding frames from a [`CaptureSource`].
+///
+/// The iterator ends after the first error, so
+/// `frames.take(n).collect::<Result<Vec<_>>>()` returns early instead of
+/// retrying a broken source forever.
+pub struct Frames<'s, S: CaptureSource + ?Sized, I> {
+    source: &'s mut S,
+    decode: fn(&Frame) -> Result<I>,
+    start: Instant,
+    failed: bool,
+}
+
+impl<'s, S: CaptureSource + ?Sized, I> Frames<'s, S, I> {
+    /// Creates an iterator that captures from `source` and decodes every
+    /// frame with `decode`, e.g. [`decode_frame`](crate::convert::decode_frame).
+    pub fn new(source: &'s mut S, decode: fn(&Frame) -> Result<I>) -> Self {
+        Self {
+            source,
+            decode,
+            start: Instant::now(),
+            failed: false,
+        }
+    }
+}
+
+impl<S: CaptureSource + ?Sized, I> Iterator for Frames<'_, S, I> {
+    type Item = Result<TimedFrame<I>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+
+        let frame = self
+            .source
+            .next_frame()
+            .and_then(|f| Ok(((self.decode)(&f)?, f.meta)));
+        let timestamp = self.start.elapsed();
+
+        match frame {
+            Ok((image, meta)) => Some(Ok(TimedFrame {
+                image,
+                timestamp,
+                meta,
+            })),
+            Err(e) => {
+                self.failed = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
@@ -0,0 +1,172 @@
+use super::{CaptureSource, Frame, FrameMeta, PixelFormat, Playback, SourceFormat};
+use eyre::{eyre, Result};
+use image::codecs::gif::GifDecoder;
+use image::{AnimationDecoder, DynamicImage};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// browsers treat tiny delays as "as fast as possible" and slow them down to
+// this, do the same so such gifs don't play back at hundreds of fps
+const MIN_DELAY: Duration = Duration::from_millis(20);
+const DEFAULT_DELAY: Duration = Duration::from_millis(100);
+
+// how often a paused gif redelivers its current frame
+const IDLE_INTERVAL: Duration = Duration::from_millis(33);
+
+/// An animated gif, decoded up front and played back forever, honoring the
+/// delay of every frame.
+pub struct GifSource {
+    frames: Vec<(Vec<u8>, Duration)>,
+    width: u32,
+    height: u32,
+    // index of the frame delivered next
+    index: usize,
+    // when the previous frame was delivered
+    last: Option<Instant>,
+    // position of the next frame within one loop of the animation
+    position: Duration,
+    paused: bool,
+    sequence: u64,
+}
+
+impl GifSource {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let reader = BufReader::new(File::open(path)?);
+        let decoder = GifDecoder::new(reader)?;
+
+        let mut frames = Vec::new();
+        let (mut width, mut height) = (0, 0);
+        for frame in decoder.into_frames() {
+            let frame = frame?;
+            let (numer, denom) = frame.delay().numer_denom_ms();
+            let delay = match Duration::from_secs_f64(numer as f64 / denom.max(1) as f64 / 1000.0) {
+                d if d < MIN_DELAY => DEFAULT_DELAY,
+                d => d,
+            };
+
+            // frames are already composited onto the full canvas
+            let img = DynamicImage::ImageRgba8(frame.into_buffer()).into_rgb8();
+            (width, height) = img.dimensions();
+            frames.push((img.into_raw(), delay));
+        }
+
+        if frames.is_empty() {
+            return Err(eyre!("'{}' has no frames", path.display()));
+        }
+
+        Ok(Self {
+            frames,
+            width,
+            height,
+            index: 0,
+            last: None,
+            position: Duration::ZERO,
+            paused: false,
+            sequence: 0,
+        })
+    }
+
+    /// Length of one loop of the animation.
+    pub fn duration(&self) -> Duration {
+        self.frames.iter().map(|(_, delay)| *delay).sum()
+    }
+
+    fn frame(&self, index: usize) -> Frame {
+        let (data, _) = &self.frames[index];
+
+        Frame {
+            data: data.clone(),
+            width: self.width,
+            height: self.height,
+            pixel_format: PixelFormat::Rgb8,
+            meta: FrameMeta {
+                sequence: self.sequence,
+                timestamp: self.position,
+                bytes_used: data.len() as u32,
+                received: Some(Instant::now()),
+            },
+        }
+    }
+}
+
+impl CaptureSource for GifSource {
+    fn next_frame(&mut self) -> Result<Frame> {
+        if self.paused {
+            thread::sleep(IDLE_INTERVAL);
+            // the frame on screen is the one before `index`
+            let current = (self.index + self.frames.len() - 1) % self.frames.len();
+            return Ok(self.frame(current));
+        }
+
+        // hold the previous frame for its delay
+        if let Some(last) = self.last {
+            let previous = (self.index + self.frames.len() - 1) % self.frames.len();
+            if let Some(wait) = self.frames[previous].1.checked_sub(last.elapsed()) {
+                thread::sleep(wait);
+            }
+        }
+
+        self.last = Some(Instant::now());
+        self.sequence += 1;
+        let frame = self.frame(self.index);
+
+        self.position += self.frames[self.index].1;
+        self.index += 1;
+        if self.index == self.frames.len() {
+            self.index = 0;
+            self.position = Duration::ZERO;
+        }
+
+        Ok(frame)
+    }
+
+    fn format(&self) -> SourceFormat {
+        SourceFormat {
+            width: self.width,
+            height: self.height,
+            pixel_format: PixelFormat::Rgb8,
+        }
+    }
+
+    fn playback(&mut self) -> Option<&mut dyn Playback> {
+        Some(self)
+    }
+}
+
+impl Playback for GifSource {
+    fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+        // show the next frame for its full delay once resumed
+        self.last = None;
+    }
+
+    fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    fn seek(&mut self, seconds: f64) -> Result<()> {
+        let duration = self.duration().as_secs_f64();
+        let target = (self.position.as_secs_f64() + seconds).rem_euclid(duration);
+
+        // find the frame showing at `target`
+        let mut position = Duration::ZERO;
+        for (index, (_, delay)) in self.frames.iter().enumerate() {
+            if (position + *delay).as_secs_f64() > target {
+                self.index = index;
+                self.position = position;
+                self.last = None;
+                return Ok(());
+            }
+            position += *delay;
+        }
+
+        self.index = 0;
+        self.position = Duration::ZERO;
+        self.last = None;
+        Ok(())
+    }
+}
@@ -0,0 +1,148 @@
+use super::{CaptureSource, Frame, FrameMeta, PixelFormat, SourceFormat};
+use crate::convert::unpad_rows;
+use eyre::{eyre, Result};
+use gstreamer as gst;
+use gstreamer::prelude::*;
+use gstreamer_app::AppSink;
+use gstreamer_video::VideoInfo;
+use std::time::{Duration, Instant};
+
+// how long to wait for the first frame, a pipeline can take a while to
+// connect to a network stream, and after that for each frame
+const FRAME_TIMEOUT: Duration = Duration::from_secs(10);
+// the name of the appsink put at the end of the pipeline
+const SINK_NAME: &str = "asciicam";
+
+/// Frames from a GStreamer pipeline described like for `gst-launch-1.0`,
+/// e.g. `v4l2src device=/dev/video2 ! jpegdec` or
+/// `rtspsrc location=rtsp://cam.local/live ! decodebin`.
+///
+/// The pipeline is finished off by converting to [`PixelFormat::Rgb8`] and
+/// an appsink that drops frames the viewer isn't ready for, so it has to end
+/// in raw video.
+pub struct GstSource {
+    pipeline: gst::Element,
+    sink: AppSink,
+    width: u32,
+    height: u32,
+    started: Instant,
+    sequence: u64,
+    // the first frame, pulled to find out the size
+    pending: Option<Frame>,
+}
+
+impl GstSource {
+    /// Starts the pipeline in `description` and waits for its first frame.
+    pub fn launch(description: &str) -> Result<Self> {
+        gst::init()?;
+        let pipeline = gst::parse_launch(&format!(
+            "{description} ! videoconvert ! video/x-raw,format=RGB ! \
+             appsink name={SINK_NAME} max-buffers=1 drop=true sync=false"
+        ))
+        .map_err(|e| eyre!("Invalid GStreamer pipeline '{description}' ({e})"))?;
+        let sink = match pipeline
+            .downcast_ref::<gst::Bin>()
+            .and_then(|bin| bin.by_name(SINK_NAME))
+            .and_then(|sink| sink.downcast::<AppSink>().ok())
+        {
+            Some(sink) => sink,
+            None => return Err(eyre!("Invalid GStreamer pipeline '{description}'")),
+        };
+        pipeline.set_state(gst::State::Playing)?;
+
+        let mut source = Self {
+            pipeline,
+            sink,
+            width: 0,
+            height: 0,
+            started: Instant::now(),
+            sequence: 0,
+            pending: None,
+        };
+        let first = source.pull()?;
+        source.width = first.width;
+        source.height = first.height;
+        source.pending = Some(first);
+
+        Ok(source)
+    }
+
+    fn pull(&mut self) -> Result<Frame> {
+        let timeout = gst::ClockTime::from_mseconds(FRAME_TIMEOUT.as_millis() as u64);
+        let sample = match self.sink.try_pull_sample(timeout) {
+            Some(sample) => sample,
+            None => return Err(self.error()),
+        };
+
+        let info = match sample.caps() {
+            Some(caps) => VideoInfo::from_caps(caps)?,
+            None => {
+                return Err(eyre!(
+                    "The GStreamer pipeline sent a frame without a format"
+                ))
+            }
+        };
+        let buffer = match sample.buffer() {
+            Some(buffer) => buffer,
+            None => return Err(eyre!("The GStreamer pipeline sent an empty frame")),
+        };
+        let map = buffer.map_readable()?;
+
+        // rows are padded to a multiple of 4 bytes
+        let (width, height) = (info.width(), info.height());
+        let stride = info.stride()[0] as usize;
+        let data = unpad_rows(map.as_slice(), stride, width as usize * 3, height);
+
+        self.sequence += 1;
+        Ok(Frame {
+            meta: FrameMeta {
+                sequence: self.sequence,
+                timestamp: buffer
+                    .pts()
+                    .map_or_else(|| self.started.elapsed(), |pts| pts.into()),
+                bytes_used: buffer.size() as u32,
+                received: Some(Instant::now()),
+            },
+            data,
+            width,
+            height,
+            pixel_format: PixelFormat::Rgb8,
+        })
+    }
+
+    /// Why no frame came, from the pipeline's bus.
+    fn error(&self) -> eyre::Report {
+        let message = self
+            .pipeline
+            .bus()
+            .and_then(|bus| bus.pop_filtered(&[gst::MessageType::Error]));
+        match message.as_ref().map(|message| message.view()) {
+            Some(gst::MessageView::Error(e)) => eyre!("GStreamer pipeline failed ({})", e.error()),
+            _ if self.sink.is_eos() => eyre!("The GStreamer pipeline ended"),
+            _ => eyre!("The GStreamer pipeline stopped delivering frames"),
+        }
+    }
+}
+
+impl CaptureSource for GstSource {
+    fn next_frame(&mut self) -> Result<Frame> {
+        match self.pending.take() {
+            Some(frame) => Ok(frame),
+            None => self.pull(),
+        }
+    }
+
+    fn format(&self) -> SourceFormat {
+        SourceFormat {
+            width: self.width,
+            height: self.height,
+            pixel_format: PixelFormat::Rgb8,
+        }
+    }
+}
+
+impl Drop for GstSource {
+    fn drop(&mut self) {
+        let _ = self.pipeline.set_state(gst::State::Null);
+    }
+}
@@ -0,0 +1,33 @@
+use eyre::{eyre, Result};
+use openh264::decoder::Decoder;
+
+/// Decodes the H.264 some UVC cameras only send their high resolutions in,
+/// one buffer from the camera at a time.
+pub(crate) struct H264Decoder {
+    decoder: Decoder,
+}
+
+impl H264Decoder {
+    pub fn new() -> Result<Self> {
+        match Decoder::new() {
+            Ok(decoder) => Ok(Self { decoder }),
+            Err(e) => Err(eyre!("Could not start the H.264 decoder ({e})")),
+        }
+    }
+
+    /// Decodes the access unit in `buf` to rgb in `data`, replacing what
+    /// was in it, and returns the size. `None` when it held no picture,
+    /// like the parameter sets some cameras send on their own.
+    pub fn decode(&mut self, buf: &[u8], data: &mut Vec<u8>) -> Result<Option<(u32, u32)>> {
+        let yuv = match self.decoder.decode(buf) {
+            Ok(Some(yuv)) => yuv,
+            Ok(None) => return Ok(None),
+            Err(e) => return Err(eyre!("Could not decode H.264 frame ({e})")),
+        };
+
+        let (width, height) = yuv.dimension_rgb();
+        data.resize(width * height * 3, 0);
+        yuv.write_rgb8(data);
+        Ok(Some((width as u32, height as u32)))
+    }
+}
@@ -0,0 +1,201 @@
+use super::pipe::read_jpeg;
+use super::{CaptureSource, Frame, FrameMeta, PixelFormat, SourceFormat};
+use eyre::{eyre, Result};
+use std::io::{BufRead, BufReader, Cursor, Read, Write};
+use std::net::TcpStream;
+use std::time::{Duration, Instant};
+
+// a camera that stops sending for this long is treated as gone
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// An MJPEG-over-HTTP stream, as served by most IP cameras. Each part of the
+/// `multipart/x-mixed-replace` response is one JPEG frame.
+pub struct MjpegSource {
+    reader: BufReader<TcpStream>,
+    boundary: String,
+    width: u32,
+    height: u32,
+    started: Instant,
+    sequence: u64,
+    // the first frame is read while connecting to learn the frame size
+    pending: Option<Vec<u8>>,
+}
+
+impl MjpegSource {
+    /// Connects to an `http://host[:port]/path` url.
+    pub fn connect(url: &str) -> Result<Self> {
+        let rest = match url.strip_prefix("http://") {
+            Some(rest) => rest,
+            None => return Err(eyre!("Only http:// streams are supported, got '{url}'")),
+        };
+        let (host, path) = match rest.find('/') {
+            Some(i) => rest.split_at(i),
+            None => (rest, "/"),
+        };
+        let addr = if host.contains(':') {
+            host.to_string()
+        } else {
+            format!("{host}:80")
+        };
+
+        let mut stream =
+            TcpStream::connect(&addr).map_err(|e| eyre!("Could not connect to '{addr}' ({e})"))?;
+        stream.set_read_timeout(Some(READ_TIMEOUT))?;
+        // http/1.0 so the server doesn't answer with chunked encoding
+        write!(
+            stream,
+            "GET {path} HTTP/1.0\r\nHost: {host}\r\nUser-Agent: asciicam\r\n\r\n"
+        )?;
+
+        let mut reader = BufReader::new(stream);
+
+        let status = read_line(&mut reader)?;
+        match status.split_whitespace().nth(1) {
+            Some("200") => (),
+            _ => return Err(eyre!("'{url}' answered with '{status}'")),
+        }
+
+        let mut boundary = None;
+        for header in read_headers(&mut reader)? {
+            if let Some((name, value)) = header.split_once(':') {
+                if name.eq_ignore_ascii_case("content-type") {
+                    boundary = parse_boundary(value);
+                }
+            }
+        }
+        let boundary = match boundary {
+            Some(b) => b,
+            None => return Err(eyre!("'{url}' is not a multipart MJPEG stream")),
+        };
+
+        let mut source = Self {
+            reader,
+            boundary,
+            width: 0,
+            height: 0,
+            started: Instant::now(),
+            sequence: 0,
+            pending: None,
+        };
+
+        let first = source.read_part()?;
+        let (width, height) = image::io::Reader::new(Cursor::new(&first))
+            .with_guessed_format()?
+            .into_dimensions()?;
+        source.width = width;
+        source.height = height;
+        source.pending = Some(first);
+
+        Ok(source)
+    }
+
+    /// Reads the next part of the multipart body.
+    fn read_part(&mut self) -> Result<Vec<u8>> {
+        // skip ahead to the next boundary, some cameras leave out the
+        // leading dashes so compare without them
+        loop {
+            let line = read_line(&mut self.reader)?;
+            if line.trim_start_matches('-') == self.boundary {
+                break;
+            }
+        }
+
+        let mut length = None;
+        for header in read_headers(&mut self.reader)? {
+            if let Some((name, value)) = header.split_once(':') {
+                if name.eq_ignore_ascii_case("content-length") {
+                    length = value.trim().parse::<usize>().ok();
+                }
+            }
+        }
+
+        match length {
+            Some(length) => {
+                let mut data = vec![0; length];
+                self.reader.read_exact(&mut data)?;
+                Ok(data)
+            }
+            // without a length, read up to the jpeg end of image marker
+            None => match read_jpeg(&mut self.reader)? {
+                Some(data) => Ok(data),
+                None => Err(eyre!("MJPEG stream ended")),
+            },
+        }
+    }
+}
+
+impl CaptureSource for MjpegSource {
+    fn next_frame(&mut self) -> Result<Frame> {
+        let data = match self.pending.take() {
+            Some(data) => data,
+            None => self.read_part()?,
+        };
+
+        self.sequence += 1;
+
+        Ok(Frame {
+            meta: FrameMeta {
+                sequence: self.sequence,
+                timestamp: self.started.elapsed(),
+                bytes_used: data.len() as u32,
+                received: Some(Instant::now()),
+            },
+            data,
+            width: self.width,
+            height: self.height,
+            pixel_format: PixelFormat::Mjpeg,
+        })
+    }
+
+    fn format(&self) -> SourceFormat {
+        SourceFormat {
+            width: self.width,
+            height: self.height,
+            pixel_format: PixelFormat::Mjpeg,
+        }
+    }
+}
+
+fn read_line(reader: &mut impl BufRead) -> Result<String> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Err(eyre!("MJPEG stream ended"));
+    }
+
+    Ok(line.trim_end().to_string())
+}
+
+/// Reads header lines up to the empty line that ends them.
+fn read_headers(reader: &mut impl BufRead) -> Result<Vec<String>> {
+    let mut headers = Vec::new();
+    loop {
+        let line = read_line(reader)?;
+        if line.is_empty() {
+            return Ok(headers);
+        }
+        headers.push(line);
+    }
+}
+
+/// Picks the boundary out of a `multipart/x-mixed-replace; boundary=...`
+/// content type.
+fn parse_boundary(content_type: &str) -> Option<String> {
+    let mut params = content_type.split(';');
+    if !params.next()?.trim().starts_with("multipart/") {
+        return None;
+    }
+
+    params.find_map(|param| {
+        let (name, value) = param.split_once('=')?;
+        if !name.trim().eq_ignore_ascii_case("boundary") {
+            return None;
+        }
+        Some(
+            value
+                .trim()
+                .trim_matches('"')
+                .trim_start_matches('-')
+                .to_string(),
+        )
+    })
+}
@@ -0,0 +1,137 @@
+use super::{CaptureSource, Frame, PixelFormat, Playback, SourceFormat};
+use crate::convert::decode_frame_rgb;
+use eyre::Result;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// how often frames are made up while fading, about the rate of a camera
+// that keeps up
+const STEP: Duration = Duration::from_millis(33);
+
+/// Fades from one frame of another source into the next, with frames made
+/// up in between, for cameras that only manage a few frames a second in
+/// low light. Each frame fades in over the blend window, or the time the
+/// source took to send it when that is shorter, which puts the picture one
+/// frame of the source behind. Frames come as [`PixelFormat::Rgb8`].
+pub struct InterpolatedSource<S> {
+    source: S,
+    window: Duration,
+    // the last frame of the source and when it came
+    last: Option<(Frame, Instant)>,
+    fade: Option<Fade>,
+}
+
+struct Fade {
+    from: Frame,
+    to: Frame,
+    start: Instant,
+    window: Duration,
+    // frames made up so far
+    steps: u32,
+}
+
+impl<S: CaptureSource> InterpolatedSource<S> {
+    pub fn new(source: S, window: Duration) -> Self {
+        Self {
+            source,
+            window,
+            last: None,
+            fade: None,
+        }
+    }
+
+    fn next_rgb(&mut self) -> Result<Frame> {
+        let frame = self.source.next_frame()?;
+        let img = decode_frame_rgb(&frame)?;
+
+        Ok(Frame {
+            width: img.width(),
+            height: img.height(),
+            data: img.into_raw(),
+            pixel_format: PixelFormat::Rgb8,
+            meta: frame.meta,
+        })
+    }
+}
+
+impl<S: CaptureSource> CaptureSource for InterpolatedSource<S> {
+    fn next_frame(&mut self) -> Result<Frame> {
+        if let Some(fade) = &mut self.fade {
+            fade.steps += 1;
+            let at = STEP * fade.steps;
+            if at < fade.window {
+                if let Some(wait) = (fade.start + at).checked_duration_since(Instant::now()) {
+                    thread::sleep(wait);
+                }
+                let weight = (at.as_secs_f32() / fade.window.as_secs_f32() * 256.0) as u16;
+                return Ok(Frame {
+                    data: blend(&fade.from.data, &fade.to.data, weight),
+                    width: fade.to.width,
+                    height: fade.to.height,
+                    pixel_format: PixelFormat::Rgb8,
+                    meta: fade.to.meta,
+                });
+            }
+
+            // the rest of the window, then the frame faded into itself
+            let fade = self.fade.take().unwrap();
+            if let Some(wait) = (fade.start + fade.window).checked_duration_since(Instant::now()) {
+                thread::sleep(wait);
+            }
+            return Ok(fade.to);
+        }
+
+        let frame = self.next_rgb()?;
+        let now = Instant::now();
+        if let Some((last, at)) = self.last.replace((frame.clone(), now)) {
+            let window = self.window.min(now - at);
+            // a camera fast enough has nothing to fade between
+            if window >= STEP * 2 && (last.width, last.height) == (frame.width, frame.height) {
+                self.fade = Some(Fade {
+                    from: last,
+                    to: frame,
+                    start: now,
+                    window,
+                    steps: 0,
+                });
+                return self.next_frame();
+            }
+        }
+
+        Ok(frame)
+    }
+
+    fn format(&self) -> SourceFormat {
+        SourceFormat {
+            pixel_format: PixelFormat::Rgb8,
+            ..self.source.format()
+        }
+    }
+
+    fn playback(&mut self) -> Option<&mut dyn Playback> {
+        self.source.playback()
+    }
+
+    fn status(&self) -> Option<String> {
+        self.source.status()
+    }
+
+    fn next_stream(&mut self) -> Result<Option<String>> {
+        self.last = None;
+        self.fade = None;
+        self.source.next_stream()
+    }
+
+    fn reconnects(&self) -> u64 {
+        self.source.reconnects()
+    }
+}
+
+/// `from` with `to` over it, `weight` out of 256.
+fn blend(from: &[u8], to: &[u8], weight: u16) -> Vec<u8> {
+    let weight = weight.min(256);
+    from.iter()
+        .zip(to)
+        .map(|(&a, &b)| ((a as u16 * (256 - weight) + b as u16 * weight) >> 8) as u8)
+        .collect()
+}
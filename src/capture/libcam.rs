@@ -0,0 +1,197 @@
+use super::{CaptureSource, Frame, FrameMeta, PixelFormat, SourceFormat};
+use eyre::{eyre, Result};
+use libcamera::camera::CameraConfigurationStatus;
+use libcamera::camera_manager::CameraManager;
+use libcamera::framebuffer_allocator::{FrameBuffer, FrameBufferAllocator};
+use libcamera::framebuffer_map::MemoryMappedFrameBuffer;
+use libcamera::geometry::Size;
+use libcamera::pixel_format::PixelFormat as LibcameraFormat;
+use libcamera::request::ReuseFlag;
+use libcamera::stream::StreamRole;
+use std::sync::mpsc::{self, Receiver, SendError, SyncSender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// DRM_FORMAT_BGR888, which despite the name is red, green, blue in memory
+const BGR888: LibcameraFormat = LibcameraFormat::new(u32::from_le_bytes(*b"BG24"), 0);
+// how long to wait for a frame before deciding the camera is stuck
+const FRAME_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// A camera behind libcamera, like the Raspberry Pi camera modules, which
+/// often can't be captured through plain v4l because the sensor needs the
+/// ISP set up first.
+///
+/// libcamera's camera handles borrow its camera manager, so both live on a
+/// capture thread of their own that hands the frames over as
+/// [`PixelFormat::Rgb8`].
+pub struct LibcameraSource {
+    frames: Receiver<Result<Frame>>,
+    width: u32,
+    height: u32,
+}
+
+impl LibcameraSource {
+    /// Opens the `index`th camera libcamera finds, at the size it picks for
+    /// a viewfinder or the one closest to `size`, and waits until it is
+    /// streaming.
+    pub fn open(index: usize, size: Option<(u32, u32)>) -> Result<Self> {
+        let (opened_tx, opened) = mpsc::sync_channel(1);
+        let (frames_tx, frames) = mpsc::sync_channel(1);
+
+        thread::spawn(move || {
+            if let Err(e) = capture(index, size, &opened_tx, &frames_tx) {
+                // until the camera is open the error is for open, after
+                // that for next_frame
+                if let Err(SendError(Err(e))) = opened_tx.send(Err(e)) {
+                    let _ = frames_tx.send(Err(e));
+                }
+            }
+        });
+
+        let (width, height) = match opened.recv() {
+            Ok(result) => result?,
+            Err(_) => return Err(eyre!("The libcamera capture thread died")),
+        };
+
+        Ok(Self {
+            frames,
+            width,
+            height,
+        })
+    }
+}
+
+impl CaptureSource for LibcameraSource {
+    fn next_frame(&mut self) -> Result<Frame> {
+        match self.frames.recv_timeout(FRAME_TIMEOUT) {
+            Ok(frame) => frame,
+            Err(_) => Err(eyre!("The camera stopped delivering frames")),
+        }
+    }
+
+    fn format(&self) -> SourceFormat {
+        SourceFormat {
+            width: self.width,
+            height: self.height,
+            pixel_format: PixelFormat::Rgb8,
+        }
+    }
+}
+
+/// Runs the camera on the capture thread, sending its size on `opened` once
+/// it streams and then every frame on `frames`, until the source is dropped.
+fn capture(
+    index: usize,
+    size: Option<(u32, u32)>,
+    opened: &SyncSender<Result<(u32, u32)>>,
+    frames: &SyncSender<Result<Frame>>,
+) -> Result<()> {
+    let manager = CameraManager::new()?;
+    let cameras = manager.cameras();
+    let camera = match cameras.get(index) {
+        Some(camera) => camera,
+        None => {
+            return Err(eyre!(
+                "Could not find libcamera camera '{index}', there are {}",
+                cameras.len()
+            ))
+        }
+    };
+    let mut camera = camera.acquire()?;
+
+    let mut configs = match camera.generate_configuration(&[StreamRole::ViewFinder]) {
+        Some(configs) => configs,
+        None => return Err(eyre!("The camera has no viewfinder stream")),
+    };
+    if let Some(mut config) = configs.get_mut(0) {
+        config.set_pixel_format(BGR888);
+        if let Some((width, height)) = size {
+            config.set_size(Size { width, height });
+        }
+    }
+    if let CameraConfigurationStatus::Invalid = configs.validate() {
+        return Err(eyre!("The camera can't be configured for a viewfinder"));
+    }
+    camera.configure(&mut configs)?;
+
+    let config = match configs.get(0) {
+        Some(config) => config,
+        None => return Err(eyre!("The camera has no viewfinder stream")),
+    };
+    // validating may have picked another format the ISP prefers
+    if config.get_pixel_format() != BGR888 {
+        return Err(eyre!("The camera can't deliver RGB frames"));
+    }
+    let Size { width, height } = config.get_size();
+    let stride = config.get_stride() as usize;
+    let stream = match config.stream() {
+        Some(stream) => stream,
+        None => return Err(eyre!("The camera has no viewfinder stream")),
+    };
+
+    let mut allocator = FrameBufferAllocator::new(&camera);
+    let mut requests = Vec::new();
+    for (i, buffer) in allocator.alloc(&stream)?.into_iter().enumerate() {
+        let buffer = MemoryMappedFrameBuffer::new(buffer)?;
+        let mut request = match camera.create_request(Some(i as u64)) {
+            Some(request) => request,
+            None => return Err(eyre!("Could not create a libcamera request")),
+        };
+        request.add_buffer(&stream, buffer)?;
+        requests.push(request);
+    }
+
+    let (completed_tx, completed) = mpsc::channel();
+    camera.on_request_completed(move |request| {
+        let _ = completed_tx.send(request);
+    });
+    camera.start(None)?;
+    for request in requests {
+        camera.queue_request(request)?;
+    }
+    let _ = opened.send(Ok((width, height)));
+
+    let started = Instant::now();
+    let row = width as usize * 3;
+    for sequence in 1.. {
+        let mut request = match completed.recv_timeout(FRAME_TIMEOUT) {
+            Ok(request) => request,
+            Err(_) => return Err(eyre!("The camera stopped delivering frames")),
+        };
+
+        let buffer: &MemoryMappedFrameBuffer<FrameBuffer> = match request.buffer(&stream) {
+            Some(buffer) => buffer,
+            None => return Err(eyre!("A libcamera request came back without its buffer")),
+        };
+        // rows are padded out to the stride
+        let mut data = Vec::with_capacity(row * height as usize);
+        if let Some(plane) = buffer.data().first() {
+            for line in plane.chunks(stride).take(height as usize) {
+                data.extend_from_slice(&line[..row.min(line.len())]);
+            }
+        }
+        data.resize(row * height as usize, 0);
+
+        request.reuse(ReuseFlag::REUSE_BUFFERS);
+        camera.queue_request(request)?;
+
+        let frame = Frame {
+            meta: FrameMeta {
+                sequence,
+                timestamp: started.elapsed(),
+                bytes_used: data.len() as u32,
+                received: Some(Instant::now()),
+            },
+            data,
+            width,
+            height,
+            pixel_format: PixelFormat::Rgb8,
+        };
+        // nobody is receiving once the source is dropped
+        if frames.send(Ok(frame)).is_err() {
+            break;
+        }
+    }
+
+    Ok(())
+}
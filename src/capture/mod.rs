@@ -0,0 +1,406 @@
+//! Frame producers.
+//!
+//! Anything that can hand out frames implements [`CaptureSource`], so the
+//! rest of the pipeline doesn't need to care whether the pixels come from a
+//! webcam, a file or a test pattern.
+
+mod background;
+#[cfg(all(target_os = "linux", not(feature = "nokhwa")))]
+mod camera;
+mod controls;
+mod deinterlace;
+#[cfg(target_os = "linux")]
+mod depth;
+mod frames;
+mod gif;
+// not called gstreamer, which would clash with the crate
+#[cfg(feature = "gstreamer")]
+mod gst;
+// only v4l cameras send it
+#[cfg(all(feature = "h264", target_os = "linux", not(feature = "nokhwa")))]
+mod h264;
+mod http;
+// it sleeps between the frames it makes up
+#[cfg(not(target_arch = "wasm32"))]
+mod interpolate;
+#[cfg(all(target_os = "linux", not(feature = "nokhwa")))]
+mod mplane;
+// not called libcamera, which would clash with the crate
+#[cfg(feature = "libcamera")]
+mod libcam;
+#[cfg(any(windows, target_os = "macos", feature = "nokhwa"))]
+mod native;
+mod pipe;
+mod pool;
+#[cfg(feature = "pipewire")]
+mod portal;
+mod presets;
+mod reconnect;
+#[cfg(feature = "rtsp")]
+mod rtsp;
+#[cfg(feature = "screen")]
+mod screen;
+mod slideshow;
+mod still;
+mod synthetic;
+#[cfg(all(target_os = "android", not(feature = "nokhwa")))]
+mod termux;
+// the capture thread of the viewer, which isn't built for the browser
+#[cfg(not(target_arch = "wasm32"))]
+mod threaded;
+// a camera that can't be opened, where there is no way to capture one
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    windows,
+    target_os = "macos",
+    feature = "nokhwa"
+)))]
+mod unsupported;
+mod video;
+
+pub use background::BackgroundSource;
+#[cfg(all(target_os = "linux", not(feature = "nokhwa")))]
+pub use camera::{list_devices, Camera, DeviceInfo};
+pub use controls::{
+    CameraControl, ControlInfo, ControlKind, Controls, EXPOSURE, FOCUS, GAIN, PAN,
+    POWER_LINE_FREQUENCY, TILT, WHITE_BALANCE, ZOOM,
+};
+pub use deinterlace::{Deinterlace, DeinterlacedSource};
+#[cfg(target_os = "linux")]
+pub use depth::{DepthCamera, DepthRange, DepthStream};
+pub use frames::{Frames, TimedFrame};
+pub use gif::GifSource;
+#[cfg(feature = "gstreamer")]
+pub use gst::GstSource;
+pub use http::MjpegSource;
+#[cfg(not(target_arch = "wasm32"))]
+pub use interpolate::InterpolatedSource;
+#[cfg(feature = "libcamera")]
+pub use libcam::LibcameraSource;
+#[cfg(any(windows, target_os = "macos", feature = "nokhwa"))]
+pub use native::{list_devices, Camera, DeviceInfo};
+pub use pipe::{InputFormat, PipeSource};
+pub use pool::FramePool;
+#[cfg(feature = "pipewire")]
+pub use portal::PipeWireSource;
+pub use presets::{default_presets_path, Presets};
+pub use reconnect::ReconnectingCamera;
+#[cfg(feature = "rtsp")]
+pub use rtsp::RtspSource;
+#[cfg(feature = "screen")]
+pub use screen::{Region, ScreenSource};
+pub use slideshow::SlideshowSource;
+pub use still::ImageSource;
+pub use synthetic::SyntheticSource;
+#[cfg(all(target_os = "android", not(feature = "nokhwa")))]
+pub use termux::{list_devices, Camera, DeviceInfo};
+#[cfg(not(target_arch = "wasm32"))]
+pub use threaded::{CaptureThread, Captured};
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    windows,
+    target_os = "macos",
+    feature = "nokhwa"
+)))]
+pub use unsupported::{list_devices, Camera, DeviceInfo};
+pub use video::VideoSource;
+
+#[cfg(all(target_os = "linux", not(feature = "nokhwa")))]
+use camera::device_present;
+use eyre::{eyre, Report, Result};
+#[cfg(any(windows, target_os = "macos", feature = "nokhwa"))]
+use native::device_present;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+#[cfg(all(target_os = "android", not(feature = "nokhwa")))]
+use termux::device_present;
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    windows,
+    target_os = "macos",
+    feature = "nokhwa"
+)))]
+use unsupported::device_present;
+
+/// How the bytes of a [`Frame`] are laid out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PixelFormat {
+    /// A complete JPEG image, as delivered by most webcams.
+    Mjpeg,
+    /// One byte of luma per pixel.
+    Gray8,
+    /// Three bytes (red, green, blue) per pixel.
+    Rgb8,
+}
+
+/// How a v4l camera hands its buffers to asciicam. Cameras captured any
+/// other way only stream with [`IoMode::Mmap`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IoMode {
+    /// Buffers the driver allocates, mapped into asciicam. What every driver
+    /// supports.
+    #[default]
+    Mmap,
+    /// Buffers asciicam allocates and the driver fills in, for drivers that
+    /// are slow to map their own.
+    UserPtr,
+}
+
+impl FromStr for IoMode {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "mmap" => Ok(IoMode::Mmap),
+            "userptr" => Ok(IoMode::UserPtr),
+            _ => Err(eyre!("Unknown io mode '{s}', expected mmap or userptr")),
+        }
+    }
+}
+
+/// The format a source produces frames in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SourceFormat {
+    pub width: u32,
+    pub height: u32,
+    pub pixel_format: PixelFormat,
+}
+
+/// Where a frame came from and when, carried along with it through the
+/// pipeline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FrameMeta {
+    /// Sequence number assigned by the source. Gaps mean frames were dropped
+    /// before they reached us.
+    pub sequence: u64,
+    /// Capture time as reported by the source. For v4l devices this is the
+    /// driver timestamp, usually on the monotonic clock.
+    pub timestamp: Duration,
+    /// Size of the frame payload in bytes.
+    pub bytes_used: u32,
+    /// When asciicam received the frame from the source.
+    pub received: Option<Instant>,
+}
+
+/// A single captured frame.
+#[derive(Debug, Clone)]
+pub struct Frame {
+    pub data: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+    pub pixel_format: PixelFormat,
+    pub meta: FrameMeta,
+}
+
+/// A producer of frames.
+pub trait CaptureSource {
+    /// Blocks until the next frame is available.
+    fn next_frame(&mut self) -> Result<Frame>;
+
+    /// Like [`next_frame`](Self::next_frame), shared, for sources that
+    /// hand out the same frame more than once and don't have to copy it.
+    fn next_shared(&mut self) -> Result<Arc<Frame>> {
+        self.next_frame().map(Arc::new)
+    }
+
+    /// The format frames from this source are delivered in.
+    fn format(&self) -> SourceFormat;
+
+    /// Buffers for the source to put the frames it converts as they are
+    /// captured in, for sources that do, which the frames are handed back
+    /// to once they are done with. Sources that don't keep allocating.
+    fn set_pool(&mut self, _pool: FramePool) {}
+
+    /// Playback controls, for sources that can pause and seek.
+    fn playback(&mut self) -> Option<&mut dyn Playback> {
+        None
+    }
+
+    /// A problem the source is working around, like a camera that was
+    /// unplugged, for the viewer to show in its status bar.
+    fn status(&self) -> Option<String> {
+        None
+    }
+
+    /// Switches to the next of the streams the source has, like the depth,
+    /// infrared and color streams of a depth camera, and returns its name.
+    /// `None` for sources with a single stream.
+    fn next_stream(&mut self) -> Result<Option<String>> {
+        Ok(None)
+    }
+
+    /// Number of times the source lost its device or stream and got it
+    /// back, for sources that recover from that.
+    fn reconnects(&self) -> u64 {
+        0
+    }
+}
+
+impl<S: CaptureSource + ?Sized> CaptureSource for Box<S> {
+    fn next_frame(&mut self) -> Result<Frame> {
+        (**self).next_frame()
+    }
+
+    fn next_shared(&mut self) -> Result<Arc<Frame>> {
+        (**self).next_shared()
+    }
+
+    fn format(&self) -> SourceFormat {
+        (**self).format()
+    }
+
+    fn set_pool(&mut self, pool: FramePool) {
+        (**self).set_pool(pool)
+    }
+
+    fn playback(&mut self) -> Option<&mut dyn Playback> {
+        (**self).playback()
+    }
+
+    fn status(&self) -> Option<String> {
+        (**self).status()
+    }
+
+    fn next_stream(&mut self) -> Result<Option<String>> {
+        (**self).next_stream()
+    }
+
+    fn reconnects(&self) -> u64 {
+        (**self).reconnects()
+    }
+}
+
+/// Controls for sources that play back recorded media.
+pub trait Playback {
+    fn toggle_pause(&mut self);
+
+    fn is_paused(&self) -> bool;
+
+    /// Seeks relative to the current position, negative seconds go back.
+    fn seek(&mut self, seconds: f64) -> Result<()>;
+}
+
+const VIDEO_EXTENSIONS: &[&str] = &[
+    "mp4", "m4v", "mkv", "webm", "mov", "avi", "flv", "mpg", "mpeg", "ts", "wmv",
+];
+
+/// Opens the source for an `--input` argument, picking the kind of source
+/// from the url scheme or file extension.
+pub fn open_input(input: &str) -> Result<Box<dyn CaptureSource + Send>> {
+    if input.starts_with("http://") {
+        return Ok(Box::new(MjpegSource::connect(input)?));
+    }
+    if input == "synthetic" || input.starts_with("synthetic:") {
+        return Ok(Box::new(open_synthetic(input)?));
+    }
+    if input == "libcamera" || input.starts_with("libcamera:") {
+        #[cfg(feature = "libcamera")]
+        return Ok(Box::new(open_libcamera(input)?));
+        #[cfg(not(feature = "libcamera"))]
+        return Err(eyre::eyre!(
+            "libcamera inputs need asciicam built with --features libcamera"
+        ));
+    }
+    if input == "depth" || input.starts_with("depth:") {
+        #[cfg(target_os = "linux")]
+        return Ok(Box::new(open_depth(input)?));
+        #[cfg(not(target_os = "linux"))]
+        return Err(eyre::eyre!("depth cameras are only supported on Linux"));
+    }
+    if input == "pipewire" {
+        #[cfg(feature = "pipewire")]
+        return Ok(Box::new(PipeWireSource::open()?));
+        #[cfg(not(feature = "pipewire"))]
+        return Err(eyre::eyre!(
+            "pipewire inputs need asciicam built with --features pipewire"
+        ));
+    }
+    if let Some(spec) = input.strip_prefix("plugin:") {
+        #[cfg(all(feature = "plugins", not(target_arch = "wasm32")))]
+        return Ok(Box::new(crate::plugin::PluginSource::open(spec)?));
+        #[cfg(not(all(feature = "plugins", not(target_arch = "wasm32"))))]
+        return Err(eyre::eyre!(
+            "plugin:{spec} inputs need asciicam built with --features plugins"
+        ));
+    }
+    if input.starts_with("rtsp://") {
+        #[cfg(feature = "rtsp")]
+        return Ok(Box::new(RtspSource::connect(input)?));
+        #[cfg(not(feature = "rtsp"))]
+        return Err(eyre::eyre!(
+            "rtsp:// inputs need asciicam built with --features rtsp"
+        ));
+    }
+
+    let extension = std::path::Path::new(input)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase());
+
+    match extension.as_deref() {
+        Some("gif") => Ok(Box::new(GifSource::open(input)?)),
+        Some(e) if VIDEO_EXTENSIONS.contains(&e) => Ok(Box::new(VideoSource::open(input)?)),
+        _ => Ok(Box::new(ImageSource::open(input)?)),
+    }
+}
+
+/// Parses `libcamera[:index[:WxH]]` and opens that camera.
+#[cfg(feature = "libcamera")]
+fn open_libcamera(input: &str) -> Result<LibcameraSource> {
+    let mut parts = input.split(':').skip(1);
+
+    let index = match parts.next() {
+        None => 0,
+        Some(index) => index.parse()?,
+    };
+    let size = match parts.next().map(|size| size.split_once('x')) {
+        None => None,
+        Some(Some((width, height))) => Some((width.parse()?, height.parse()?)),
+        Some(None) => {
+            return Err(eyre::eyre!(
+                "Expected libcamera[:index[:WxH]], got '{input}'"
+            ))
+        }
+    };
+
+    LibcameraSource::open(index, size)
+}
+
+/// Parses `depth[:index[:NEAR-FAR]]` into a [`DepthCamera`], the range in
+/// meters.
+#[cfg(target_os = "linux")]
+fn open_depth(input: &str) -> Result<DepthCamera> {
+    let mut parts = input.split(':').skip(1);
+
+    let index = match parts.next() {
+        None => 0,
+        Some(index) => index.parse()?,
+    };
+    let range = match parts.next() {
+        None => None,
+        Some(range) => Some(range.parse()?),
+    };
+
+    DepthCamera::open(index, range)
+}
+
+/// Parses `synthetic[:WxH[:seed]]` into a paced [`SyntheticSource`].
+fn open_synthetic(input: &str) -> Result<SyntheticSource> {
+    let mut parts = input.split(':').skip(1);
+
+    let (width, height) = match parts.next().map(|size| size.split_once('x')) {
+        None => (640, 480),
+        Some(Some((width, height))) => (width.parse()?, height.parse()?),
+        Some(None) => return Err(eyre::eyre!("Expected synthetic:WxH[:seed], got '{input}'")),
+    };
+    let seed = match parts.next() {
+        None => 0,
+        Some(seed) => seed.parse()?,
+    };
+
+    Ok(SyntheticSource::new(width, height, seed).paced(true))
+}
@@ -0,0 +1,198 @@
+//! Streaming from devices that only speak the multi-planar API, like the
+//! ISPs of Rockchip and Amlogic boards. The v4l crate only streams
+//! single-plane buffers, so this talks to the driver itself.
+
+use super::FrameMeta;
+use std::io;
+use std::mem;
+use std::os::raw::c_void;
+use std::ptr;
+use std::slice;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use v4l::device::Handle;
+use v4l::v4l2::{self, vidioc};
+use v4l::v4l_sys::{
+    v4l2_buf_type_V4L2_BUF_TYPE_VIDEO_CAPTURE_MPLANE as CAPTURE_MPLANE, v4l2_buffer, v4l2_format,
+    v4l2_memory_V4L2_MEMORY_MMAP as MEMORY_MMAP, v4l2_plane, v4l2_requestbuffers, VIDEO_MAX_PLANES,
+};
+use v4l::{Device, Format, FourCC};
+
+type Planes = [v4l2_plane; VIDEO_MAX_PLANES as usize];
+
+/// The first plane of a buffer mapped into our memory. Formats with more
+/// planes, like NM12, have their luma in the first one.
+struct Mapping {
+    ptr: *mut c_void,
+    length: usize,
+}
+
+/// An MMAP stream over the multi-planar API.
+pub(super) struct MplaneStream {
+    handle: Arc<Handle>,
+    buffers: Vec<Mapping>,
+    // the buffer handed out last, queued again on the next call
+    dequeued: Option<u32>,
+}
+
+// the mappings are only read through &mut self
+unsafe impl Send for MplaneStream {}
+
+impl MplaneStream {
+    /// Sets the format of `dev` to `fourcc` at `resolution`, or whatever it
+    /// picks instead, and starts streaming `count` buffers.
+    pub fn open(
+        dev: &Device,
+        fourcc: FourCC,
+        resolution: Option<(u32, u32)>,
+        count: u32,
+    ) -> io::Result<(Self, Format)> {
+        let handle = dev.handle();
+        let fd = handle.fd();
+
+        // every struct handed to the driver is zeroed and of the type
+        // the request takes, and the mappings are unmapped on drop
+        unsafe {
+            let mut fmt: v4l2_format = mem::zeroed();
+            fmt.type_ = CAPTURE_MPLANE;
+            v4l2::ioctl(fd, vidioc::VIDIOC_G_FMT, as_arg(&mut fmt))?;
+            fmt.fmt.pix_mp.pixelformat = u32::from_le_bytes(fourcc.repr);
+            if let Some((width, height)) = resolution {
+                fmt.fmt.pix_mp.width = width;
+                fmt.fmt.pix_mp.height = height;
+            }
+            v4l2::ioctl(fd, vidioc::VIDIOC_S_FMT, as_arg(&mut fmt))?;
+
+            let pix = fmt.fmt.pix_mp;
+            let mut format = Format::new(
+                pix.width,
+                pix.height,
+                FourCC::new(&pix.pixelformat.to_le_bytes()),
+            );
+            format.stride = pix.plane_fmt[0].bytesperline;
+
+            let mut request: v4l2_requestbuffers = mem::zeroed();
+            request.count = count;
+            request.type_ = CAPTURE_MPLANE;
+            request.memory = MEMORY_MMAP;
+            v4l2::ioctl(fd, vidioc::VIDIOC_REQBUFS, as_arg(&mut request))?;
+
+            let mut stream = Self {
+                handle,
+                buffers: Vec::new(),
+                dequeued: None,
+            };
+            for index in 0..request.count {
+                let mut planes: Planes = mem::zeroed();
+                let mut buf = buffer(index, &mut planes);
+                v4l2::ioctl(fd, vidioc::VIDIOC_QUERYBUF, as_arg(&mut buf))?;
+
+                let length = planes[0].length as usize;
+                let ptr = v4l2::mmap(
+                    ptr::null_mut(),
+                    length,
+                    libc::PROT_READ | libc::PROT_WRITE,
+                    libc::MAP_SHARED,
+                    fd,
+                    planes[0].m.mem_offset as libc::off_t,
+                )?;
+                stream.buffers.push(Mapping { ptr, length });
+                v4l2::ioctl(fd, vidioc::VIDIOC_QBUF, as_arg(&mut buf))?;
+            }
+
+            let mut kind = CAPTURE_MPLANE;
+            v4l2::ioctl(fd, vidioc::VIDIOC_STREAMON, as_arg(&mut kind))?;
+
+            Ok((stream, format))
+        }
+    }
+
+    /// Blocks until the driver filled the next buffer and returns what it
+    /// filled in.
+    pub fn next(&mut self) -> io::Result<(&[u8], FrameMeta)> {
+        let fd = self.handle.fd();
+
+        // as in open, and a dequeued buffer isn't written to by the
+        // driver until it is queued again
+        unsafe {
+            if let Some(index) = self.dequeued.take() {
+                let mut planes: Planes = mem::zeroed();
+                let mut buf = buffer(index, &mut planes);
+                v4l2::ioctl(fd, vidioc::VIDIOC_QBUF, as_arg(&mut buf))?;
+            }
+
+            // the device is opened non-blocking
+            let mut poll = libc::pollfd {
+                fd,
+                events: libc::POLLIN,
+                revents: 0,
+            };
+            while libc::poll(&mut poll, 1, -1) < 0 {
+                let e = io::Error::last_os_error();
+                if e.kind() != io::ErrorKind::Interrupted {
+                    return Err(e);
+                }
+            }
+
+            let mut planes: Planes = mem::zeroed();
+            let mut buf = buffer(0, &mut planes);
+            v4l2::ioctl(fd, vidioc::VIDIOC_DQBUF, as_arg(&mut buf))?;
+            self.dequeued = Some(buf.index);
+
+            let mapping = &self.buffers[buf.index as usize];
+            let plane = planes[0];
+            let start = (plane.data_offset as usize).min(mapping.length);
+            // 0 when the driver doesn't report it
+            let end = match plane.bytesused as usize {
+                0 => mapping.length,
+                n => n.min(mapping.length),
+            };
+            let data = slice::from_raw_parts(mapping.ptr as *const u8, mapping.length);
+
+            let meta = FrameMeta {
+                sequence: buf.sequence.into(),
+                timestamp: Duration::from_secs(buf.timestamp.tv_sec as u64)
+                    + Duration::from_micros(buf.timestamp.tv_usec as u64),
+                bytes_used: plane.bytesused,
+                received: Some(Instant::now()),
+            };
+            Ok((&data[start..end.max(start)], meta))
+        }
+    }
+}
+
+impl Drop for MplaneStream {
+    fn drop(&mut self) {
+        let fd = self.handle.fd();
+
+        // nothing borrows the mappings past &mut self
+        unsafe {
+            let mut kind = CAPTURE_MPLANE;
+            let _ = v4l2::ioctl(fd, vidioc::VIDIOC_STREAMOFF, as_arg(&mut kind));
+            for mapping in &self.buffers {
+                let _ = v4l2::munmap(mapping.ptr, mapping.length);
+            }
+
+            let mut request: v4l2_requestbuffers = mem::zeroed();
+            request.type_ = CAPTURE_MPLANE;
+            request.memory = MEMORY_MMAP;
+            let _ = v4l2::ioctl(fd, vidioc::VIDIOC_REQBUFS, as_arg(&mut request));
+        }
+    }
+}
+
+/// A buffer of the stream pointing at `planes`, for the driver to fill in.
+fn buffer(index: u32, planes: &mut Planes) -> v4l2_buffer {
+    // all zeroes is a valid buffer, filled in below
+    let mut buf: v4l2_buffer = unsafe { mem::zeroed() };
+    buf.index = index;
+    buf.type_ = CAPTURE_MPLANE;
+    buf.memory = MEMORY_MMAP;
+    buf.m.planes = planes.as_mut_ptr();
+    buf.length = planes.len() as u32;
+    buf
+}
+
+fn as_arg<T>(arg: &mut T) -> *mut c_void {
+    arg as *mut T as *mut c_void
+}
@@ -0,0 +1,216 @@
+use super::{CaptureSource, Frame, FrameMeta, Frames, PixelFormat, SourceFormat};
+use crate::convert::{decode_frame, decode_frame_rgb};
+use eyre::{eyre, Result};
+use image::{GrayImage, RgbImage};
+use nokhwa::pixel_format::RgbFormat;
+use nokhwa::utils::{
+    ApiBackend, CameraFormat, CameraIndex, FrameFormat, RequestedFormat, RequestedFormatType,
+    Resolution,
+};
+use std::path::PathBuf;
+#[cfg(target_os = "macos")]
+use std::sync::mpsc;
+use std::time::Instant;
+
+// the capture API of the system, where there is no v4l
+#[cfg(windows)]
+const BACKEND: ApiBackend = ApiBackend::MediaFoundation;
+#[cfg(target_os = "macos")]
+const BACKEND: ApiBackend = ApiBackend::AVFoundation;
+// whatever nokhwa has for anywhere else, v4l on Linux
+#[cfg(not(any(windows, target_os = "macos")))]
+const BACKEND: ApiBackend = ApiBackend::Auto;
+// the frame rate asked for along with a resolution, the closest one the
+// camera has is used
+const FRAME_RATE: u32 = 30;
+
+/// A camera found by [`list_devices`].
+#[derive(Debug, Clone)]
+pub struct DeviceInfo {
+    pub index: usize,
+    /// The system's name for the device, its symbolic link on Windows and
+    /// unique id on macOS.
+    pub path: PathBuf,
+    pub name: Option<String>,
+}
+
+/// Lists the cameras on the system.
+pub fn list_devices() -> Vec<DeviceInfo> {
+    let cameras = nokhwa::query(BACKEND).unwrap_or_default();
+
+    cameras
+        .into_iter()
+        .filter_map(|camera| {
+            Some(DeviceInfo {
+                index: camera.index().as_index().ok()? as usize,
+                path: PathBuf::from(camera.misc()),
+                name: Some(camera.human_name()),
+            })
+        })
+        .collect()
+}
+
+/// Whether the camera with `index` is plugged in.
+pub(crate) fn device_present(index: usize) -> bool {
+    list_devices().iter().any(|device| device.index == index)
+}
+
+/// Asks macOS for permission to use the camera, which shows a prompt the
+/// first time, and waits for the answer.
+#[cfg(target_os = "macos")]
+fn request_access() -> Result<()> {
+    if nokhwa::nokhwa_check() {
+        return Ok(());
+    }
+
+    let (granted_tx, granted_rx) = mpsc::sync_channel(1);
+    nokhwa::nokhwa_initialize(move |granted| {
+        let _ = granted_tx.send(granted);
+    });
+    match granted_rx.recv() {
+        Ok(true) => Ok(()),
+        _ => Err(eyre!(
+            "Not allowed to use the camera, see System Settings > Privacy & Security > Camera"
+        )),
+    }
+}
+
+/// A camera captured through the system's own API where there is no v4l:
+/// Media Foundation on Windows and AVFoundation on macOS. Built with the
+/// `nokhwa` feature it is used everywhere else too, with whatever nokhwa
+/// captures through there.
+///
+/// Frames are MJPEG when the camera delivers that, which most USB webcams
+/// do, and are decoded to [`PixelFormat::Rgb8`] otherwise, like from the
+/// built in cameras of Macs.
+pub struct Camera {
+    camera: nokhwa::Camera,
+    buffer: Vec<u8>,
+    width: u32,
+    height: u32,
+    pixel_format: PixelFormat,
+    started: Instant,
+    meta: FrameMeta,
+}
+
+impl Camera {
+    /// Opens the camera with `index` and starts a capture stream.
+    pub fn open(index: usize) -> Result<Self> {
+        Self::open_with(index, None)
+    }
+
+    /// Like [`open`](Camera::open), but asks the camera for a specific
+    /// `(width, height)`. The camera may pick the closest size it supports.
+    pub fn open_with(index: usize, resolution: Option<(u32, u32)>) -> Result<Self> {
+        #[cfg(target_os = "macos")]
+        request_access()?;
+
+        let requested = match resolution {
+            Some((width, height)) => RequestedFormatType::Closest(CameraFormat::new(
+                Resolution::new(width, height),
+                FrameFormat::MJPEG,
+                FRAME_RATE,
+            )),
+            None => RequestedFormatType::None,
+        };
+        let format = RequestedFormat::new::<RgbFormat>(requested);
+
+        let mut camera =
+            match nokhwa::Camera::with_backend(CameraIndex::Index(index as u32), format, BACKEND) {
+                Ok(camera) => camera,
+                Err(_) => {
+                    return Err(eyre!(
+                        "Could not find device '{index}'. Is a webcam available / plugged in?"
+                    ))
+                }
+            };
+        camera.open_stream()?;
+        let resolution = camera.resolution();
+        let pixel_format = match camera.frame_format() {
+            FrameFormat::MJPEG => PixelFormat::Mjpeg,
+            _ => PixelFormat::Rgb8,
+        };
+
+        Ok(Self {
+            camera,
+            buffer: Vec::new(),
+            width: resolution.width(),
+            height: resolution.height(),
+            pixel_format,
+            started: Instant::now(),
+            meta: FrameMeta::default(),
+        })
+    }
+
+    /// Width of the captured frames in pixels.
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Height of the captured frames in pixels.
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Iterates over decoded grayscale frames.
+    pub fn frames(&mut self) -> Frames<'_, Self, GrayImage> {
+        Frames::new(self, decode_frame)
+    }
+
+    /// Iterates over decoded color frames.
+    pub fn rgb_frames(&mut self) -> Frames<'_, Self, RgbImage> {
+        Frames::new(self, decode_frame_rgb)
+    }
+
+    /// Blocks until the next buffer is available, in the
+    /// [`format`](CaptureSource::format) of the camera.
+    pub fn next_buffer(&mut self) -> Result<&[u8]> {
+        let buffer = self.camera.frame()?;
+        match self.pixel_format {
+            PixelFormat::Mjpeg => {
+                self.buffer.clear();
+                self.buffer.extend_from_slice(buffer.buffer());
+            }
+            _ => self.buffer = buffer.decode_image::<RgbFormat>()?.into_raw(),
+        }
+
+        // neither API hands out sequence numbers or driver timestamps
+        self.meta = FrameMeta {
+            sequence: self.meta.sequence + 1,
+            timestamp: self.started.elapsed(),
+            bytes_used: self.buffer.len() as u32,
+            received: Some(Instant::now()),
+        };
+
+        Ok(&self.buffer)
+    }
+
+    /// Metadata of the buffer last returned by
+    /// [`next_buffer`](Camera::next_buffer).
+    pub fn meta(&self) -> FrameMeta {
+        self.meta
+    }
+}
+
+impl CaptureSource for Camera {
+    fn next_frame(&mut self) -> Result<Frame> {
+        let (width, height) = (self.width, self.height);
+        let data = self.next_buffer()?.to_vec();
+
+        Ok(Frame {
+            data,
+            width,
+            height,
+            pixel_format: self.pixel_format,
+            meta: self.meta,
+        })
+    }
+
+    fn format(&self) -> SourceFormat {
+        SourceFormat {
+            width: self.width,
+            height: self.height,
+            pixel_format: self.pixel_format,
+        }
+    }
+}
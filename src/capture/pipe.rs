@@ -0,0 +1,189 @@
+use super::{CaptureSource, Frame, FrameMeta, PixelFormat, SourceFormat};
+use eyre::{eyre, Result};
+use std::io::{BufRead, BufReader, Cursor, Read, Stdin};
+use std::str::FromStr;
+use std::time::Instant;
+
+/// How frames are laid out in a byte stream read by [`PipeSource`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputFormat {
+    /// Concatenated JPEG images, the size is taken from the first one.
+    Mjpeg,
+    /// Raw frames of `width * height` luma bytes.
+    Gray8 { width: u32, height: u32 },
+    /// Raw frames of `width * height * 3` rgb bytes.
+    Rgb8 { width: u32, height: u32 },
+}
+
+impl FromStr for InputFormat {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (name, size) = match s.split_once(':') {
+            Some((name, size)) => (name, Some(size)),
+            None => (s, None),
+        };
+        let size = match size.map(|size| size.split_once('x')) {
+            None => None,
+            Some(Some((width, height))) => Some((width.parse()?, height.parse()?)),
+            Some(None) => return Err(eyre!("Expected a size like 640x480 in '{s}'")),
+        };
+
+        match (name, size) {
+            ("mjpeg", None) => Ok(InputFormat::Mjpeg),
+            ("gray8", Some((width, height))) => Ok(InputFormat::Gray8 { width, height }),
+            ("rgb8", Some((width, height))) => Ok(InputFormat::Rgb8 { width, height }),
+            ("gray8" | "rgb8", None) => Err(eyre!("'{name}' needs a size, e.g. {name}:640x480")),
+            _ => Err(eyre!(
+                "Unknown input format '{s}', expected mjpeg, gray8:WxH or rgb8:WxH"
+            )),
+        }
+    }
+}
+
+/// Frames read from a byte stream, usually stdin at the end of a pipeline.
+pub struct PipeSource<R> {
+    reader: BufReader<R>,
+    input_format: InputFormat,
+    width: u32,
+    height: u32,
+    started: Instant,
+    sequence: u64,
+    // the first jpeg is read up front to learn the frame size
+    pending: Option<Vec<u8>>,
+}
+
+impl PipeSource<Stdin> {
+    pub fn stdin(input_format: InputFormat) -> Result<Self> {
+        Self::new(std::io::stdin(), input_format)
+    }
+}
+
+impl<R: Read> PipeSource<R> {
+    pub fn new(reader: R, input_format: InputFormat) -> Result<Self> {
+        let mut reader = BufReader::new(reader);
+
+        let (width, height, pending) = match input_format {
+            InputFormat::Gray8 { width, height } | InputFormat::Rgb8 { width, height } => {
+                if width == 0 || height == 0 {
+                    return Err(eyre!("Invalid input size {width}x{height}"));
+                }
+                (width, height, None)
+            }
+            InputFormat::Mjpeg => {
+                let first = match read_jpeg(&mut reader)? {
+                    Some(jpeg) => jpeg,
+                    None => return Err(eyre!("Input ended before the first frame")),
+                };
+                let (width, height) = image::io::Reader::new(Cursor::new(&first))
+                    .with_guessed_format()?
+                    .into_dimensions()?;
+                (width, height, Some(first))
+            }
+        };
+
+        Ok(Self {
+            reader,
+            input_format,
+            width,
+            height,
+            started: Instant::now(),
+            sequence: 0,
+            pending,
+        })
+    }
+
+    fn pixel_format(&self) -> PixelFormat {
+        match self.input_format {
+            InputFormat::Mjpeg => PixelFormat::Mjpeg,
+            InputFormat::Gray8 { .. } => PixelFormat::Gray8,
+            InputFormat::Rgb8 { .. } => PixelFormat::Rgb8,
+        }
+    }
+}
+
+impl<R: Read> CaptureSource for PipeSource<R> {
+    fn next_frame(&mut self) -> Result<Frame> {
+        let pixels = self.width as usize * self.height as usize;
+
+        let data = match self.input_format {
+            InputFormat::Mjpeg => match self.pending.take() {
+                Some(jpeg) => jpeg,
+                None => match read_jpeg(&mut self.reader)? {
+                    Some(jpeg) => jpeg,
+                    None => return Err(eyre!("Input ended")),
+                },
+            },
+            InputFormat::Gray8 { .. } | InputFormat::Rgb8 { .. } => {
+                let channels = match self.input_format {
+                    InputFormat::Rgb8 { .. } => 3,
+                    _ => 1,
+                };
+                let mut data = vec![0; pixels * channels];
+                self.reader
+                    .read_exact(&mut data)
+                    .map_err(|_| eyre!("Input ended"))?;
+                data
+            }
+        };
+
+        self.sequence += 1;
+
+        Ok(Frame {
+            meta: FrameMeta {
+                sequence: self.sequence,
+                timestamp: self.started.elapsed(),
+                bytes_used: data.len() as u32,
+                received: Some(Instant::now()),
+            },
+            data,
+            width: self.width,
+            height: self.height,
+            pixel_format: self.pixel_format(),
+        })
+    }
+
+    fn format(&self) -> SourceFormat {
+        SourceFormat {
+            width: self.width,
+            height: self.height,
+            pixel_format: self.pixel_format(),
+        }
+    }
+}
+
+/// Reads the next JPEG image from `reader`, from its start of image marker
+/// up to the end of image marker. Returns `None` at the end of the stream.
+pub(crate) fn read_jpeg(reader: &mut impl BufRead) -> Result<Option<Vec<u8>>> {
+    let mut data = Vec::new();
+
+    // skip whatever comes before the start of image marker, multipart
+    // boundaries or padding
+    loop {
+        data.clear();
+        if reader.read_until(0xff, &mut data)? == 0 {
+            return Ok(None);
+        }
+        // peek rather than consume, the byte may be the 0xff of the marker
+        let next = reader.fill_buf()?.first().copied();
+        match next {
+            None => return Ok(None),
+            Some(0xd8) => {
+                reader.consume(1);
+                break;
+            }
+            Some(_) => (),
+        }
+    }
+
+    data.clear();
+    data.extend_from_slice(&[0xff, 0xd8]);
+    loop {
+        if reader.read_until(0xd9, &mut data)? == 0 {
+            return Ok(None);
+        }
+        if data.ends_with(&[0xff, 0xd9]) {
+            return Ok(Some(data));
+        }
+    }
+}
@@ -0,0 +1,38 @@
+use std::sync::{Arc, Mutex};
+
+// buffers kept around at most, a frame being decoded, one waiting to be
+// rendered and one on screen, plus one to spare
+const POOL_SIZE: usize = 4;
+
+/// Buffers for frame data, handed back once a frame is done with and taken
+/// again for the next one, so a long run doesn't allocate a frame's worth of
+/// pixels every frame.
+///
+/// Clones share the same buffers, one can stay on the thread filling frames
+/// and another go to the thread done with them.
+#[derive(Clone, Default)]
+pub struct FramePool {
+    buffers: Arc<Mutex<Vec<Vec<u8>>>>,
+}
+
+impl FramePool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// An empty buffer, with the room of one handed back when there is one.
+    pub fn take(&self) -> Vec<u8> {
+        let mut buffer = self.buffers.lock().unwrap().pop().unwrap_or_default();
+        buffer.clear();
+        buffer
+    }
+
+    /// Hands `buffer` back to be taken again, or drops it when the pool is
+    /// full.
+    pub fn give(&self, buffer: Vec<u8>) {
+        let mut buffers = self.buffers.lock().unwrap();
+        if buffers.len() < POOL_SIZE {
+            buffers.push(buffer);
+        }
+    }
+}
@@ -0,0 +1,265 @@
+use super::{CaptureSource, Frame, FrameMeta, PixelFormat, SourceFormat};
+use crate::convert::{unpad_rows, yuyv_to_rgb};
+use eyre::{eyre, Result};
+use pipewire as pw;
+use pw::spa::param::format::{FormatProperties, MediaSubtype, MediaType};
+use pw::spa::param::format_utils::parse_format;
+use pw::spa::param::video::{VideoFormat, VideoInfoRaw};
+use pw::spa::param::ParamType;
+use pw::spa::pod::serialize::PodSerializer;
+use pw::spa::pod::{self, Pod};
+use pw::spa::utils::{Direction, Fraction, Rectangle, SpaTypes};
+use pw::stream::{Stream, StreamFlags};
+use std::io::Cursor;
+use std::os::fd::OwnedFd;
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// how long to wait for the camera to agree on a format, and after that for
+// each frame
+const FRAME_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A camera shared by PipeWire through the camera portal, the way sandboxed
+/// apps like Flatpaks get to cameras without access to `/dev/video*`.
+///
+/// The portal asks the user for permission the first time. PipeWire objects
+/// can't leave the thread they were made on, so the stream runs on a thread
+/// of its own that hands the frames over as [`PixelFormat::Rgb8`].
+pub struct PipeWireSource {
+    frames: Receiver<Result<Frame>>,
+    width: u32,
+    height: u32,
+}
+
+impl PipeWireSource {
+    /// Asks the portal for the cameras and starts streaming from the default
+    /// one, waiting until it delivers.
+    pub fn open() -> Result<Self> {
+        let remote = match pollster::block_on(ashpd::desktop::camera::request()) {
+            Ok(Some(remote)) => remote,
+            Ok(None) => return Err(eyre!("The camera portal has no cameras")),
+            Err(e) => {
+                return Err(eyre!(
+                    "Not allowed to use the camera through the portal ({e})"
+                ))
+            }
+        };
+
+        let (opened_tx, opened) = mpsc::sync_channel(1);
+        let (frames_tx, frames) = mpsc::sync_channel(1);
+        thread::spawn(move || {
+            if let Err(e) = stream(remote, opened_tx, frames_tx.clone()) {
+                let _ = frames_tx.send(Err(e));
+            }
+        });
+
+        let (width, height) = match opened.recv_timeout(FRAME_TIMEOUT) {
+            Ok(size) => size,
+            Err(_) => match frames.try_recv() {
+                Ok(Err(e)) => return Err(e),
+                _ => return Err(eyre!("The camera never agreed on a format")),
+            },
+        };
+
+        Ok(Self {
+            frames,
+            width,
+            height,
+        })
+    }
+}
+
+impl CaptureSource for PipeWireSource {
+    fn next_frame(&mut self) -> Result<Frame> {
+        match self.frames.recv_timeout(FRAME_TIMEOUT) {
+            Ok(frame) => frame,
+            Err(_) => Err(eyre!("The camera stopped delivering frames")),
+        }
+    }
+
+    fn format(&self) -> SourceFormat {
+        SourceFormat {
+            width: self.width,
+            height: self.height,
+            pixel_format: PixelFormat::Rgb8,
+        }
+    }
+}
+
+struct State {
+    format: VideoInfoRaw,
+    // taken once the format is known
+    opened: Option<SyncSender<(u32, u32)>>,
+    frames: SyncSender<Result<Frame>>,
+    started: Instant,
+    sequence: u64,
+}
+
+/// Runs a PipeWire stream from the camera behind `remote` until the source
+/// is dropped.
+fn stream(
+    remote: OwnedFd,
+    opened: SyncSender<(u32, u32)>,
+    frames: SyncSender<Result<Frame>>,
+) -> Result<()> {
+    pw::init();
+    let mainloop = pw::MainLoop::new()?;
+    let context = pw::Context::new(&mainloop)?;
+    let core = context.connect_fd(remote, None)?;
+
+    let stream = Stream::new(
+        &core,
+        "asciicam",
+        pw::properties! {
+            *pw::keys::MEDIA_TYPE => "Video",
+            *pw::keys::MEDIA_CATEGORY => "Capture",
+            *pw::keys::MEDIA_ROLE => "Camera",
+        },
+    )?;
+
+    let state = State {
+        format: VideoInfoRaw::new(),
+        opened: Some(opened),
+        frames,
+        started: Instant::now(),
+        sequence: 0,
+    };
+    let quit = mainloop.clone();
+    let _listener = stream
+        .add_local_listener_with_user_data(state)
+        .param_changed(|_, state, id, param| {
+            let param = match param {
+                Some(param) if id == ParamType::Format.as_raw() => param,
+                _ => return,
+            };
+            match parse_format(param) {
+                Ok((MediaType::Video, MediaSubtype::Raw)) => (),
+                _ => return,
+            }
+            if state.format.parse(param).is_err() {
+                return;
+            }
+            if let Some(opened) = state.opened.take() {
+                let size = state.format.size();
+                let _ = opened.send((size.width, size.height));
+            }
+        })
+        .process(move |stream, state| {
+            let mut buffer = match stream.dequeue_buffer() {
+                Some(buffer) => buffer,
+                None => return,
+            };
+            let data = match buffer.datas_mut().first_mut() {
+                Some(data) => data,
+                None => return,
+            };
+            let (offset, size, stride) = {
+                let chunk = data.chunk();
+                (
+                    chunk.offset() as usize,
+                    chunk.size() as usize,
+                    chunk.stride() as usize,
+                )
+            };
+            let bytes = match data.data() {
+                Some(bytes) if offset + size <= bytes.len() => &bytes[offset..offset + size],
+                _ => return,
+            };
+
+            let Rectangle { width, height } = state.format.size();
+            let rgb = match state.format.format() {
+                VideoFormat::RGB => unpad_rows(bytes, stride, width as usize * 3, height),
+                VideoFormat::YUY2 => {
+                    yuyv_to_rgb(&unpad_rows(bytes, stride, width as usize * 2, height))
+                }
+                _ => return,
+            };
+
+            state.sequence += 1;
+            let frame = Frame {
+                meta: FrameMeta {
+                    sequence: state.sequence,
+                    timestamp: state.started.elapsed(),
+                    bytes_used: size as u32,
+                    received: Some(Instant::now()),
+                },
+                data: rgb,
+                width,
+                height,
+                pixel_format: PixelFormat::Rgb8,
+            };
+            // a frame the viewer isn't ready for yet is dropped, stalling
+            // the PipeWire loop would back up the camera
+            if let Err(TrySendError::Disconnected(_)) = state.frames.try_send(Ok(frame)) {
+                quit.quit();
+            }
+        })
+        .register()?;
+
+    // the formats webcams deliver that are easy to turn into rgb, at any
+    // size and frame rate
+    let formats = pod::object!(
+        SpaTypes::ObjectParamFormat,
+        ParamType::EnumFormat,
+        pod::property!(FormatProperties::MediaType, Id, MediaType::Video),
+        pod::property!(FormatProperties::MediaSubtype, Id, MediaSubtype::Raw),
+        pod::property!(
+            FormatProperties::VideoFormat,
+            Choice,
+            Enum,
+            Id,
+            VideoFormat::YUY2,
+            VideoFormat::YUY2,
+            VideoFormat::RGB
+        ),
+        pod::property!(
+            FormatProperties::VideoSize,
+            Choice,
+            Range,
+            Rectangle,
+            Rectangle {
+                width: 640,
+                height: 480
+            },
+            Rectangle {
+                width: 1,
+                height: 1
+            },
+            Rectangle {
+                width: 4096,
+                height: 4096
+            }
+        ),
+        pod::property!(
+            FormatProperties::VideoFramerate,
+            Choice,
+            Range,
+            Fraction,
+            Fraction { num: 30, denom: 1 },
+            Fraction { num: 0, denom: 1 },
+            Fraction {
+                num: 1000,
+                denom: 1
+            }
+        ),
+    );
+    let formats = PodSerializer::serialize(Cursor::new(Vec::new()), &pod::Value::Object(formats))
+        .map_err(|e| eyre!("Could not describe the video formats ({e:?})"))?
+        .0
+        .into_inner();
+    let mut params = match Pod::from_bytes(&formats) {
+        Some(pod) => [pod],
+        None => return Err(eyre!("Could not describe the video formats")),
+    };
+
+    stream.connect(
+        Direction::Input,
+        None,
+        StreamFlags::AUTOCONNECT | StreamFlags::MAP_BUFFERS,
+        &mut params,
+    )?;
+    mainloop.run();
+
+    Ok(())
+}
@@ -0,0 +1,141 @@
+use super::{CameraControl, Controls, PAN, TILT, ZOOM};
+use eyre::{eyre, Result};
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// the controls a preset remembers
+const PRESET_CONTROLS: [CameraControl; 3] = [PAN, TILT, ZOOM];
+
+/// Where presets are kept when no file is given:
+/// `$XDG_CONFIG_HOME/asciicam/presets`, or `~/.config/asciicam/presets`.
+pub fn default_presets_path() -> PathBuf {
+    let config = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) => PathBuf::from(dir),
+        None => match env::var_os("HOME") {
+            Some(home) => PathBuf::from(home).join(".config"),
+            None => env::temp_dir(),
+        },
+    };
+
+    config.join("asciicam").join("presets")
+}
+
+/// Saved positions of a pan/tilt/zoom camera, in slots 1 to 9.
+///
+/// They are kept in a text file with a line per slot, like
+/// `1 pan_absolute=3600 tilt_absolute=-7200 zoom_absolute=100`, so they can
+/// be edited by hand too. Only absolute controls can be stored, a camera
+/// that can only move relative to where it is has no presets.
+pub struct Presets {
+    path: PathBuf,
+    slots: BTreeMap<u8, Vec<(String, i64)>>,
+}
+
+impl Presets {
+    /// Reads the presets in `path`, there are none yet if it doesn't exist.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(eyre!("Could not read '{}': {e}", path.display())),
+        };
+
+        let mut slots = BTreeMap::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut words = line.split_whitespace();
+            let slot = match words.next().map(str::parse) {
+                Some(Ok(slot)) => slot,
+                _ => {
+                    return Err(eyre!(
+                        "{}:{}: expected a slot number",
+                        path.display(),
+                        number + 1
+                    ))
+                }
+            };
+            let mut values = Vec::new();
+            for word in words {
+                match word.split_once('=').map(|(k, v)| (k, v.parse())) {
+                    Some((key, Ok(value))) => values.push((key.to_string(), value)),
+                    _ => {
+                        return Err(eyre!(
+                            "{}:{}: expected NAME=VALUE, got '{word}'",
+                            path.display(),
+                            number + 1
+                        ))
+                    }
+                }
+            }
+            slots.insert(slot, values);
+        }
+
+        Ok(Self { path, slots })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Stores where the camera is pointing in `slot` and writes the file.
+    pub fn save(&mut self, slot: u8, controls: &Controls) -> Result<()> {
+        let mut values = Vec::new();
+        for control in &PRESET_CONTROLS {
+            match controls.lookup(control) {
+                Ok(info) if info.key.ends_with("_absolute") => {
+                    if let Some(value) = info.value {
+                        values.push((info.key, value));
+                    }
+                }
+                _ => (),
+            }
+        }
+        if values.is_empty() {
+            return Err(eyre!(
+                "The camera has no absolute pan, tilt or zoom to save"
+            ));
+        }
+        self.slots.insert(slot, values);
+
+        let mut text = String::from("# asciicam camera presets: SLOT NAME=VALUE...\n");
+        for (slot, values) in &self.slots {
+            let _ = write!(text, "{slot}");
+            for (key, value) in values {
+                let _ = write!(text, " {key}={value}");
+            }
+            text.push('\n');
+        }
+
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        if let Err(e) = fs::write(&self.path, text) {
+            return Err(eyre!("Could not write '{}': {e}", self.path.display()));
+        }
+
+        Ok(())
+    }
+
+    /// Points the camera where `slot` says.
+    pub fn recall(&self, slot: u8, controls: &Controls) -> Result<()> {
+        let values = match self.slots.get(&slot) {
+            Some(values) => values,
+            None => return Err(eyre!("No preset {slot} saved yet")),
+        };
+
+        for (key, value) in values {
+            controls.set(key, *value)?;
+        }
+
+        Ok(())
+    }
+}
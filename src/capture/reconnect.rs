@@ -0,0 +1,294 @@
+use super::{
+    device_present, Camera, CaptureSource, Frame, FrameMeta, FramePool, IoMode, PixelFormat,
+    SourceFormat,
+};
+use crate::convert::LevelWindow;
+use eyre::{eyre, Result};
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+// how often the device node is looked for while the camera is gone
+const RETRY_INTERVAL: Duration = Duration::from_secs(1);
+// how often the placeholder frame is delivered meanwhile
+const PLACEHOLDER_INTERVAL: Duration = Duration::from_millis(100);
+// the first wait between attempts to open the camera at startup, doubling
+// up to the maximum
+const INITIAL_BACKOFF: Duration = Duration::from_millis(100);
+const MAX_BACKOFF: Duration = Duration::from_secs(5);
+// times in a row the stream is rebuilt for a device that is still there
+// before giving up on it
+const MAX_REOPEN_ATTEMPTS: u32 = 5;
+// how long the status bar keeps showing an error the camera recovered from
+const RECOVERED_STATUS: Duration = Duration::from_secs(5);
+
+/// A [`Camera`] that survives being unplugged.
+///
+/// When capturing fails the device is closed and black placeholder frames
+/// are delivered instead, with [`status`](CaptureSource::status) saying we
+/// are waiting for the camera, until `/dev/videoN` shows up again and can
+/// be opened. The placeholders are [`PixelFormat::Gray8`] of the size the
+/// camera last had.
+///
+/// Errors while the device is still there, like `EIO` from a flaky USB hub,
+/// tear down and rebuild the stream right away. Only when that keeps
+/// failing does [`next_frame`](CaptureSource::next_frame) return an error.
+pub struct ReconnectingCamera {
+    index: usize,
+    resolution: Option<(u32, u32)>,
+    io_mode: IoMode,
+    black_level: u8,
+    window: Option<LevelWindow>,
+    pool: FramePool,
+    camera: Option<Camera>,
+    format: SourceFormat,
+    // whether the device node was there at the last attempt
+    present: bool,
+    last_attempt: Option<Instant>,
+    // failures in a row without a frame in between
+    attempts: u32,
+    // the last capture error and when it happened
+    error: Option<(String, Instant)>,
+    sequence: u64,
+    reconnects: u64,
+}
+
+impl ReconnectingCamera {
+    /// Opens the camera like [`Camera::open_with`], failing if it can't be
+    /// opened right now.
+    pub fn open(index: usize, resolution: Option<(u32, u32)>) -> Result<Self> {
+        Self::open_io(index, resolution, IoMode::Mmap)
+    }
+
+    /// Like [`open`](ReconnectingCamera::open), streaming with `io_mode`,
+    /// also when the camera is opened again.
+    pub fn open_io(index: usize, resolution: Option<(u32, u32)>, io_mode: IoMode) -> Result<Self> {
+        let camera = open_camera(index, resolution, io_mode)?;
+
+        Ok(Self {
+            index,
+            resolution,
+            io_mode,
+            black_level: 0,
+            window: None,
+            pool: FramePool::new(),
+            format: camera.format(),
+            camera: Some(camera),
+            present: true,
+            last_attempt: None,
+            attempts: 0,
+            error: None,
+            sequence: 0,
+            reconnects: 0,
+        })
+    }
+
+    /// Like [`open`](ReconnectingCamera::open), but keeps trying with
+    /// exponential backoff for up to `timeout` when the device is busy or
+    /// not there yet, e.g. at boot before USB devices have settled. The
+    /// last error is returned once `timeout` has passed.
+    pub fn open_retrying(
+        index: usize,
+        resolution: Option<(u32, u32)>,
+        io_mode: IoMode,
+        timeout: Duration,
+    ) -> Result<Self> {
+        let started = Instant::now();
+        let mut backoff = INITIAL_BACKOFF;
+
+        loop {
+            match Self::open_io(index, resolution, io_mode) {
+                Ok(camera) => return Ok(camera),
+                Err(e) if started.elapsed() >= timeout => return Err(e),
+                Err(_) => (),
+            }
+
+            // don't sleep past the deadline, but do try once more at it
+            let left = timeout.saturating_sub(started.elapsed());
+            thread::sleep(backoff.min(left));
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+        }
+    }
+
+    /// The index of the device, `/dev/video{index}`.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Sets the black level of a raw sensor, see
+    /// [`Camera::set_black_level`], also when the camera is opened again.
+    pub fn set_black_level(&mut self, black_level: u8) {
+        self.black_level = black_level;
+        if let Some(camera) = &mut self.camera {
+            set_levels(camera, self.black_level, self.window);
+        }
+    }
+
+    /// Sets the window of a high bit depth sensor, see
+    /// [`Camera::set_window`], also when the camera is opened again.
+    pub fn set_window(&mut self, window: Option<LevelWindow>) {
+        self.window = window;
+        if let Some(camera) = &mut self.camera {
+            set_levels(camera, self.black_level, self.window);
+        }
+    }
+
+    /// Whether the camera is currently open.
+    pub fn is_connected(&self) -> bool {
+        self.camera.is_some()
+    }
+
+    fn reconnect(&mut self) -> Result<()> {
+        // the first attempt after an error is right away
+        if matches!(self.last_attempt, Some(last) if last.elapsed() < RETRY_INTERVAL) {
+            return Ok(());
+        }
+        self.last_attempt = Some(Instant::now());
+
+        let present = device_present(self.index);
+        if present && !self.present {
+            info!("/dev/video{} is back", self.index);
+        } else if !present && self.present {
+            warn!("/dev/video{} went away, waiting for it", self.index);
+        }
+        self.present = present;
+        if !self.present {
+            // unplugged, which can take as long as it takes
+            self.attempts = 0;
+            return Ok(());
+        }
+
+        match open_camera(self.index, self.resolution, self.io_mode) {
+            Ok(mut camera) => {
+                set_levels(&mut camera, self.black_level, self.window);
+                camera.set_pool(self.pool.clone());
+                info!("reopened /dev/video{}", self.index);
+                self.reconnects += 1;
+                self.format = camera.format();
+                self.camera = Some(camera);
+                Ok(())
+            }
+            Err(e) => self.failed(e),
+        }
+    }
+
+    /// Records a failed attempt at capturing, giving up once there were too
+    /// many in a row.
+    fn failed(&mut self, e: eyre::Report) -> Result<()> {
+        self.attempts += 1;
+        warn!(
+            attempt = self.attempts,
+            "/dev/video{} failed: {e}", self.index
+        );
+        if self.attempts >= MAX_REOPEN_ATTEMPTS {
+            return Err(eyre!(
+                "/dev/video{} keeps failing, gave up after {} attempts: {e}",
+                self.index,
+                self.attempts
+            ));
+        }
+        self.error = Some((e.to_string(), Instant::now()));
+
+        Ok(())
+    }
+
+    fn placeholder(&mut self) -> Frame {
+        thread::sleep(PLACEHOLDER_INTERVAL);
+        self.sequence += 1;
+
+        let (width, height) = (self.format.width, self.format.height);
+        Frame {
+            data: vec![0; width as usize * height as usize],
+            width,
+            height,
+            pixel_format: PixelFormat::Gray8,
+            meta: FrameMeta {
+                sequence: self.sequence,
+                received: Some(Instant::now()),
+                ..FrameMeta::default()
+            },
+        }
+    }
+}
+
+impl CaptureSource for ReconnectingCamera {
+    fn next_frame(&mut self) -> Result<Frame> {
+        if self.camera.is_none() {
+            self.reconnect()?;
+        }
+
+        if let Some(camera) = &mut self.camera {
+            match camera.next_frame() {
+                Ok(frame) => {
+                    self.sequence = frame.meta.sequence;
+                    self.attempts = 0;
+                    return Ok(frame);
+                }
+                // closing the device lets it go away if it was unplugged, and
+                // gets a fresh stream if it wasn't
+                Err(e) => {
+                    self.camera = None;
+                    self.last_attempt = None;
+                    self.failed(e)?;
+                }
+            }
+        }
+
+        Ok(self.placeholder())
+    }
+
+    fn format(&self) -> SourceFormat {
+        self.format
+    }
+
+    fn set_pool(&mut self, pool: FramePool) {
+        if let Some(camera) = &mut self.camera {
+            camera.set_pool(pool.clone());
+        }
+        self.pool = pool;
+    }
+
+    fn status(&self) -> Option<String> {
+        match (&self.camera, &self.error) {
+            (None, _) if !self.present => {
+                Some(format!("Waiting for camera /dev/video{}…", self.index))
+            }
+            (None, Some((error, _))) => Some(format!(
+                "Capture failed ({error}), reopening /dev/video{}…",
+                self.index
+            )),
+            (Some(_), Some((error, at))) if at.elapsed() < RECOVERED_STATUS => {
+                Some(format!("Recovered from a capture error ({error})"))
+            }
+            _ => None,
+        }
+    }
+
+    fn reconnects(&self) -> u64 {
+        self.reconnects
+    }
+}
+
+#[cfg(all(target_os = "linux", not(feature = "nokhwa")))]
+fn open_camera(index: usize, resolution: Option<(u32, u32)>, io_mode: IoMode) -> Result<Camera> {
+    Camera::open_io(index, resolution, io_mode)
+}
+
+// only v4l cameras have a choice
+#[cfg(not(all(target_os = "linux", not(feature = "nokhwa"))))]
+fn open_camera(index: usize, resolution: Option<(u32, u32)>, io_mode: IoMode) -> Result<Camera> {
+    if io_mode != IoMode::Mmap {
+        return Err(eyre!("--io-mode is only for v4l cameras"));
+    }
+    Camera::open_with(index, resolution)
+}
+
+#[cfg(all(target_os = "linux", not(feature = "nokhwa")))]
+fn set_levels(camera: &mut Camera, black_level: u8, window: Option<LevelWindow>) {
+    camera.set_black_level(black_level);
+    camera.set_window(window);
+}
+
+// raw sensors only stream through v4l
+#[cfg(not(all(target_os = "linux", not(feature = "nokhwa"))))]
+fn set_levels(_camera: &mut Camera, _black_level: u8, _window: Option<LevelWindow>) {}
@@ -0,0 +1,164 @@
+use super::video::probe;
+use super::{CaptureSource, Frame, FrameMeta, PixelFormat, SourceFormat};
+use eyre::{eyre, Result};
+use std::io::Read;
+use std::path::Path;
+use std::process::{Child, ChildStdout, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+// how long ffmpeg waits on a silent stream before giving up, in microseconds
+const SOCKET_TIMEOUT: &str = "5000000";
+
+// delay between reconnect attempts, doubling up to the maximum
+const RECONNECT_DELAY: Duration = Duration::from_millis(500);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(10);
+
+/// An RTSP stream (usually H.264 from an IP camera) decoded by an `ffmpeg`
+/// child process. Needs `ffmpeg` and `ffprobe` in `$PATH`.
+///
+/// When the stream drops the last frame is held while reconnecting.
+pub struct RtspSource {
+    url: String,
+    width: u32,
+    height: u32,
+    child: Option<(Child, ChildStdout)>,
+    last: Frame,
+    started: Instant,
+    sequence: u64,
+    retry_at: Instant,
+    retry_delay: Duration,
+    reconnects: u64,
+}
+
+impl RtspSource {
+    pub fn connect(url: &str) -> Result<Self> {
+        let (width, height, _) = probe(Path::new(url))?;
+        let child = spawn(url, width, height)?;
+
+        Ok(Self {
+            url: url.to_string(),
+            width,
+            height,
+            child: Some(child),
+            last: Frame {
+                data: vec![0; width as usize * height as usize * 3],
+                width,
+                height,
+                pixel_format: PixelFormat::Rgb8,
+                meta: FrameMeta::default(),
+            },
+            started: Instant::now(),
+            sequence: 0,
+            retry_at: Instant::now(),
+            retry_delay: RECONNECT_DELAY,
+            reconnects: 0,
+        })
+    }
+
+    /// Whether frames are currently coming in.
+    pub fn is_connected(&self) -> bool {
+        self.child.is_some()
+    }
+
+    fn disconnect(&mut self) {
+        if let Some((mut child, _)) = self.child.take() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+
+        self.retry_at = Instant::now() + self.retry_delay;
+        self.retry_delay = (self.retry_delay * 2).min(MAX_RECONNECT_DELAY);
+    }
+}
+
+impl CaptureSource for RtspSource {
+    fn next_frame(&mut self) -> Result<Frame> {
+        if self.child.is_none() {
+            if let Some(wait) = self.retry_at.checked_duration_since(Instant::now()) {
+                // keep handing out the last frame so the viewer stays
+                // responsive while the camera is away
+                thread::sleep(wait.min(RECONNECT_DELAY));
+                return Ok(self.last.clone());
+            }
+
+            match spawn(&self.url, self.width, self.height) {
+                Ok(child) => self.child = Some(child),
+                Err(e) => {
+                    warn!("could not reconnect to {}: {e}", self.url);
+                    self.disconnect();
+                    return Ok(self.last.clone());
+                }
+            }
+        }
+
+        let read = match &mut self.child {
+            Some((_, stdout)) => stdout.read_exact(&mut self.last.data),
+            None => return Ok(self.last.clone()),
+        };
+        if let Err(e) = read {
+            warn!("lost {} ({e}), reconnecting", self.url);
+            self.disconnect();
+            return Ok(self.last.clone());
+        }
+
+        if self.retry_delay != RECONNECT_DELAY {
+            info!("reconnected to {}", self.url);
+            self.reconnects += 1;
+        }
+        self.retry_delay = RECONNECT_DELAY;
+        self.sequence += 1;
+        self.last.meta = FrameMeta {
+            sequence: self.sequence,
+            timestamp: self.started.elapsed(),
+            bytes_used: self.last.data.len() as u32,
+            received: Some(Instant::now()),
+        };
+
+        Ok(self.last.clone())
+    }
+
+    fn format(&self) -> SourceFormat {
+        SourceFormat {
+            width: self.width,
+            height: self.height,
+            pixel_format: PixelFormat::Rgb8,
+        }
+    }
+
+    fn reconnects(&self) -> u64 {
+        self.reconnects
+    }
+}
+
+impl Drop for RtspSource {
+    fn drop(&mut self) {
+        if let Some((mut child, _)) = self.child.take() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+/// Starts decoding `url` into raw rgb24 frames scaled to the probed size,
+/// so a camera changing resolution on reconnect can't break the stream.
+fn spawn(url: &str, width: u32, height: u32) -> Result<(Child, ChildStdout)> {
+    let mut child = Command::new("ffmpeg")
+        .args(["-v", "error", "-nostdin", "-rtsp_transport", "tcp"])
+        .args(["-timeout", SOCKET_TIMEOUT])
+        .args(["-i", url])
+        .arg("-vf")
+        .arg(format!("scale={width}:{height}"))
+        .args(["-an", "-f", "rawvideo", "-pix_fmt", "rgb24", "-"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| eyre!("Could not run ffmpeg, is it installed? ({e})"))?;
+
+    match child.stdout.take() {
+        Some(stdout) => Ok((child, stdout)),
+        None => Err(eyre!("Could not read from ffmpeg")),
+    }
+}
@@ -0,0 +1,211 @@
+use super::{CaptureSource, Frame, FrameMeta, PixelFormat, SourceFormat};
+use eyre::{eyre, Result};
+use std::str::FromStr;
+use std::thread;
+use std::time::{Duration, Instant};
+use x11rb::connection::Connection;
+use x11rb::protocol::shm::{self, ConnectionExt as _};
+use x11rb::protocol::xproto::{ImageFormat, Window};
+use x11rb::rust_connection::RustConnection;
+
+// grabbing faster than this only burns cpu, terminals can't keep up anyway
+const FRAME_INTERVAL: Duration = Duration::from_millis(33);
+
+/// A rectangle of the screen, parsed from X11 geometry syntax `WxH+X+Y`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    pub x: i16,
+    pub y: i16,
+    pub width: u16,
+    pub height: u16,
+}
+
+impl FromStr for Region {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (size, offset) = match s.split_once('+') {
+            Some((size, offset)) => (size, offset),
+            None => (s, "0+0"),
+        };
+        let (width, height) = match size.split_once('x') {
+            Some(size) => size,
+            None => return Err(eyre!("Expected a region like 800x600+0+0, got '{s}'")),
+        };
+        let (x, y) = match offset.split_once('+') {
+            Some(offset) => offset,
+            None => return Err(eyre!("Expected a region like 800x600+0+0, got '{s}'")),
+        };
+
+        Ok(Self {
+            x: x.parse()?,
+            y: y.parse()?,
+            width: width.parse()?,
+            height: height.parse()?,
+        })
+    }
+}
+
+/// Captures the X11 screen, or a region of it, through the MIT-SHM
+/// extension so frames don't have to be copied over the socket.
+///
+/// Only X11 is supported, Wayland compositors don't let clients read the
+/// screen this way.
+pub struct ScreenSource {
+    conn: RustConnection,
+    root: Window,
+    region: Region,
+    seg: shm::Seg,
+    addr: *mut u8,
+    last: Option<Instant>,
+    started: Instant,
+    sequence: u64,
+}
+
+// SAFETY: the shared memory segment is only read through `addr` by the
+// source owning it and detached once it is dropped, whatever thread that is on
+unsafe impl Send for ScreenSource {}
+
+impl ScreenSource {
+    /// Connects to `$DISPLAY` and captures `region`, or the whole screen.
+    pub fn open(region: Option<Region>) -> Result<Self> {
+        let (conn, screen_num) =
+            x11rb::connect(None).map_err(|e| eyre!("Could not connect to the X server ({e})"))?;
+
+        let screen = &conn.setup().roots[screen_num];
+        let root = screen.root;
+        let region = region.unwrap_or(Region {
+            x: 0,
+            y: 0,
+            width: screen.width_in_pixels,
+            height: screen.height_in_pixels,
+        });
+        if region.width == 0 || region.height == 0 {
+            return Err(eyre!("Can't capture an empty region"));
+        }
+
+        if conn.shm_query_version()?.reply().is_err() {
+            return Err(eyre!("The X server doesn't support MIT-SHM"));
+        }
+
+        // the server writes 4 bytes per pixel for 24 and 32 bit visuals
+        let size = region.width as usize * region.height as usize * 4;
+        let (shmid, addr) = unsafe {
+            let shmid = libc::shmget(libc::IPC_PRIVATE, size, libc::IPC_CREAT | 0o600);
+            if shmid < 0 {
+                return Err(eyre!("Could not allocate shared memory"));
+            }
+            let addr = libc::shmat(shmid, std::ptr::null(), 0);
+            if addr as isize == -1 {
+                libc::shmctl(shmid, libc::IPC_RMID, std::ptr::null_mut());
+                return Err(eyre!("Could not attach shared memory"));
+            }
+            (shmid, addr as *mut u8)
+        };
+
+        let seg = conn.generate_id()?;
+        let attached = conn
+            .shm_attach(seg, shmid as u32, false)
+            .map_err(eyre::Report::from)
+            .and_then(|cookie| cookie.check().map_err(eyre::Report::from));
+
+        // once both sides are attached the segment can be marked for removal,
+        // it goes away for real when the last one detaches
+        unsafe {
+            libc::shmctl(shmid, libc::IPC_RMID, std::ptr::null_mut());
+        }
+        if let Err(e) = attached {
+            unsafe {
+                libc::shmdt(addr as *const libc::c_void);
+            }
+            return Err(e);
+        }
+
+        Ok(Self {
+            conn,
+            root,
+            region,
+            seg,
+            addr,
+            last: None,
+            started: Instant::now(),
+            sequence: 0,
+        })
+    }
+
+    pub fn region(&self) -> Region {
+        self.region
+    }
+}
+
+impl CaptureSource for ScreenSource {
+    fn next_frame(&mut self) -> Result<Frame> {
+        if let Some(last) = self.last {
+            if let Some(wait) = FRAME_INTERVAL.checked_sub(last.elapsed()) {
+                thread::sleep(wait);
+            }
+        }
+        self.last = Some(Instant::now());
+
+        let Region {
+            x,
+            y,
+            width,
+            height,
+        } = self.region;
+        self.conn
+            .shm_get_image(
+                self.root,
+                x,
+                y,
+                width,
+                height,
+                !0,
+                ImageFormat::Z_PIXMAP.into(),
+                self.seg,
+                0,
+            )?
+            .reply()?;
+
+        let pixels = width as usize * height as usize;
+        let bgrx = unsafe { std::slice::from_raw_parts(self.addr, pixels * 4) };
+
+        let mut data = Vec::with_capacity(pixels * 3);
+        for px in bgrx.chunks_exact(4) {
+            data.extend_from_slice(&[px[2], px[1], px[0]]);
+        }
+
+        self.sequence += 1;
+
+        Ok(Frame {
+            meta: FrameMeta {
+                sequence: self.sequence,
+                timestamp: self.started.elapsed(),
+                bytes_used: data.len() as u32,
+                received: self.last,
+            },
+            data,
+            width: width.into(),
+            height: height.into(),
+            pixel_format: PixelFormat::Rgb8,
+        })
+    }
+
+    fn format(&self) -> SourceFormat {
+        SourceFormat {
+            width: self.region.width.into(),
+            height: self.region.height.into(),
+            pixel_format: PixelFormat::Rgb8,
+        }
+    }
+}
+
+impl Drop for ScreenSource {
+    fn drop(&mut self) {
+        let _ = self.conn.shm_detach(self.seg);
+        let _ = self.conn.flush();
+        unsafe {
+            libc::shmdt(self.addr as *const libc::c_void);
+        }
+    }
+}
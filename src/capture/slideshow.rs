@@ -0,0 +1,159 @@
+use super::{CaptureSource, Frame, FrameMeta, PixelFormat, Playback, SourceFormat};
+use crate::batch::is_image;
+use eyre::{eyre, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// like a still image, the current slide is redelivered at this rate
+const FRAME_INTERVAL: Duration = Duration::from_millis(33);
+
+/// Cycles through a list of images, showing each one for a fixed interval
+/// and starting over after the last.
+///
+/// The [`Playback`] controls pause on the current image, and seeking moves
+/// a slide back or forward, whatever the number of seconds.
+pub struct SlideshowSource {
+    paths: Vec<PathBuf>,
+    index: usize,
+    interval: Duration,
+    frame: Frame,
+    shown_at: Instant,
+    paused: bool,
+    sequence: u64,
+}
+
+impl SlideshowSource {
+    /// A slideshow of `paths`, in that order.
+    pub fn new(paths: Vec<PathBuf>, interval: Duration) -> Result<Self> {
+        if paths.is_empty() {
+            return Err(eyre!("The slideshow has no images"));
+        }
+
+        let mut slideshow = Self {
+            paths,
+            index: 0,
+            interval,
+            frame: Frame {
+                data: Vec::new(),
+                width: 0,
+                height: 0,
+                pixel_format: PixelFormat::Rgb8,
+                meta: FrameMeta::default(),
+            },
+            shown_at: Instant::now(),
+            paused: false,
+            sequence: 0,
+        };
+        slideshow.show(0, 1)?;
+
+        Ok(slideshow)
+    }
+
+    /// A slideshow of the images in `dir`, sorted by name.
+    pub fn from_dir(dir: impl AsRef<Path>, interval: Duration) -> Result<Self> {
+        let mut paths = Vec::new();
+        for entry in fs::read_dir(dir.as_ref())? {
+            let path = entry?.path();
+            if is_image(&path) {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+
+        if paths.is_empty() {
+            return Err(eyre!("'{}' has no images", dir.as_ref().display()));
+        }
+
+        Self::new(paths, interval)
+    }
+
+    /// Path of the image currently shown.
+    pub fn current(&self) -> &Path {
+        &self.paths[self.index]
+    }
+
+    /// Shows the image at `index`, or the first one after it in the `step`
+    /// direction that can be decoded.
+    fn show(&mut self, index: usize, step: isize) -> Result<()> {
+        let len = self.paths.len();
+
+        for attempt in 0..len {
+            let index =
+                (index as isize + step * attempt as isize).rem_euclid(len as isize) as usize;
+            // an unreadable image is skipped rather than ending the show
+            let img = match image::open(&self.paths[index]) {
+                Ok(img) => img.to_rgb8(),
+                Err(_) => continue,
+            };
+
+            self.index = index;
+            self.shown_at = Instant::now();
+            self.frame = Frame {
+                width: img.width(),
+                height: img.height(),
+                data: img.into_raw(),
+                pixel_format: PixelFormat::Rgb8,
+                meta: FrameMeta::default(),
+            };
+            return Ok(());
+        }
+
+        Err(eyre!("None of the slideshow images could be read"))
+    }
+}
+
+impl CaptureSource for SlideshowSource {
+    fn next_frame(&mut self) -> Result<Frame> {
+        thread::sleep(FRAME_INTERVAL);
+
+        if !self.paused && self.shown_at.elapsed() >= self.interval {
+            self.show(self.index + 1, 1)?;
+        }
+
+        self.sequence += 1;
+        let mut frame = self.frame.clone();
+        frame.meta = FrameMeta {
+            sequence: self.sequence,
+            bytes_used: frame.data.len() as u32,
+            received: Some(Instant::now()),
+            ..FrameMeta::default()
+        };
+
+        Ok(frame)
+    }
+
+    fn format(&self) -> SourceFormat {
+        SourceFormat {
+            width: self.frame.width,
+            height: self.frame.height,
+            pixel_format: PixelFormat::Rgb8,
+        }
+    }
+
+    fn playback(&mut self) -> Option<&mut dyn Playback> {
+        Some(self)
+    }
+}
+
+impl Playback for SlideshowSource {
+    fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+        // a resumed slide gets its full interval again
+        self.shown_at = Instant::now();
+    }
+
+    fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    fn seek(&mut self, seconds: f64) -> Result<()> {
+        let len = self.paths.len();
+        if seconds < 0.0 {
+            self.show((self.index + len - 1) % len, -1)
+        } else {
+            self.show((self.index + 1) % len, 1)
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use super::{CaptureSource, Frame, FrameMeta, PixelFormat, SourceFormat};
+use eyre::Result;
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// a still image never changes, redelivering it at this rate keeps a picture
+// in picture inset over it moving without spinning
+const FRAME_INTERVAL: Duration = Duration::from_millis(33);
+
+/// A still image file (jpeg, png, webp, ...) delivered as an endless stream
+/// of identical frames.
+pub struct ImageSource {
+    frame: Frame,
+    last: Option<Instant>,
+}
+
+impl ImageSource {
+    /// Decodes the image at `path`, the format is guessed from its extension.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let img = image::open(path)?.to_rgb8();
+
+        Ok(Self::from_rgb(img))
+    }
+
+    /// Wraps an already decoded image.
+    pub fn from_rgb(img: image::RgbImage) -> Self {
+        let (width, height) = img.dimensions();
+        let data = img.into_raw();
+
+        Self {
+            frame: Frame {
+                meta: FrameMeta {
+                    bytes_used: data.len() as u32,
+                    ..FrameMeta::default()
+                },
+                data,
+                width,
+                height,
+                pixel_format: PixelFormat::Rgb8,
+            },
+            last: None,
+        }
+    }
+}
+
+impl CaptureSource for ImageSource {
+    fn next_frame(&mut self) -> Result<Frame> {
+        if let Some(last) = self.last {
+            if let Some(wait) = FRAME_INTERVAL.checked_sub(last.elapsed()) {
+                thread::sleep(wait);
+            }
+        }
+
+        let now = Instant::now();
+        self.last = Some(now);
+
+        let mut frame = self.frame.clone();
+        frame.meta.received = Some(now);
+        self.frame.meta.sequence += 1;
+
+        Ok(frame)
+    }
+
+    fn format(&self) -> SourceFormat {
+        SourceFormat {
+            width: self.frame.width,
+            height: self.frame.height,
+            pixel_format: PixelFormat::Rgb8,
+        }
+    }
+}
@@ -0,0 +1,123 @@
+use super::{CaptureSource, Frame, FrameMeta, PixelFormat, SourceFormat};
+use eyre::Result;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// the nominal spacing of synthetic frames, used for timestamps and pacing
+const FRAME_INTERVAL: Duration = Duration::from_millis(33);
+
+// how far the noise moves a pixel either way
+const NOISE: u64 = 8;
+
+/// A generated test pattern: a diagonal gradient with an inverted disc
+/// moving across it and some noise on top.
+///
+/// Frames only depend on the seed and their index, so the same source always
+/// produces the same pixels. Useful for tests, benchmarks and demos without
+/// a camera.
+pub struct SyntheticSource {
+    width: u32,
+    height: u32,
+    seed: u64,
+    index: u64,
+    paced: bool,
+    last: Option<Instant>,
+}
+
+impl SyntheticSource {
+    /// A source of `width` x `height` gray frames, delivered as fast as
+    /// they are asked for.
+    pub fn new(width: u32, height: u32, seed: u64) -> Self {
+        Self {
+            width: width.max(1),
+            height: height.max(1),
+            seed,
+            index: 0,
+            paced: false,
+            last: None,
+        }
+    }
+
+    /// Whether to deliver frames at roughly 30 per second, like a camera.
+    pub fn paced(mut self, paced: bool) -> Self {
+        self.paced = paced;
+        self
+    }
+
+    /// Generates frame number `index`, without advancing the source.
+    pub fn frame_at(&self, index: u64) -> Frame {
+        let (width, height) = (self.width as u64, self.height as u64);
+
+        let cx = (index * 2 % width) as i64;
+        let cy = (height / 2) as i64;
+        let radius = (width.min(height) / 4).max(1) as i64;
+
+        let mut state = self.seed ^ index.wrapping_mul(0x2545_f491_4f6c_dd1d);
+        let mut data = Vec::with_capacity((width * height) as usize);
+
+        for y in 0..height {
+            for x in 0..width {
+                let gradient = (x * 255 / (width - 1).max(1) + y * 255 / (height - 1).max(1)) / 2;
+
+                let (dx, dy) = (x as i64 - cx, y as i64 - cy);
+                let value = if dx * dx + dy * dy <= radius * radius {
+                    255 - gradient
+                } else {
+                    gradient
+                };
+
+                let noise = (splitmix64(&mut state) % (2 * NOISE + 1)) as i64 - NOISE as i64;
+                data.push((value as i64 + noise).clamp(0, 255) as u8);
+            }
+        }
+
+        Frame {
+            meta: FrameMeta {
+                sequence: index,
+                timestamp: FRAME_INTERVAL * index as u32,
+                bytes_used: data.len() as u32,
+                received: None,
+            },
+            data,
+            width: self.width,
+            height: self.height,
+            pixel_format: PixelFormat::Gray8,
+        }
+    }
+}
+
+impl CaptureSource for SyntheticSource {
+    fn next_frame(&mut self) -> Result<Frame> {
+        if self.paced {
+            if let Some(last) = self.last {
+                if let Some(wait) = FRAME_INTERVAL.checked_sub(last.elapsed()) {
+                    thread::sleep(wait);
+                }
+            }
+        }
+        let now = Instant::now();
+        self.last = Some(now);
+
+        let mut frame = self.frame_at(self.index);
+        frame.meta.received = Some(now);
+        self.index += 1;
+
+        Ok(frame)
+    }
+
+    fn format(&self) -> SourceFormat {
+        SourceFormat {
+            width: self.width,
+            height: self.height,
+            pixel_format: PixelFormat::Gray8,
+        }
+    }
+}
+
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
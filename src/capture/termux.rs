@@ -0,0 +1,204 @@
+use super::{CaptureSource, Frame, FrameMeta, Frames, PixelFormat, SourceFormat};
+use crate::convert::{decode_frame, decode_frame_rgb};
+use eyre::{eyre, Result};
+use image::{GrayImage, RgbImage};
+use std::env;
+use std::fs;
+use std::io::Cursor;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::time::Instant;
+
+/// A camera found by [`list_devices`].
+#[derive(Debug, Clone)]
+pub struct DeviceInfo {
+    pub index: usize,
+    /// The camera id Android knows it by.
+    pub path: PathBuf,
+    /// Which way it faces, `back` or `front`.
+    pub name: Option<String>,
+}
+
+/// Lists the cameras of the phone through `termux-camera-info`, empty when
+/// the Termux:API app isn't installed.
+pub fn list_devices() -> Vec<DeviceInfo> {
+    let output = match Command::new("termux-camera-info")
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+    {
+        Ok(output) if output.status.success() => output,
+        _ => return Vec::new(),
+    };
+    let info = String::from_utf8_lossy(&output.stdout);
+
+    // an array of an object per camera with "id" before "facing", not worth
+    // a json parser
+    let ids = strings_after(&info, "\"id\"");
+    let facing = strings_after(&info, "\"facing\"");
+    ids.into_iter()
+        .enumerate()
+        .filter_map(|(i, id)| {
+            Some(DeviceInfo {
+                index: id.parse().ok()?,
+                path: PathBuf::from(id),
+                name: facing.get(i).map(|facing| facing.to_string()),
+            })
+        })
+        .collect()
+}
+
+/// Whether the phone has a camera with `index`.
+pub(crate) fn device_present(index: usize) -> bool {
+    list_devices().iter().any(|device| device.index == index)
+}
+
+/// The string values of every `key` in `json`.
+fn strings_after<'a>(json: &'a str, key: &str) -> Vec<&'a str> {
+    json.split(key)
+        .skip(1)
+        .filter_map(|rest| {
+            let value = rest.trim_start().strip_prefix(':')?.trim_start();
+            value.strip_prefix('"')?.split('"').next()
+        })
+        .collect()
+}
+
+/// A phone camera on Android, captured by running `termux-camera-photo`
+/// from the Termux:API app for every frame. Needs `pkg install termux-api`
+/// and the Termux:API app with the camera permission.
+///
+/// Each photo takes the phone a moment, so expect a frame or two a second.
+/// Frames are the JPEGs the camera takes, as [`PixelFormat::Mjpeg`].
+pub struct Camera {
+    index: usize,
+    // where termux-camera-photo leaves each photo
+    path: PathBuf,
+    buffer: Vec<u8>,
+    width: u32,
+    height: u32,
+    started: Instant,
+    meta: FrameMeta,
+}
+
+impl Camera {
+    /// Opens the camera with `index`, `0` is usually the back one and `1`
+    /// the front one, and takes a first photo.
+    pub fn open(index: usize) -> Result<Self> {
+        Self::open_with(index, None)
+    }
+
+    /// Like [`open`](Camera::open). `termux-camera-photo` always takes
+    /// photos at the largest size of the camera, so `resolution` is ignored.
+    pub fn open_with(index: usize, _resolution: Option<(u32, u32)>) -> Result<Self> {
+        let mut camera = Self {
+            index,
+            path: env::temp_dir().join(format!("asciicam-{}-{index}.jpg", std::process::id())),
+            buffer: Vec::new(),
+            width: 0,
+            height: 0,
+            started: Instant::now(),
+            meta: FrameMeta::default(),
+        };
+        camera.next_buffer()?;
+        let (width, height) = image::io::Reader::new(Cursor::new(&camera.buffer))
+            .with_guessed_format()?
+            .into_dimensions()?;
+        camera.width = width;
+        camera.height = height;
+
+        Ok(camera)
+    }
+
+    /// Width of the captured frames in pixels.
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Height of the captured frames in pixels.
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Iterates over decoded grayscale frames.
+    pub fn frames(&mut self) -> Frames<'_, Self, GrayImage> {
+        Frames::new(self, decode_frame)
+    }
+
+    /// Iterates over decoded color frames.
+    pub fn rgb_frames(&mut self) -> Frames<'_, Self, RgbImage> {
+        Frames::new(self, decode_frame_rgb)
+    }
+
+    /// Takes a photo and returns its JPEG.
+    pub fn next_buffer(&mut self) -> Result<&[u8]> {
+        let _ = fs::remove_file(&self.path);
+        let status = Command::new("termux-camera-photo")
+            .arg("-c")
+            .arg(self.index.to_string())
+            .arg(&self.path)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .map_err(|e| {
+                eyre!("Could not run termux-camera-photo, is termux-api installed? ({e})")
+            })?;
+
+        // termux-camera-photo exits fine without a photo when the app has no
+        // camera permission or the camera doesn't exist
+        self.buffer = match fs::read(&self.path) {
+            Ok(buffer) if status.success() && !buffer.is_empty() => buffer,
+            _ => {
+                return Err(eyre!(
+                    "Could not take a photo with camera '{}'. Does the Termux:API app have the camera permission?",
+                    self.index
+                ))
+            }
+        };
+
+        self.meta = FrameMeta {
+            sequence: self.meta.sequence + 1,
+            timestamp: self.started.elapsed(),
+            bytes_used: self.buffer.len() as u32,
+            received: Some(Instant::now()),
+        };
+
+        Ok(&self.buffer)
+    }
+
+    /// Metadata of the buffer last returned by
+    /// [`next_buffer`](Camera::next_buffer).
+    pub fn meta(&self) -> FrameMeta {
+        self.meta
+    }
+}
+
+impl CaptureSource for Camera {
+    fn next_frame(&mut self) -> Result<Frame> {
+        let (width, height) = (self.width, self.height);
+        let data = self.next_buffer()?.to_vec();
+
+        Ok(Frame {
+            data,
+            width,
+            height,
+            pixel_format: PixelFormat::Mjpeg,
+            meta: self.meta,
+        })
+    }
+
+    fn format(&self) -> SourceFormat {
+        SourceFormat {
+            width: self.width,
+            height: self.height,
+            pixel_format: PixelFormat::Mjpeg,
+        }
+    }
+}
+
+impl Drop for Camera {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
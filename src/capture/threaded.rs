@@ -0,0 +1,260 @@
+use super::{CaptureSource, Frame, FramePool, PixelFormat};
+use crate::convert::{decode_frame, decode_mjpeg_rgb_into};
+use eyre::{eyre, Result};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+use tracing::{debug, error, trace_span};
+
+type Job = Box<dyn FnOnce(&mut Box<dyn CaptureSource + Send>) + Send>;
+
+/// A frame captured by a [`CaptureThread`], along with the status of the
+/// source at the time.
+pub struct Captured {
+    pub frame: Frame,
+    pub status: Option<String>,
+    /// How long the source took to deliver the frame.
+    pub capture: Duration,
+    /// How long decoding it took.
+    pub decode: Duration,
+    /// How often the source reconnected so far.
+    pub reconnects: u64,
+}
+
+#[derive(Default)]
+struct Latest {
+    captured: Option<Captured>,
+    // the error that stopped the capture thread
+    error: Option<String>,
+    // frames replaced by a newer one before anyone took them, or that
+    // didn't decode
+    skipped: u64,
+}
+
+struct Shared {
+    latest: Mutex<Latest>,
+    // one for waiting in async code and one for waiting without
+    notify: Notify,
+    ready: Condvar,
+}
+
+/// Runs the main source on a thread of its own that captures and decodes
+/// frames while the one before is rendered, so waiting for the camera is
+/// something to [`select!`](tokio::select) on next to keyboard and
+/// network events.
+///
+/// Only the latest frame is kept, one the viewer didn't get to before the
+/// next one is dropped. JPEG frames are decoded on the thread, to
+/// [`PixelFormat::Rgb8`] with color and [`PixelFormat::Gray8`] without,
+/// other frames are passed through as they are.
+///
+/// Color frames are decoded into buffers from a [`FramePool`], which the
+/// source gets too, see [`CaptureSource::set_pool`]. Frames handed back
+/// with [`recycle`](CaptureThread::recycle) are decoded into again.
+pub struct CaptureThread {
+    shared: Arc<Shared>,
+    jobs: Sender<Job>,
+    pool: FramePool,
+}
+
+impl CaptureThread {
+    pub fn spawn(mut source: Box<dyn CaptureSource + Send>, color: bool) -> Self {
+        let shared = Arc::new(Shared {
+            latest: Mutex::new(Latest::default()),
+            notify: Notify::new(),
+            ready: Condvar::new(),
+        });
+        let (jobs, jobs_rx) = mpsc::channel();
+        let pool = FramePool::new();
+        source.set_pool(pool.clone());
+
+        let thread_shared = Arc::clone(&shared);
+        let thread_pool = pool.clone();
+        thread::spawn(move || capture(source, color, jobs_rx, thread_shared, thread_pool));
+
+        Self { shared, jobs, pool }
+    }
+
+    /// Number of frames captured so far that were replaced by a newer one
+    /// before they were taken, or were dropped because they didn't decode.
+    pub fn skipped(&self) -> u64 {
+        self.shared.latest.lock().unwrap().skipped
+    }
+
+    /// Hands back a frame taken from this thread that is done with, for its
+    /// buffer to be decoded into again.
+    pub fn recycle(&self, frame: Frame) {
+        self.pool.give(frame.data);
+    }
+
+    /// Waits for a frame newer than the last one taken.
+    pub async fn next(&self) -> Result<Captured> {
+        loop {
+            if let Some(result) = self.take() {
+                return result;
+            }
+            self.shared.notify.notified().await;
+        }
+    }
+
+    /// Like [`next`](CaptureThread::next), blocking the thread instead.
+    pub fn next_blocking(&self) -> Result<Captured> {
+        let mut latest = self.shared.latest.lock().unwrap();
+        loop {
+            if let Some(error) = &latest.error {
+                return Err(eyre!("{error}"));
+            }
+            if let Some(captured) = latest.captured.take() {
+                return Ok(captured);
+            }
+            latest = self.shared.ready.wait(latest).unwrap();
+        }
+    }
+
+    fn take(&self) -> Option<Result<Captured>> {
+        let mut latest = self.shared.latest.lock().unwrap();
+        if let Some(error) = &latest.error {
+            return Some(Err(eyre!("{error}")));
+        }
+        latest.captured.take().map(Ok)
+    }
+
+    /// Runs `f` with the source on the capture thread and returns what it
+    /// returns. Waits for the frame being captured first, a slow camera
+    /// makes this slow too.
+    pub fn with<T, F>(&self, f: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut dyn CaptureSource) -> T + Send + 'static,
+    {
+        let (result_tx, result) = mpsc::sync_channel(1);
+        self.send(Box::new(move |source| {
+            let _ = result_tx.send(f(&mut **source));
+        }))?;
+
+        result
+            .recv()
+            .map_err(|_| eyre!("The capture thread stopped"))
+    }
+
+    /// Captures from `source` from now on. The current one is dropped by
+    /// the time this returns, for devices that can't be opened twice.
+    pub fn replace(&self, source: Box<dyn CaptureSource + Send>) -> Result<()> {
+        let (done_tx, done) = mpsc::sync_channel(1);
+        self.send(Box::new(move |current| {
+            *current = source;
+            let _ = done_tx.send(());
+        }))?;
+
+        done.recv().map_err(|_| eyre!("The capture thread stopped"))
+    }
+
+    fn send(&self, job: Job) -> Result<()> {
+        self.jobs
+            .send(job)
+            .map_err(|_| eyre!("The capture thread stopped"))
+    }
+}
+
+fn capture(
+    mut source: Box<dyn CaptureSource + Send>,
+    color: bool,
+    jobs: Receiver<Job>,
+    shared: Arc<Shared>,
+    pool: FramePool,
+) {
+    loop {
+        loop {
+            match jobs.try_recv() {
+                Ok(job) => job(&mut source),
+                Err(TryRecvError::Empty) => break,
+                // nobody is waiting on a dropped thread
+                Err(TryRecvError::Disconnected) => return,
+            }
+        }
+
+        let started = Instant::now();
+        let frame = trace_span!("capture").in_scope(|| source.next_frame());
+        let captured = Instant::now();
+        let result =
+            frame.map(|frame| trace_span!("decode").in_scope(|| decode(frame, color, &pool)));
+        let (capture_time, decode_time) = (captured - started, captured.elapsed());
+        let status = source.status();
+        let reconnects = source.reconnects();
+
+        let mut latest = shared.latest.lock().unwrap();
+        let stopped = match result {
+            Ok(Ok(frame)) => {
+                // a frame the viewer didn't get to
+                if let Some(skipped) = latest.captured.take() {
+                    latest.skipped += 1;
+                    pool.give(skipped.frame.data);
+                }
+                latest.captured = Some(Captured {
+                    frame,
+                    status,
+                    capture: capture_time,
+                    decode: decode_time,
+                    reconnects,
+                });
+                false
+            }
+            // flaky cameras send a truncated or corrupt jpeg now and then,
+            // the next one is likely fine
+            Ok(Err(e)) => {
+                debug!("dropping a frame that doesn't decode: {e}");
+                latest.skipped += 1;
+                false
+            }
+            // the source gave up, reconnecting already failed if it does
+            Err(e) => {
+                error!("capturing stopped: {e}");
+                latest.error = Some(e.to_string());
+                true
+            }
+        };
+        drop(latest);
+        shared.notify.notify_one();
+        shared.ready.notify_all();
+
+        if stopped {
+            return;
+        }
+    }
+}
+
+fn decode(frame: Frame, color: bool, pool: &FramePool) -> Result<Frame> {
+    if frame.pixel_format != PixelFormat::Mjpeg {
+        return Ok(frame);
+    }
+
+    // the size of the jpeg itself, which a camera can get wrong
+    let (width, height, data, pixel_format) = if color {
+        let mut data = pool.take();
+        match decode_mjpeg_rgb_into(&frame.data, &mut data) {
+            Ok((width, height)) => (width, height, data, PixelFormat::Rgb8),
+            Err(e) => {
+                pool.give(data);
+                return Err(e);
+            }
+        }
+    } else {
+        let img = decode_frame(&frame)?;
+        (
+            img.width(),
+            img.height(),
+            img.into_raw(),
+            PixelFormat::Gray8,
+        )
+    };
+
+    Ok(Frame {
+        data,
+        width,
+        height,
+        pixel_format,
+        meta: frame.meta,
+    })
+}
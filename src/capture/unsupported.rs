@@ -0,0 +1,78 @@
+use super::{CaptureSource, Frame, FrameMeta, Frames, SourceFormat};
+use crate::convert::{decode_frame, decode_frame_rgb};
+use eyre::{eyre, Result};
+use image::{GrayImage, RgbImage};
+use std::convert::Infallible;
+use std::path::PathBuf;
+
+/// A camera found by [`list_devices`], of which there are none here.
+#[derive(Debug, Clone)]
+pub struct DeviceInfo {
+    pub index: usize,
+    pub path: PathBuf,
+    pub name: Option<String>,
+}
+
+/// Lists the cameras on the system, always empty where asciicam has no way
+/// to capture them.
+pub fn list_devices() -> Vec<DeviceInfo> {
+    Vec::new()
+}
+
+/// Whether the camera with `index` is plugged in, never.
+pub(crate) fn device_present(_index: usize) -> bool {
+    false
+}
+
+/// Stands in for a camera on systems asciicam can't capture cameras on,
+/// like the BSDs, so that files, streams and the other sources still work
+/// there. It can't be opened.
+pub struct Camera {
+    unsupported: Infallible,
+}
+
+impl Camera {
+    pub fn open(index: usize) -> Result<Self> {
+        Self::open_with(index, None)
+    }
+
+    pub fn open_with(_index: usize, _resolution: Option<(u32, u32)>) -> Result<Self> {
+        Err(eyre!(
+            "Capturing cameras isn't supported on this system, render a file or stream with --input instead"
+        ))
+    }
+
+    pub fn width(&self) -> u32 {
+        match self.unsupported {}
+    }
+
+    pub fn height(&self) -> u32 {
+        match self.unsupported {}
+    }
+
+    pub fn frames(&mut self) -> Frames<'_, Self, GrayImage> {
+        Frames::new(self, decode_frame)
+    }
+
+    pub fn rgb_frames(&mut self) -> Frames<'_, Self, RgbImage> {
+        Frames::new(self, decode_frame_rgb)
+    }
+
+    pub fn next_buffer(&mut self) -> Result<&[u8]> {
+        match self.unsupported {}
+    }
+
+    pub fn meta(&self) -> FrameMeta {
+        match self.unsupported {}
+    }
+}
+
+impl CaptureSource for Camera {
+    fn next_frame(&mut self) -> Result<Frame> {
+        match self.unsupported {}
+    }
+
+    fn format(&self) -> SourceFormat {
+        match self.unsupported {}
+    }
+}
@@ -0,0 +1,220 @@
+use super::{CaptureSource, Frame, FrameMeta, PixelFormat, Playback, SourceFormat};
+use eyre::{eyre, Result};
+use std::io::{ErrorKind, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdout, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// how often a paused or finished video redelivers its last frame
+const IDLE_INTERVAL: Duration = Duration::from_millis(33);
+
+/// A video file decoded by an `ffmpeg` child process and played back at its
+/// native frame rate. Needs `ffmpeg` and `ffprobe` in `$PATH`.
+///
+/// When the video ends the last frame is held until the source is dropped.
+pub struct VideoSource {
+    path: PathBuf,
+    width: u32,
+    height: u32,
+    fps: f64,
+    child: Child,
+    stdout: ChildStdout,
+    // position of the first frame read from the current child
+    offset: f64,
+    frames_read: u64,
+    started: Instant,
+    last: Frame,
+    paused: bool,
+    ended: bool,
+    sequence: u64,
+}
+
+impl VideoSource {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let (width, height, fps) = probe(&path)?;
+        if !(fps.is_finite() && fps > 0.0) {
+            return Err(eyre!("'{}' has an invalid frame rate", path.display()));
+        }
+        let (child, stdout) = spawn(&path, 0.0)?;
+
+        Ok(Self {
+            path,
+            width,
+            height,
+            fps,
+            child,
+            stdout,
+            offset: 0.0,
+            frames_read: 0,
+            started: Instant::now(),
+            last: Frame {
+                data: vec![0; width as usize * height as usize * 3],
+                width,
+                height,
+                pixel_format: PixelFormat::Rgb8,
+                meta: FrameMeta::default(),
+            },
+            paused: false,
+            ended: false,
+            sequence: 0,
+        })
+    }
+
+    /// Current playback position in seconds.
+    pub fn position(&self) -> f64 {
+        self.offset + self.frames_read as f64 / self.fps
+    }
+
+    fn restart(&mut self, position: f64) -> Result<()> {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+
+        let (child, stdout) = spawn(&self.path, position)?;
+        self.child = child;
+        self.stdout = stdout;
+        self.offset = position;
+        self.frames_read = 0;
+        self.started = Instant::now();
+        self.ended = false;
+
+        Ok(())
+    }
+}
+
+impl CaptureSource for VideoSource {
+    fn next_frame(&mut self) -> Result<Frame> {
+        if self.paused || self.ended {
+            thread::sleep(IDLE_INTERVAL);
+            return Ok(self.last.clone());
+        }
+
+        // wait until the frame is due so playback runs at the native rate
+        let due = self.started + Duration::from_secs_f64(self.frames_read as f64 / self.fps);
+        if let Some(wait) = due.checked_duration_since(Instant::now()) {
+            thread::sleep(wait);
+        }
+
+        match self.stdout.read_exact(&mut self.last.data) {
+            Ok(()) => (),
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => {
+                self.ended = true;
+                return Ok(self.last.clone());
+            }
+            Err(e) => return Err(e.into()),
+        }
+
+        self.frames_read += 1;
+        self.sequence += 1;
+        self.last.meta = FrameMeta {
+            sequence: self.sequence,
+            timestamp: Duration::from_secs_f64(self.position()),
+            bytes_used: self.last.data.len() as u32,
+            received: Some(Instant::now()),
+        };
+
+        Ok(self.last.clone())
+    }
+
+    fn format(&self) -> SourceFormat {
+        SourceFormat {
+            width: self.width,
+            height: self.height,
+            pixel_format: PixelFormat::Rgb8,
+        }
+    }
+
+    fn playback(&mut self) -> Option<&mut dyn Playback> {
+        Some(self)
+    }
+}
+
+impl Playback for VideoSource {
+    fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+
+        if !self.paused {
+            // pick the pacing back up from the current position rather than
+            // racing to catch up with the time spent paused
+            let played = Duration::from_secs_f64(self.frames_read as f64 / self.fps);
+            self.started = Instant::now()
+                .checked_sub(played)
+                .unwrap_or_else(Instant::now);
+        }
+    }
+
+    fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    fn seek(&mut self, seconds: f64) -> Result<()> {
+        let position = (self.position() + seconds).max(0.0);
+        self.restart(position)
+    }
+}
+
+impl Drop for VideoSource {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Returns the width, height and frame rate of the first video stream. The
+/// frame rate is NaN when the container doesn't know it, e.g. for live streams.
+pub(crate) fn probe(path: &Path) -> Result<(u32, u32, f64)> {
+    let output = Command::new("ffprobe")
+        .args([
+            "-v",
+            "error",
+            "-select_streams",
+            "v:0",
+            "-show_entries",
+            "stream=width,height,r_frame_rate",
+            "-of",
+            "csv=p=0",
+        ])
+        .arg(path)
+        .stderr(Stdio::null())
+        .output()
+        .map_err(|e| eyre!("Could not run ffprobe, is ffmpeg installed? ({e})"))?;
+
+    if !output.status.success() {
+        return Err(eyre!("ffprobe could not read '{}'", path.display()));
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let fields: Vec<&str> = stdout.trim().split(',').collect();
+
+    match fields[..] {
+        [width, height, rate] => {
+            let fps = match rate.split_once('/') {
+                Some((num, den)) => num.parse::<f64>()? / den.parse::<f64>()?,
+                None => rate.parse()?,
+            };
+            Ok((width.parse()?, height.parse()?, fps))
+        }
+        _ => Err(eyre!("'{}' has no video stream", path.display())),
+    }
+}
+
+/// Starts decoding `path` from `position` seconds into raw rgb24 frames.
+pub(crate) fn spawn(path: &Path, position: f64) -> Result<(Child, ChildStdout)> {
+    let mut child = Command::new("ffmpeg")
+        .args(["-v", "error", "-nostdin", "-ss"])
+        .arg(format!("{position:.3}"))
+        .arg("-i")
+        .arg(path)
+        .args(["-an", "-f", "rawvideo", "-pix_fmt", "rgb24", "-"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| eyre!("Could not run ffmpeg, is it installed? ({e})"))?;
+
+    match child.stdout.take() {
+        Some(stdout) => Ok((child, stdout)),
+        None => Err(eyre!("Could not read from ffmpeg")),
+    }
+}
@@ -0,0 +1,772 @@
+use crate::capture::{Frame, PixelFormat};
+use eyre::{eyre, Report, Result};
+use image::{GrayImage, ImageBuffer, Pixel, Rgb, RgbImage};
+use std::mem;
+use std::num::NonZeroU32;
+use std::str::FromStr;
+#[cfg(all(not(target_arch = "wasm32"), not(feature = "mozjpeg")))]
+use zune_jpeg::zune_core::{colorspace::ColorSpace, options::DecoderOptions};
+#[cfg(all(not(target_arch = "wasm32"), not(feature = "mozjpeg")))]
+use zune_jpeg::JpegDecoder;
+
+/// A raw MJPEG buffer together with its size and the size to scale it to.
+pub struct CameraBuffer<'b> {
+    pub stream_buf: &'b [u8],
+    pub src_width: u32,
+    pub src_height: u32,
+    pub dst_width: u32,
+    pub dst_height: u32,
+}
+
+impl<'b> CameraBuffer<'b> {
+    /// Decodes the MJPEG buffer to grayscale and resizes it to
+    /// `dst_width` x `dst_height`.
+    pub fn get_cam(buff: Self) -> Result<GrayImage> {
+        let raw_pixels = decode_mjpeg_raw(buff.stream_buf)?.2;
+
+        let src = match GrayImage::from_raw(
+            non_zero(buff.src_width)?.get(),
+            non_zero(buff.src_height)?.get(),
+            raw_pixels,
+        ) {
+            None => return Err(eyre!("Could not convert raw buffer to image buffer")),
+            Some(v) => v,
+        };
+
+        resize(&src, buff.dst_width, buff.dst_height)
+    }
+}
+
+/// Decodes a frame of any [`PixelFormat`] into a full size grayscale image.
+pub fn decode_frame(frame: &Frame) -> Result<GrayImage> {
+    let mut img = GrayImage::default();
+    decode_frame_into(frame, &mut img)?;
+
+    Ok(img)
+}
+
+/// Decodes a frame of any [`PixelFormat`] into a full size color image.
+pub fn decode_frame_rgb(frame: &Frame) -> Result<RgbImage> {
+    let mut img = RgbImage::default();
+    decode_frame_rgb_into(frame, &mut img)?;
+
+    Ok(img)
+}
+
+/// Like [`decode_frame`], but decodes into `img`, reusing its memory. Once
+/// it has held a frame of the size, only the JPEG decoder allocates.
+pub fn decode_frame_into(frame: &Frame, img: &mut GrayImage) -> Result<()> {
+    let mut data = mem::take(img).into_raw();
+    let (width, height) = match frame.pixel_format {
+        PixelFormat::Mjpeg => decode_mjpeg_gray_into(&frame.data, &mut data)?,
+        PixelFormat::Gray8 => {
+            data.clear();
+            data.extend_from_slice(raw_pixels(frame, 1)?);
+            (frame.width, frame.height)
+        }
+        PixelFormat::Rgb8 => {
+            data.clear();
+            // what imageops::grayscale does
+            data.extend(
+                raw_pixels(frame, 3)?
+                    .chunks_exact(3)
+                    .map(|p| Rgb([p[0], p[1], p[2]]).to_luma().0[0]),
+            );
+            (frame.width, frame.height)
+        }
+    };
+
+    *img = into_image(width, height, data)?;
+    Ok(())
+}
+
+/// Like [`decode_frame_into`], in color.
+pub fn decode_frame_rgb_into(frame: &Frame, img: &mut RgbImage) -> Result<()> {
+    let mut data = mem::take(img).into_raw();
+    let (width, height) = match frame.pixel_format {
+        PixelFormat::Mjpeg => decode_mjpeg_rgb_into(&frame.data, &mut data)?,
+        PixelFormat::Gray8 => {
+            data.clear();
+            data.extend(raw_pixels(frame, 1)?.iter().flat_map(|&v| [v, v, v]));
+            (frame.width, frame.height)
+        }
+        PixelFormat::Rgb8 => {
+            data.clear();
+            data.extend_from_slice(raw_pixels(frame, 3)?);
+            (frame.width, frame.height)
+        }
+    };
+
+    *img = into_image(width, height, data)?;
+    Ok(())
+}
+
+/// The pixels of a raw frame with `channels` bytes a pixel, without
+/// anything past them.
+fn raw_pixels(frame: &Frame, channels: usize) -> Result<&[u8]> {
+    let len = frame.width as usize * frame.height as usize * channels;
+    match frame.data.get(..len) {
+        None => Err(eyre!(
+            "{:?} frame is smaller than its dimensions",
+            frame.pixel_format
+        )),
+        Some(pixels) => Ok(pixels),
+    }
+}
+
+fn into_image<P: Pixel<Subpixel = u8>>(
+    width: u32,
+    height: u32,
+    data: Vec<u8>,
+) -> Result<ImageBuffer<P, Vec<u8>>> {
+    match ImageBuffer::from_raw(width, height, data) {
+        None => Err(eyre!("Could not convert raw buffer to image buffer")),
+        Some(v) => Ok(v),
+    }
+}
+
+/// Decodes a JPEG image into a full size grayscale image.
+pub fn decode_mjpeg(buf: &[u8]) -> Result<GrayImage> {
+    let (width, height, raw_pixels) = decode_mjpeg_raw(buf)?;
+
+    match GrayImage::from_raw(width, height, raw_pixels) {
+        None => Err(eyre!("Could not convert raw buffer to image buffer")),
+        Some(v) => Ok(v),
+    }
+}
+
+/// Decodes a JPEG image into a full size color image.
+pub fn decode_mjpeg_rgb(buf: &[u8]) -> Result<RgbImage> {
+    let mut data = Vec::new();
+    let (width, height) = decode_mjpeg_rgb_into(buf, &mut data)?;
+
+    match RgbImage::from_raw(width, height, data) {
+        None => Err(eyre!("Could not convert raw buffer to image buffer")),
+        Some(v) => Ok(v),
+    }
+}
+
+/// Like [`decode_mjpeg_rgb`], but decodes into `data`, replacing what was in
+/// it, and returns the size. A buffer kept from the frame before has the
+/// room for this one already.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn decode_mjpeg_rgb_into(buf: &[u8], data: &mut Vec<u8>) -> Result<(u32, u32)> {
+    #[cfg(feature = "vaapi")]
+    if let Some(size) = crate::vaapi::decode_into(buf, crate::vaapi::Output::Rgb, data) {
+        return Ok(size);
+    }
+
+    decode_rgb_into(buf, data)
+}
+
+/// Resizes a grayscale image to `dst_width` x `dst_height`.
+pub fn resize(img: &GrayImage, dst_width: u32, dst_height: u32) -> Result<GrayImage> {
+    let mut dst = GrayImage::new(non_zero(dst_width)?.get(), non_zero(dst_height)?.get());
+    Resizer::default().resize(img, &mut dst)?;
+
+    Ok(dst)
+}
+
+/// Resizes a color image to `dst_width` x `dst_height`.
+pub fn resize_rgb(img: &RgbImage, dst_width: u32, dst_height: u32) -> Result<RgbImage> {
+    let mut dst = RgbImage::new(non_zero(dst_width)?.get(), non_zero(dst_height)?.get());
+    Resizer::default().resize(img, &mut dst)?;
+
+    Ok(dst)
+}
+
+/// Resizes images picking the nearest pixel, keeping which source pixel
+/// every destination column and row samples from one frame to the next.
+///
+/// The tables are worked out again only when the sizes change, after that
+/// resizing allocates nothing and takes no arithmetic per pixel.
+#[derive(Debug, Default)]
+pub struct Resizer {
+    // source and destination size and the channels the tables are for
+    key: (u32, u32, u32, u32, usize),
+    // byte offset into a source row for every destination column
+    columns: Vec<usize>,
+    // source row for every destination row
+    rows: Vec<usize>,
+    // the same for averaging every source pixel under a destination one
+    // instead of picking one
+    span_key: (u32, u32, u32, u32),
+    column_spans: Vec<(usize, usize)>,
+    row_spans: Vec<(usize, usize)>,
+    gamma: Option<Box<Gamma>>,
+}
+
+impl Resizer {
+    /// Resizes `img` to the size `dst` already has, filling it in place.
+    pub fn resize<P>(
+        &mut self,
+        img: &ImageBuffer<P, Vec<u8>>,
+        dst: &mut ImageBuffer<P, Vec<u8>>,
+    ) -> Result<()>
+    where
+        P: Pixel<Subpixel = u8>,
+    {
+        let (src_width, src_height) = (non_zero(img.width())?.get(), non_zero(img.height())?.get());
+        let (dst_width, dst_height) = (non_zero(dst.width())?.get(), non_zero(dst.height())?.get());
+        let channels = P::CHANNEL_COUNT as usize;
+
+        let key = (src_width, src_height, dst_width, dst_height, channels);
+        if self.key != key {
+            self.key = key;
+            self.columns = samples(src_width, dst_width)
+                .map(|x| x * channels)
+                .collect();
+            self.rows = samples(src_height, dst_height).collect();
+        }
+
+        let src_row = src_width as usize * channels;
+        let dst_row = dst_width as usize * channels;
+        for (out, &y) in dst.chunks_exact_mut(dst_row).zip(&self.rows) {
+            let line = &img.as_raw()[y * src_row..][..src_row];
+
+            for (pixel, &x) in out.chunks_exact_mut(channels).zip(&self.columns) {
+                pixel.copy_from_slice(&line[x..][..channels]);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Like [`resize`](Self::resize), but averages the whole rectangle of
+    /// source pixels under each destination one, which for a terminal cell
+    /// is about twice as tall as it is wide. Edges don't shimmer from one
+    /// frame to the next as the camera's noise moves which pixel is picked,
+    /// and small motion reads as a change of shade.
+    pub fn resize_area<P>(
+        &mut self,
+        img: &ImageBuffer<P, Vec<u8>>,
+        dst: &mut ImageBuffer<P, Vec<u8>>,
+    ) -> Result<()>
+    where
+        P: Pixel<Subpixel = u8>,
+    {
+        self.average(img, dst, false)
+    }
+
+    /// Like [`resize_area`](Self::resize_area), but averages in linear
+    /// light rather than the gamma encoded values. Thin bright or dark
+    /// things, like hair or text, are kept as a lighter shade instead of
+    /// darkening the cells they are in.
+    pub fn resize_linear<P>(
+        &mut self,
+        img: &ImageBuffer<P, Vec<u8>>,
+        dst: &mut ImageBuffer<P, Vec<u8>>,
+    ) -> Result<()>
+    where
+        P: Pixel<Subpixel = u8>,
+    {
+        self.average(img, dst, true)
+    }
+
+    fn average<P>(
+        &mut self,
+        img: &ImageBuffer<P, Vec<u8>>,
+        dst: &mut ImageBuffer<P, Vec<u8>>,
+        linear: bool,
+    ) -> Result<()>
+    where
+        P: Pixel<Subpixel = u8>,
+    {
+        let (src_width, src_height) = (non_zero(img.width())?.get(), non_zero(img.height())?.get());
+        let (dst_width, dst_height) = (non_zero(dst.width())?.get(), non_zero(dst.height())?.get());
+        let channels = P::CHANNEL_COUNT as usize;
+
+        let key = (src_width, src_height, dst_width, dst_height);
+        if self.span_key != key {
+            self.span_key = key;
+            self.column_spans = spans(src_width, dst_width).collect();
+            self.row_spans = spans(src_height, dst_height).collect();
+        }
+        let gamma = if linear {
+            Some(&**self.gamma.get_or_insert_with(|| Box::new(Gamma::new())))
+        } else {
+            None
+        };
+
+        let src_row = src_width as usize * channels;
+        let dst_row = dst_width as usize * channels;
+        for (out, &(top, bottom)) in dst.chunks_exact_mut(dst_row).zip(&self.row_spans) {
+            for (pixel, &(left, right)) in out.chunks_exact_mut(channels).zip(&self.column_spans) {
+                // a u8 pixel has at most 4 channels
+                let mut sums = [0u64; 4];
+                for y in top..bottom {
+                    let line = &img.as_raw()[y * src_row..][..src_row];
+                    for x in left..right {
+                        for (sum, &v) in sums.iter_mut().zip(&line[x * channels..][..channels]) {
+                            *sum += match gamma {
+                                Some(gamma) => gamma.decode[v as usize] as u64,
+                                None => v as u64,
+                            };
+                        }
+                    }
+                }
+
+                let count = ((bottom - top) * (right - left)) as u64;
+                for (v, sum) in pixel.iter_mut().zip(sums) {
+                    *v = match gamma {
+                        Some(gamma) => gamma.encode((sum / count) as u16),
+                        None => ((sum + count / 2) / count) as u8,
+                    };
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Lookup tables from sRGB values to linear light and back.
+#[derive(Debug)]
+struct Gamma {
+    // every 8 bit value in linear light, out of 65535
+    decode: [u16; 256],
+    // linear light in steps of 16 back to 8 bits, finer than that only
+    // matters for the darkest values, which are all one character anyway
+    encode: [u8; 4096],
+}
+
+impl Gamma {
+    fn new() -> Self {
+        let mut decode = [0; 256];
+        for (v, out) in decode.iter_mut().enumerate() {
+            let c = v as f32 / 255.0;
+            let linear = if c <= 0.04045 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            };
+            *out = (linear * 65535.0).round() as u16;
+        }
+
+        let mut encode = [0; 4096];
+        for (i, out) in encode.iter_mut().enumerate() {
+            let linear = (i as f32 + 0.5) / 4096.0;
+            let c = if linear <= 0.0031308 {
+                linear * 12.92
+            } else {
+                1.055 * linear.powf(1.0 / 2.4) - 0.055
+            };
+            *out = (c * 255.0).round().clamp(0.0, 255.0) as u8;
+        }
+
+        Self { decode, encode }
+    }
+
+    fn encode(&self, linear: u16) -> u8 {
+        self.encode[linear as usize >> 4]
+    }
+}
+
+/// The range of source indices under each of `dst` destination pixels, at
+/// least one when scaling up.
+fn spans(src: u32, dst: u32) -> impl Iterator<Item = (usize, usize)> {
+    let (src, dst) = (src as usize, dst as usize);
+    (0..dst).map(move |i| {
+        let start = (i * src / dst).min(src - 1);
+        (start, ((i + 1) * src / dst).max(start + 1))
+    })
+}
+
+/// The source index under the center of each of `dst` destination pixels.
+fn samples(src: u32, dst: u32) -> impl Iterator<Item = usize> {
+    let (src, dst) = (src as usize, dst as usize);
+    (0..dst).map(move |i| (2 * i + 1) * src / (2 * dst))
+}
+
+fn decode_mjpeg_raw(buf: &[u8]) -> Result<(u32, u32, Vec<u8>)> {
+    let mut data = Vec::new();
+    let (width, height) = decode_mjpeg_gray_into(buf, &mut data)?;
+
+    Ok((width, height, data))
+}
+
+/// Like [`decode_mjpeg_rgb_into`], in grayscale.
+#[cfg(not(target_arch = "wasm32"))]
+fn decode_mjpeg_gray_into(buf: &[u8], data: &mut Vec<u8>) -> Result<(u32, u32)> {
+    #[cfg(feature = "vaapi")]
+    if let Some(size) = crate::vaapi::decode_into(buf, crate::vaapi::Output::Gray, data) {
+        return Ok(size);
+    }
+
+    decode_gray_into(buf, data)
+}
+
+#[cfg(all(not(target_arch = "wasm32"), feature = "mozjpeg"))]
+fn decode_rgb_into(buf: &[u8], data: &mut Vec<u8>) -> Result<(u32, u32)> {
+    let decoder = mozjpeg::Decompress::with_markers(mozjpeg::ALL_MARKERS).from_mem(buf)?;
+    let mut img = decoder.rgb()?;
+    let (width, height) = (img.width() as u32, img.height() as u32);
+
+    let raw_pixels: Vec<[u8; 3]> = match img.read_scanlines() {
+        None => {
+            return Err(eyre!("Could not decompress image"));
+        }
+        Some(v) => v,
+    };
+
+    img.finish_decompress();
+
+    data.clear();
+    data.extend(raw_pixels.iter().flatten());
+    Ok((width, height))
+}
+
+#[cfg(all(not(target_arch = "wasm32"), feature = "mozjpeg"))]
+fn decode_gray_into(buf: &[u8], data: &mut Vec<u8>) -> Result<(u32, u32)> {
+    let decoder = mozjpeg::Decompress::with_markers(mozjpeg::ALL_MARKERS).from_mem(buf)?;
+    let mut img = decoder.grayscale()?;
+    let (width, height) = (img.width() as u32, img.height() as u32);
+
+    let raw_pixels: Vec<u8> = match img.read_scanlines() {
+        None => {
+            return Err(eyre!("Could not decompress image"));
+        }
+        Some(v) => v,
+    };
+
+    img.finish_decompress();
+
+    data.clear();
+    data.extend_from_slice(&raw_pixels);
+    Ok((width, height))
+}
+
+// mozjpeg needs a C compiler for the target, built without it zune-jpeg
+// decodes in pure rust instead, a little slower
+#[cfg(all(not(target_arch = "wasm32"), not(feature = "mozjpeg")))]
+fn decode_rgb_into(buf: &[u8], data: &mut Vec<u8>) -> Result<(u32, u32)> {
+    decode_zune(buf, ColorSpace::RGB, data)
+}
+
+#[cfg(all(not(target_arch = "wasm32"), not(feature = "mozjpeg")))]
+fn decode_gray_into(buf: &[u8], data: &mut Vec<u8>) -> Result<(u32, u32)> {
+    decode_zune(buf, ColorSpace::Luma, data)
+}
+
+#[cfg(all(not(target_arch = "wasm32"), not(feature = "mozjpeg")))]
+fn decode_zune(buf: &[u8], colorspace: ColorSpace, data: &mut Vec<u8>) -> Result<(u32, u32)> {
+    let options = DecoderOptions::default().jpeg_set_out_colorspace(colorspace);
+    let mut decoder = JpegDecoder::new_with_options(buf, options);
+    decoder
+        .decode_headers()
+        .map_err(|e| eyre!("Could not decompress image ({e:?})"))?;
+    let (width, height) = match (decoder.dimensions(), decoder.output_buffer_size()) {
+        (Some(dimensions), Some(size)) => {
+            data.resize(size, 0);
+            dimensions
+        }
+        _ => return Err(eyre!("Could not decompress image")),
+    };
+    decoder
+        .decode_into(data)
+        .map_err(|e| eyre!("Could not decompress image ({e:?})"))?;
+
+    Ok((width as u32, height as u32))
+}
+
+// mozjpeg is C and doesn't build for the browser, image's own decoder does
+// the job there, only slower
+#[cfg(target_arch = "wasm32")]
+fn decode_mjpeg_gray_into(buf: &[u8], data: &mut Vec<u8>) -> Result<(u32, u32)> {
+    let img = image::load_from_memory_with_format(buf, image::ImageFormat::Jpeg)?.to_luma8();
+
+    data.clear();
+    data.extend_from_slice(&img);
+    Ok(img.dimensions())
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn decode_mjpeg_rgb_into(buf: &[u8], data: &mut Vec<u8>) -> Result<(u32, u32)> {
+    let img = image::load_from_memory_with_format(buf, image::ImageFormat::Jpeg)?.to_rgb8();
+
+    data.clear();
+    data.extend_from_slice(&img);
+    Ok(img.dimensions())
+}
+
+fn non_zero(v: u32) -> Result<NonZeroU32> {
+    match NonZeroU32::new(v) {
+        None => Err(eyre!("Could not create NonZeroU32")),
+        Some(v) => Ok(v),
+    }
+}
+
+/// The first `row` bytes of each of `height` lines `stride` apart, padded
+/// with zeroes when the buffer is short.
+pub(crate) fn unpad_rows(bytes: &[u8], stride: usize, row: usize, height: u32) -> Vec<u8> {
+    let mut out = Vec::with_capacity(row * height as usize);
+    for line in bytes.chunks(stride.max(row)).take(height as usize) {
+        out.extend_from_slice(&line[..row.min(line.len())]);
+    }
+    out.resize(row * height as usize, 0);
+
+    out
+}
+
+/// A grayscale format with more than 8 bits per pixel, every pixel stored
+/// in 16 little endian bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HighBitDepth {
+    /// 10 bits in the low ones.
+    Y10,
+    /// 12 bits in the low ones.
+    Y12,
+    Y16,
+    /// 10 bits in the high ones, of which only the luma plane is used.
+    P010,
+}
+
+impl HighBitDepth {
+    /// The format of the v4l format `fourcc`, like `Y10 `.
+    pub fn from_fourcc(fourcc: &[u8; 4]) -> Option<Self> {
+        match fourcc {
+            b"Y10 " => Some(HighBitDepth::Y10),
+            b"Y12 " => Some(HighBitDepth::Y12),
+            b"Y16 " => Some(HighBitDepth::Y16),
+            b"P010" => Some(HighBitDepth::P010),
+            _ => None,
+        }
+    }
+
+    /// The brightest a pixel gets.
+    pub fn max(self) -> u16 {
+        match self {
+            HighBitDepth::Y10 | HighBitDepth::P010 => 1023,
+            HighBitDepth::Y12 => 4095,
+            HighBitDepth::Y16 => u16::MAX,
+        }
+    }
+
+    fn sample(self, bytes: [u8; 2]) -> u16 {
+        match self {
+            HighBitDepth::P010 => u16::from_le_bytes(bytes) >> 6,
+            _ => u16::from_le_bytes(bytes),
+        }
+    }
+}
+
+/// The sensor values mapped onto 0-255, anything darker than `low` is
+/// black and anything brighter than `high` is white. Parsed from `LOW-HIGH`,
+/// in the units of the format, `64-900` for a 10 bit sensor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LevelWindow {
+    pub low: u16,
+    pub high: u16,
+}
+
+impl FromStr for LevelWindow {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (low, high) = match s.split_once('-') {
+            Some((low, high)) => (low.parse::<u16>()?, high.parse::<u16>()?),
+            None => return Err(eyre!("Expected a window like 64-900, got '{s}'")),
+        };
+        if high <= low {
+            return Err(eyre!("The high end of '{s}' has to be above the low one"));
+        }
+
+        Ok(Self { low, high })
+    }
+}
+
+/// How much each of red, green and blue counts towards the brightness of
+/// a pixel of a color source. Parsed from `bt601`, `bt709` or three weights
+/// like `0.3,0.6,0.1`, which are scaled to add up to one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LumaWeights {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+}
+
+impl LumaWeights {
+    /// The weights of SD video and of JPEG, what mozjpeg decodes to gray
+    /// with. Reds and browns read brighter than with [`BT709`](Self::BT709).
+    pub const BT601: Self = Self {
+        r: 0.299,
+        g: 0.587,
+        b: 0.114,
+    };
+    /// The weights of HD video and sRGB, greens count for more.
+    pub const BT709: Self = Self {
+        r: 0.2126,
+        g: 0.7152,
+        b: 0.0722,
+    };
+
+    pub fn luma(self, [r, g, b]: [u8; 3]) -> u8 {
+        (self.r * r as f32 + self.g * g as f32 + self.b * b as f32)
+            .round()
+            .clamp(0.0, 255.0) as u8
+    }
+}
+
+impl FromStr for LumaWeights {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "bt601" => return Ok(Self::BT601),
+            "bt709" => return Ok(Self::BT709),
+            _ => (),
+        }
+
+        let weights = s
+            .split(',')
+            .map(|w| w.trim().parse::<f32>())
+            .collect::<Result<Vec<_>, _>>();
+        let (r, g, b) = match weights.as_deref() {
+            Ok(&[r, g, b]) if r >= 0.0 && g >= 0.0 && b >= 0.0 => (r, g, b),
+            _ => {
+                return Err(eyre!(
+                    "Expected bt601, bt709 or weights like 0.3,0.6,0.1, got '{s}'"
+                ))
+            }
+        };
+        let sum = r + g + b;
+        if sum <= 0.0 {
+            return Err(eyre!("The weights of '{s}' add up to nothing"));
+        }
+
+        Ok(Self {
+            r: r / sum,
+            g: g / sum,
+            b: b / sum,
+        })
+    }
+}
+
+/// Converts a frame of `format` to 8 bit grayscale through `window`, by
+/// default the whole range of the format. Rows are `stride` bytes apart.
+pub fn window_gray(
+    raw: &[u8],
+    width: u32,
+    height: u32,
+    stride: usize,
+    format: HighBitDepth,
+    window: Option<LevelWindow>,
+) -> Vec<u8> {
+    let LevelWindow { low, high } = window.unwrap_or(LevelWindow {
+        low: 0,
+        high: format.max(),
+    });
+    let range = (high - low) as u32;
+    let mut out = Vec::with_capacity(width as usize * height as usize);
+    for pixel in unpad_rows(raw, stride, width as usize * 2, height).chunks_exact(2) {
+        let value = format.sample([pixel[0], pixel[1]]).clamp(low, high) - low;
+        out.push((value as u32 * 255 / range) as u8);
+    }
+
+    out
+}
+
+/// The order of the color filters over the pixels of a raw sensor, named
+/// after its top left 2x2 cell row by row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BayerPattern {
+    Bggr,
+    Gbrg,
+    Grbg,
+    Rggb,
+}
+
+impl BayerPattern {
+    /// The pattern of the 8 bit v4l format `fourcc`, like `BA81` for
+    /// SBGGR8.
+    pub fn from_fourcc(fourcc: &[u8; 4]) -> Option<Self> {
+        match fourcc {
+            b"BA81" => Some(BayerPattern::Bggr),
+            b"GBRG" => Some(BayerPattern::Gbrg),
+            b"GRBG" => Some(BayerPattern::Grbg),
+            b"RGGB" => Some(BayerPattern::Rggb),
+            _ => None,
+        }
+    }
+
+    // where the red pixel is in each 2x2 cell, blue is across from it
+    fn red(self) -> (usize, usize) {
+        match self {
+            BayerPattern::Bggr => (1, 1),
+            BayerPattern::Gbrg => (0, 1),
+            BayerPattern::Grbg => (1, 0),
+            BayerPattern::Rggb => (0, 0),
+        }
+    }
+}
+
+/// What every value of a raw sensor becomes with `black_level`, what the
+/// sensor reads with no light at all, taken off and the rest stretched back
+/// to 0-255. Worked out once for every black level set, for [`demosaic`].
+pub fn black_levels(black_level: u8) -> [u8; 256] {
+    let range = 255 - black_level.min(254) as u32;
+    std::array::from_fn(|v| ((v as u32).saturating_sub(black_level as u32) * 255 / range) as u8)
+}
+
+/// Converts an 8 bit raw sensor frame, with `stride` bytes from one row to
+/// the next, to rgb in `out`. Every 2x2 cell of the pattern becomes one
+/// color, its red, blue and the average of its greens, which halves the
+/// color resolution but not the brightness, plenty for characters that
+/// are several pixels across anyway.
+///
+/// Every value is looked up in `levels` first, see [`black_levels`].
+pub fn demosaic(
+    raw: &[u8],
+    width: u32,
+    height: u32,
+    stride: usize,
+    pattern: BayerPattern,
+    levels: &[u8; 256],
+    out: &mut Vec<u8>,
+) -> Result<()> {
+    let (width, height) = (width as usize, height as usize);
+    let stride = stride.max(width);
+    if height > 0 && raw.len() < (height - 1) * stride + width {
+        return Err(eyre!("Bayer frame is smaller than its dimensions"));
+    }
+    let (red_x, red_y) = pattern.red();
+
+    // an odd last row or column stays black
+    out.clear();
+    out.resize(width * height * 3, 0);
+    for y in (0..height & !1).step_by(2) {
+        for x in (0..width & !1).step_by(2) {
+            let at = |dx: usize, dy: usize| levels[raw[(y + dy) * stride + x + dx] as usize];
+            let green = (at(1 - red_x, red_y) as u16 + at(red_x, 1 - red_y) as u16) / 2;
+            let color = [at(red_x, red_y), green as u8, at(1 - red_x, 1 - red_y)];
+
+            for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                out[((y + dy) * width + x + dx) * 3..][..3].copy_from_slice(&color);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Converts YUYV 4:2:2 with BT.601 studio range coefficients to rgb, the
+/// reverse of what the loopback sink does.
+pub fn yuyv_to_rgb(yuyv: &[u8]) -> Vec<u8> {
+    let rgb = |y: u8, u: u8, v: u8| {
+        let (c, d, e) = (y as i32 - 16, u as i32 - 128, v as i32 - 128);
+        let clamp = |value: i32| ((value + 128) >> 8).clamp(0, 255) as u8;
+        [
+            clamp(298 * c + 409 * e),
+            clamp(298 * c - 100 * d - 208 * e),
+            clamp(298 * c + 516 * d),
+        ]
+    };
+
+    let mut out = Vec::with_capacity(yuyv.len() / 2 * 3);
+    for pair in yuyv.chunks_exact(4) {
+        out.extend_from_slice(&rgb(pair[0], pair[1], pair[3]));
+        out.extend_from_slice(&rgb(pair[2], pair[1], pair[3]));
+    }
+
+    out
+}
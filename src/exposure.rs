@@ -0,0 +1,195 @@
+//! Holding the picture at a brightness by adjusting the camera, for cameras
+//! whose own automatic exposure is missing or turned off.
+
+use crate::capture::{ControlInfo, Controls, Frame, EXPOSURE, GAIN};
+use crate::convert::{decode_frame, resize};
+use eyre::{eyre, Report, Result};
+use image::imageops;
+use std::str::FromStr;
+
+// the brightness is measured at this size, the mean hardly changes with
+// more pixels
+const SAMPLE_WIDTH: u32 = 64;
+const SAMPLE_HEIGHT: u32 = 48;
+/// Mean brightness [`AutoExposure`] aims for when not told otherwise, a
+/// little under the middle so faces don't end up washed out.
+pub const DEFAULT_TARGET: u8 = 110;
+// how far from the target the brightness can be without changing anything,
+// keeps sensor noise from making the loop hunt
+const TOLERANCE: f32 = 8.0;
+// the fraction of the correction made at once, as a power of the ratio
+// between the target and the measured brightness. the rest is left to the
+// following frames, so an overshoot dies down instead of oscillating
+const DAMPING: f32 = 0.3;
+// frames to wait after a change before measuring again, cameras take a few
+// to apply a new exposure
+const SETTLE_FRAMES: u32 = 4;
+
+/// The part of the picture the brightness is measured in, as fractions of
+/// its width and height, so that a bright window behind someone doesn't
+/// leave their face black.
+///
+/// Parsed from percentages like a screen region, `WxH+X+Y`: `40x60+30+20`
+/// is 40% of the width and 60% of the height, 30% from the left and 20%
+/// from the top.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MeterRegion {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl MeterRegion {
+    /// The same region with left and right swapped, for a picture that is
+    /// mirrored after it is measured.
+    pub fn mirrored(self) -> Self {
+        Self {
+            x: 1.0 - self.x - self.width,
+            ..self
+        }
+    }
+}
+
+impl FromStr for MeterRegion {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let parts = s
+            .split_once('x')
+            .and_then(|(width, rest)| Some((width, rest.split_once('+')?)))
+            .and_then(|(width, (height, rest))| Some((width, height, rest.split_once('+')?)));
+        let (width, height, (x, y)) = match parts {
+            Some(parts) => parts,
+            None => {
+                return Err(eyre!(
+                    "Expected a region in percent like 40x60+30+20, got '{s}'"
+                ))
+            }
+        };
+
+        let region = Self {
+            x: x.parse::<f32>()? / 100.0,
+            y: y.parse::<f32>()? / 100.0,
+            width: width.parse::<f32>()? / 100.0,
+            height: height.parse::<f32>()? / 100.0,
+        };
+        let in_picture = |start: f32, size: f32| size > 0.0 && start >= 0.0 && start + size <= 1.0;
+        if !in_picture(region.x, region.width) || !in_picture(region.y, region.height) {
+            return Err(eyre!("The region '{s}' is not inside the picture"));
+        }
+
+        Ok(region)
+    }
+}
+
+/// A control loop that measures the mean brightness of the frames and
+/// nudges the camera's exposure, and its gain once exposure is at its limit,
+/// toward a target.
+pub struct AutoExposure {
+    target: f32,
+    region: Option<MeterRegion>,
+    // frames left until the last change shows up
+    settling: u32,
+}
+
+impl AutoExposure {
+    /// Aims for a mean brightness of `target`, from 0 to 255.
+    pub fn new(target: u8) -> Self {
+        Self {
+            target: target as f32,
+            region: None,
+            settling: 0,
+        }
+    }
+
+    /// Measures the brightness in `region` only, instead of the whole
+    /// picture.
+    pub fn region(mut self, region: MeterRegion) -> Self {
+        self.region = Some(region);
+        self
+    }
+
+    pub fn target(&self) -> u8 {
+        self.target as u8
+    }
+
+    /// Measures `frame` and moves the controls a step toward the target when
+    /// it is too dark or too bright, switching them to manual first.
+    pub fn update(&mut self, frame: &Frame, controls: &Controls) -> Result<()> {
+        if self.settling > 0 {
+            self.settling -= 1;
+            return Ok(());
+        }
+
+        let brightness = mean_brightness(frame, self.region)?;
+        if (brightness - self.target).abs() <= TOLERANCE {
+            return Ok(());
+        }
+        let ratio = (self.target / brightness.max(1.0)).powf(DAMPING);
+
+        controls.set_manual(&EXPOSURE)?;
+        controls.set_manual(&GAIN)?;
+        let exposure = controls.lookup(&EXPOSURE)?;
+        // not every camera has gain, exposure alone will do
+        let gain = controls.lookup(&GAIN).ok();
+
+        // exposure first when brightening and gain first when darkening,
+        // gain adds noise so it is only used when exposure can't go further
+        let order = if ratio > 1.0 {
+            [Some(exposure), gain]
+        } else {
+            [gain, Some(exposure)]
+        };
+        for control in order.into_iter().flatten() {
+            if let Some(value) = scaled(&control, ratio) {
+                controls.write(&control, value)?;
+                self.settling = SETTLE_FRAMES;
+                break;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The mean brightness of `frame`, or of `region` of it, from 0 to 255.
+pub fn mean_brightness(frame: &Frame, region: Option<MeterRegion>) -> Result<f32> {
+    let mut sample = resize(&decode_frame(frame)?, SAMPLE_WIDTH, SAMPLE_HEIGHT)?;
+    if let Some(region) = region {
+        let (width, height) = (SAMPLE_WIDTH as f32, SAMPLE_HEIGHT as f32);
+        sample = imageops::crop_imm(
+            &sample,
+            (region.x * width) as u32,
+            (region.y * height) as u32,
+            ((region.width * width) as u32).max(1),
+            ((region.height * height) as u32).max(1),
+        )
+        .to_image();
+    }
+    let sum: u64 = sample.iter().map(|&pixel| pixel as u64).sum();
+
+    Ok(sum as f32 / sample.len().max(1) as f32)
+}
+
+/// The value of `control` multiplied by `ratio`, counted from its minimum,
+/// moved by at least one step and kept in range. `None` when it is already
+/// at the end of its range.
+fn scaled(control: &ControlInfo, ratio: f32) -> Option<i64> {
+    let value = control.value.unwrap_or(control.default);
+    let unit = (control.step as i64).max(1);
+
+    let position = (value - control.minimum + unit) as f32 * ratio;
+    let mut wanted = control.minimum - unit + position.round() as i64;
+    wanted -= (wanted - control.minimum).rem_euclid(unit);
+    if wanted == value {
+        wanted = if ratio > 1.0 {
+            value + unit
+        } else {
+            value - unit
+        };
+    }
+
+    let wanted = wanted.clamp(control.minimum, control.maximum);
+    (wanted != value).then_some(wanted)
+}
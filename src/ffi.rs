@@ -0,0 +1,190 @@
+//! A C ABI for the conversion core, enabled with the `ffi` feature.
+//!
+//! See `include/asciicam.h` for the C declarations.
+
+use crate::capture::{Frame, FrameMeta, PixelFormat};
+use crate::render::{parse_charset, render_frame, AsciiRenderer, RenderOptions, DEFAULT_CHARSET};
+use eyre::{eyre, Result};
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+
+pub const ASCIICAM_FORMAT_MJPEG: u32 = 0;
+pub const ASCIICAM_FORMAT_GRAY8: u32 = 1;
+pub const ASCIICAM_FORMAT_RGB8: u32 = 2;
+
+/// Options for [`asciicam_convert`].
+#[repr(C)]
+pub struct AsciicamOptions {
+    /// Size of the output in characters.
+    pub columns: u32,
+    pub rows: u32,
+    /// One of the `ASCIICAM_FORMAT_*` constants.
+    pub pixel_format: u32,
+    /// Flip the image horizontally.
+    pub mirror: bool,
+    /// NUL terminated UTF-8 charset ordered dark to bright, or NULL for the
+    /// default.
+    pub charset: *const c_char,
+}
+
+/// A NUL terminated string owned by asciicam, free it with
+/// [`asciicam_free`].
+#[repr(C)]
+pub struct AsciicamOutput {
+    pub data: *mut c_char,
+    /// Length in bytes, not counting the NUL.
+    pub len: usize,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(msg: String) {
+    // interior NULs can't be represented, replace them rather than losing
+    // the whole message
+    let msg = CString::new(msg.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|e| *e.borrow_mut() = Some(msg));
+}
+
+/// Returns the default options: 80x24, MJPEG input, mirrored, default
+/// charset.
+#[no_mangle]
+pub extern "C" fn asciicam_default_options() -> AsciicamOptions {
+    AsciicamOptions {
+        columns: 80,
+        rows: 24,
+        pixel_format: ASCIICAM_FORMAT_MJPEG,
+        mirror: true,
+        charset: ptr::null(),
+    }
+}
+
+/// Converts an image into ascii art, one line per row separated by `\n`.
+///
+/// `buf` holds `len` bytes of image data in `opts.pixel_format`. `width` and
+/// `height` are the size of the image, they are ignored for MJPEG input
+/// where the size is read from the image itself.
+///
+/// Returns 0 on success and fills in `out`, or -1 on failure, in which case
+/// [`asciicam_last_error`] describes what went wrong.
+///
+/// # Safety
+///
+/// `buf` must point to `len` readable bytes, `opts` must point to valid
+/// options and `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn asciicam_convert(
+    buf: *const u8,
+    len: usize,
+    width: u32,
+    height: u32,
+    opts: *const AsciicamOptions,
+    out: *mut AsciicamOutput,
+) -> c_int {
+    if buf.is_null() || opts.is_null() || out.is_null() {
+        set_last_error("buf, opts and out must not be NULL".to_string());
+        return -1;
+    }
+
+    let buf = std::slice::from_raw_parts(buf, len);
+    let opts = &*opts;
+
+    let result = catch_unwind(AssertUnwindSafe(|| convert(buf, width, height, opts)));
+
+    match result {
+        Ok(Ok(text)) => {
+            let len = text.len();
+            match CString::new(text) {
+                Ok(text) => {
+                    *out = AsciicamOutput {
+                        data: text.into_raw(),
+                        len,
+                    };
+                    0
+                }
+                Err(e) => {
+                    set_last_error(e.to_string());
+                    -1
+                }
+            }
+        }
+        Ok(Err(e)) => {
+            set_last_error(format!("{e:#}"));
+            -1
+        }
+        Err(_) => {
+            set_last_error("asciicam panicked during conversion".to_string());
+            -1
+        }
+    }
+}
+
+unsafe fn convert(buf: &[u8], width: u32, height: u32, opts: &AsciicamOptions) -> Result<String> {
+    let pixel_format = match opts.pixel_format {
+        ASCIICAM_FORMAT_MJPEG => PixelFormat::Mjpeg,
+        ASCIICAM_FORMAT_GRAY8 => PixelFormat::Gray8,
+        ASCIICAM_FORMAT_RGB8 => PixelFormat::Rgb8,
+        f => return Err(eyre!("Unknown pixel format {f}")),
+    };
+
+    let charset = if opts.charset.is_null() {
+        DEFAULT_CHARSET.to_vec()
+    } else {
+        parse_charset(CStr::from_ptr(opts.charset).to_str()?)?
+    };
+
+    let frame = Frame {
+        data: buf.to_vec(),
+        width,
+        height,
+        pixel_format,
+        meta: FrameMeta::default(),
+    };
+
+    let options = RenderOptions {
+        mirror: opts.mirror,
+        color: false,
+        ..RenderOptions::default()
+    };
+
+    let grid = render_frame(
+        &frame,
+        &AsciiRenderer::new(&charset),
+        opts.columns,
+        opts.rows,
+        &options,
+    )?;
+
+    Ok(grid.to_text())
+}
+
+/// Frees the string in `out` and resets it to NULL. Passing an already
+/// freed or zeroed output is a no-op.
+///
+/// # Safety
+///
+/// `out` must be NULL or point to an output filled in by
+/// [`asciicam_convert`].
+#[no_mangle]
+pub unsafe extern "C" fn asciicam_free(out: *mut AsciicamOutput) {
+    if out.is_null() || (*out).data.is_null() {
+        return;
+    }
+
+    drop(CString::from_raw((*out).data));
+    (*out).data = ptr::null_mut();
+    (*out).len = 0;
+}
+
+/// Returns a description of the last error on this thread, or NULL. The
+/// string stays valid until the next failing call on the same thread.
+#[no_mangle]
+pub extern "C" fn asciicam_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| match &*e.borrow() {
+        Some(msg) => msg.as_ptr(),
+        None => ptr::null(),
+    })
+}
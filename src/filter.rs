@@ -0,0 +1,637 @@
+//! Adjustments applied to the pixels before they are rendered.
+
+use eyre::{eyre, Report, Result};
+use image::{GrayImage, ImageBuffer, Pixel, Rgb, RgbImage};
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// A step of a [`FilterChain`], parsed from `--filter` arguments like
+/// `contrast=1.5` or `denoise`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Filter {
+    /// Swap dark and bright.
+    Invert,
+    /// Add a constant to every pixel, -255 to 255.
+    Brightness(i16),
+    /// Scale the distance of every pixel from mid gray, must be positive.
+    Contrast(f32),
+    /// Multiply every pixel, clamping what ends up brighter than white,
+    /// must be positive.
+    Gain(f32),
+    /// Raise every pixel to the power of one over this, above 1 brightens
+    /// the shadows, must be positive.
+    Gamma(f32),
+    /// Average every pixel with the frames before, keeping this much of the
+    /// average each frame, from 0 to 1, see [`TemporalDenoiser`].
+    Denoise(f32),
+    /// Only the edges of the picture, as bright as they are sharp, see
+    /// [`edges`].
+    Edges,
+    /// Flatten the shading to this many tones, at least 2, and outline the
+    /// edges, see [`toon`].
+    Toon(u8),
+    /// Darken the picture towards its corners by this much, from 0 to 1,
+    /// see [`vignette`].
+    Vignette(f32),
+    /// Scanlines and smear, see [`crt`].
+    Crt,
+    /// A filter of a native plugin, see [`plugin`](crate::plugin).
+    #[cfg(all(feature = "plugins", not(target_arch = "wasm32")))]
+    Plugin(crate::plugin::PluginFilter),
+}
+
+// what the filters without a value given take
+const DENOISE_STRENGTH: f32 = 0.7;
+const TOON_LEVELS: u8 = 4;
+const VIGNETTE_STRENGTH: f32 = 0.5;
+
+impl Filter {
+    /// Checks that the filter's parameters are in range.
+    pub fn validate(&self) -> Result<()> {
+        match *self {
+            Filter::Brightness(b) if !(-255..=255).contains(&b) => {
+                Err(eyre!("Brightness must be between -255 and 255, got {b}"))
+            }
+            Filter::Contrast(c) if !(c.is_finite() && c > 0.0) => {
+                Err(eyre!("Contrast must be positive, got {c}"))
+            }
+            Filter::Gain(g) if !(g.is_finite() && g > 0.0) => {
+                Err(eyre!("Gain must be positive, got {g}"))
+            }
+            Filter::Gamma(g) if !(g.is_finite() && g > 0.0) => {
+                Err(eyre!("Gamma must be positive, got {g}"))
+            }
+            Filter::Denoise(s) if !(0.0..=1.0).contains(&s) => {
+                Err(eyre!("Denoise strength must be between 0 and 1, got {s}"))
+            }
+            Filter::Toon(levels) if levels < 2 => {
+                Err(eyre!("Toon needs at least 2 levels, got {levels}"))
+            }
+            Filter::Vignette(s) if !(0.0..=1.0).contains(&s) => {
+                Err(eyre!("Vignette strength must be between 0 and 1, got {s}"))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether the filter works on every pixel on its own, so that it can
+    /// be looked up in a [`tone_table`].
+    pub fn is_tone(&self) -> bool {
+        matches!(
+            self,
+            Filter::Invert
+                | Filter::Brightness(_)
+                | Filter::Contrast(_)
+                | Filter::Gain(_)
+                | Filter::Gamma(_)
+        )
+    }
+
+    /// Applies the filter to a single channel value, the ones that aren't
+    /// [`is_tone`](Self::is_tone) leave it as it is.
+    pub fn apply(&self, value: u8) -> u8 {
+        match *self {
+            Filter::Invert => 255 - value,
+            Filter::Brightness(b) => (value as i16 + b).clamp(0, 255) as u8,
+            Filter::Contrast(c) => ((value as f32 - 128.0) * c + 128.0).clamp(0.0, 255.0) as u8,
+            Filter::Gain(g) => (value as f32 * g).min(255.0) as u8,
+            Filter::Gamma(g) => ((value as f32 / 255.0).powf(1.0 / g) * 255.0).round() as u8,
+            _ => value,
+        }
+    }
+
+    pub fn apply_luma(&self, img: &mut GrayImage) {
+        for v in img.iter_mut() {
+            *v = self.apply(*v);
+        }
+    }
+
+    pub fn apply_rgb(&self, img: &mut RgbImage) {
+        for v in img.iter_mut() {
+            *v = self.apply(*v);
+        }
+    }
+}
+
+/// `filters` applied one after the other to every value a channel can have,
+/// so filtering a frame takes a lookup per channel instead of the math of
+/// every filter.
+pub fn tone_table(filters: &[Filter]) -> [u8; 256] {
+    let mut table = [0; 256];
+    for (value, out) in table.iter_mut().enumerate() {
+        *out = filters
+            .iter()
+            .fold(value as u8, |value, filter| filter.apply(value));
+    }
+
+    table
+}
+
+/// Looks up every channel of `samples` in a [`tone_table`].
+pub fn apply_table(table: &[u8; 256], samples: &mut [u8]) {
+    for value in samples {
+        *value = table[*value as usize];
+    }
+}
+
+impl Filter {
+    /// This filter as a [`Stage`] of its own. `None` for a plugin filter
+    /// whose plugin couldn't make one, which is logged.
+    pub fn stage(&self) -> Option<Box<dyn Stage>> {
+        let stage: Box<dyn Stage> = match *self {
+            Filter::Invert
+            | Filter::Brightness(_)
+            | Filter::Contrast(_)
+            | Filter::Gain(_)
+            | Filter::Gamma(_) => Box::new(tone_stage(tone_table(std::slice::from_ref(self)))),
+            Filter::Denoise(strength) => {
+                let mut denoiser = TemporalDenoiser::new();
+                Box::new(move |luma: &mut GrayImage, _: Option<&mut RgbImage>| {
+                    denoiser.apply(luma, strength)
+                })
+            }
+            Filter::Edges => Box::new(edges),
+            Filter::Toon(levels) => {
+                Box::new(move |luma: &mut GrayImage, rgb: Option<&mut RgbImage>| {
+                    toon(luma, rgb, levels)
+                })
+            }
+            Filter::Vignette(strength) => {
+                Box::new(move |luma: &mut GrayImage, rgb: Option<&mut RgbImage>| {
+                    vignette(luma, strength);
+                    if let Some(rgb) = rgb {
+                        vignette(rgb, strength);
+                    }
+                })
+            }
+            Filter::Crt => Box::new(|luma: &mut GrayImage, rgb: Option<&mut RgbImage>| {
+                crt(luma);
+                if let Some(rgb) = rgb {
+                    crt(rgb);
+                }
+            }),
+            #[cfg(all(feature = "plugins", not(target_arch = "wasm32")))]
+            Filter::Plugin(filter) => match filter.stage() {
+                Ok(stage) => stage,
+                // the chain goes on without it rather than not at all
+                Err(e) => {
+                    tracing::warn!("{e}");
+                    return None;
+                }
+            },
+        };
+
+        Some(stage)
+    }
+}
+
+/// Looks up `luma`, and `rgb` along with it, in `table`.
+fn tone_stage(table: [u8; 256]) -> impl Stage {
+    move |luma: &mut GrayImage, rgb: Option<&mut RgbImage>| {
+        apply_table(&table, luma);
+        if let Some(rgb) = rgb {
+            apply_table(&table, rgb);
+        }
+    }
+}
+
+/// A step of a [`FilterChain`] that works on the whole picture, and can
+/// remember the frames before. Every [`Filter`] makes one, see
+/// [`Filter::stage`], and stages of your own are added with
+/// [`FilterChain::push`], which is how filters outside of this crate get
+/// in.
+pub trait Stage: Send {
+    /// Filters `luma`, which the characters are picked from, and `rgb`
+    /// along with it when the picture is shown in color.
+    fn apply(&mut self, luma: &mut GrayImage, rgb: Option<&mut RgbImage>);
+}
+
+impl<F> Stage for F
+where
+    F: FnMut(&mut GrayImage, Option<&mut RgbImage>) + Send,
+{
+    fn apply(&mut self, luma: &mut GrayImage, rgb: Option<&mut RgbImage>) {
+        self(luma, rgb)
+    }
+}
+
+/// [`Filter`]s applied one after the other, each run of the ones that are
+/// [`Filter::is_tone`] looked up in a single [`tone_table`]. The stages
+/// that remember the frames before keep doing so for as long as the chain
+/// is kept.
+#[derive(Default)]
+pub struct FilterChain {
+    filters: Vec<Filter>,
+    stages: Vec<Box<dyn Stage>>,
+}
+
+impl FilterChain {
+    pub fn new(filters: &[Filter]) -> Self {
+        let mut chain = Self {
+            filters: filters.to_vec(),
+            stages: Vec::new(),
+        };
+
+        for run in filters.chunk_by(|a, b| a.is_tone() && b.is_tone()) {
+            match run {
+                [filter, ..] if filter.is_tone() => chain.push(tone_stage(tone_table(run))),
+                [filter] => {
+                    if let Some(stage) = filter.stage() {
+                        chain.stages.push(stage);
+                    }
+                }
+                // chunk_by only makes runs of tone filters longer than one
+                _ => (),
+            }
+        }
+
+        chain
+    }
+
+    /// The filters the chain was made from, without the stages pushed on
+    /// their own.
+    pub fn filters(&self) -> &[Filter] {
+        &self.filters
+    }
+
+    /// Adds a stage of your own to the end of the chain.
+    pub fn push(&mut self, stage: impl Stage + 'static) {
+        self.stages.push(Box::new(stage));
+    }
+
+    /// Runs every stage in order, see [`Stage::apply`].
+    pub fn apply(&mut self, luma: &mut GrayImage, mut rgb: Option<&mut RgbImage>) {
+        for stage in &mut self.stages {
+            stage.apply(luma, rgb.as_deref_mut());
+        }
+    }
+}
+
+// the most a channel is scaled by the gray world balance, so a picture that
+// really is mostly one color isn't turned gray
+const MAX_CHANNEL_GAIN: f32 = 2.0;
+
+/// Balances the colors of `img` in software, for cameras without a white
+/// balance control: the gray world assumption is that a scene averages out to
+/// gray, so each channel is scaled to have the same mean.
+pub fn gray_world(img: &mut RgbImage) {
+    let mut sums = [0u64; 3];
+    for pixel in img.pixels() {
+        for (sum, &value) in sums.iter_mut().zip(pixel.0.iter()) {
+            *sum += value as u64;
+        }
+    }
+    if sums.contains(&0) {
+        return;
+    }
+
+    let gray = sums.iter().sum::<u64>() as f32 / 3.0;
+    let gains = sums.map(|sum| (gray / sum as f32).clamp(1.0 / MAX_CHANNEL_GAIN, MAX_CHANNEL_GAIN));
+    for pixel in img.pixels_mut() {
+        for (value, gain) in pixel.0.iter_mut().zip(gains) {
+            *value = (*value as f32 * gain).min(255.0) as u8;
+        }
+    }
+}
+
+impl FromStr for Filter {
+    type Err = Report;
+
+    /// Parses `invert`, `edges`, `crt` or `brightness`, `contrast`, `gain`
+    /// or `gamma=<n>`, or `denoise`, `toon` or `vignette` with
+    /// an optional `=<n>`. With the `plugins` feature also
+    /// `plugin=<name>[:<args>]`.
+    fn from_str(s: &str) -> Result<Self> {
+        let (name, value) = match s.split_once('=') {
+            Some((name, value)) => (name, Some(value)),
+            None => (s, None),
+        };
+
+        let filter = match (name, value) {
+            ("invert", None) => Filter::Invert,
+            ("brightness", Some(v)) => Filter::Brightness(v.parse()?),
+            ("contrast", Some(v)) => Filter::Contrast(v.parse()?),
+            ("gain", Some(v)) => Filter::Gain(v.parse()?),
+            ("gamma", Some(v)) => Filter::Gamma(v.parse()?),
+            ("denoise", v) => Filter::Denoise(v.map_or(Ok(DENOISE_STRENGTH), str::parse)?),
+            ("toon", v) => Filter::Toon(v.map_or(Ok(TOON_LEVELS), str::parse)?),
+            ("vignette", v) => Filter::Vignette(v.map_or(Ok(VIGNETTE_STRENGTH), str::parse)?),
+            ("edges", None) => Filter::Edges,
+            ("crt", None) => Filter::Crt,
+            #[cfg(all(feature = "plugins", not(target_arch = "wasm32")))]
+            ("plugin", Some(v)) => Filter::Plugin(v.parse()?),
+            _ => return Err(eyre!("Unknown filter '{s}', see --help for the filters")),
+        };
+
+        filter.validate()?;
+        Ok(filter)
+    }
+}
+
+// how far a pixel has to move from its average to count as motion, which
+// is shown right away instead of smeared
+const MOTION_THRESHOLD: u8 = 40;
+
+/// Averages every pixel with itself in the frames before, so the noise of
+/// a camera turned up to high gain in the dark doesn't make the characters
+/// boil. Pixels that change by a lot are taken as they are, so that
+/// movement doesn't leave trails.
+#[derive(Debug, Default)]
+pub struct TemporalDenoiser {
+    // the running average of every pixel, in 256ths
+    average: Vec<u16>,
+}
+
+impl TemporalDenoiser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replaces every pixel of `img` with its running average. `strength`
+    /// is how much of the average is kept each frame, from 0 for none to
+    /// 1 for a picture that never changes, the average starts over when
+    /// the size changes.
+    pub fn apply(&mut self, img: &mut GrayImage, strength: f32) {
+        if self.average.len() != img.len() {
+            self.average = img.iter().map(|&v| (v as u16) << 8).collect();
+            return;
+        }
+
+        let keep = (strength.clamp(0.0, 1.0) * 256.0) as u32;
+        for (average, v) in self.average.iter_mut().zip(img.iter_mut()) {
+            let current = (*average >> 8) as u8;
+            if current.abs_diff(*v) > MOTION_THRESHOLD {
+                *average = (*v as u16) << 8;
+                continue;
+            }
+
+            let blended = (*average as u32 * keep + ((*v as u32) << 8) * (256 - keep)) >> 8;
+            *average = blended as u16;
+            *v = ((blended + 128) >> 8).min(255) as u8;
+        }
+    }
+}
+
+/// Darkens `img` towards its edges, the corners by `strength`, from 0 for
+/// not at all to 1 for black, falling off with the square of the distance
+/// from the center. Draws the eye to the middle and away from whatever is
+/// lying around at the edges of the frame.
+pub fn vignette<P: Pixel<Subpixel = u8>>(img: &mut ImageBuffer<P, Vec<u8>>, strength: f32) {
+    let (width, height) = img.dimensions();
+    let (cx, cy) = (width as f32 / 2.0, height as f32 / 2.0);
+    let strength = strength.clamp(0.0, 1.0);
+
+    for (x, y, pixel) in img.enumerate_pixels_mut() {
+        let (dx, dy) = ((x as f32 + 0.5 - cx) / cx, (y as f32 + 0.5 - cy) / cy);
+        // 1 in the corners
+        let distance = (dx * dx + dy * dy) / 2.0;
+        let scale = 1.0 - strength * distance;
+        for v in pixel.channels_mut() {
+            *v = (*v as f32 * scale) as u8;
+        }
+    }
+}
+
+// how sharp a change in brightness has to be to be outlined, in the sum of
+// the horizontal and vertical Sobel gradients
+const EDGE_THRESHOLD: i32 = 320;
+
+/// The sum of the horizontal and vertical Sobel gradients of every pixel
+/// of `luma`, 0 along its border.
+fn sobel(luma: &GrayImage) -> Vec<i32> {
+    let (width, height) = (luma.width() as usize, luma.height() as usize);
+    let mut gradients = vec![0; width * height];
+    for y in 1..height.saturating_sub(1) {
+        for x in 1..width.saturating_sub(1) {
+            let at = |dx: usize, dy: usize| luma.as_raw()[(y + dy - 1) * width + x + dx - 1] as i32;
+            let gx = at(2, 0) + 2 * at(2, 1) + at(2, 2) - at(0, 0) - 2 * at(0, 1) - at(0, 2);
+            let gy = at(0, 2) + 2 * at(1, 2) + at(2, 2) - at(0, 0) - 2 * at(1, 0) - at(2, 0);
+            gradients[y * width + x] = gx.abs() + gy.abs();
+        }
+    }
+
+    gradients
+}
+
+/// Replaces `luma` with its edges, as bright as they are sharp, on black,
+/// so only the outlines of things are drawn. `rgb` keeps its colors along
+/// the edges and goes dark everywhere else.
+pub fn edges(luma: &mut GrayImage, rgb: Option<&mut RgbImage>) {
+    let gradients = sobel(luma);
+    // a quarter of the sharpest edge there can be is already white
+    for (v, &gradient) in luma.iter_mut().zip(&gradients) {
+        *v = (gradient / 4).min(255) as u8;
+    }
+    if let Some(rgb) = rgb {
+        for (pixel, &v) in rgb.pixels_mut().zip(luma.iter()) {
+            *pixel = pixel.map(|c| ((c as u16 * v as u16) / 255) as u8);
+        }
+    }
+}
+
+/// Makes `luma`, and `rgb` along with it, look like a cartoon: shading is
+/// flattened to `levels` tones, at least 2, and sharp edges like the
+/// outline of a face are drawn over it at full brightness, in the densest
+/// character of the charset. At the size of a terminal that reads better
+/// than every shade of the picture does.
+pub fn toon(luma: &mut GrayImage, rgb: Option<&mut RgbImage>, levels: u8) {
+    let outline: Vec<bool> = sobel(luma)
+        .into_iter()
+        .map(|gradient| gradient > EDGE_THRESHOLD)
+        .collect();
+
+    let steps = levels.max(2) as u32 - 1;
+    let posterize = |v: u8| ((v as u32 * steps + 127) / 255 * 255 / steps) as u8;
+    for (v, &edge) in luma.iter_mut().zip(&outline) {
+        *v = if edge { 255 } else { posterize(*v) };
+    }
+    if let Some(rgb) = rgb {
+        for (pixel, &edge) in rgb.pixels_mut().zip(&outline) {
+            *pixel = if edge {
+                Rgb([255; 3])
+            } else {
+                pixel.map(posterize)
+            };
+        }
+    }
+}
+
+/// What shows where a [`ChromaKey`] keyed out the picture.
+#[derive(Debug, Clone)]
+pub enum Background {
+    Black,
+    Color(Rgb<u8>),
+    /// Stretched over the whole picture.
+    Image(Arc<RgbImage>),
+}
+
+/// Replaces everything of about one color, like a green screen, with a
+/// [`Background`].
+#[derive(Debug, Clone)]
+pub struct ChromaKey {
+    /// The color keyed out.
+    pub key: Rgb<u8>,
+    /// How far from the key color, from 0 to 1 in its hue and saturation
+    /// alone, a pixel is still replaced completely.
+    pub similarity: f32,
+    /// How much further than `similarity` the background is blended in
+    /// less and less, so the edges of what is in front aren't jagged.
+    pub blend: f32,
+    pub background: Background,
+}
+
+impl ChromaKey {
+    pub fn apply(&self, img: &mut RgbImage) {
+        let (width, height) = img.dimensions();
+        let key = chroma(self.key);
+
+        for (x, y, pixel) in img.enumerate_pixels_mut() {
+            let (cb, cr) = chroma(*pixel);
+            let distance = ((cb - key.0).powi(2) + (cr - key.1).powi(2)).sqrt();
+            // how much of the background shows, 1 inside the similarity
+            let alpha = if distance <= self.similarity {
+                1.0
+            } else if distance < self.similarity + self.blend {
+                1.0 - (distance - self.similarity) / self.blend
+            } else {
+                continue;
+            };
+
+            let background = match &self.background {
+                Background::Black => Rgb([0; 3]),
+                Background::Color(color) => *color,
+                Background::Image(image) => *image.get_pixel(
+                    (x as u64 * image.width() as u64 / width as u64) as u32,
+                    (y as u64 * image.height() as u64 / height as u64) as u32,
+                ),
+            };
+            for (v, b) in pixel.0.iter_mut().zip(background.0) {
+                *v = (*v as f32 * (1.0 - alpha) + b as f32 * alpha).round() as u8;
+            }
+        }
+    }
+}
+
+/// The blue and red difference of `color`, from -0.5 to 0.5, which leaves
+/// out how bright it is so shadows on the screen are keyed out too.
+fn chroma(color: Rgb<u8>) -> (f32, f32) {
+    let [r, g, b] = color.0.map(|c| c as f32 / 255.0);
+    (
+        -0.168_736 * r - 0.331_264 * g + 0.5 * b,
+        0.5 * r - 0.418_688 * g - 0.081_312 * b,
+    )
+}
+
+/// A color cast laid over the picture in the color modes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Tint {
+    /// The brown of old photographs.
+    Sepia,
+    /// The blue of cyanotype prints, from a deep blue in the shadows to
+    /// nearly white.
+    Cyanotype,
+    /// Red, green and blue each multiplied by their own factor.
+    Multiply([f32; 3]),
+}
+
+// the shadows and highlights of a cyanotype
+const CYANOTYPE_DARK: [f32; 3] = [8.0, 38.0, 82.0];
+const CYANOTYPE_LIGHT: [f32; 3] = [220.0, 236.0, 250.0];
+
+impl Tint {
+    /// The tint after `tint` when cycling through them with a key, which
+    /// after the presets is none again.
+    pub fn next(tint: Option<Tint>) -> Option<Tint> {
+        match tint {
+            None => Some(Tint::Sepia),
+            Some(Tint::Sepia) => Some(Tint::Cyanotype),
+            Some(_) => None,
+        }
+    }
+
+    pub fn apply(self, img: &mut RgbImage) {
+        for pixel in img.pixels_mut() {
+            let [r, g, b] = pixel.0.map(|c| c as f32);
+            let out = match self {
+                Tint::Sepia => [
+                    0.393 * r + 0.769 * g + 0.189 * b,
+                    0.349 * r + 0.686 * g + 0.168 * b,
+                    0.272 * r + 0.534 * g + 0.131 * b,
+                ],
+                Tint::Cyanotype => {
+                    let level = (0.299 * r + 0.587 * g + 0.114 * b) / 255.0;
+                    [0, 1, 2].map(|i| {
+                        CYANOTYPE_DARK[i] + (CYANOTYPE_LIGHT[i] - CYANOTYPE_DARK[i]) * level
+                    })
+                }
+                Tint::Multiply([mr, mg, mb]) => [r * mr, g * mg, b * mb],
+            };
+            pixel.0 = out.map(|c| c.clamp(0.0, 255.0) as u8);
+        }
+    }
+}
+
+impl FromStr for Tint {
+    type Err = Report;
+
+    /// Parses `sepia`, `cyanotype` or the factors to multiply red, green
+    /// and blue by, like `1.2,1,0.8`.
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "sepia" => return Ok(Tint::Sepia),
+            "cyanotype" => return Ok(Tint::Cyanotype),
+            _ => (),
+        }
+
+        let factors = s
+            .split(',')
+            .map(|f| f.trim().parse::<f32>())
+            .collect::<Result<Vec<_>, _>>();
+        match factors.as_deref() {
+            Ok(&[r, g, b]) if [r, g, b].iter().all(|f| f.is_finite() && *f >= 0.0) => {
+                Ok(Tint::Multiply([r, g, b]))
+            }
+            _ => Err(eyre!(
+                "Unknown tint '{s}', expected sepia, cyanotype or factors like 1.2,1,0.8"
+            )),
+        }
+    }
+}
+
+// how much of its brightness every other row keeps, out of 256
+const SCANLINE_LEVEL: u16 = 160;
+
+/// The green of an old monochrome monitor's phosphor.
+pub const PHOSPHOR: Rgb<u8> = Rgb([51, 255, 102]);
+
+/// Makes `img` look like it's on an old CRT: every other row is darker,
+/// like the gaps between the scanlines, and every pixel smears a little
+/// into the one right of it, like the beam did.
+pub fn crt<P: Pixel<Subpixel = u8>>(img: &mut ImageBuffer<P, Vec<u8>>) {
+    let channels = P::CHANNEL_COUNT as usize;
+    let row = img.width() as usize * channels;
+    if row == 0 {
+        return;
+    }
+
+    for (y, line) in img.chunks_exact_mut(row).enumerate() {
+        // right to left, so every pixel takes from the left one before it
+        // was smeared itself
+        for i in (channels..row).rev() {
+            line[i] = ((line[i] as u16 * 3 + line[i - channels] as u16) / 4) as u8;
+        }
+        if y % 2 == 1 {
+            for v in line.iter_mut() {
+                *v = ((*v as u16 * SCANLINE_LEVEL) >> 8) as u8;
+            }
+        }
+    }
+}
+
+/// Colors `rgb` in [`PHOSPHOR`] green, as bright as `luma` is.
+pub fn phosphor(rgb: &mut RgbImage, luma: &GrayImage) {
+    for (color, gray) in rgb.pixels_mut().zip(luma.pixels()) {
+        let level = gray.0[0] as u16;
+        *color = PHOSPHOR.map(|c| ((c as u16 * level) / 255) as u8);
+    }
+}
@@ -0,0 +1,188 @@
+//! Measuring how long a change on the screen takes to come back through the
+//! camera, by flashing the terminal between black and white at a camera
+//! aimed at it.
+
+use crate::capture::{CaptureSource, Frame};
+use crate::convert::decode_frame;
+use crossterm::event::{self, Event, KeyCode, KeyEvent};
+use crossterm::style::{Color, ResetColor, SetBackgroundColor};
+use crossterm::{cursor, execute, terminal};
+use eyre::{eyre, Result};
+use std::fmt;
+use std::io::{stdout, Write};
+use std::time::{Duration, Instant};
+
+// how long the screen stays black, and then white, before their brightness
+// is measured, for the camera's auto exposure to settle
+const SETTLE: Duration = Duration::from_secs(2);
+// how long the screen stays put between flashes, so every flash starts from
+// a picture the camera has caught up with
+const REST: Duration = Duration::from_millis(300);
+// a flash the camera hasn't seen after this long is counted as missed
+const FLASH_TIMEOUT: Duration = Duration::from_secs(2);
+// how far apart the brightness of black and white has to be, out of 255,
+// to tell them apart through noise
+const MIN_CONTRAST: f32 = 20.0;
+
+/// The latencies of the flashes the camera saw.
+#[derive(Debug, Clone, Default)]
+pub struct LatencyStats {
+    /// From the terminal changing color to a frame showing it, in the order
+    /// of the flashes.
+    pub samples: Vec<Duration>,
+    /// Flashes the camera didn't see in time.
+    pub missed: u32,
+}
+
+impl LatencyStats {
+    /// The sample `percent` of the way from the fastest to the slowest.
+    pub fn percentile(&self, percent: usize) -> Option<Duration> {
+        let mut sorted = self.samples.clone();
+        sorted.sort_unstable();
+        let index = (sorted.len() * percent / 100).min(sorted.len().checked_sub(1)?);
+        Some(sorted[index])
+    }
+
+    pub fn mean(&self) -> Option<Duration> {
+        let total: Duration = self.samples.iter().sum();
+        Some(total / u32::try_from(self.samples.len()).ok().filter(|&n| n > 0)?)
+    }
+}
+
+impl fmt::Display for LatencyStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ms = |latency: Option<Duration>| latency.unwrap_or_default().as_millis();
+        if self.samples.is_empty() {
+            return write!(f, "the camera saw none of the {} flashes", self.missed);
+        }
+
+        write!(
+            f,
+            "{} flashes: min {}ms, median {}ms, mean {}ms, p95 {}ms, max {}ms, {} missed",
+            self.samples.len(),
+            ms(self.percentile(0)),
+            ms(self.percentile(50)),
+            ms(self.mean()),
+            ms(self.percentile(95)),
+            ms(self.percentile(100)),
+            self.missed
+        )
+    }
+}
+
+/// Keeps the terminal in raw mode on the alternate screen until dropped.
+struct Screen;
+
+impl Screen {
+    fn enter() -> Result<Self> {
+        terminal::enable_raw_mode()?;
+        execute!(stdout(), terminal::EnterAlternateScreen, cursor::Hide)?;
+        Ok(Self)
+    }
+
+    /// Turns the whole terminal `color`.
+    fn fill(&self, color: Color) -> Result<()> {
+        execute!(
+            stdout(),
+            SetBackgroundColor(color),
+            terminal::Clear(terminal::ClearType::All)
+        )?;
+        Ok(())
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        let _ = execute!(
+            stdout(),
+            ResetColor,
+            cursor::Show,
+            terminal::LeaveAlternateScreen
+        );
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// Flashes the terminal `flashes` times, each time from black to white or
+/// back, and times how long `source` takes to deliver a frame that shows
+/// it. The camera has to be aimed at the terminal, filling as much of its
+/// picture as it can. `q` stops early.
+///
+/// The time includes the terminal drawing the color, the screen showing
+/// it, the camera exposing, compressing and sending the frame, the frames
+/// queued up before it, and decoding it.
+pub fn measure(source: &mut dyn CaptureSource, flashes: u32) -> Result<LatencyStats> {
+    let screen = Screen::enter()?;
+
+    screen.fill(Color::Black)?;
+    let black = settle(source, SETTLE)?;
+    screen.fill(Color::White)?;
+    let white = settle(source, SETTLE)?;
+    if white - black < MIN_CONTRAST {
+        return Err(eyre!(
+            "The camera barely sees the screen flash (black {black:.0}, white {white:.0}), \
+             aim it at the terminal"
+        ));
+    }
+    let threshold = (black + white) / 2.0;
+
+    let mut stats = LatencyStats::default();
+    let mut lit = true;
+    for _ in 0..flashes {
+        settle(source, REST)?;
+        if quit_pressed()? {
+            break;
+        }
+
+        lit = !lit;
+        screen.fill(if lit { Color::White } else { Color::Black })?;
+        let flashed = Instant::now();
+
+        loop {
+            let level = brightness(&source.next_frame()?)?;
+            if (level > threshold) == lit {
+                stats.samples.push(flashed.elapsed());
+                break;
+            }
+            if flashed.elapsed() > FLASH_TIMEOUT {
+                stats.missed += 1;
+                break;
+            }
+        }
+    }
+
+    Ok(stats)
+}
+
+/// Keeps capturing for `duration` and returns the brightness of the last
+/// frame.
+fn settle(source: &mut dyn CaptureSource, duration: Duration) -> Result<f32> {
+    let started = Instant::now();
+    loop {
+        let level = brightness(&source.next_frame()?)?;
+        if started.elapsed() >= duration {
+            return Ok(level);
+        }
+    }
+}
+
+/// The average brightness of `frame`, from 0 to 255.
+fn brightness(frame: &Frame) -> Result<f32> {
+    let img = decode_frame(frame)?;
+    let total: u64 = img.iter().map(|&v| v as u64).sum();
+
+    Ok(total as f32 / img.len().max(1) as f32)
+}
+
+fn quit_pressed() -> Result<bool> {
+    while event::poll(Duration::ZERO)? {
+        if let Event::Key(KeyEvent {
+            code: KeyCode::Char('q') | KeyCode::Esc,
+            ..
+        }) = event::read()?
+        {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
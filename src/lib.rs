@@ -0,0 +1,73 @@
+//! asciicam turns webcam frames into ascii art.
+//!
+//! The crate is split into four stages that can be used on their own:
+//!
+//! - [`capture`] produces frames, from a v4l device or any other
+//!   [`CaptureSource`]
+//! - [`convert`] decodes and resizes a buffer into a [`GrayImage`](image::GrayImage)
+//! - [`render`] maps the grayscale pixels onto a charset and writes them out
+//! - [`sink`] sends rendered frames to the terminal, files and other outputs
+//!
+//! [`AsciicamBuilder`] ties the stages together into the interactive viewer
+//! the `asciicam` binary runs.
+//!
+//! ```no_run
+//! use asciicam::{capture::Camera, convert::CameraBuffer, render::write_image_buffer};
+//!
+//! # fn main() -> eyre::Result<()> {
+//! let mut cam = Camera::open(0)?;
+//! let (width, height) = (cam.width(), cam.height());
+//! let frame = CameraBuffer::get_cam(CameraBuffer {
+//!     stream_buf: cam.next_buffer()?,
+//!     src_width: width,
+//!     src_height: height,
+//!     dst_width: 80,
+//!     dst_height: 24,
+//! })?;
+//! write_image_buffer(&frame, &mut std::io::stdout())?;
+//! # Ok(())
+//! # }
+//! ```
+
+// the viewer needs a terminal, which the browser doesn't have
+#[cfg(not(target_arch = "wasm32"))]
+pub mod app;
+pub mod batch;
+// there is no clock to time anything with in the browser
+#[cfg(not(target_arch = "wasm32"))]
+pub mod bench;
+pub mod broadcast;
+pub mod capture;
+pub mod convert;
+pub mod exposure;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod filter;
+// flashes the terminal
+#[cfg(not(target_arch = "wasm32"))]
+pub mod latency;
+pub mod metrics;
+pub mod motion;
+// dlopen, which the browser doesn't have
+#[cfg(all(feature = "plugins", not(target_arch = "wasm32")))]
+pub mod plugin;
+#[cfg(feature = "python")]
+mod python;
+pub mod render;
+// reads scripts from files
+#[cfg(all(feature = "script", not(target_arch = "wasm32")))]
+pub mod script;
+pub mod sink;
+pub mod stabilize;
+pub mod stats;
+pub mod systemd;
+#[cfg(all(feature = "vaapi", not(target_arch = "wasm32")))]
+mod vaapi;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use app::{App, AsciicamBuilder, FrameStats};
+pub use capture::{Camera, CaptureSource, Frame, FrameMeta};
+pub use convert::CameraBuffer;
+pub use render::{write_image_buffer, CharArr, CharLut};
@@ -1,218 +1,1612 @@
-use crossterm::execute;
-use crossterm::{
-    cursor,
-    event::{poll, read, Event, KeyCode, KeyEvent},
-    terminal,
+use asciicam::app::{AsciicamBuilder, Comparison, Corner};
+use asciicam::batch::{expand_glob, Converter};
+use asciicam::bench::{measure, Combination, Fixture};
+use asciicam::broadcast::RenderSettings;
+use asciicam::capture::{
+    open_input, BackgroundSource, Camera, CaptureSource, Controls, Deinterlace, DeinterlacedSource,
+    InputFormat, IoMode, PipeSource, SlideshowSource, FOCUS, POWER_LINE_FREQUENCY, WHITE_BALANCE,
+    ZOOM,
 };
-use eyre::{eyre, Result};
-use fast_image_resize as fr;
-use image::GrayImage;
-use std::fs::File;
-use std::io::{stdout, Write};
-use std::num::NonZeroU32;
-use v4l::{
-    buffer::Type, io::mmap::Stream, io::traits::CaptureStream, video::Capture, Device, FourCC,
+use asciicam::convert::{LevelWindow, LumaWeights};
+use asciicam::exposure::{MeterRegion, DEFAULT_TARGET};
+use asciicam::filter::{Background, ChromaKey, Filter, Tint};
+use asciicam::metrics::Metrics;
+use asciicam::render::{
+    parse_charset, ColorMode, FitMode, RenderMode, RenderOptions, CHARSETS, DEFAULT_CHARSET,
+    NIGHT_CHARSET,
 };
+#[cfg(target_os = "linux")]
+use asciicam::sink::LoopbackSink;
+#[cfg(unix)]
+use asciicam::sink::{default_socket_path, UnixSink};
+use asciicam::sink::{FixedSize, RecordSink, Sink, TcpSink, WebSocketSink, WsFormat};
+use asciicam::stats::StatsLog;
+use asciicam::systemd::{self, Watchdog};
+use clap::{ArgAction, Args, Parser, Subcommand};
+use crossterm::event::{self, Event, KeyCode, KeyEvent};
+use crossterm::terminal;
+use eyre::{eyre, Result};
+use image::Rgb;
+use std::env;
+use std::fs;
+#[cfg(unix)]
+use std::fs::OpenOptions;
+use std::io::{self, IsTerminal, Write};
+#[cfg(unix)]
+use std::net::Shutdown;
+use std::net::TcpListener;
+use std::ops::ControlFlow;
+#[cfg(unix)]
+use std::os::unix::{net::UnixStream, process::CommandExt};
+use std::path::{Path, PathBuf};
+#[cfg(unix)]
+use std::process::{self, Stdio};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing_subscriber::fmt::format::FmtSpan;
+use tracing_subscriber::EnvFilter;
+
+// what --night multiplies the brightness by without a gain given
+const NIGHT_GAIN: f32 = 4.0;
+// how far --hysteresis lets a cell leave its char without a margin given
+const HYSTERESIS_MARGIN: u8 = 6;
+// how often `convert --watch` looks for new files
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+// how long `daemon` waits for the detached process to start listening
+#[cfg(unix)]
+const DAEMON_STARTUP: Duration = Duration::from_secs(10);
+
+/// An ascii webcam in your console.
+#[derive(Parser)]
+#[command(version, about, args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[command(flatten)]
+    view: ViewCommand,
+
+    /// Log what asciicam is doing, -vv for more and -vvv for every frame.
+    /// RUST_LOG takes precedence
+    #[arg(short, long, action = ArgAction::Count, global = true)]
+    verbose: u8,
 
-struct CharArr<'c> {
-    charset: &'c [char],
-    pixel: u8,
+    /// Append the log to this file instead of writing it to stderr, which
+    /// the viewer leaves alone while it draws on the terminal. Where a
+    /// detached daemon's errors go too
+    #[arg(long, alias = "log", value_name = "PATH", global = true)]
+    log_file: Option<PathBuf>,
 }
 
-struct CameraBuffer<'b> {
-    stream_buf: &'b [u8],
-    src_width: u32,
-    src_height: u32,
-    dst_width: u32,
-    dst_height: u32,
+#[derive(Subcommand)]
+enum Command {
+    /// View the camera or an input file, the default when no command is given
+    View(ViewCommand),
+    /// Convert image files to text files, or keep converting the images
+    /// dropped into a folder
+    Convert(ConvertArgs),
+    /// Stream the camera to clients over the network
+    Serve(ServeArgs),
+    /// Keep capturing and serving in the background, without a terminal
+    Daemon(DaemonArgs),
+    /// Watch a running daemon in this terminal, q detaches again
+    Attach(AttachArgs),
+    /// List or change the camera's controls, like exposure and gain
+    Controls(ControlsArgs),
+    /// Time decoding, resizing, rendering and writing every render mode and
+    /// charset on canned frames, without a camera or terminal
+    Bench(BenchArgs),
+    /// Measure how long the screen takes to show up through the camera, by
+    /// flashing the terminal at a camera aimed at it
+    Latency(LatencyArgs),
 }
 
-impl<'b> CameraBuffer<'b> {
-    fn get_cam(buff: Self) -> Result<GrayImage> {
-        let decoder =
-            mozjpeg::Decompress::with_markers(mozjpeg::ALL_MARKERS).from_mem(buff.stream_buf)?;
-        let mut img = decoder.grayscale()?;
+#[derive(Args)]
+struct ViewArgs {
+    /// Render a file or stream instead of the camera: an image, gif, video,
+    /// http:// or rtsp:// url, `libcamera[:N[:WxH]]`, `depth[:N[:NEAR-FAR]]`,
+    /// `pipewire`, `plugin:NAME[:ARGS]`, or `-` for frames on stdin. A
+    /// directory or a pattern like `photos/*.jpg` shows its images as a
+    /// slideshow
+    #[arg(short, long)]
+    input: Option<String>,
 
-        let raw_pixels = match img.read_scanlines() {
-            None => {
-                return Err(eyre!("Could not decompress image"));
-            }
-            Some(v) => v,
-        };
+    /// Seconds each image of a slideshow is shown for
+    #[arg(long, value_name = "SECS", default_value_t = 5.0)]
+    slide_interval: f64,
 
-        img.finish_decompress();
+    /// Layout of frames read from stdin: mjpeg, gray8:WxH or rgb8:WxH
+    #[arg(long, default_value = "mjpeg")]
+    input_format: InputFormat,
 
-        let src_frame = fr::Image::from_vec_u8(
-            match NonZeroU32::new(buff.src_width) {
-                None => {
-                    return Err(eyre!("Could not create NonZeroU32"));
-                }
-                Some(v) => v,
-            },
-            match NonZeroU32::new(buff.src_height) {
-                None => {
-                    return Err(eyre!("Could not create NonZeroU32"));
-                }
-                Some(v) => v,
-            },
-            raw_pixels,
-            fr::PixelType::U8,
-        )?;
+    /// Capture the X11 screen instead of the camera, optionally just a
+    /// region like 800x600+100+50
+    #[arg(long, value_name = "REGION", conflicts_with = "input")]
+    screen: Option<Option<String>>,
 
-        let dst_width = match NonZeroU32::new(buff.dst_width) {
-            None => {
-                return Err(eyre!("Could not create NonZeroU32"));
-            }
-            Some(v) => v,
+    /// Render the frames of a GStreamer pipeline like for gst-launch-1.0,
+    /// e.g. "v4l2src device=/dev/video2 ! jpegdec"
+    #[arg(long, value_name = "PIPELINE", conflicts_with_all = ["input", "screen"])]
+    gst: Option<String>,
+
+    /// Show the picture twice side by side, the right half with this charset
+    #[arg(long, value_name = "CHARSET")]
+    compare_charset: Option<String>,
+
+    /// Show the picture twice side by side, the right half in this render
+    /// mode
+    #[arg(long, value_name = "MODE")]
+    compare_mode: Option<RenderMode>,
+
+    /// Show the picture twice side by side, the right half with these
+    /// filters instead of the --filter ones
+    #[arg(long = "compare-filter", value_name = "FILTER")]
+    compare_filters: Vec<Filter>,
+
+    /// Draw a second source small in a corner: a device index, `screen` or
+    /// `screen:REGION`, or anything --input takes
+    #[arg(long, value_name = "SOURCE")]
+    pip: Option<String>,
+
+    /// Corner the --pip inset starts in: top-left, top-right, bottom-left or
+    /// bottom-right
+    #[arg(long, default_value = "bottom-right")]
+    pip_corner: Corner,
+
+    /// Deinterlace the --pip source: bob or weave
+    #[arg(long, value_name = "MODE", requires = "pip")]
+    pip_deinterlace: Option<Deinterlace>,
+
+    /// Index of the v4l device to capture from, /dev/videoN
+    #[arg(short, long, default_value_t = 0)]
+    device: usize,
+
+    /// Seconds to keep retrying when the device is busy or not there yet,
+    /// e.g. when started at boot
+    #[arg(long, value_name = "SECS", default_value_t = 0)]
+    wait: u64,
+
+    /// Focus the camera by hand at this distance, focus_absolute in its
+    /// own units, or `auto` for autofocus
+    #[arg(long, value_name = "VALUE|auto")]
+    focus: Option<ControlValue>,
+
+    /// Optical zoom of the camera, zoom_absolute in its own units
+    #[arg(long, value_name = "VALUE")]
+    optical_zoom: Option<i64>,
+
+    /// White balance of the camera as a color temperature in kelvin, `auto`,
+    /// or `gray-world` to balance the colors in software. Cameras without
+    /// the control get `gray-world` either way
+    #[arg(long, value_name = "KELVIN|auto|gray-world")]
+    white_balance: Option<WhiteBalance>,
+
+    /// Mains frequency of the lights, 50 or 60, so the camera filters out
+    /// the banding they cause, or `off` or `auto`
+    #[arg(long, value_name = "HZ", value_parser = parse_power_line)]
+    power_line: Option<i64>,
+
+    /// Keep the picture at a mean brightness from 0 to 255, 110 by default,
+    /// by adjusting exposure and gain, for cameras whose own automatic
+    /// exposure is missing or no good
+    #[arg(long, value_name = "BRIGHTNESS")]
+    auto_exposure: Option<Option<u8>>,
+
+    /// Measure the brightness for --auto-exposure in this part of the
+    /// picture, in percent like 40x60+30+20, e.g. where the face is in front
+    /// of a bright window
+    #[arg(long, value_name = "REGION", requires = "auto_exposure")]
+    meter: Option<MeterRegion>,
+
+    /// Rhai script run on every frame, a file or the name of one in
+    /// $XDG_CONFIG_HOME/asciicam/scripts, needs --features script
+    #[arg(long, value_name = "SCRIPT")]
+    script: Option<String>,
+
+    /// File pan/tilt/zoom presets are saved to with alt+1-9, by default
+    /// $XDG_CONFIG_HOME/asciicam/presets
+    #[arg(long, value_name = "PATH")]
+    presets: Option<PathBuf>,
+
+    /// Capture resolution to ask the device for, e.g. 1280x720
+    #[arg(short, long, value_parser = parse_resolution)]
+    resolution: Option<(u32, u32)>,
+
+    /// Deinterlace the frames of analog capture: bob for frames that comb
+    /// where things move, weave for sources that send every field on its own
+    #[arg(long, value_name = "MODE")]
+    deinterlace: Option<Deinterlace>,
+
+    /// Fade from one camera frame into the next over this many
+    /// milliseconds, with frames made up in between, for cameras that only
+    /// manage a few frames a second in low light
+    #[arg(long, value_name = "WINDOW")]
+    interpolate: Option<u64>,
+
+    /// How the camera hands over its buffers: mmap or userptr
+    #[arg(long, value_name = "MODE", default_value = "mmap")]
+    io_mode: IoMode,
+
+    /// What a raw Bayer sensor reads in the dark, out of 255, taken off
+    /// every pixel
+    #[arg(long, value_name = "LEVEL", default_value_t = 0)]
+    black_level: u8,
+
+    /// Sensor values shown from black to white on 10 to 16 bit sensors,
+    /// e.g. 64-900, by default all of them
+    #[arg(long, value_name = "LOW-HIGH")]
+    window: Option<LevelWindow>,
+
+    /// How color turns into brightness: bt601, bt709 or red, green and blue
+    /// weights like 0.3,0.6,0.1, by default whatever the decoder does
+    #[arg(long, value_name = "WEIGHTS")]
+    luma: Option<LumaWeights>,
+
+    /// Hold the picture of a handheld or clip-on camera still, cropping a
+    /// little off every side
+    #[arg(long)]
+    stabilize: bool,
+
+    /// Replace everything of about this color with --key-background, like a
+    /// green screen, given as RRGGBB, 00b140 by default
+    #[arg(long, value_name = "COLOR", value_parser = parse_color)]
+    chroma_key: Option<Option<Rgb<u8>>>,
+
+    /// How far from the --chroma-key color, from 0 to 1 in hue and
+    /// saturation, is still replaced completely
+    #[arg(
+        long,
+        value_name = "AMOUNT",
+        default_value_t = 0.1,
+        requires = "chroma_key"
+    )]
+    key_similarity: f32,
+
+    /// How much further than --key-similarity the background is blended in
+    /// less and less, for smooth edges
+    #[arg(
+        long,
+        value_name = "AMOUNT",
+        default_value_t = 0.08,
+        requires = "chroma_key"
+    )]
+    key_blend: f32,
+
+    /// What replaces the --chroma-key color: black, a color as RRGGBB or an
+    /// image file
+    #[arg(
+        long,
+        value_name = "BACKGROUND",
+        default_value = "black",
+        requires = "chroma_key"
+    )]
+    key_background: String,
+
+    /// Color cast laid over the picture in the color modes: sepia,
+    /// cyanotype or factors to multiply red, green and blue by, like
+    /// 1.2,1,0.8
+    #[arg(long)]
+    tint: Option<Tint>,
+
+    /// Show the picture in the green of an old monochrome monitor
+    #[arg(long)]
+    phosphor: bool,
+
+    /// Average the pixels under every cell instead of picking one, so edges
+    /// don't shimmer and small motion is easier to see
+    #[arg(long)]
+    area: bool,
+
+    /// Average the pixels under every cell in linear light instead of
+    /// picking one, keeping thin things like hair and text
+    #[arg(long)]
+    linear: bool,
+
+    /// Characters to map pixels onto, ordered from dark to bright
+    #[arg(short, long)]
+    charset: Option<String>,
+
+    /// Render mode: ascii, half-block, braille, color or matrix
+    #[arg(short, long, default_value = "ascii")]
+    mode: RenderMode,
+
+    /// Color output: mono, 256 or truecolor
+    #[arg(long, default_value = "truecolor")]
+    color: ColorMode,
+
+    /// How to fit the image to the terminal: stretch, fit or fill
+    #[arg(short, long, default_value = "stretch")]
+    fit: FitMode,
+
+    /// Filters applied in order: invert, brightness=<n>, contrast=<n>,
+    /// gain=<n>, gamma=<n>, edges, crt, or denoise, toon or vignette with
+    /// an optional =<n>, or plugin=<name>[:<args>] with
+    /// --features plugins
+    #[arg(long = "filter")]
+    filters: Vec<Filter>,
+
+    /// Keep the character of a cell until its brightness is more than
+    /// MARGIN, 6 by default out of 255, past it, so cells on the edge
+    /// between two characters don't flicker
+    #[arg(long, value_name = "MARGIN")]
+    hysteresis: Option<Option<u8>>,
+
+    /// For a dark room: multiply the brightness by GAIN, 4 by default, and
+    /// leave the darkest levels blank so the noise doesn't show
+    #[arg(long, value_name = "GAIN")]
+    night: Option<Option<f32>>,
+
+    /// Don't mirror the image
+    #[arg(long)]
+    no_mirror: bool,
+
+    /// Always draw every cell, even when the terminal can't keep up with the
+    /// camera and the picture lags behind
+    #[arg(long)]
+    full_quality: bool,
+
+    /// Append the frame rate, CPU time, bytes written to the terminal and
+    /// motion to this file every second, as CSV or as JSON lines when it
+    /// ends in .jsonl
+    #[arg(long, value_name = "PATH")]
+    stats_log: Option<PathBuf>,
+
+    /// Also write the rendered frames to this v4l2loopback device, /dev/videoN
+    #[arg(long, value_name = "N")]
+    loopback: Option<usize>,
+
+    /// Size of the frames written to --loopback
+    #[arg(long, value_parser = parse_resolution, default_value = "1280x720")]
+    loopback_size: (u32, u32),
+
+    /// Publish motion events, snapshots and heartbeats to this MQTT broker,
+    /// mqtt://[user:password@]host[:port]
+    #[arg(long, value_name = "URL")]
+    mqtt: Option<String>,
+
+    /// Prefix of the MQTT topics: PREFIX/motion, /snapshot, /health and
+    /// /status
+    #[arg(long, value_name = "PREFIX", default_value = "asciicam")]
+    mqtt_topic: String,
+
+    /// Seconds between MQTT snapshots, 0 for none
+    #[arg(long, value_name = "SECS", default_value_t = 60)]
+    mqtt_snapshot_interval: u64,
+
+    /// Seconds between MQTT heartbeats
+    #[arg(long, value_name = "SECS", default_value_t = 30)]
+    mqtt_heartbeat: u64,
+
+    /// Fraction of the picture that has to change to count as motion
+    #[arg(long, default_value_t = 0.02)]
+    motion_threshold: f32,
+
+    /// Publish rendered frames on a ZeroMQ PUB socket bound to this
+    /// endpoint, e.g. tcp://*:5556
+    #[arg(long, value_name = "ENDPOINT")]
+    zmq: Option<String>,
+
+    /// Also publish the captured grayscale frames on --zmq
+    #[arg(long)]
+    zmq_raw: bool,
+}
+
+#[derive(Args)]
+struct ViewCommand {
+    #[command(flatten)]
+    view: ViewArgs,
+
+    /// Where the view is drawn: terminal, or none to only feed --record,
+    /// --serve and the other outputs
+    #[arg(long, default_value = "terminal")]
+    output: Output,
+
+    /// Show how many frames were dropped or late in the status bar, and sum
+    /// them up at exit
+    #[arg(long)]
+    stats: bool,
+
+    /// Also record the feed to this file, in the asciicast v2 format that
+    /// `asciinema play` plays back
+    #[arg(long, value_name = "FILE")]
+    record: Option<PathBuf>,
+
+    /// Record at this many columns and rows, e.g. 200x60, instead of the
+    /// size of the view
+    #[arg(long, value_name = "COLSxROWS", value_parser = parse_resolution)]
+    record_size: Option<(u32, u32)>,
+
+    /// Also serve the feed: tcp:ADDR, ws:ADDR, http:ADDR or ssh:ADDR, where
+    /// ADDR can be just a port. Can be given more than once
+    #[arg(long, value_name = "SERVER")]
+    serve: Vec<Server>,
+}
+
+/// Where `view` draws frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Output {
+    Terminal,
+    None,
+}
+
+impl FromStr for Output {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "terminal" => Ok(Output::Terminal),
+            "none" => Ok(Output::None),
+            _ => Err(eyre!("Unknown output '{s}', expected terminal or none")),
+        }
+    }
+}
+
+/// A server streaming the feed, `kind:ADDR` on the command line.
+#[derive(Debug, Clone)]
+enum Server {
+    Tcp(String),
+    Ws(String),
+    Http(String),
+    Ssh(String),
+}
+
+impl FromStr for Server {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (kind, addr) = match s.split_once(':') {
+            Some(server) => server,
+            None => return Err(eyre!("Expected a server like tcp:2323, got '{s}'")),
+        };
+        // a bare port listens on every interface
+        let addr = match addr.parse::<u16>() {
+            Ok(port) => format!("0.0.0.0:{port}"),
+            Err(_) => addr.to_string(),
         };
 
-        let dst_height = match NonZeroU32::new(buff.dst_height) {
-            None => {
-                return Err(eyre!("Could not create NonZeroU32"));
-            }
-            Some(v) => v,
+        match kind {
+            "tcp" => Ok(Server::Tcp(addr)),
+            "ws" => Ok(Server::Ws(addr)),
+            "http" => Ok(Server::Http(addr)),
+            "ssh" => Ok(Server::Ssh(addr)),
+            _ => Err(eyre!(
+                "Unknown server '{kind}', expected one of tcp, ws, http, ssh"
+            )),
+        }
+    }
+}
+
+/// What servers render with, shared by all of them.
+struct ServerConfig {
+    settings: RenderSettings,
+    // for clients that don't report their size
+    size: (u32, u32),
+    ws_format: WsFormat,
+    ssh_password: Option<String>,
+    ssh_host_key: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct ServeArgs {
+    /// Stream ANSI frames to telnet / netcat clients on this address,
+    /// e.g. 0.0.0.0:2323
+    #[arg(long, value_name = "ADDR")]
+    tcp: Option<String>,
+
+    /// Push frames to WebSocket clients on this address, e.g. 0.0.0.0:8080
+    #[arg(long, value_name = "ADDR")]
+    ws: Option<String>,
+
+    /// Serve a page showing the feed in the browser on this address, e.g.
+    /// 0.0.0.0:8080
+    #[arg(long, value_name = "ADDR")]
+    http: Option<String>,
+
+    /// Run an ssh server on this address, e.g. 0.0.0.0:2222. Every session
+    /// is rendered at the size of its terminal
+    #[arg(long, value_name = "ADDR")]
+    ssh: Option<String>,
+
+    /// Password ssh clients have to log in with, anyone can watch without
+    #[arg(long, value_name = "PASSWORD")]
+    ssh_password: Option<String>,
+
+    /// OpenSSH private key for the ssh server, a new one is generated per
+    /// start without it
+    #[arg(long, value_name = "PATH")]
+    ssh_host_key: Option<PathBuf>,
+
+    /// Encoding of WebSocket frames: ansi, or json cell diffs
+    #[arg(long, default_value = "ansi")]
+    ws_format: WsFormat,
+
+    /// Serve Prometheus metrics on http://ADDR/metrics, e.g. 0.0.0.0:9184:
+    /// frames, decode times, clients, dropped frames and reconnects
+    #[arg(long, value_name = "ADDR")]
+    metrics: Option<String>,
+
+    /// Width in characters for clients that don't report their size
+    #[arg(long, default_value_t = 80)]
+    columns: u32,
+
+    /// Height in lines for clients that don't report their size
+    #[arg(long, default_value_t = 24)]
+    rows: u32,
+
+    #[command(flatten)]
+    view: ViewArgs,
+}
+
+#[derive(Args)]
+struct ConvertArgs {
+    /// Images to convert, `*` and `?` in quoted patterns are expanded
+    #[arg(required_unless_present = "watch")]
+    files: Vec<String>,
+
+    /// Keep converting the images that appear in this folder
+    #[arg(short, long, value_name = "DIR", conflicts_with = "files")]
+    watch: Option<PathBuf>,
+
+    /// Folder to write the .txt files to
+    #[arg(short, long, default_value = ".")]
+    out: PathBuf,
+
+    /// Width of the output in characters
+    #[arg(long, default_value_t = 80)]
+    columns: u32,
+
+    /// Height of the output in lines
+    #[arg(long, default_value_t = 40)]
+    rows: u32,
+
+    /// Characters to map pixels onto, ordered from dark to bright
+    #[arg(short, long)]
+    charset: Option<String>,
+
+    /// Render mode: ascii, half-block, braille, color or matrix
+    #[arg(short, long, default_value = "ascii")]
+    mode: RenderMode,
+
+    /// Color output: mono writes plain text, 256 and truecolor add escape codes
+    #[arg(long, default_value = "mono")]
+    color: ColorMode,
+
+    /// How to fit the image to the output: stretch, fit or fill
+    #[arg(short, long, default_value = "fit")]
+    fit: FitMode,
+
+    /// Filters applied in order: invert, brightness=<n>, contrast=<n>,
+    /// gain=<n>, gamma=<n>, edges, crt, or denoise, toon or vignette with
+    /// an optional =<n>, or plugin=<name>[:<args>] with
+    /// --features plugins
+    #[arg(long = "filter")]
+    filters: Vec<Filter>,
+}
+
+#[derive(Args)]
+struct DaemonArgs {
+    /// Socket `asciicam attach` connects to, $XDG_RUNTIME_DIR/asciicam.sock
+    /// by default
+    #[arg(long, value_name = "PATH")]
+    socket: Option<PathBuf>,
+
+    /// Don't detach from the terminal, e.g. when run by a service manager
+    #[arg(long)]
+    foreground: bool,
+
+    #[command(flatten)]
+    serve: ServeArgs,
+}
+
+#[derive(Args)]
+struct AttachArgs {
+    /// Socket of the daemon, $XDG_RUNTIME_DIR/asciicam.sock by default
+    #[arg(long, value_name = "PATH")]
+    socket: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct BenchArgs {
+    /// JPEG files to benchmark with instead of the built in 640x480,
+    /// 1280x720 and 1920x1080 frames
+    files: Vec<PathBuf>,
+
+    /// Frames rendered per combination, more gives steadier numbers
+    #[arg(long, default_value_t = 50)]
+    frames: u32,
+
+    /// Width of the output in characters
+    #[arg(long, default_value_t = 160)]
+    columns: u32,
+
+    /// Height of the output in lines
+    #[arg(long, default_value_t = 48)]
+    rows: u32,
+
+    /// Only benchmark these render modes
+    #[arg(short, long = "mode")]
+    modes: Vec<RenderMode>,
+}
+
+#[derive(Args)]
+struct LatencyArgs {
+    /// Index of the v4l device to capture from, /dev/videoN
+    #[arg(short, long, default_value_t = 0)]
+    device: usize,
+
+    /// Measure a file or stream instead of the camera, anything `view
+    /// --input` takes
+    #[arg(short, long)]
+    input: Option<String>,
+
+    /// Resolution to ask the camera for, e.g. 1280x720
+    #[arg(short, long, value_parser = parse_resolution)]
+    resolution: Option<(u32, u32)>,
+
+    /// Times to flash the terminal between black and white
+    #[arg(long, default_value_t = 20)]
+    flashes: u32,
+}
+
+#[derive(Args)]
+struct ControlsArgs {
+    /// Index of the v4l device, /dev/videoN
+    #[arg(short, long, default_value_t = 0)]
+    device: usize,
+
+    /// List the controls with their current values and ranges, the default
+    /// when nothing is set, read, loaded or saved
+    #[arg(short, long)]
+    list: bool,
+
+    /// Print the current value of a control
+    #[arg(long, value_name = "NAME")]
+    get: Vec<String>,
+
+    /// Set a control, e.g. exposure_auto=1 or gain=30, applied in order
+    #[arg(long, value_name = "NAME=VALUE")]
+    set: Vec<ControlSetting>,
+
+    /// Restore the controls saved in a profile, before any --set
+    #[arg(long, value_name = "PATH")]
+    load: Option<PathBuf>,
+
+    /// Save the values of all the controls to a profile, after any --set
+    #[arg(long, value_name = "PATH")]
+    save: Option<PathBuf>,
+}
+
+/// A `name=value` control assignment, `true` and `false` work for booleans.
+#[derive(Clone)]
+struct ControlSetting {
+    key: String,
+    value: i64,
+}
+
+impl FromStr for ControlSetting {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (key, value) = match s.split_once('=') {
+            Some(setting) => setting,
+            None => return Err(eyre!("Expected NAME=VALUE, got '{s}'")),
+        };
+        let value = match value {
+            "true" => 1,
+            "false" => 0,
+            value => value.parse()?,
         };
 
-        let mut dst_frame = fr::Image::new(dst_width, dst_height, src_frame.pixel_type());
+        Ok(Self {
+            key: key.to_string(),
+            value,
+        })
+    }
+}
+
+/// A camera control value, or `auto` for its automatic mode.
+#[derive(Clone, Copy)]
+struct ControlValue(Option<i64>);
+
+impl FromStr for ControlValue {
+    type Err = eyre::Report;
 
-        let mut dst_view = dst_frame.view_mut();
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "auto" => Ok(Self(None)),
+            value => Ok(Self(Some(value.parse()?))),
+        }
+    }
+}
 
-        let mut resizer = fr::Resizer::new(fr::ResizeAlg::Nearest);
+/// `--white-balance`, set on the camera or done in software.
+#[derive(Clone, Copy)]
+enum WhiteBalance {
+    Camera(ControlValue),
+    GrayWorld,
+}
+
+impl FromStr for WhiteBalance {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "gray-world" => Ok(Self::GrayWorld),
+            value => Ok(Self::Camera(value.parse()?)),
+        }
+    }
+}
+
+fn parse_resolution(s: &str) -> Result<(u32, u32)> {
+    match s.split_once('x') {
+        Some((width, height)) => Ok((width.parse()?, height.parse()?)),
+        None => Err(eyre!("Expected a resolution like 1280x720, got '{s}'")),
+    }
+}
+
+/// A color given as `RRGGBB`, with or without a leading `#`.
+fn parse_color(s: &str) -> Result<Rgb<u8>> {
+    let hex = s.trim_start_matches('#');
+    if hex.len() != 6 || !hex.is_ascii() {
+        return Err(eyre!("Expected a color like 00b140, got '{s}'"));
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16);
 
-        match resizer.resize(&src_frame.view(), &mut dst_view) {
-            Ok(_) => (),
-            Err(e) => {
-                return Err(e.into());
+    Ok(Rgb([channel(0)?, channel(2)?, channel(4)?]))
+}
+
+/// The `--chroma-key` settings, loading the background image if there is
+/// one.
+fn chroma_key(view: &ViewArgs) -> Result<Option<ChromaKey>> {
+    let key = match view.chroma_key {
+        Some(key) => key.unwrap_or(Rgb([0x00, 0xb1, 0x40])),
+        None => return Ok(None),
+    };
+    let background = match view.key_background.as_str() {
+        "black" => Background::Black,
+        s => match parse_color(s) {
+            Ok(color) => Background::Color(color),
+            Err(_) => match image::open(s) {
+                Ok(image) => Background::Image(Arc::new(image.to_rgb8())),
+                Err(e) => return Err(eyre!("Could not load the background '{s}': {e}")),
+            },
+        },
+    };
+
+    Ok(Some(ChromaKey {
+        key,
+        similarity: view.key_similarity,
+        blend: view.key_blend,
+        background,
+    }))
+}
+
+/// The power_line_frequency menu value for `--power-line`.
+fn parse_power_line(s: &str) -> Result<i64> {
+    match s.trim_end_matches("hz").trim_end_matches("Hz") {
+        "off" => Ok(0),
+        "50" => Ok(1),
+        "60" => Ok(2),
+        "auto" => Ok(3),
+        _ => Err(eyre!("Expected 50, 60, off or auto, got '{s}'")),
+    }
+}
+
+/// Opens the `--pip` source on a thread of its own, so it doesn't slow down
+/// the main one.
+fn open_pip(pip: String, deinterlace: Option<Deinterlace>) -> Result<BackgroundSource> {
+    BackgroundSource::spawn(move || -> Result<Box<dyn CaptureSource>> {
+        let source: Box<dyn CaptureSource> = if let Ok(index) = pip.parse() {
+            Box::new(Camera::open(index)?)
+        } else if pip == "screen" {
+            Box::new(open_screen(None)?)
+        } else {
+            match pip.strip_prefix("screen:") {
+                Some(region) => Box::new(open_screen(Some(region))?),
+                None => open_input(&pip)?,
             }
         };
 
-        let frame: GrayImage = match image::ImageBuffer::from_raw(
-            dst_width.get(),
-            dst_height.get(),
-            dst_frame.buffer().to_vec(),
-        ) {
-            None => {
-                return Err(eyre!("Could not convert raw buffer to image buffer"));
+        Ok(match deinterlace {
+            Some(mode) => Box::new(DeinterlacedSource::new(source, mode)),
+            None => source,
+        })
+    })
+}
+
+#[cfg(feature = "screen")]
+fn open_screen(region: Option<&str>) -> Result<asciicam::capture::ScreenSource> {
+    let region = match region {
+        Some(region) => Some(region.parse()?),
+        None => None,
+    };
+
+    asciicam::capture::ScreenSource::open(region)
+}
+
+#[cfg(not(feature = "screen"))]
+fn open_screen(_region: Option<&str>) -> Result<asciicam::capture::ImageSource> {
+    Err(eyre!(
+        "--screen needs asciicam built with --features screen"
+    ))
+}
+
+#[cfg(feature = "mqtt")]
+fn open_mqtt(url: &str, args: &ViewArgs) -> Result<asciicam::sink::MqttSink> {
+    let mut settings = asciicam::sink::MqttSettings::from_url(url)?;
+    settings.topics = asciicam::sink::MqttTopics::with_prefix(&args.mqtt_topic);
+    settings.snapshot_interval = match args.mqtt_snapshot_interval {
+        0 => None,
+        secs => Some(Duration::from_secs(secs)),
+    };
+    settings.heartbeat_interval = Duration::from_secs(args.mqtt_heartbeat.max(1));
+    settings.motion_threshold = args.motion_threshold;
+
+    asciicam::sink::MqttSink::connect(settings)
+}
+
+#[cfg(not(feature = "mqtt"))]
+fn open_mqtt(_url: &str, args: &ViewArgs) -> Result<asciicam::sink::WriterSink> {
+    // only used with the feature
+    let _ = (
+        &args.mqtt_topic,
+        args.mqtt_snapshot_interval,
+        args.mqtt_heartbeat,
+        args.motion_threshold,
+    );
+    Err(eyre!("--mqtt needs asciicam built with --features mqtt"))
+}
+
+#[cfg(feature = "script")]
+fn with_script(builder: AsciicamBuilder, name: &str) -> Result<AsciicamBuilder> {
+    Ok(builder.script(asciicam::script::Script::find(name)?))
+}
+
+#[cfg(not(feature = "script"))]
+fn with_script(_builder: AsciicamBuilder, _name: &str) -> Result<AsciicamBuilder> {
+    Err(eyre!(
+        "--script needs asciicam built with --features script"
+    ))
+}
+
+#[cfg(feature = "gstreamer")]
+fn open_gst(pipeline: &str) -> Result<asciicam::capture::GstSource> {
+    asciicam::capture::GstSource::launch(pipeline)
+}
+
+#[cfg(not(feature = "gstreamer"))]
+fn open_gst(_pipeline: &str) -> Result<asciicam::capture::ImageSource> {
+    Err(eyre!(
+        "--gst needs asciicam built with --features gstreamer"
+    ))
+}
+
+#[cfg(feature = "zmq")]
+fn open_zmq(endpoint: &str, args: &ViewArgs) -> Result<asciicam::sink::ZmqSink> {
+    Ok(asciicam::sink::ZmqSink::bind(endpoint, args.color)?.raw(args.zmq_raw))
+}
+
+#[cfg(not(feature = "zmq"))]
+fn open_zmq(_endpoint: &str, args: &ViewArgs) -> Result<asciicam::sink::WriterSink> {
+    // only used with the feature
+    let _ = args.zmq_raw;
+    Err(eyre!("--zmq needs asciicam built with --features zmq"))
+}
+
+/// How frames are rendered for remote clients, which every client does at
+/// its own size.
+fn render_settings(view: &ViewArgs) -> Result<RenderSettings> {
+    Ok(RenderSettings {
+        render_mode: view.mode,
+        charset: match &view.charset {
+            Some(charset) => parse_charset(charset)?,
+            None if view.night.is_some() => NIGHT_CHARSET.to_vec(),
+            None => DEFAULT_CHARSET.to_vec(),
+        },
+        color_mode: view.color,
+        options: RenderOptions {
+            mirror: !view.no_mirror,
+            fit: view.fit,
+            color: view.color != ColorMode::Mono,
+            gray_world: matches!(view.white_balance, Some(WhiteBalance::GrayWorld)),
+            filters: match view.night {
+                Some(gain) => [Filter::Gain(gain.unwrap_or(NIGHT_GAIN))]
+                    .into_iter()
+                    .chain(view.filters.iter().copied())
+                    .collect(),
+                None => view.filters.clone(),
+            },
+            hysteresis: view
+                .hysteresis
+                .map(|margin| margin.unwrap_or(HYSTERESIS_MARGIN)),
+            luma: view.luma,
+            stabilize: view.stabilize,
+            tint: view.tint,
+            chroma_key: chroma_key(view)?,
+            phosphor: view.phosphor,
+            area: view.area,
+            linear: view.linear,
+            ..RenderOptions::default()
+        },
+    })
+}
+
+#[cfg(feature = "ssh")]
+fn open_ssh(addr: &str, config: &ServerConfig) -> Result<asciicam::sink::SshSink> {
+    let settings = asciicam::sink::SshSettings {
+        password: config.ssh_password.clone(),
+        host_key: config.ssh_host_key.clone(),
+    };
+
+    let sink = asciicam::sink::SshSink::bind(addr, settings, config.settings.clone())?;
+    eprintln!("Serving ssh on {}", sink.local_addr());
+    Ok(sink)
+}
+
+#[cfg(not(feature = "ssh"))]
+fn open_ssh(_addr: &str, config: &ServerConfig) -> Result<asciicam::sink::TcpSink> {
+    // only used with the feature
+    let _ = (&config.ssh_password, &config.ssh_host_key);
+    Err(eyre!("ssh needs asciicam built with --features ssh"))
+}
+
+/// Starts `server` in the background.
+fn open_server(server: &Server, config: &ServerConfig) -> Result<Box<dyn Sink>> {
+    let (settings, size) = (config.settings.clone(), config.size);
+
+    match server {
+        Server::Tcp(addr) => {
+            let sink = TcpSink::bind(addr.as_str(), settings, size)?;
+            eprintln!("Serving telnet on {}", sink.local_addr());
+            Ok(Box::new(sink))
+        }
+        Server::Ws(addr) => {
+            let sink = WebSocketSink::bind(addr.as_str(), config.ws_format, settings, size)?;
+            eprintln!("Serving WebSocket on ws://{}", sink.local_addr());
+            Ok(Box::new(sink))
+        }
+        Server::Http(addr) => {
+            let sink = WebSocketSink::bind_http(addr.as_str(), settings, size)?;
+            eprintln!("Serving the viewer on http://{}", sink.local_addr());
+            Ok(Box::new(sink))
+        }
+        Server::Ssh(addr) => Ok(Box::new(open_ssh(addr, config)?)),
+    }
+}
+
+/// Picks up a socket handed over by systemd, which are told apart by their
+/// `FileDescriptorName=`: tcp, ws or http.
+fn inherit_server(
+    name: &str,
+    listener: TcpListener,
+    config: &ServerConfig,
+) -> Result<Box<dyn Sink>> {
+    let (settings, size) = (config.settings.clone(), config.size);
+
+    match name {
+        "tcp" => Ok(Box::new(TcpSink::from_listener(listener, settings, size)?)),
+        "ws" => Ok(Box::new(WebSocketSink::from_listener(
+            listener,
+            config.ws_format,
+            settings,
+            size,
+        )?)),
+        "http" => Ok(Box::new(WebSocketSink::http_from_listener(
+            listener, settings, size,
+        )?)),
+        _ => Err(eyre!(
+            "Socket '{name}' from systemd needs FileDescriptorName=tcp, ws or http"
+        )),
+    }
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let draws = match &cli.command {
+        Some(Command::View(args)) => args.output == Output::Terminal,
+        Some(Command::Attach(_) | Command::Latency(_)) => true,
+        Some(_) => false,
+        None => cli.view.output == Output::Terminal,
+    };
+    init_logging(cli.verbose, cli.log_file.as_deref(), draws)?;
+    #[cfg(feature = "plugins")]
+    log_plugins();
+
+    match cli.command {
+        Some(Command::View(args)) => view(args),
+        Some(Command::Convert(args)) => convert(args),
+        Some(Command::Serve(args)) => serve(args),
+        Some(Command::Daemon(args)) => daemon(args, cli.log_file.as_deref()),
+        Some(Command::Attach(args)) => attach(args),
+        Some(Command::Controls(args)) => controls(args),
+        Some(Command::Bench(args)) => bench(args),
+        Some(Command::Latency(args)) => latency(args),
+        None => view(cli.view),
+    }
+}
+
+/// Loads the plugins, if parsing the arguments didn't already, and logs
+/// what they have and which ones didn't load.
+#[cfg(feature = "plugins")]
+fn log_plugins() {
+    let plugins = asciicam::plugin::plugins();
+    for (path, e) in plugins.skipped() {
+        tracing::warn!("skipping plugin {}: {e}", path.display());
+    }
+    tracing::debug!(
+        filters = ?plugins.filter_names().collect::<Vec<_>>(),
+        sources = ?plugins.source_names().collect::<Vec<_>>(),
+        "plugins"
+    );
+}
+
+/// Sends the log to `log_file`, or to stderr unless the terminal is being
+/// drawn on (`draws`) and it would end up over the picture.
+fn init_logging(verbose: u8, log_file: Option<&Path>, draws: bool) -> Result<()> {
+    let level = match verbose {
+        0 => "warn",
+        1 => "info",
+        2 => "debug",
+        _ => "trace",
+    };
+    let filter = EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| EnvFilter::new(format!("asciicam={level}")));
+    // closing a span logs how long the stage took
+    let subscriber = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_span_events(FmtSpan::CLOSE);
+
+    match log_file {
+        Some(path) => {
+            let file = fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .map_err(|e| eyre!("Could not open '{}' to log to ({e})", path.display()))?;
+            subscriber
+                .with_ansi(false)
+                .with_writer(Mutex::new(file))
+                .init();
+        }
+        None if draws && io::stderr().is_terminal() => (),
+        None => subscriber.with_writer(io::stderr).init(),
+    }
+
+    Ok(())
+}
+
+fn view(args: ViewCommand) -> Result<()> {
+    let config = ServerConfig {
+        settings: render_settings(&args.view)?,
+        size: (80, 24),
+        ws_format: WsFormat::default(),
+        ssh_password: None,
+        ssh_host_key: None,
+    };
+    let color_mode = args.view.color;
+    // the background of the key is loaded once, for both
+    let key = config.settings.options.chroma_key.clone();
+    let mut builder = builder(args.view, key)?;
+
+    if let Some(path) = &args.record {
+        let sink = RecordSink::create(path, color_mode)?;
+        builder = match args.record_size {
+            Some((columns, rows)) => {
+                builder.sink(FixedSize::new(sink, config.settings.clone(), columns, rows))
             }
-            Some(v) => v,
+            None => builder.sink(sink),
         };
+    }
+    for server in &args.serve {
+        builder = builder.sink(open_server(server, &config)?);
+    }
 
-        Ok(frame)
+    let mut app = builder.show_stats(args.stats).build()?;
+    let result = match args.output {
+        Output::Terminal => app.run(),
+        Output::None => {
+            // there is no window to fit, but the size is still the nicest
+            // guess when started from a terminal
+            let (columns, rows) = terminal::size().map_or((80, 24), |(w, h)| (w.into(), h.into()));
+            app.run_with(columns, rows, |_, _| ControlFlow::Continue(()))
+        }
+    };
+    if args.stats {
+        eprintln!("{}", app.drop_stats());
     }
+
+    result
 }
 
-impl<'c> CharArr<'c> {
-    fn new(charset: &'c [char], pixel: u8) -> Self {
-        Self { charset, pixel }
+/// Opens a slideshow of the images in the directory or matching the pattern
+/// `input`.
+fn open_slideshow(input: &str, interval: f64) -> Result<SlideshowSource> {
+    if !interval.is_finite() || interval <= 0.0 {
+        return Err(eyre!("--slide-interval must be more than 0 seconds"));
     }
+    let interval = Duration::from_secs_f64(interval);
 
-    fn get_char(self) -> char {
-        let idx: usize = (self.pixel as usize * (self.charset.len() - 1)) / 255_usize;
-        self.charset[idx]
+    if Path::new(input).is_dir() {
+        return SlideshowSource::from_dir(input, interval);
+    }
+    match expand_glob(input)? {
+        paths if paths.is_empty() => Err(eyre!("No images match '{input}'")),
+        paths => SlideshowSource::new(paths, interval),
     }
 }
 
-fn write_image_buffer(image_buffer: &GrayImage, out: &mut impl Write) -> Result<()> {
-    let bh = image_buffer.height();
-    let bw = image_buffer.width();
-    let mut buf: String = String::with_capacity(bw as usize * bh as usize + (2 * bh) as usize);
+/// Configures the viewer from the options shared by every command that
+/// captures, with `chroma_key` made from them by [`chroma_key`].
+fn builder(args: ViewArgs, chroma_key: Option<ChromaKey>) -> Result<AsciicamBuilder> {
+    let mut builder = AsciicamBuilder::new()
+        .device(args.device)
+        .open_timeout(Duration::from_secs(args.wait))
+        .render_mode(args.mode)
+        .color_mode(args.color)
+        .fit_mode(args.fit)
+        .mirror(!args.no_mirror)
+        .adaptive_quality(!args.full_quality);
 
-    for y in 0..bh {
-        // this flips the image
-        for x in (0..bw).rev() {
-            let pixel = image::ImageBuffer::get_pixel(image_buffer, x, y).0;
-            let metadata = CharArr::new(
-                // the extra char is to avoid floating point arithmetic and won't be displayed
-                &[
-                    ' ', ' ', ' ', '.', ':', '-', '=', '+', '*', '#', '%', '@', '?',
-                ],
-                pixel[0],
-            );
-            let c = CharArr::get_char(metadata);
-            buf.push(c);
+    if let Some(path) = &args.stats_log {
+        builder = builder.stats_log(StatsLog::create(path)?);
+    }
+
+    if let Some(focus) = args.focus {
+        builder = builder.camera_control(FOCUS, focus.0);
+    }
+    if let Some(zoom) = args.optical_zoom {
+        builder = builder.camera_control(ZOOM, Some(zoom));
+    }
+    match args.white_balance {
+        Some(WhiteBalance::Camera(value)) => {
+            builder = builder.camera_control(WHITE_BALANCE, value.0)
         }
-        buf.push('\r');
-        buf.push('\n');
+        Some(WhiteBalance::GrayWorld) => builder = builder.gray_world(true),
+        None => (),
     }
-    write!(out, "{buf}")?;
-    Ok(())
+    if let Some(frequency) = args.power_line {
+        builder = builder.camera_control(POWER_LINE_FREQUENCY, Some(frequency));
+    }
+    if let Some(target) = args.auto_exposure {
+        builder = builder.auto_exposure(target.unwrap_or(DEFAULT_TARGET));
+    }
+    if let Some(region) = args.meter {
+        builder = builder.meter_region(region);
+    }
+    if let Some(script) = &args.script {
+        builder = with_script(builder, script)?;
+    }
+    if let Some(presets) = &args.presets {
+        builder = builder.presets(presets);
+    }
+
+    match args.input.as_deref() {
+        Some("-") => builder = builder.source(PipeSource::stdin(args.input_format)?),
+        Some(input) if Path::new(input).is_dir() || input.contains(['*', '?']) => {
+            builder = builder.source(open_slideshow(input, args.slide_interval)?)
+        }
+        Some(input) => builder = builder.source(open_input(input)?),
+        None => (),
+    }
+    if let Some(region) = &args.screen {
+        builder = builder.source(open_screen(region.as_deref())?);
+    }
+    if let Some(pipeline) = &args.gst {
+        builder = builder.source(open_gst(pipeline)?);
+    }
+    if let Some(pip) = &args.pip {
+        builder = builder
+            .pip(open_pip(pip.clone(), args.pip_deinterlace)?)
+            .pip_corner(args.pip_corner);
+    }
+    if args.compare_charset.is_some()
+        || args.compare_mode.is_some()
+        || !args.compare_filters.is_empty()
+    {
+        builder = builder.compare(Comparison {
+            render_mode: args.compare_mode,
+            charset: args.compare_charset.clone(),
+            filters: args.compare_filters.clone(),
+        });
+    }
+    if let Some((width, height)) = args.resolution {
+        builder = builder.resolution(width, height);
+    }
+    builder = builder.io_mode(args.io_mode).black_level(args.black_level);
+    if let Some(window) = args.window {
+        builder = builder.window(window);
+    }
+    if let Some(mode) = args.deinterlace {
+        builder = builder.deinterlace(mode);
+    }
+    if let Some(window) = args.interpolate {
+        builder = builder.interpolate(Duration::from_millis(window));
+    }
+    if let Some(weights) = args.luma {
+        builder = builder.luma(weights);
+    }
+    builder = builder
+        .phosphor(args.phosphor)
+        .stabilize(args.stabilize)
+        .area(args.area)
+        .linear(args.linear);
+    if let Some(charset) = &args.charset {
+        builder = builder.charset(charset);
+    }
+    for filter in &args.filters {
+        builder = builder.filter(*filter);
+    }
+    if let Some(margin) = args.hysteresis {
+        builder = builder.hysteresis(margin.unwrap_or(HYSTERESIS_MARGIN));
+    }
+    if let Some(tint) = args.tint {
+        builder = builder.tint(tint);
+    }
+    if let Some(key) = chroma_key {
+        builder = builder.chroma_key(key);
+    }
+    if let Some(gain) = args.night {
+        builder = builder.night(gain.unwrap_or(NIGHT_GAIN));
+    }
+    if let Some(index) = args.loopback {
+        let (width, height) = args.loopback_size;
+        builder = builder.sink(open_loopback(index, width, height)?);
+    }
+    if let Some(url) = &args.mqtt {
+        builder = builder.sink(open_mqtt(url, &args)?);
+    }
+    if let Some(endpoint) = &args.zmq {
+        builder = builder.sink(open_zmq(endpoint, &args)?);
+    }
+
+    Ok(builder)
 }
 
-fn main() -> Result<()> {
-    let dev = match Device::new(0) {
-        Ok(dev) => dev,
-        Err(_) => {
+fn serve(args: ServeArgs) -> Result<()> {
+    let inherited = systemd::listeners()?;
+    if args.tcp.is_none()
+        && args.ws.is_none()
+        && args.http.is_none()
+        && args.ssh.is_none()
+        && inherited.is_empty()
+    {
+        return Err(eyre!("Nothing to serve, pass --tcp, --ws, --http or --ssh"));
+    }
+
+    let settings = render_settings(&args.view)?;
+    let (columns, rows) = (args.columns, args.rows);
+    run_servers(servers(args, settings, inherited)?, columns, rows)
+}
+
+/// Configures the viewer with a sink for every server asked for, and for
+/// every socket handed over by systemd, rendering with `settings`.
+fn servers(
+    args: ServeArgs,
+    settings: RenderSettings,
+    inherited: Vec<(String, TcpListener)>,
+) -> Result<AsciicamBuilder> {
+    let key = settings.options.chroma_key.clone();
+    let config = ServerConfig {
+        settings,
+        size: (args.columns, args.rows),
+        ws_format: args.ws_format,
+        ssh_password: args.ssh_password,
+        ssh_host_key: args.ssh_host_key,
+    };
+    let servers = [
+        args.ssh.map(Server::Ssh),
+        args.tcp.map(Server::Tcp),
+        args.ws.map(Server::Ws),
+        args.http.map(Server::Http),
+    ];
+    let mut builder = builder(args.view, key)?;
+
+    if let Some(addr) = &args.metrics {
+        let metrics = Metrics::bind(addr.as_str())?;
+        eprintln!("Serving metrics on http://{}/metrics", metrics.local_addr());
+        builder = builder.metrics(metrics);
+    }
+    for (name, listener) in inherited {
+        builder = builder.sink(inherit_server(&name, listener, &config)?);
+    }
+    for server in servers.iter().flatten() {
+        builder = builder.sink(open_server(server, &config)?);
+    }
+
+    Ok(builder)
+}
+
+#[cfg(target_os = "linux")]
+fn open_loopback(index: usize, width: u32, height: u32) -> Result<LoopbackSink> {
+    LoopbackSink::open(index, width, height)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn open_loopback(_index: usize, _width: u32, _height: u32) -> Result<asciicam::sink::WriterSink> {
+    Err(eyre!(
+        "--loopback needs v4l2loopback, which is only on Linux"
+    ))
+}
+
+#[cfg(not(unix))]
+fn daemon(args: DaemonArgs, log_file: Option<&Path>) -> Result<()> {
+    // only used on unix
+    let _ = (args.socket, args.foreground, args.serve, log_file);
+    Err(eyre!(
+        "asciicam daemon needs unix sockets, use asciicam serve instead"
+    ))
+}
+
+#[cfg(unix)]
+fn daemon(args: DaemonArgs, log_file: Option<&Path>) -> Result<()> {
+    let socket = args.socket.clone().unwrap_or_else(default_socket_path);
+    if !args.foreground {
+        return spawn_daemon(&socket, log_file);
+    }
+
+    let settings = render_settings(&args.serve.view)?;
+    let (columns, rows) = (args.serve.columns, args.serve.rows);
+    let sink = UnixSink::bind(&socket, settings.clone(), (columns, rows))?;
+
+    let builder = servers(args.serve, settings, systemd::listeners()?)?.sink(sink);
+    run_servers(builder, columns, rows)
+}
+
+/// Runs the capture loop behind the servers, telling systemd once they are
+/// up and pinging its watchdog if it asked for one.
+fn run_servers(mut builder: AsciicamBuilder, columns: u32, rows: u32) -> Result<()> {
+    if let Some(watchdog) = Watchdog::from_env() {
+        builder = builder.sink(watchdog);
+    }
+    let mut app = builder.build()?;
+    systemd::notify("READY=1")?;
+
+    app.run_with(columns, rows, |_, _| ControlFlow::Continue(()))
+}
+
+/// Runs this same command again with `--foreground` in its own process
+/// group, so it keeps going when the terminal goes away, and waits until it
+/// is listening on `socket`. It logs to `log_file` itself, errors it exits
+/// with before that go there too.
+#[cfg(unix)]
+fn spawn_daemon(socket: &Path, log_file: Option<&Path>) -> Result<()> {
+    if UnixStream::connect(socket).is_ok() {
+        return Err(eyre!(
+            "A daemon is already running on '{}'",
+            socket.display()
+        ));
+    }
+
+    let log = match log_file {
+        Some(path) => Stdio::from(OpenOptions::new().create(true).append(true).open(path)?),
+        None => Stdio::null(),
+    };
+    let mut child = process::Command::new(env::current_exe()?)
+        .args(env::args_os().skip(1))
+        .arg("--foreground")
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(log)
+        .process_group(0)
+        .spawn()?;
+
+    let started = Instant::now();
+    while started.elapsed() < DAEMON_STARTUP {
+        if let Some(status) = child.try_wait()? {
             return Err(eyre!(
-                "Could not find default device '0'. Is a webcam available / plugged in?"
-            ))
+                "The daemon exited on startup ({status}), see --log-file for why"
+            ));
+        }
+        if UnixStream::connect(socket).is_ok() {
+            println!(
+                "asciicam daemon running as pid {}, attach with `asciicam attach`",
+                child.id()
+            );
+            return Ok(());
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+
+    Err(eyre!(
+        "The daemon (pid {}) didn't start listening on '{}'",
+        child.id(),
+        socket.display()
+    ))
+}
+
+fn bench(args: BenchArgs) -> Result<()> {
+    let fixtures = if args.files.is_empty() {
+        Fixture::builtin()?
+    } else {
+        args.files
+            .iter()
+            .map(|path| Fixture::open(path))
+            .collect::<Result<Vec<_>>>()?
+    };
+    let combinations: Vec<Combination> = Combination::all()
+        .into_iter()
+        .filter(|c| args.modes.is_empty() || args.modes.contains(&c.mode))
+        .collect();
+
+    println!(
+        "{:<12} {:<11} {:<12} {:>9} {:>9} {:>9} {:>9} {:>8}",
+        "frame", "mode", "charset", "decode", "resize", "map", "write", "fps"
+    );
+    for fixture in &fixtures {
+        for &combination in &combinations {
+            let timings = measure(fixture, combination, args.columns, args.rows, args.frames)?;
+            let total = timings.decode + timings.resize + timings.map + timings.write;
+            let charset = match combination.charset {
+                Some(index) => CHARSETS[index].chars().take(12).collect(),
+                None => "-".to_string(),
+            };
+            let ms = |d: Duration| format!("{:.2}ms", d.as_secs_f64() * 1000.0);
+
+            println!(
+                "{:<12} {:<11} {:<12} {:>9} {:>9} {:>9} {:>9} {:>8.1}",
+                fixture.name,
+                combination.mode.name(),
+                charset,
+                ms(timings.decode),
+                ms(timings.resize),
+                ms(timings.map),
+                ms(timings.write),
+                1.0 / total.as_secs_f64().max(f64::EPSILON)
+            );
         }
+    }
+
+    Ok(())
+}
+
+fn latency(args: LatencyArgs) -> Result<()> {
+    let mut source: Box<dyn CaptureSource> = match &args.input {
+        Some(input) => open_input(input)?,
+        None => Box::new(Camera::open_with(args.device, args.resolution)?),
     };
 
-    let mut fmt = dev.format()?;
+    let stats = asciicam::latency::measure(&mut *source, args.flashes)?;
+    println!("{stats}");
 
-    fmt.fourcc = FourCC::new(b"MJPG");
-    dev.set_format(&fmt)?;
+    Ok(())
+}
 
-    let mut stream = Stream::with_buffers(&dev, Type::VideoCapture, 4)?;
+fn controls(args: ControlsArgs) -> Result<()> {
+    let controls = Controls::open(args.device)?;
 
-    let mut stdout = stdout();
+    if let Some(path) = &args.load {
+        controls.load_profile(path)?;
+    }
+    for setting in &args.set {
+        controls.set(&setting.key, setting.value)?;
+    }
+    for key in &args.get {
+        println!("{}", controls.get(key)?);
+    }
+    if let Some(path) = &args.save {
+        controls.save_profile(path)?;
+    }
+    let changed = !args.set.is_empty() || args.load.is_some() || args.save.is_some();
+    if args.list || (!changed && args.get.is_empty()) {
+        for control in controls.list()? {
+            println!("{control}");
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn attach(args: AttachArgs) -> Result<()> {
+    // only used on unix
+    let _ = args.socket;
+    Err(eyre!("asciicam attach needs unix sockets"))
+}
+
+#[cfg(unix)]
+fn attach(args: AttachArgs) -> Result<()> {
+    let socket = args.socket.unwrap_or_else(default_socket_path);
+    let stream = match UnixStream::connect(&socket) {
+        Ok(stream) => stream,
+        Err(e) => return Err(eyre!("No daemon running on '{}' ({e})", socket.display())),
+    };
 
     terminal::enable_raw_mode()?;
+    let result = attached(stream);
+    terminal::disable_raw_mode()?;
+    // the daemon hides the cursor and leaves its last frame behind
+    let mut stdout = io::stdout();
+    let _ = stdout.write_all(b"\x1b[?25h\x1b[2J\x1b[H");
+    let _ = stdout.flush();
 
-    loop {
-        let (term_width, term_height) = terminal::size()?;
-        let (buf, _) = stream.next()?;
-        let metadata = CameraBuffer {
-            stream_buf: buf,
-            src_width: fmt.width,
-            dst_height: fmt.height,
-            src_height: term_height.into(),
-            dst_width: term_width.into(),
-        };
+    result
+}
 
-        let frame: GrayImage = match CameraBuffer::get_cam(metadata) {
-            Ok(frame) => frame,
-            Err(e) => {
-                terminal::disable_raw_mode()?;
-                return Err(e);
-            }
-        };
+/// Copies frames from the daemon to the terminal and terminal size changes
+/// back, until `q` is pressed or the daemon goes away.
+#[cfg(unix)]
+fn attached(mut stream: UnixStream) -> Result<()> {
+    let (columns, rows) = terminal::size()?;
+    writeln!(stream, "{columns}x{rows}")?;
 
-        if poll(std::time::Duration::from_secs(0))? {
-            let event = read()?;
+    let mut frames = stream.try_clone()?;
+    let copier = thread::spawn(move || io::copy(&mut frames, &mut io::stdout().lock()));
 
-            if let Event::Key(KeyEvent {
-                code: KeyCode::Char(c),
+    while !copier.is_finished() {
+        if !event::poll(Duration::from_millis(100))? {
+            continue;
+        }
+        match event::read()? {
+            Event::Resize(columns, rows) => writeln!(stream, "{columns}x{rows}")?,
+            Event::Key(KeyEvent {
+                code: KeyCode::Char('q'),
                 ..
-            }) = event
-            {
-                match c {
-                    'q' => break,
-                    's' => {
-                        let dt = chrono::Utc::now();
-                        let mut file = File::create(format!(
-                            "asciicam-{}.txt",
-                            dt.format("%Y-%m-%d_%H:%M:%S")
-                        ))?;
-                        write_image_buffer(&frame, &mut file)?;
-                    }
-                    _ => (),
-                }
-            };
+            }) => break,
+            _ => (),
         }
+    }
 
-        execute!(
-            stdout,
-            terminal::Clear(terminal::ClearType::All),
-            cursor::MoveTo(0, 0)
-        )?;
+    let _ = stream.shutdown(Shutdown::Both);
+    Ok(())
+}
+
+fn convert(args: ConvertArgs) -> Result<()> {
+    let charset = match &args.charset {
+        Some(charset) => parse_charset(charset)?,
+        None => DEFAULT_CHARSET.to_vec(),
+    };
+    for filter in &args.filters {
+        filter.validate()?;
+    }
+    if args.columns == 0 || args.rows == 0 {
+        return Err(eyre!("The output needs at least one column and row"));
+    }
+    fs::create_dir_all(&args.out)?;
 
-        write_image_buffer(&frame, &mut stdout)?;
+    let options = RenderOptions {
+        mirror: false,
+        fit: args.fit,
+        color: args.color != ColorMode::Mono,
+        filters: args.filters,
+        ..RenderOptions::default()
+    };
+    let converter = Converter::new(
+        args.mode.renderer(&charset),
+        args.columns,
+        args.rows,
+        args.color,
+        options,
+    );
 
-        stdout.flush()?;
+    if let Some(dir) = &args.watch {
+        return converter.watch(
+            dir,
+            &args.out,
+            WATCH_INTERVAL,
+            |input, result| match result {
+                Ok(output) => println!("{} -> {}", input.display(), output.display()),
+                Err(e) => eprintln!("{}: {e}", input.display()),
+            },
+        );
     }
 
-    terminal::disable_raw_mode()?;
+    let mut failed = 0;
+    for pattern in &args.files {
+        for input in expand_glob(pattern)? {
+            match converter.convert_file(&input, &args.out) {
+                Ok(output) => println!("{} -> {}", input.display(), output.display()),
+                Err(e) => {
+                    eprintln!("{}: {e}", input.display());
+                    failed += 1;
+                }
+            }
+        }
+    }
 
-    Ok(())
+    match failed {
+        0 => Ok(()),
+        n => Err(eyre!("{n} file(s) could not be converted")),
+    }
 }
@@ -0,0 +1,206 @@
+//! A Prometheus endpoint for keeping an eye on a camera box that serves the
+//! feed for weeks on end.
+
+use crate::stats::DropStats;
+use eyre::Result;
+use std::fmt::Write as _;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use tracing::debug;
+
+// upper bounds of the decode latency buckets in seconds, from a small JPEG
+// on a desktop to a 4K one on a raspberry pi
+const DECODE_BUCKETS: [f64; 10] = [
+    0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5,
+];
+// how long a scraper gets to send its request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Default)]
+struct Counters {
+    frames: u64,
+    // frames that took at most as long as each bucket
+    decode_buckets: [u64; DECODE_BUCKETS.len()],
+    decode_seconds: f64,
+    clients: usize,
+    drops: DropStats,
+    reconnects: u64,
+}
+
+/// Serves the numbers of the capture pipeline on `/metrics` in the
+/// Prometheus text format:
+///
+/// - `asciicam_frames_total`, the frame rate being its `rate()`
+/// - `asciicam_decode_seconds`, a histogram of how long frames took to decode
+/// - `asciicam_clients`, the clients connected to all servers
+/// - `asciicam_dropped_frames_total`, by the driver or skipped by asciicam
+/// - `asciicam_reconnects_total`, of the camera or stream
+///
+/// The viewer reports to it after every frame, see
+/// [`AsciicamBuilder::metrics`](crate::app::AsciicamBuilder::metrics).
+/// Clones report to the same endpoint.
+#[derive(Clone)]
+pub struct Metrics {
+    counters: Arc<Mutex<Counters>>,
+    addr: SocketAddr,
+}
+
+impl Metrics {
+    /// Listens on `addr` and answers scrapes in the background.
+    pub fn bind(addr: impl ToSocketAddrs) -> Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        let addr = listener.local_addr()?;
+        let counters = Arc::new(Mutex::new(Counters::default()));
+
+        let scraped = Arc::clone(&counters);
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                if let Err(e) = answer(stream, &scraped) {
+                    debug!("metrics request failed: {e}");
+                }
+            }
+        });
+
+        Ok(Self { counters, addr })
+    }
+
+    /// The address the endpoint is listening on.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Counts a frame that took `decode` to decode.
+    pub fn frame(&self, decode: Duration) {
+        let seconds = decode.as_secs_f64();
+        let mut counters = self.counters.lock().unwrap();
+        counters.frames += 1;
+        counters.decode_seconds += seconds;
+        for (count, bound) in counters.decode_buckets.iter_mut().zip(DECODE_BUCKETS) {
+            if seconds <= bound {
+                *count += 1;
+            }
+        }
+    }
+
+    /// Updates the clients connected right now and the totals of frames
+    /// lost and reconnects so far.
+    pub fn update(&self, clients: usize, drops: &DropStats, reconnects: u64) {
+        let mut counters = self.counters.lock().unwrap();
+        counters.clients = clients;
+        counters.drops = *drops;
+        counters.reconnects = reconnects;
+    }
+
+    /// Everything in the Prometheus text format, what a scrape gets.
+    pub fn encode(&self) -> String {
+        encode(&self.counters.lock().unwrap())
+    }
+}
+
+fn encode(counters: &Counters) -> String {
+    let mut out = String::new();
+
+    // writing to a String can't fail
+    header(
+        &mut out,
+        "frames_total",
+        "counter",
+        "Frames captured and rendered.",
+    );
+    let _ = writeln!(out, "asciicam_frames_total {}", counters.frames);
+
+    header(
+        &mut out,
+        "decode_seconds",
+        "histogram",
+        "Time taken to decode a frame.",
+    );
+    for (count, bound) in counters.decode_buckets.iter().zip(DECODE_BUCKETS) {
+        let _ = writeln!(
+            out,
+            "asciicam_decode_seconds_bucket{{le=\"{bound}\"}} {count}"
+        );
+    }
+    let frames = counters.frames;
+    let _ = writeln!(
+        out,
+        "asciicam_decode_seconds_bucket{{le=\"+Inf\"}} {frames}"
+    );
+    let _ = writeln!(
+        out,
+        "asciicam_decode_seconds_sum {}",
+        counters.decode_seconds
+    );
+    let _ = writeln!(out, "asciicam_decode_seconds_count {frames}");
+
+    header(
+        &mut out,
+        "clients",
+        "gauge",
+        "Clients connected to the servers.",
+    );
+    let _ = writeln!(out, "asciicam_clients {}", counters.clients);
+
+    header(
+        &mut out,
+        "dropped_frames_total",
+        "counter",
+        "Frames never rendered.",
+    );
+    let (driver, skipped) = (counters.drops.driver, counters.drops.skipped);
+    let _ = writeln!(
+        out,
+        "asciicam_dropped_frames_total{{reason=\"driver\"}} {driver}"
+    );
+    let _ = writeln!(
+        out,
+        "asciicam_dropped_frames_total{{reason=\"skipped\"}} {skipped}"
+    );
+
+    header(
+        &mut out,
+        "reconnects_total",
+        "counter",
+        "Times the source came back.",
+    );
+    let _ = writeln!(out, "asciicam_reconnects_total {}", counters.reconnects);
+
+    out
+}
+
+/// The `# HELP` and `# TYPE` lines of the metric `asciicam_{name}`.
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP asciicam_{name} {help}");
+    let _ = writeln!(out, "# TYPE asciicam_{name} {kind}");
+}
+
+/// Answers a request with the metrics on `/metrics` and a 404 anywhere
+/// else.
+fn answer(mut stream: TcpStream, counters: &Mutex<Counters>) -> Result<()> {
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    // the request line fits in the first read, the headers don't matter
+    let mut buf = [0; 4096];
+    let n = stream.read(&mut buf)?;
+    let request = String::from_utf8_lossy(&buf[..n]);
+    let path = request.split_whitespace().nth(1).unwrap_or_default();
+
+    let (status, body) = match path {
+        "/metrics" => ("200 OK", encode(&counters.lock().unwrap())),
+        _ => ("404 Not Found", "not found, try /metrics\n".to_string()),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\n\
+         Content-Type: text/plain; version=0.0.4; charset=utf-8\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n\r\n",
+        body.len()
+    )?;
+    stream.write_all(body.as_bytes())?;
+    stream.flush()?;
+
+    Ok(())
+}
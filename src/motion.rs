@@ -0,0 +1,47 @@
+//! Telling how much of the picture moved between frames.
+
+use crate::capture::Frame;
+use crate::convert::{decode_frame, resize};
+use eyre::Result;
+use image::GrayImage;
+
+// frames are compared at this size, which is plenty to notice someone
+// walking by and ignores single noisy pixels
+const SAMPLE_WIDTH: u32 = 64;
+const SAMPLE_HEIGHT: u32 = 48;
+// how much a sample has to change in brightness to count as moved, keeps
+// sensor noise out
+const PIXEL_THRESHOLD: u8 = 24;
+
+/// Compares every frame with the one before it.
+#[derive(Default)]
+pub struct MotionDetector {
+    previous: Option<GrayImage>,
+}
+
+impl MotionDetector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fraction of the picture that changed since the last frame, from 0 to
+    /// 1. The first frame has nothing to compare with and is 0.
+    pub fn update(&mut self, frame: &Frame) -> Result<f32> {
+        let sample = resize(&decode_frame(frame)?, SAMPLE_WIDTH, SAMPLE_HEIGHT)?;
+
+        let changed = match &self.previous {
+            Some(previous) => {
+                let moved = sample
+                    .iter()
+                    .zip(previous.iter())
+                    .filter(|(a, b)| a.abs_diff(**b) > PIXEL_THRESHOLD)
+                    .count();
+                moved as f32 / sample.len() as f32
+            }
+            None => 0.0,
+        };
+        self.previous = Some(sample);
+
+        Ok(changed)
+    }
+}
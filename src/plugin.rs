@@ -0,0 +1,428 @@
+//! Native filters and sources loaded at startup from shared libraries,
+//! enabled with the `plugins` feature.
+//!
+//! See `include/asciicam_plugin.h` for the C interface a plugin exports.
+//! Every library in [`default_plugins_dir`] is loaded the first time
+//! [`plugins`] is called and never unloaded.
+
+use crate::capture::{CaptureSource, Frame, FrameMeta, PixelFormat, SourceFormat};
+use crate::filter::Stage;
+use eyre::{eyre, Result};
+use image::{GrayImage, RgbImage};
+use libloading::Library;
+use std::env;
+use std::ffi::{c_char, c_int, c_void, CStr, CString};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::ptr;
+use std::slice;
+use std::str::FromStr;
+use std::sync::OnceLock;
+use std::time::Instant;
+use tracing::debug;
+
+pub const ASCIICAM_PLUGIN_ABI: u32 = 1;
+
+// the same as in asciicam.h
+pub const ASCIICAM_FORMAT_MJPEG: u32 = 0;
+pub const ASCIICAM_FORMAT_GRAY8: u32 = 1;
+pub const ASCIICAM_FORMAT_RGB8: u32 = 2;
+
+/// A frame handed to a filter plugin to change in place.
+#[repr(C)]
+pub struct AsciicamImage {
+    /// `width * height` bytes.
+    pub luma: *mut u8,
+    /// `width * height * 3` bytes, or NULL unless shown in color.
+    pub rgb: *mut u8,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[repr(C)]
+pub struct AsciicamFilterPlugin {
+    pub name: *const c_char,
+    pub create: extern "C" fn(args: *const c_char) -> *mut c_void,
+    pub apply: extern "C" fn(state: *mut c_void, image: *mut AsciicamImage) -> c_int,
+    pub destroy: extern "C" fn(state: *mut c_void),
+}
+
+/// A frame handed out by a source plugin, owned by the plugin.
+#[repr(C)]
+pub struct AsciicamFrame {
+    /// One of the `ASCIICAM_FORMAT_*` constants.
+    pub pixel_format: u32,
+    pub width: u32,
+    pub height: u32,
+    pub data: *const u8,
+    pub len: usize,
+}
+
+#[repr(C)]
+pub struct AsciicamSourcePlugin {
+    pub name: *const c_char,
+    pub open: extern "C" fn(args: *const c_char) -> *mut c_void,
+    pub next_frame: extern "C" fn(state: *mut c_void, frame: *mut AsciicamFrame) -> c_int,
+    pub close: extern "C" fn(state: *mut c_void),
+}
+
+/// What `asciicam_plugin_init` returns.
+#[repr(C)]
+pub struct AsciicamPlugin {
+    pub abi_version: u32,
+    pub filters: *const AsciicamFilterPlugin,
+    pub filter_count: usize,
+    pub sources: *const AsciicamSourcePlugin,
+    pub source_count: usize,
+}
+
+// the plugin tables are static data of libraries that are never unloaded,
+// and only read
+unsafe impl Send for AsciicamFilterPlugin {}
+unsafe impl Sync for AsciicamFilterPlugin {}
+unsafe impl Send for AsciicamSourcePlugin {}
+unsafe impl Sync for AsciicamSourcePlugin {}
+
+/// Where plugins are loaded from: `$XDG_CONFIG_HOME/asciicam/plugins`, or
+/// `~/.config/asciicam/plugins`.
+pub fn default_plugins_dir() -> PathBuf {
+    let config = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) => PathBuf::from(dir),
+        None => match env::var_os("HOME") {
+            Some(home) => PathBuf::from(home).join(".config"),
+            None => env::temp_dir(),
+        },
+    };
+
+    config.join("asciicam").join("plugins")
+}
+
+/// The filters and sources of every plugin that loaded.
+pub struct Plugins {
+    // kept loaded for as long as the process runs, the tables below point
+    // into them
+    _libraries: Vec<Library>,
+    filters: Vec<(String, &'static AsciicamFilterPlugin)>,
+    sources: Vec<(String, &'static AsciicamSourcePlugin)>,
+    // the libraries that didn't load and why, --filter plugin=... loads
+    // them before there is a log to tell
+    skipped: Vec<(PathBuf, String)>,
+}
+
+static PLUGINS: OnceLock<Plugins> = OnceLock::new();
+
+/// The plugins in [`default_plugins_dir`], loaded the first time this is
+/// called. A library that fails to load is left out, see
+/// [`Plugins::skipped`], so one broken plugin doesn't keep asciicam from
+/// starting.
+pub fn plugins() -> &'static Plugins {
+    PLUGINS.get_or_init(|| Plugins::discover(&default_plugins_dir()))
+}
+
+impl Plugins {
+    fn discover(dir: &Path) -> Self {
+        let mut plugins = Plugins {
+            _libraries: Vec::new(),
+            filters: Vec::new(),
+            sources: Vec::new(),
+            skipped: Vec::new(),
+        };
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            // no directory, no plugins
+            Err(_) => return plugins,
+        };
+
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                path.extension()
+                    .is_some_and(|e| e == env::consts::DLL_EXTENSION)
+            })
+            .collect();
+        // the first plugin with a name wins, the same one every time
+        paths.sort();
+
+        for path in paths {
+            // SAFETY: running the initializers of whatever is in the plugin
+            // directory is what it is there for
+            match unsafe { plugins.load(&path) } {
+                Ok(()) => debug!("loaded plugin {}", path.display()),
+                Err(e) => plugins.skipped.push((path, e.to_string())),
+            }
+        }
+
+        plugins
+    }
+
+    unsafe fn load(&mut self, path: &Path) -> Result<()> {
+        let library = Library::new(path)?;
+        let init =
+            *library.get::<extern "C" fn() -> *const AsciicamPlugin>(b"asciicam_plugin_init\0")?;
+        let plugin = init();
+        if plugin.is_null() {
+            return Err(eyre!("asciicam_plugin_init returned NULL"));
+        }
+        // the library is never unloaded, so neither is what it points to
+        let plugin: &'static AsciicamPlugin = &*plugin;
+        if plugin.abi_version != ASCIICAM_PLUGIN_ABI {
+            return Err(eyre!(
+                "built for plugin ABI {}, this asciicam has {ASCIICAM_PLUGIN_ABI}",
+                plugin.abi_version
+            ));
+        }
+
+        // all or nothing, the library goes away with an error
+        let filters = table(plugin.filters, plugin.filter_count)
+            .iter()
+            .map(|filter| Ok((name(filter.name)?, filter)))
+            .collect::<Result<Vec<_>>>()?;
+        let sources = table(plugin.sources, plugin.source_count)
+            .iter()
+            .map(|source| Ok((name(source.name)?, source)))
+            .collect::<Result<Vec<_>>>()?;
+        self.filters.extend(filters);
+        self.sources.extend(sources);
+        self._libraries.push(library);
+
+        Ok(())
+    }
+
+    /// Names of the filters, for `--filter plugin=<name>`.
+    pub fn filter_names(&self) -> impl Iterator<Item = &str> {
+        self.filters.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// The libraries that failed to load, and why.
+    pub fn skipped(&self) -> &[(PathBuf, String)] {
+        &self.skipped
+    }
+
+    /// Names of the sources, for `--input plugin:<name>`.
+    pub fn source_names(&self) -> impl Iterator<Item = &str> {
+        self.sources.iter().map(|(name, _)| name.as_str())
+    }
+}
+
+unsafe fn table<T>(items: *const T, count: usize) -> &'static [T] {
+    if items.is_null() || count == 0 {
+        return &[];
+    }
+
+    slice::from_raw_parts(items, count)
+}
+
+unsafe fn name(name: *const c_char) -> Result<String> {
+    if name.is_null() {
+        return Err(eyre!("a filter or source has no name"));
+    }
+
+    Ok(CStr::from_ptr(name).to_string_lossy().into_owned())
+}
+
+/// `name` and `args` of `name[:args]`, args empty when there are none.
+fn split_args(s: &str) -> (&str, &str) {
+    s.split_once(':').unwrap_or((s, ""))
+}
+
+/// A filter of a plugin with its args, a [`Filter::Plugin`](crate::filter::Filter::Plugin).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PluginFilter {
+    // into the filters of plugins()
+    index: usize,
+    // parsed once from the command line and kept for as long as the
+    // plugins are, which is until the process exits
+    args: &'static str,
+}
+
+impl FromStr for PluginFilter {
+    type Err = eyre::Report;
+
+    /// Parses `<name>[:<args>]`.
+    fn from_str(s: &str) -> Result<Self> {
+        let (name, args) = split_args(s);
+        let index = plugins()
+            .filters
+            .iter()
+            .position(|(filter, _)| filter == name)
+            .ok_or_else(|| {
+                eyre!(
+                    "No plugin filter '{name}' in {}",
+                    default_plugins_dir().display()
+                )
+            })?;
+
+        Ok(Self {
+            index,
+            args: Box::leak(Box::<str>::from(args)),
+        })
+    }
+}
+
+impl PluginFilter {
+    /// A stage with state of its own, as a [`FilterChain`](crate::filter::FilterChain)
+    /// needs one.
+    pub fn stage(self) -> Result<Box<dyn Stage>> {
+        let (name, plugin) = &plugins().filters[self.index];
+        let args = CString::new(self.args)?;
+        let state = (plugin.create)(args.as_ptr());
+        if state.is_null() {
+            return Err(eyre!("Plugin filter '{name}' doesn't take '{}'", self.args));
+        }
+
+        Ok(Box::new(FilterState {
+            plugin: *plugin,
+            state,
+        }))
+    }
+}
+
+struct FilterState {
+    plugin: &'static AsciicamFilterPlugin,
+    state: *mut c_void,
+}
+
+// the header promises the state is fine with moving between threads as
+// long as it is used from one at a time
+unsafe impl Send for FilterState {}
+
+impl Stage for FilterState {
+    fn apply(&mut self, luma: &mut GrayImage, rgb: Option<&mut RgbImage>) {
+        let mut image = AsciicamImage {
+            width: luma.width(),
+            height: luma.height(),
+            luma: luma.as_mut_ptr(),
+            rgb: rgb.map_or(ptr::null_mut(), |rgb| rgb.as_mut_ptr()),
+        };
+        if (self.plugin.apply)(self.state, &mut image) != 0 {
+            debug!("plugin filter failed on a frame");
+        }
+    }
+}
+
+impl Drop for FilterState {
+    fn drop(&mut self) {
+        (self.plugin.destroy)(self.state);
+    }
+}
+
+/// Frames from the source of a plugin, opened with
+/// `--input plugin:<name>[:<args>]`.
+pub struct PluginSource {
+    name: String,
+    plugin: &'static AsciicamSourcePlugin,
+    state: *mut c_void,
+    format: SourceFormat,
+    started: Instant,
+    sequence: u64,
+    // the first frame, taken to find out the size
+    pending: Option<Frame>,
+}
+
+// see FilterState
+unsafe impl Send for PluginSource {}
+
+impl PluginSource {
+    /// Opens the source `name` of a plugin with `args`, `name[:args]` in
+    /// `spec`, and takes its first frame.
+    pub fn open(spec: &str) -> Result<Self> {
+        let (name, args) = split_args(spec);
+        let plugin = plugins()
+            .sources
+            .iter()
+            .find(|(source, _)| source == name)
+            .map(|(_, plugin)| *plugin)
+            .ok_or_else(|| {
+                eyre!(
+                    "No plugin source '{name}' in {}",
+                    default_plugins_dir().display()
+                )
+            })?;
+
+        let c_args = CString::new(args)?;
+        let state = (plugin.open)(c_args.as_ptr());
+        if state.is_null() {
+            return Err(eyre!("Plugin source '{name}' could not be opened"));
+        }
+
+        let mut source = Self {
+            name: name.to_string(),
+            plugin,
+            state,
+            format: SourceFormat {
+                width: 0,
+                height: 0,
+                pixel_format: PixelFormat::Mjpeg,
+            },
+            started: Instant::now(),
+            sequence: 0,
+            pending: None,
+        };
+        let first = source.next_frame()?;
+        source.pending = Some(first);
+
+        Ok(source)
+    }
+}
+
+impl CaptureSource for PluginSource {
+    fn next_frame(&mut self) -> Result<Frame> {
+        if let Some(frame) = self.pending.take() {
+            return Ok(frame);
+        }
+
+        let mut frame = AsciicamFrame {
+            pixel_format: 0,
+            width: 0,
+            height: 0,
+            data: ptr::null(),
+            len: 0,
+        };
+        if (self.plugin.next_frame)(self.state, &mut frame) != 0 || frame.data.is_null() {
+            return Err(eyre!("Plugin source '{}' has no more frames", self.name));
+        }
+
+        let pixel_format = match frame.pixel_format {
+            ASCIICAM_FORMAT_MJPEG => PixelFormat::Mjpeg,
+            ASCIICAM_FORMAT_GRAY8 => PixelFormat::Gray8,
+            ASCIICAM_FORMAT_RGB8 => PixelFormat::Rgb8,
+            format => {
+                return Err(eyre!(
+                    "Plugin source '{}' sent unknown pixel format {format}",
+                    self.name
+                ))
+            }
+        };
+        // SAFETY: the plugin keeps the data around until the next call
+        let data = unsafe { slice::from_raw_parts(frame.data, frame.len) }.to_vec();
+        self.format = SourceFormat {
+            width: frame.width,
+            height: frame.height,
+            pixel_format,
+        };
+
+        self.sequence += 1;
+        Ok(Frame {
+            meta: FrameMeta {
+                sequence: self.sequence,
+                timestamp: self.started.elapsed(),
+                bytes_used: data.len() as u32,
+                received: Some(Instant::now()),
+            },
+            data,
+            width: frame.width,
+            height: frame.height,
+            pixel_format,
+        })
+    }
+
+    fn format(&self) -> SourceFormat {
+        self.format
+    }
+}
+
+impl Drop for PluginSource {
+    fn drop(&mut self) {
+        (self.plugin.close)(self.state);
+    }
+}
@@ -0,0 +1,169 @@
+//! Python bindings, enabled with the `python` feature and built with
+//! maturin (see `pyproject.toml`).
+//!
+//! ```python
+//! import asciicam
+//!
+//! print(asciicam.list_devices())
+//! cam = asciicam.Camera(0)
+//! print(cam.capture_ascii(80, 24))
+//! ```
+
+use crate::capture::{
+    list_devices as list_v4l_devices, Camera, CaptureSource, Frame, FrameMeta, PixelFormat,
+};
+use crate::convert::decode_frame;
+use crate::render::{parse_charset, render_frame, AsciiRenderer, RenderOptions, DEFAULT_CHARSET};
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+fn to_py_err(e: eyre::Report) -> PyErr {
+    PyRuntimeError::new_err(format!("{e:#}"))
+}
+
+fn to_ascii(
+    frame: &Frame,
+    columns: u32,
+    rows: u32,
+    charset: Option<&str>,
+    mirror: bool,
+) -> PyResult<String> {
+    let charset = match charset {
+        None => DEFAULT_CHARSET.to_vec(),
+        Some(charset) => {
+            parse_charset(charset).map_err(|e| PyValueError::new_err(e.to_string()))?
+        }
+    };
+
+    let options = RenderOptions {
+        mirror,
+        color: false,
+        ..RenderOptions::default()
+    };
+
+    let grid = render_frame(
+        frame,
+        &AsciiRenderer::new(&charset),
+        columns,
+        rows,
+        &options,
+    )
+    .map_err(to_py_err)?;
+
+    Ok(grid.to_text())
+}
+
+/// A v4l webcam.
+#[pyclass(unsendable, name = "Camera")]
+struct PyCamera {
+    inner: Camera,
+}
+
+#[pymethods]
+impl PyCamera {
+    #[new]
+    #[pyo3(signature = (device = 0, width = None, height = None))]
+    fn new(device: usize, width: Option<u32>, height: Option<u32>) -> PyResult<Self> {
+        let resolution = match (width, height) {
+            (Some(width), Some(height)) => Some((width, height)),
+            (None, None) => None,
+            _ => {
+                return Err(PyValueError::new_err(
+                    "width and height must be given together",
+                ))
+            }
+        };
+
+        Ok(Self {
+            inner: Camera::open_with(device, resolution).map_err(to_py_err)?,
+        })
+    }
+
+    #[getter]
+    fn width(&self) -> u32 {
+        self.inner.width()
+    }
+
+    #[getter]
+    fn height(&self) -> u32 {
+        self.inner.height()
+    }
+
+    /// Captures a frame and returns the raw JPEG bytes.
+    fn capture<'py>(&mut self, py: Python<'py>) -> PyResult<&'py PyBytes> {
+        let buf = self.inner.next_buffer().map_err(to_py_err)?;
+        Ok(PyBytes::new(py, buf))
+    }
+
+    /// Captures a frame and returns `(width, height, pixels)` with one byte
+    /// of luma per pixel.
+    fn capture_gray<'py>(&mut self, py: Python<'py>) -> PyResult<(u32, u32, &'py PyBytes)> {
+        let frame = self.inner.next_frame().map_err(to_py_err)?;
+        let img = decode_frame(&frame).map_err(to_py_err)?;
+        Ok((img.width(), img.height(), PyBytes::new(py, img.as_raw())))
+    }
+
+    /// Captures a frame and converts it to ascii art.
+    #[pyo3(signature = (columns = 80, rows = 24, charset = None, mirror = true))]
+    fn capture_ascii(
+        &mut self,
+        columns: u32,
+        rows: u32,
+        charset: Option<&str>,
+        mirror: bool,
+    ) -> PyResult<String> {
+        let frame = self.inner.next_frame().map_err(to_py_err)?;
+        to_ascii(&frame, columns, rows, charset, mirror)
+    }
+}
+
+/// Lists the v4l devices as `(index, path, name)` tuples.
+#[pyfunction]
+fn list_devices() -> Vec<(usize, String, Option<String>)> {
+    list_v4l_devices()
+        .into_iter()
+        .map(|d| (d.index, d.path.display().to_string(), d.name))
+        .collect()
+}
+
+/// Converts an image to ascii art. `format` is one of "mjpeg", "gray8" or
+/// "rgb8", `width` and `height` are only needed for the raw formats.
+#[pyfunction]
+#[pyo3(signature = (data, columns = 80, rows = 24, width = 0, height = 0, format = "mjpeg", charset = None, mirror = false))]
+#[allow(clippy::too_many_arguments)]
+fn convert(
+    data: &[u8],
+    columns: u32,
+    rows: u32,
+    width: u32,
+    height: u32,
+    format: &str,
+    charset: Option<&str>,
+    mirror: bool,
+) -> PyResult<String> {
+    let pixel_format = match format {
+        "mjpeg" => PixelFormat::Mjpeg,
+        "gray8" => PixelFormat::Gray8,
+        "rgb8" => PixelFormat::Rgb8,
+        _ => return Err(PyValueError::new_err(format!("Unknown format '{format}'"))),
+    };
+
+    let frame = Frame {
+        data: data.to_vec(),
+        width,
+        height,
+        pixel_format,
+        meta: FrameMeta::default(),
+    };
+
+    to_ascii(&frame, columns, rows, charset, mirror)
+}
+
+#[pymodule]
+fn asciicam(_py: Python<'_>, m: &PyModule) -> PyResult<()> {
+    m.add_class::<PyCamera>()?;
+    m.add_function(wrap_pyfunction!(list_devices, m)?)?;
+    m.add_function(wrap_pyfunction!(convert, m)?)?;
+    Ok(())
+}
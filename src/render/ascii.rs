@@ -0,0 +1,39 @@
+use super::{CharLut, Grid, Renderer};
+use image::{GrayImage, RgbImage};
+use rayon::prelude::*;
+
+/// Maps every pixel onto a character from a charset ordered dark to bright.
+pub struct AsciiRenderer {
+    chars: CharLut,
+}
+
+impl AsciiRenderer {
+    pub fn new(charset: &[char]) -> Self {
+        Self {
+            chars: CharLut::new(charset),
+        }
+    }
+}
+
+impl Renderer for AsciiRenderer {
+    fn chars(&self) -> Option<&CharLut> {
+        Some(&self.chars)
+    }
+
+    fn render(&self, luma: &GrayImage, _rgb: Option<&RgbImage>) -> Grid {
+        let mut grid = Grid::default();
+        self.render_into(luma, None, &mut grid);
+        grid
+    }
+
+    fn render_into(&self, luma: &GrayImage, _rgb: Option<&RgbImage>, grid: &mut Grid) {
+        grid.reset(luma.width(), luma.height());
+
+        // a row at a time, which leaves the compiler nothing to bounds check,
+        // on as many threads as there are cores
+        let width = luma.width().max(1) as usize;
+        grid.par_rows_mut()
+            .zip(luma.as_raw().par_chunks_exact(width))
+            .for_each(|(cells, pixels)| self.chars.map_row(pixels, cells));
+    }
+}
@@ -0,0 +1,51 @@
+use super::{Grid, Renderer};
+use image::{GrayImage, RgbImage};
+
+/// Renders a 2x4 block of pixels per cell as a braille pattern, with a dot
+/// for every pixel brighter than the threshold.
+pub struct BrailleRenderer {
+    pub threshold: u8,
+}
+
+impl Default for BrailleRenderer {
+    fn default() -> Self {
+        Self { threshold: 128 }
+    }
+}
+
+// bit of each dot in the braille block, indexed by [row][column]
+const DOTS: [[u32; 2]; 4] = [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+
+impl Renderer for BrailleRenderer {
+    fn cell_size(&self) -> (u32, u32) {
+        (2, 4)
+    }
+
+    fn render(&self, luma: &GrayImage, _rgb: Option<&RgbImage>) -> Grid {
+        let mut grid = Grid::default();
+        self.render_into(luma, None, &mut grid);
+        grid
+    }
+
+    fn render_into(&self, luma: &GrayImage, _rgb: Option<&RgbImage>, grid: &mut Grid) {
+        grid.reset(luma.width() / 2, luma.height() / 4);
+
+        for y in 0..grid.height() {
+            for x in 0..grid.width() {
+                let mut bits = 0;
+
+                for (dy, row) in DOTS.iter().enumerate() {
+                    for (dx, bit) in row.iter().enumerate() {
+                        let pixel = luma.get_pixel(x * 2 + dx as u32, y * 4 + dy as u32)[0];
+                        if pixel > self.threshold {
+                            bits |= bit;
+                        }
+                    }
+                }
+
+                // every combination of dots is a valid braille codepoint
+                grid.get_mut(x, y).glyph = char::from_u32(0x2800 + bits).unwrap_or(' ');
+            }
+        }
+    }
+}
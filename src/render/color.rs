@@ -0,0 +1,53 @@
+use super::{CharLut, Grid, Renderer};
+use image::{GrayImage, Rgb, RgbImage};
+use rayon::prelude::*;
+
+/// Like [`AsciiRenderer`](super::AsciiRenderer), but colors every character
+/// with the color of its pixel.
+pub struct ColorRenderer {
+    chars: CharLut,
+}
+
+impl ColorRenderer {
+    pub fn new(charset: &[char]) -> Self {
+        Self {
+            chars: CharLut::new(charset),
+        }
+    }
+}
+
+impl Renderer for ColorRenderer {
+    fn needs_color(&self) -> bool {
+        true
+    }
+
+    fn chars(&self) -> Option<&CharLut> {
+        Some(&self.chars)
+    }
+
+    fn render(&self, luma: &GrayImage, rgb: Option<&RgbImage>) -> Grid {
+        let mut grid = Grid::default();
+        self.render_into(luma, rgb, &mut grid);
+        grid
+    }
+
+    fn render_into(&self, luma: &GrayImage, rgb: Option<&RgbImage>, grid: &mut Grid) {
+        grid.reset(luma.width(), luma.height());
+
+        let width = luma.width().max(1) as usize;
+        let colors = rgb.map(|rgb| rgb.as_raw());
+        grid.par_rows_mut()
+            .zip(luma.as_raw().par_chunks_exact(width))
+            .enumerate()
+            .for_each(|(y, (cells, pixels))| {
+                self.chars.map_row(pixels, cells);
+
+                let row = y * width * 3..(y + 1) * width * 3;
+                if let Some(colors) = colors.and_then(|colors| colors.get(row)) {
+                    for (cell, color) in cells.iter_mut().zip(colors.chunks_exact(3)) {
+                        cell.fg = Some(Rgb([color[0], color[1], color[2]]));
+                    }
+                }
+            });
+    }
+}
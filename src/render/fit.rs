@@ -0,0 +1,99 @@
+use eyre::{eyre, Report, Result};
+use std::str::FromStr;
+
+/// Terminal cells are roughly twice as tall as they are wide.
+pub const CELL_ASPECT: f64 = 2.0;
+
+/// How an image is fitted into a grid with a different aspect ratio.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FitMode {
+    /// Scale to the full grid, distorting the image.
+    #[default]
+    Stretch,
+    /// Scale the whole image to fit inside the grid, leaving blank borders.
+    Fit,
+    /// Scale the image to cover the whole grid, cropping the overflow.
+    Fill,
+}
+
+/// Where a fitted image ends up.
+pub(crate) struct Layout {
+    /// The part of the source image to use, as (x, y, width, height).
+    pub crop: Option<(u32, u32, u32, u32)>,
+    /// Size of the rendered image in cells.
+    pub columns: u32,
+    pub rows: u32,
+    /// Offset of the rendered image within the grid.
+    pub x: u32,
+    pub y: u32,
+}
+
+impl FitMode {
+    /// Lays out a `width` x `height` image in a grid of `columns` x `rows`.
+    pub(crate) fn layout(self, width: u32, height: u32, columns: u32, rows: u32) -> Layout {
+        let stretch = Layout {
+            crop: None,
+            columns,
+            rows,
+            x: 0,
+            y: 0,
+        };
+
+        if width == 0 || height == 0 || columns == 0 || rows == 0 {
+            return stretch;
+        }
+
+        let image_aspect = width as f64 / height as f64;
+        let grid_aspect = columns as f64 / (rows as f64 * CELL_ASPECT);
+
+        match self {
+            FitMode::Stretch => stretch,
+            FitMode::Fit => {
+                let (c, r) = if image_aspect > grid_aspect {
+                    let r = (columns as f64 / image_aspect / CELL_ASPECT).round() as u32;
+                    (columns, r.clamp(1, rows))
+                } else {
+                    let c = (rows as f64 * CELL_ASPECT * image_aspect).round() as u32;
+                    (c.clamp(1, columns), rows)
+                };
+
+                Layout {
+                    crop: None,
+                    columns: c,
+                    rows: r,
+                    x: (columns - c) / 2,
+                    y: (rows - r) / 2,
+                }
+            }
+            FitMode::Fill => {
+                let crop = if image_aspect > grid_aspect {
+                    let w = ((height as f64 * grid_aspect).round() as u32).clamp(1, width);
+                    ((width - w) / 2, 0, w, height)
+                } else {
+                    let h = ((width as f64 / grid_aspect).round() as u32).clamp(1, height);
+                    (0, (height - h) / 2, width, h)
+                };
+
+                Layout {
+                    crop: Some(crop),
+                    ..stretch
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for FitMode {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "stretch" => Ok(FitMode::Stretch),
+            "fit" => Ok(FitMode::Fit),
+            "fill" => Ok(FitMode::Fill),
+            _ => Err(eyre!(
+                "Unknown fit mode '{s}', expected one of stretch, fit, fill"
+            )),
+        }
+    }
+}
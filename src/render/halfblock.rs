@@ -0,0 +1,54 @@
+use super::{Grid, Renderer};
+use image::{GrayImage, RgbImage};
+
+/// Renders two pixels per cell using the upper half block character.
+///
+/// With color input the top pixel becomes the foreground and the bottom pixel
+/// the background, without it each half is either lit or dark.
+pub struct HalfBlockRenderer;
+
+const THRESHOLD: u8 = 128;
+
+impl Renderer for HalfBlockRenderer {
+    fn cell_size(&self) -> (u32, u32) {
+        (1, 2)
+    }
+
+    fn needs_color(&self) -> bool {
+        true
+    }
+
+    fn render(&self, luma: &GrayImage, rgb: Option<&RgbImage>) -> Grid {
+        let mut grid = Grid::default();
+        self.render_into(luma, rgb, &mut grid);
+        grid
+    }
+
+    fn render_into(&self, luma: &GrayImage, rgb: Option<&RgbImage>, grid: &mut Grid) {
+        grid.reset(luma.width(), luma.height() / 2);
+
+        for y in 0..grid.height() {
+            for x in 0..grid.width() {
+                let cell = grid.get_mut(x, y);
+
+                match rgb {
+                    Some(rgb) => {
+                        cell.glyph = '▀';
+                        cell.fg = Some(*rgb.get_pixel(x, y * 2));
+                        cell.bg = Some(*rgb.get_pixel(x, y * 2 + 1));
+                    }
+                    None => {
+                        let top = luma.get_pixel(x, y * 2)[0] >= THRESHOLD;
+                        let bottom = luma.get_pixel(x, y * 2 + 1)[0] >= THRESHOLD;
+                        cell.glyph = match (top, bottom) {
+                            (false, false) => ' ',
+                            (true, false) => '▀',
+                            (false, true) => '▄',
+                            (true, true) => '█',
+                        };
+                    }
+                }
+            }
+        }
+    }
+}
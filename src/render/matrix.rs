@@ -0,0 +1,155 @@
+use super::{Grid, Renderer};
+use image::{GrayImage, Rgb, RgbImage};
+use std::sync::Mutex;
+
+// what the rain is made of, only what the rasterizer's font has
+const GLYPHS: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ$+-*/=%\"'#&_(),.;:?!\\|{}<>[]^~";
+// how long a trail is, in cells
+const TRAIL: f32 = 12.0;
+// how bright the picture shows through where no rain is falling, out of 1
+const BACKGROUND: f32 = 0.3;
+// below this a cell is left blank
+const MIN_LEVEL: f32 = 0.06;
+// the chance a column starts a new drop each frame, in the dark and where
+// the picture is at its brightest
+const SPAWN_DARK: f32 = 0.01;
+const SPAWN_BRIGHT: f32 = 0.25;
+// the chance a glyph under the rain is swapped for another each frame
+const FLICKER: f32 = 0.04;
+
+const GREEN: Rgb<u8> = Rgb([0, 255, 70]);
+const HEAD: Rgb<u8> = Rgb([200, 255, 210]);
+
+/// Green glyphs raining down the terminal, with the picture of the camera
+/// in them: where it is bright the rain falls thicker and brighter, and
+/// the picture shows faintly where none is falling. The rain keeps falling
+/// from one frame to the next, so every frame is rendered a little further
+/// along.
+#[derive(Default)]
+pub struct MatrixRenderer {
+    rain: Mutex<Rain>,
+}
+
+#[derive(Default)]
+struct Rain {
+    width: u32,
+    height: u32,
+    // the row the drop of every column is at, and how many rows it falls
+    // a frame, none between drops
+    drops: Vec<Option<(f32, f32)>>,
+    // the glyph of every cell, an index into GLYPHS
+    glyphs: Vec<u8>,
+    random: u64,
+}
+
+impl Renderer for MatrixRenderer {
+    fn render(&self, luma: &GrayImage, _rgb: Option<&RgbImage>) -> Grid {
+        let mut grid = Grid::default();
+        self.render_into(luma, None, &mut grid);
+        grid
+    }
+
+    fn render_into(&self, luma: &GrayImage, _rgb: Option<&RgbImage>, grid: &mut Grid) {
+        let mut rain = self.rain.lock().unwrap_or_else(|e| e.into_inner());
+        rain.fall(luma);
+
+        grid.reset(luma.width(), luma.height());
+        for (y, row) in grid.rows_mut().enumerate() {
+            for (x, cell) in row.iter_mut().enumerate() {
+                let brightness = luma.get_pixel(x as u32, y as u32).0[0] as f32 / 255.0;
+                // 1 at the head of the drop, fading out along its trail
+                let trail = match rain.drops[x] {
+                    Some((head, _)) if head >= y as f32 && head - (y as f32) < TRAIL => {
+                        1.0 - (head - y as f32) / TRAIL
+                    }
+                    _ => 0.0,
+                };
+
+                let level = (trail * (0.4 + 0.6 * brightness)).max(brightness * BACKGROUND);
+                if level < MIN_LEVEL {
+                    continue;
+                }
+
+                cell.glyph = GLYPHS[rain.glyphs[y * luma.width() as usize + x] as usize] as char;
+                let color = if trail > 1.0 - 1.0 / TRAIL {
+                    HEAD
+                } else {
+                    GREEN
+                };
+                cell.fg = Some(color.map(|c| (c as f32 * level) as u8));
+            }
+        }
+    }
+}
+
+impl Rain {
+    /// Moves every drop along by a frame, starting new ones in the columns
+    /// where `luma` is bright.
+    fn fall(&mut self, luma: &GrayImage) {
+        let (width, height) = luma.dimensions();
+        if (self.width, self.height) != (width, height) {
+            self.width = width;
+            self.height = height;
+            self.drops = vec![None; width as usize];
+            if self.random == 0 {
+                self.random = 0x2545_f491_4f6c_dd1d;
+            }
+            self.glyphs = (0..width * height)
+                .map(|_| (self.next() % GLYPHS.len() as u64) as u8)
+                .collect();
+        }
+
+        for x in 0..width as usize {
+            match self.drops[x] {
+                Some((head, speed)) if head - TRAIL < height as f32 => {
+                    self.drops[x] = Some((head + speed, speed));
+                }
+                _ => {
+                    let brightness = column_brightness(luma, x as u32);
+                    let chance = SPAWN_DARK + (SPAWN_BRIGHT - SPAWN_DARK) * brightness;
+                    self.drops[x] = if self.chance(chance) {
+                        let speed = 0.3 + 0.7 * self.fraction();
+                        Some((0.0, speed))
+                    } else {
+                        None
+                    };
+                }
+            }
+        }
+
+        let mut glyphs = std::mem::take(&mut self.glyphs);
+        for glyph in &mut glyphs {
+            if self.chance(FLICKER) {
+                *glyph = (self.next() % GLYPHS.len() as u64) as u8;
+            }
+        }
+        self.glyphs = glyphs;
+    }
+
+    /// xorshift, plenty random for rain.
+    fn next(&mut self) -> u64 {
+        self.random ^= self.random << 13;
+        self.random ^= self.random >> 7;
+        self.random ^= self.random << 17;
+        self.random
+    }
+
+    /// Anything from 0 to 1.
+    fn fraction(&mut self) -> f32 {
+        (self.next() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    fn chance(&mut self, chance: f32) -> bool {
+        self.fraction() < chance
+    }
+}
+
+/// How bright column `x` of `luma` is on average, from 0 to 1.
+fn column_brightness(luma: &GrayImage, x: u32) -> f32 {
+    let height = luma.height().max(1);
+    let sum: u32 = (0..luma.height())
+        .map(|y| luma.get_pixel(x, y).0[0] as u32)
+        .sum();
+
+    sum as f32 / height as f32 / 255.0
+}
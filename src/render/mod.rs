@@ -0,0 +1,1046 @@
+//! Turning images into grids of characters.
+//!
+//! A [`Renderer`] maps a (resized) image onto a [`Grid`] of [`Cell`]s, which
+//! [`write_grid`] then writes out with the escape codes needed for any
+//! colors.
+
+mod ascii;
+mod braille;
+mod color;
+mod fit;
+mod halfblock;
+mod matrix;
+mod raster;
+
+pub use ascii::AsciiRenderer;
+pub use braille::BrailleRenderer;
+pub use color::ColorRenderer;
+pub use fit::{FitMode, CELL_ASPECT};
+pub use halfblock::HalfBlockRenderer;
+pub use matrix::MatrixRenderer;
+pub use raster::{rasterize, GLYPH_HEIGHT, GLYPH_WIDTH};
+
+use crate::capture::{Frame, FrameMeta};
+use crate::convert::{decode_frame_into, decode_frame_rgb_into, LumaWeights, Resizer};
+use crate::filter::{gray_world, phosphor, ChromaKey, Filter, FilterChain, Tint, PHOSPHOR};
+use crate::stabilize::Stabilizer;
+use crate::stats::{StageTimings, Stopwatch};
+use eyre::{eyre, Report, Result};
+use fit::Layout;
+use image::imageops::flip_horizontal_in_place;
+use image::{GrayImage, ImageBuffer, Pixel, Rgb, RgbImage};
+use rayon::prelude::*;
+use std::fmt::Write as _;
+use std::io::Write;
+use std::mem;
+use std::str::FromStr;
+use std::sync::OnceLock;
+use tracing::trace_span;
+
+/// The default charset, ordered from dark to bright.
+///
+/// The extra char at the end is to avoid floating point arithmetic and won't
+/// be displayed.
+pub const DEFAULT_CHARSET: &[char] = &[
+    ' ', ' ', ' ', '.', ':', '-', '=', '+', '*', '#', '%', '@', '?',
+];
+
+/// The charset of night mode, like [`DEFAULT_CHARSET`] but with more of the
+/// dark end left blank, where the noise of a camera turned up all the way
+/// would otherwise sparkle.
+pub const NIGHT_CHARSET: &[char] = &[
+    ' ', ' ', ' ', ' ', ' ', '.', ':', '-', '=', '+', '*', '#', '@', '?',
+];
+
+/// Charsets to cycle through, ordered from dark to bright. Pass them through
+/// [`parse_charset`] before use.
+pub const CHARSETS: &[&str] = &[
+    " .:-=+*#%@",
+    " .'`^\",:;Il!i><~+_-?][}{1)(|\\/tfjrxnuvczXYUJCLQ0OZmwqpdbkhao*#MW&8%B@$",
+    " ░▒▓█",
+    " .oO@",
+    " #",
+];
+
+/// Turns a user supplied charset, ordered from dark to bright, into one
+/// usable with [`CharArr`] by repeating the brightest char, see
+/// [`DEFAULT_CHARSET`].
+pub fn parse_charset(charset: &str) -> Result<Vec<char>> {
+    let mut chars: Vec<char> = charset.chars().collect();
+
+    match chars.last() {
+        Some(&last) if chars.len() >= 2 => {
+            chars.push(last);
+            Ok(chars)
+        }
+        _ => Err(eyre!("The charset needs at least two characters")),
+    }
+}
+
+/// Maps a single grayscale pixel onto a charset.
+pub struct CharArr<'c> {
+    charset: &'c [char],
+    pixel: u8,
+}
+
+impl<'c> CharArr<'c> {
+    pub fn new(charset: &'c [char], pixel: u8) -> Self {
+        Self { charset, pixel }
+    }
+
+    /// Returns the char the pixel maps to.
+    pub fn get_char(self) -> char {
+        let idx: usize = (self.pixel as usize * (self.charset.len() - 1)) / 255_usize;
+        self.charset[idx]
+    }
+}
+
+/// [`CharArr`] worked out for every brightness up front, so mapping a pixel
+/// is a lookup instead of a multiply and a divide.
+#[derive(Clone)]
+pub struct CharLut {
+    chars: [char; 256],
+    // the glyph every brightness maps to, counting runs of the same char
+    // from dark to bright, and the darkest and brightest brightness of
+    // every glyph
+    glyphs: [u8; 256],
+    bands: [(u8, u8); 256],
+}
+
+impl CharLut {
+    pub fn new(charset: &[char]) -> Self {
+        let mut chars = [' '; 256];
+        for (pixel, c) in chars.iter_mut().enumerate() {
+            *c = CharArr::new(charset, pixel as u8).get_char();
+        }
+
+        let mut glyphs = [0; 256];
+        let mut bands = [(0, 0); 256];
+        let mut glyph = 0;
+        for (pixel, pair) in (1..).zip(chars.windows(2)) {
+            if pair[0] != pair[1] {
+                glyph += 1;
+                bands[glyph].0 = pixel as u8;
+            }
+            glyphs[pixel] = glyph as u8;
+            bands[glyph].1 = pixel as u8;
+        }
+
+        Self {
+            chars,
+            glyphs,
+            bands,
+        }
+    }
+
+    /// Returns the char `pixel` maps to.
+    pub fn get(&self, pixel: u8) -> char {
+        self.chars[pixel as usize]
+    }
+
+    /// Keeps every pixel of `luma` on the glyph its cell was shown with,
+    /// kept in `shown`, until it leaves the brightness of that glyph by
+    /// more than `margin`. A cell sitting on the edge between two chars
+    /// would otherwise flip between them with every frame. Pixels within
+    /// the margin are clamped into the glyph, the ones inside it are left
+    /// alone. Everything is shown as it is when the size changes.
+    pub fn hold(&self, luma: &mut [u8], shown: &mut Vec<u8>, margin: u8) {
+        if shown.len() != luma.len() {
+            shown.clear();
+            shown.extend(luma.iter().map(|&v| self.glyphs[v as usize]));
+            return;
+        }
+
+        for (glyph, v) in shown.iter_mut().zip(luma) {
+            let (lo, hi) = self.bands[*glyph as usize];
+            if (lo.saturating_sub(margin)..=hi.saturating_add(margin)).contains(v) {
+                *v = (*v).clamp(lo, hi);
+            } else {
+                *glyph = self.glyphs[*v as usize];
+            }
+        }
+    }
+
+    /// Sets the glyphs of a row of cells to the chars its pixels map to,
+    /// eight at a time with AVX2 on cpus that have it.
+    pub fn map_row(&self, pixels: &[u8], cells: &mut [Cell]) {
+        let len = pixels.len().min(cells.len());
+        #[cfg(target_arch = "x86_64")]
+        let done = if is_x86_feature_detected!("avx2") {
+            // SAFETY: the cpu has AVX2
+            unsafe { self.map_row_avx2(&pixels[..len], &mut cells[..len]) }
+        } else {
+            0
+        };
+        #[cfg(not(target_arch = "x86_64"))]
+        let done = 0;
+
+        self.map_row_scalar(&pixels[done..len], &mut cells[done..len]);
+    }
+
+    /// [`map_row`](Self::map_row) a pixel at a time, on any cpu.
+    pub fn map_row_scalar(&self, pixels: &[u8], cells: &mut [Cell]) {
+        for (cell, &pixel) in cells.iter_mut().zip(pixels) {
+            cell.glyph = self.get(pixel);
+        }
+    }
+
+    /// Gathers the chars of every eight pixels from the table at once and
+    /// returns how many pixels that was, the rest are left to the scalar
+    /// loop.
+    #[cfg(target_arch = "x86_64")]
+    #[target_feature(enable = "avx2")]
+    unsafe fn map_row_avx2(&self, pixels: &[u8], cells: &mut [Cell]) -> usize {
+        use std::arch::x86_64::*;
+
+        let table = self.chars.as_ptr() as *const i32;
+        let mut glyphs = [0u32; 8];
+        for (pixels, cells) in pixels.chunks_exact(8).zip(cells.chunks_exact_mut(8)) {
+            // the pixels widened to indices into the table, which are always
+            // in it
+            let indices = _mm256_cvtepu8_epi32(_mm_loadl_epi64(pixels.as_ptr() as *const __m128i));
+            let chars = _mm256_i32gather_epi32::<4>(table, indices);
+            _mm256_storeu_si256(glyphs.as_mut_ptr() as *mut __m256i, chars);
+
+            for (cell, &glyph) in cells.iter_mut().zip(&glyphs) {
+                // gathered from a table of chars
+                cell.glyph = char::from_u32_unchecked(glyph);
+            }
+        }
+
+        pixels.len() / 8 * 8
+    }
+}
+
+/// A single character cell with optional colors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cell {
+    pub glyph: char,
+    pub fg: Option<Rgb<u8>>,
+    pub bg: Option<Rgb<u8>>,
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Self {
+            glyph: ' ',
+            fg: None,
+            bg: None,
+        }
+    }
+}
+
+impl Cell {
+    pub fn new(glyph: char) -> Self {
+        Self {
+            glyph,
+            ..Self::default()
+        }
+    }
+}
+
+/// A rectangular grid of cells, stored row by row, along with the metadata
+/// of the frame it was rendered from.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Grid {
+    width: u32,
+    height: u32,
+    cells: Vec<Cell>,
+    meta: FrameMeta,
+}
+
+impl Grid {
+    /// Creates a grid filled with blank cells.
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            cells: vec![Cell::default(); width as usize * height as usize],
+            meta: FrameMeta::default(),
+        }
+    }
+
+    /// Makes this grid `width` x `height` blank cells, keeping its memory
+    /// when it has room for them.
+    pub fn reset(&mut self, width: u32, height: u32) {
+        self.width = width;
+        self.height = height;
+        self.cells.clear();
+        self.cells
+            .resize(width as usize * height as usize, Cell::default());
+        self.meta = FrameMeta::default();
+    }
+
+    /// Metadata of the frame this grid was rendered from.
+    pub fn meta(&self) -> &FrameMeta {
+        &self.meta
+    }
+
+    pub fn set_meta(&mut self, meta: FrameMeta) {
+        self.meta = meta;
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn cells(&self) -> &[Cell] {
+        &self.cells
+    }
+
+    pub fn get(&self, x: u32, y: u32) -> &Cell {
+        &self.cells[(y * self.width + x) as usize]
+    }
+
+    pub fn get_mut(&mut self, x: u32, y: u32) -> &mut Cell {
+        &mut self.cells[(y * self.width + x) as usize]
+    }
+
+    /// Iterates over the rows of the grid, top to bottom.
+    pub fn rows(&self) -> impl Iterator<Item = &[Cell]> {
+        // chunks panics on a zero chunk size, an empty grid has no rows anyway
+        self.cells.chunks(self.width.max(1) as usize)
+    }
+
+    /// Like [`rows`](Grid::rows), mutably.
+    pub fn rows_mut(&mut self) -> impl Iterator<Item = &mut [Cell]> {
+        self.cells.chunks_mut(self.width.max(1) as usize)
+    }
+
+    /// Like [`rows_mut`](Grid::rows_mut), handed out to the rayon thread
+    /// pool, in order.
+    pub fn par_rows_mut(&mut self) -> impl IndexedParallelIterator<Item = &mut [Cell]> {
+        self.cells.par_chunks_mut(self.width.max(1) as usize)
+    }
+
+    /// The glyphs of the grid without any colors, one line per row
+    /// terminated by `\n`.
+    pub fn to_text(&self) -> String {
+        let mut text = String::with_capacity(self.cells.len() + self.height as usize);
+
+        for row in self.rows() {
+            text.extend(row.iter().map(|cell| cell.glyph));
+            text.push('\n');
+        }
+
+        text
+    }
+
+    /// Writes `text` over the cells starting at (`x`, `y`), without colors,
+    /// clipping whatever doesn't fit.
+    pub fn print(&mut self, x: u32, y: u32, text: &str) {
+        if y >= self.height {
+            return;
+        }
+        for (x, glyph) in (x..self.width).zip(text.chars()) {
+            *self.get_mut(x, y) = Cell::new(glyph);
+        }
+    }
+
+    /// The grid stretched or shrunk to `width` x `height` cells, each one
+    /// taking the cell it lands on.
+    pub fn resized(&self, width: u32, height: u32) -> Grid {
+        let mut resized = Grid::new(width, height);
+        resized.meta = self.meta;
+        if self.width == 0 || self.height == 0 {
+            return resized;
+        }
+
+        for y in 0..height {
+            for x in 0..width {
+                *resized.get_mut(x, y) =
+                    *self.get(x * self.width / width, y * self.height / height);
+            }
+        }
+        resized
+    }
+
+    /// Copies `other` into this grid with its top left corner at (`x`, `y`),
+    /// clipping whatever doesn't fit.
+    pub fn blit(&mut self, other: &Grid, x: u32, y: u32) {
+        for oy in 0..other.height.min(self.height.saturating_sub(y)) {
+            for ox in 0..other.width.min(self.width.saturating_sub(x)) {
+                *self.get_mut(x + ox, y + oy) = *other.get(ox, oy);
+            }
+        }
+    }
+}
+
+/// Something that can turn an image into a [`Grid`].
+pub trait Renderer {
+    /// How many source pixels a single cell covers, as (columns, rows).
+    ///
+    /// The image passed to [`render`](Renderer::render) should be resized to
+    /// the grid size multiplied by this.
+    fn cell_size(&self) -> (u32, u32) {
+        (1, 1)
+    }
+
+    /// Whether the renderer makes use of color input.
+    fn needs_color(&self) -> bool {
+        false
+    }
+
+    /// The chars the renderer maps every pixel onto by its brightness, for
+    /// [`RenderOptions::hysteresis`]. `None` for renderers that pick them
+    /// some other way, which it then leaves alone.
+    fn chars(&self) -> Option<&CharLut> {
+        None
+    }
+
+    /// Renders the image into a grid of `luma.width() / cell_width` by
+    /// `luma.height() / cell_height` cells. `rgb`, when given, has the same
+    /// size as `luma`.
+    fn render(&self, luma: &GrayImage, rgb: Option<&RgbImage>) -> Grid;
+
+    /// Like [`render`](Renderer::render), but into `grid`, which the built
+    /// in renderers [`reset`](Grid::reset) instead of allocating another.
+    fn render_into(&self, luma: &GrayImage, rgb: Option<&RgbImage>, grid: &mut Grid) {
+        *grid = self.render(luma, rgb);
+    }
+}
+
+impl<R: Renderer + ?Sized> Renderer for Box<R> {
+    fn cell_size(&self) -> (u32, u32) {
+        (**self).cell_size()
+    }
+
+    fn needs_color(&self) -> bool {
+        (**self).needs_color()
+    }
+
+    fn chars(&self) -> Option<&CharLut> {
+        (**self).chars()
+    }
+
+    fn render(&self, luma: &GrayImage, rgb: Option<&RgbImage>) -> Grid {
+        (**self).render(luma, rgb)
+    }
+
+    fn render_into(&self, luma: &GrayImage, rgb: Option<&RgbImage>, grid: &mut Grid) {
+        (**self).render_into(luma, rgb, grid)
+    }
+}
+
+/// The built in renderers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderMode {
+    Ascii,
+    HalfBlock,
+    Braille,
+    Color,
+    /// Green glyphs raining down, thicker where the picture is bright.
+    Matrix,
+}
+
+impl RenderMode {
+    pub const ALL: [RenderMode; 5] = [
+        RenderMode::Ascii,
+        RenderMode::HalfBlock,
+        RenderMode::Braille,
+        RenderMode::Color,
+        RenderMode::Matrix,
+    ];
+
+    /// Creates the renderer for this mode. `charset` is used by the modes
+    /// that map luma onto characters.
+    pub fn renderer(self, charset: &[char]) -> Box<dyn Renderer> {
+        match self {
+            RenderMode::Ascii => Box::new(AsciiRenderer::new(charset)),
+            RenderMode::HalfBlock => Box::new(HalfBlockRenderer),
+            RenderMode::Braille => Box::new(BrailleRenderer::default()),
+            RenderMode::Color => Box::new(ColorRenderer::new(charset)),
+            RenderMode::Matrix => Box::new(MatrixRenderer::default()),
+        }
+    }
+
+    /// The name the mode is parsed from.
+    pub fn name(self) -> &'static str {
+        match self {
+            RenderMode::Ascii => "ascii",
+            RenderMode::HalfBlock => "half-block",
+            RenderMode::Braille => "braille",
+            RenderMode::Color => "color",
+            RenderMode::Matrix => "matrix",
+        }
+    }
+
+    /// The mode after this one, wrapping around.
+    pub fn next(self) -> Self {
+        let idx = Self::ALL.iter().position(|m| *m == self).unwrap_or(0);
+        Self::ALL[(idx + 1) % Self::ALL.len()]
+    }
+}
+
+impl FromStr for RenderMode {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "ascii" => Ok(RenderMode::Ascii),
+            "half-block" => Ok(RenderMode::HalfBlock),
+            "braille" => Ok(RenderMode::Braille),
+            "color" => Ok(RenderMode::Color),
+            "matrix" => Ok(RenderMode::Matrix),
+            _ => Err(eyre!(
+                "Unknown render mode '{s}', expected ascii, half-block, braille, color or matrix"
+            )),
+        }
+    }
+}
+
+/// How colors are written to the output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorMode {
+    /// No colors at all.
+    Mono,
+    /// The 256 color palette, for terminals without 24-bit color.
+    Ansi256,
+    /// 24-bit colors.
+    #[default]
+    TrueColor,
+}
+
+impl FromStr for ColorMode {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "mono" => Ok(ColorMode::Mono),
+            "256" => Ok(ColorMode::Ansi256),
+            "truecolor" => Ok(ColorMode::TrueColor),
+            _ => Err(eyre!(
+                "Unknown color mode '{s}', expected one of mono, 256, truecolor"
+            )),
+        }
+    }
+}
+
+/// Maps a color onto the closest entry of the 6x6x6 color cube or the
+/// grayscale ramp of the 256 color palette.
+pub fn ansi256(Rgb([r, g, b]): Rgb<u8>) -> u8 {
+    let (max, min) = (r.max(g).max(b), r.min(g).min(b));
+
+    if max - min < 10 {
+        // the grayscale ramp runs from 8 to 238 in steps of 10
+        return match max {
+            0..=3 => 16,
+            248.. => 231,
+            v => 232 + ((v as u16 - 3) / 10).min(23) as u8,
+        };
+    }
+
+    let level = |v: u8| ((v as u16 * 5 + 127) / 255) as u8;
+    16 + 36 * level(r) + 6 * level(g) + level(b)
+}
+
+/// Settings controlling how a frame is turned into a grid.
+#[derive(Debug, Clone)]
+pub struct RenderOptions {
+    /// Flip the image horizontally, like a mirror.
+    pub mirror: bool,
+    pub fit: FitMode,
+    /// Decode color for renderers that can use it.
+    pub color: bool,
+    /// Balance the colors in software, see [`gray_world`].
+    pub gray_world: bool,
+    /// Applied in order after resizing, see [`FilterChain`]. Only
+    /// [`render_frame_with`] remembers the frames before for the ones that
+    /// need them.
+    pub filters: Vec<Filter>,
+    /// How far the brightness of a cell has to leave the char it is shown
+    /// with before it changes, see [`CharLut::hold`]. Only
+    /// [`render_frame_with`] remembers the chars shown before.
+    pub hysteresis: Option<u8>,
+    /// Digital zoom into the center of the image, 1.0 shows all of it.
+    pub zoom: f32,
+    /// Undo the shake of a handheld camera, see [`Stabilizer`]. Only
+    /// [`render_frame_with`] remembers the frames before.
+    pub stabilize: bool,
+    /// A color cast laid over the picture when it is shown in color.
+    pub tint: Option<Tint>,
+    /// Replace a green screen or the like with a background, see
+    /// [`ChromaKey`].
+    pub chroma_key: Option<ChromaKey>,
+    /// Show the picture in the green of an old monochrome monitor.
+    pub phosphor: bool,
+    /// Weights to turn color into brightness with. `None` leaves it to the
+    /// decoder, which for MJPEG is BT.601 and for color renderers BT.709.
+    pub luma: Option<LumaWeights>,
+    /// Average the pixels under every cell instead of picking one, see
+    /// [`Resizer::resize_area`].
+    pub area: bool,
+    /// Average them in linear light, see [`Resizer::resize_linear`]. Implies
+    /// `area`.
+    pub linear: bool,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            mirror: true,
+            fit: FitMode::default(),
+            color: true,
+            gray_world: false,
+            filters: Vec::new(),
+            hysteresis: None,
+            zoom: 1.0,
+            stabilize: false,
+            tint: None,
+            chroma_key: None,
+            phosphor: false,
+            luma: None,
+            area: false,
+            linear: false,
+        }
+    }
+}
+
+/// The images a frame is decoded, cropped and resized into and the resizer
+/// doing it, kept by [`render_frame_with`] from one frame to the next so
+/// they are only set up again when the size changes. With the grids handed
+/// back through [`recycle`](Self::recycle), rendering allocates nothing
+/// after that but what the JPEG decoder does.
+#[derive(Default)]
+pub struct RenderBuffers {
+    // the frame at full size, and the part of it that is shown when that
+    // is less
+    full_luma: GrayImage,
+    full_rgb: RgbImage,
+    cropped_luma: GrayImage,
+    cropped_rgb: RgbImage,
+    luma: GrayImage,
+    rgb: RgbImage,
+    resizer: Resizer,
+    stabilizer: Stabilizer,
+    filters: FilterChain,
+    // the glyph every cell was last shown with, see CharLut::hold
+    glyphs: Vec<u8>,
+    timings: StageTimings,
+    // what the renderer drew, and a grid handed back to place it in
+    rendered: Grid,
+    spare: Grid,
+}
+
+impl RenderBuffers {
+    /// How long decoding, resizing and mapping the last frame rendered with
+    /// these buffers took, the other stages are left at zero.
+    pub fn timings(&self) -> StageTimings {
+        self.timings
+    }
+
+    /// The brightness the last frame rendered with these buffers was mapped
+    /// from, filtered and mirrored.
+    pub fn luma(&self) -> &GrayImage {
+        &self.luma
+    }
+
+    /// Hands back a grid rendered with these buffers once it has been
+    /// written, for the next frame to be rendered into.
+    pub fn recycle(&mut self, grid: Grid) {
+        self.spare = grid;
+    }
+}
+
+/// Decodes, resizes and renders a frame into a grid of `columns` x `rows`
+/// cells.
+pub fn render_frame(
+    frame: &Frame,
+    renderer: &dyn Renderer,
+    columns: u32,
+    rows: u32,
+    options: &RenderOptions,
+) -> Result<Grid> {
+    render_frame_with(
+        frame,
+        renderer,
+        columns,
+        rows,
+        options,
+        &mut RenderBuffers::default(),
+    )
+}
+
+/// Like [`render_frame`], resizing into `buffers` instead of fresh images.
+pub fn render_frame_with(
+    frame: &Frame,
+    renderer: &dyn Renderer,
+    columns: u32,
+    rows: u32,
+    options: &RenderOptions,
+    buffers: &mut RenderBuffers,
+) -> Result<Grid> {
+    let _span = trace_span!("render", columns, rows).entered();
+    let (cell_width, cell_height) = renderer.cell_size();
+    let mut stopwatch = Stopwatch::start();
+
+    let color = renderer.needs_color() && options.color;
+    // picking the weights or keying needs the colors, even when they
+    // aren't shown
+    if color || options.luma.is_some() || options.chroma_key.is_some() {
+        decode_frame_rgb_into(frame, &mut buffers.full_rgb)?;
+        let region = visible_region(&buffers.full_rgb, &mut buffers.stabilizer, options)?;
+        let decode = stopwatch.lap();
+        let layout = options.fit.layout(region.2, region.3, columns, rows);
+        let (width, height) = (layout.columns * cell_width, layout.rows * cell_height);
+
+        let full = cropped(
+            &buffers.full_rgb,
+            within(region, layout.crop),
+            &mut buffers.cropped_rgb,
+        );
+        let rgb = sized(&mut buffers.rgb, width, height);
+        if options.linear {
+            buffers.resizer.resize_linear(full, rgb)?;
+        } else if options.area {
+            buffers.resizer.resize_area(full, rgb)?;
+        } else {
+            buffers.resizer.resize(full, rgb)?;
+        }
+        // on the colors the camera sees, before anything changes them
+        if let Some(key) = &options.chroma_key {
+            key.apply(rgb);
+        }
+        if options.gray_world {
+            gray_world(rgb);
+        }
+        if options.mirror {
+            flip_horizontal_in_place(rgb);
+        }
+
+        // what imageops::grayscale does, into the buffer
+        let luma = sized(&mut buffers.luma, width, height);
+        match options.luma {
+            Some(weights) => {
+                for (gray, pixel) in luma.pixels_mut().zip(rgb.pixels()) {
+                    gray.0 = [weights.luma(pixel.0)];
+                }
+            }
+            None => {
+                for (gray, pixel) in luma.pixels_mut().zip(rgb.pixels()) {
+                    *gray = pixel.to_luma();
+                }
+            }
+        }
+        let rgb_filtered = if color { Some(&mut *rgb) } else { None };
+        filter_chain(&mut buffers.filters, &options.filters).apply(luma, rgb_filtered);
+        // after the brightness is taken, which stays that of the picture
+        if let (true, Some(tint)) = (color, options.tint) {
+            tint.apply(rgb);
+        }
+        if options.phosphor && color {
+            phosphor(rgb, luma);
+        }
+        let resize = stopwatch.lap();
+
+        hold(renderer, luma, &mut buffers.glyphs, options);
+        let rgb = if color { Some(&*rgb) } else { None };
+        renderer.render_into(luma, rgb, &mut buffers.rendered);
+        let spare = mem::take(&mut buffers.spare);
+        let mut grid = place(&mut buffers.rendered, spare, &layout, columns, rows);
+        // without colors to tint, the characters are
+        if options.phosphor && !color {
+            tint(&mut grid);
+        }
+        grid.meta = frame.meta;
+        buffers.timings = StageTimings {
+            decode,
+            resize,
+            map: stopwatch.lap(),
+            ..StageTimings::default()
+        };
+        Ok(grid)
+    } else {
+        decode_frame_into(frame, &mut buffers.full_luma)?;
+        let region = visible_region(&buffers.full_luma, &mut buffers.stabilizer, options)?;
+        let decode = stopwatch.lap();
+        let layout = options.fit.layout(region.2, region.3, columns, rows);
+        let (width, height) = (layout.columns * cell_width, layout.rows * cell_height);
+
+        let full = cropped(
+            &buffers.full_luma,
+            within(region, layout.crop),
+            &mut buffers.cropped_luma,
+        );
+        let luma = sized(&mut buffers.luma, width, height);
+        if options.linear {
+            buffers.resizer.resize_linear(full, luma)?;
+        } else if options.area {
+            buffers.resizer.resize_area(full, luma)?;
+        } else {
+            buffers.resizer.resize(full, luma)?;
+        }
+        if options.mirror {
+            flip_horizontal_in_place(luma);
+        }
+        filter_chain(&mut buffers.filters, &options.filters).apply(luma, None);
+        let resize = stopwatch.lap();
+
+        hold(renderer, luma, &mut buffers.glyphs, options);
+        renderer.render_into(luma, None, &mut buffers.rendered);
+        let spare = mem::take(&mut buffers.spare);
+        let mut grid = place(&mut buffers.rendered, spare, &layout, columns, rows);
+        if options.phosphor {
+            tint(&mut grid);
+        }
+        grid.meta = frame.meta;
+        buffers.timings = StageTimings {
+            decode,
+            resize,
+            map: stopwatch.lap(),
+            ..StageTimings::default()
+        };
+        Ok(grid)
+    }
+}
+
+/// Draws every character of `grid` in [`PHOSPHOR`] green.
+fn tint(grid: &mut Grid) {
+    for row in grid.rows_mut() {
+        for cell in row {
+            cell.fg = Some(PHOSPHOR);
+        }
+    }
+}
+
+/// Keeps the cells of `luma` on the glyphs they were shown with, when
+/// hysteresis is on and the renderer maps by brightness.
+fn hold(
+    renderer: &dyn Renderer,
+    luma: &mut GrayImage,
+    glyphs: &mut Vec<u8>,
+    options: &RenderOptions,
+) {
+    if let (Some(margin), Some(chars)) = (options.hysteresis, renderer.chars()) {
+        chars.hold(luma, glyphs, margin);
+    }
+}
+
+/// `chain`, made again from `filters` when they changed, which starts the
+/// stages that remember the frames before over.
+fn filter_chain<'a>(chain: &'a mut FilterChain, filters: &[Filter]) -> &'a mut FilterChain {
+    if chain.filters() != filters {
+        *chain = FilterChain::new(filters);
+    }
+
+    chain
+}
+
+/// `buffer` at `width` x `height`, allocated again only when its size is off.
+fn sized<P: Pixel<Subpixel = u8>>(
+    buffer: &mut ImageBuffer<P, Vec<u8>>,
+    width: u32,
+    height: u32,
+) -> &mut ImageBuffer<P, Vec<u8>> {
+    if buffer.dimensions() != (width, height) {
+        *buffer = ImageBuffer::new(width, height);
+    }
+    buffer
+}
+
+// a part of an image, as (x, y, width, height)
+type Region = (u32, u32, u32, u32);
+
+/// The part of `img` that is shown before the layout crops it, inside the
+/// stabilizer's margins and zoomed in on.
+fn visible_region<P: Pixel<Subpixel = u8>>(
+    img: &ImageBuffer<P, Vec<u8>>,
+    stabilizer: &mut Stabilizer,
+    options: &RenderOptions,
+) -> Result<Region> {
+    let (width, height) = img.dimensions();
+    let mut region = (0, 0, width, height);
+    if options.stabilize {
+        stabilizer.update(img)?;
+        region = stabilizer.region(width, height);
+    }
+
+    Ok(zoom(region, options.zoom))
+}
+
+/// The middle `1 / zoom` of `region`.
+fn zoom(region: Region, zoom: f32) -> Region {
+    if zoom <= 1.0 {
+        return region;
+    }
+
+    let (x, y, width, height) = region;
+    let zoomed_width = ((width as f32 / zoom) as u32).max(1);
+    let zoomed_height = ((height as f32 / zoom) as u32).max(1);
+    (
+        x + (width - zoomed_width) / 2,
+        y + (height - zoomed_height) / 2,
+        zoomed_width,
+        zoomed_height,
+    )
+}
+
+/// `crop`, which is relative to `region`, within the whole image.
+fn within(region: Region, crop: Option<Region>) -> Region {
+    match crop {
+        Some((x, y, width, height)) => (region.0 + x, region.1 + y, width, height),
+        None => region,
+    }
+}
+
+/// `region` of `img`, copied into `buffer` unless it is all of it.
+fn cropped<'a, P: Pixel<Subpixel = u8>>(
+    img: &'a ImageBuffer<P, Vec<u8>>,
+    region: Region,
+    buffer: &'a mut ImageBuffer<P, Vec<u8>>,
+) -> &'a ImageBuffer<P, Vec<u8>> {
+    let (x, y, width, height) = region;
+    if region == (0, 0, img.width(), img.height()) {
+        return img;
+    }
+
+    let buffer = sized(buffer, width, height);
+    let channels = P::CHANNEL_COUNT as usize;
+    let (src_row, row) = (img.width() as usize * channels, width as usize * channels);
+    let lines = img.as_raw().chunks_exact(src_row).skip(y as usize);
+    for (out, line) in buffer.chunks_exact_mut(row.max(1)).zip(lines) {
+        out.copy_from_slice(&line[x as usize * channels..][..row]);
+    }
+    buffer
+}
+
+/// `rendered` where the layout puts it in a grid of `columns` x `rows`,
+/// drawn into `spare`. When it fills the grid already the two are swapped
+/// instead.
+fn place(rendered: &mut Grid, spare: Grid, layout: &Layout, columns: u32, rows: u32) -> Grid {
+    if rendered.width == columns && rendered.height == rows {
+        return mem::replace(rendered, spare);
+    }
+
+    let mut placed = spare;
+    placed.reset(columns, rows);
+    placed.blit(rendered, layout.x, layout.y);
+    placed
+}
+
+/// Writes the grid out row by row with `\r\n` line endings, so it displays
+/// correctly in raw mode. Colors are written as 24-bit SGR escape codes.
+pub fn write_grid(grid: &Grid, out: &mut impl Write) -> Result<()> {
+    write_grid_with(grid, ColorMode::TrueColor, out)
+}
+
+/// Like [`write_grid`], but writes colors according to `color_mode`.
+pub fn write_grid_with(grid: &Grid, color_mode: ColorMode, out: &mut impl Write) -> Result<()> {
+    GridWriter::default().write(grid, color_mode, out)
+}
+
+/// Writes grids like [`write_grid_with`], keeping the text of every row from
+/// one grid to the next. Once it has written a grid as big as the next one
+/// it allocates nothing.
+#[derive(Default)]
+pub struct GridWriter {
+    rows: Vec<String>,
+}
+
+impl GridWriter {
+    pub fn write(
+        &mut self,
+        grid: &Grid,
+        color_mode: ColorMode,
+        out: &mut impl Write,
+    ) -> Result<()> {
+        let height = grid.rows().count();
+        if self.rows.len() < height {
+            self.rows.resize_with(height, String::new);
+        }
+        let rows = &mut self.rows[..height];
+
+        // every row starts without colors, so they can be written on threads
+        // of their own and put back together in order
+        grid.cells
+            .par_chunks(grid.width.max(1) as usize)
+            .zip(rows.par_iter_mut())
+            .try_for_each(|(row, buf)| write_row(row, color_mode, buf))?;
+
+        for row in rows.iter() {
+            out.write_all(row.as_bytes())?;
+        }
+        Ok(())
+    }
+}
+
+fn write_row(row: &[Cell], color_mode: ColorMode, buf: &mut String) -> Result<()> {
+    buf.clear();
+    let mut fg = None;
+    let mut bg = None;
+
+    for cell in row {
+        if color_mode != ColorMode::Mono {
+            if cell.fg != fg {
+                write_color(buf, color_mode, 38, cell.fg)?;
+                fg = cell.fg;
+            }
+            if cell.bg != bg {
+                write_color(buf, color_mode, 48, cell.bg)?;
+                bg = cell.bg;
+            }
+        }
+        buf.push(cell.glyph);
+    }
+
+    if fg.is_some() || bg.is_some() {
+        buf.push_str("\x1b[0m");
+    }
+    buf.push('\r');
+    buf.push('\n');
+
+    Ok(())
+}
+
+// `layer` is 38 for the foreground and 48 for the background
+fn write_color(
+    buf: &mut String,
+    color_mode: ColorMode,
+    layer: u8,
+    color: Option<Rgb<u8>>,
+) -> Result<()> {
+    match (color, color_mode) {
+        (None, _) | (_, ColorMode::Mono) => write!(buf, "\x1b[{}m", layer + 1)?,
+        (Some(c), ColorMode::Ansi256) => buf.push_str(ansi256_sgr(layer)[ansi256(c) as usize]),
+        (Some(Rgb([r, g, b])), ColorMode::TrueColor) => write!(buf, "\x1b[{layer};2;{r};{g};{b}m")?,
+    }
+    Ok(())
+}
+
+/// The SGR sequence for every entry of the 256 color palette, on `layer`,
+/// so writing a cell copies one instead of formatting it.
+fn ansi256_sgr(layer: u8) -> &'static [&'static str; 256] {
+    static SGR: OnceLock<[[&str; 256]; 2]> = OnceLock::new();
+
+    let sgr = SGR.get_or_init(|| {
+        // built once for the life of the process
+        [38, 48]
+            .map(|layer| std::array::from_fn(|index| &*format!("\x1b[{layer};5;{index}m").leak()))
+    });
+    &sgr[usize::from(layer == 48)]
+}
+
+/// Writes the image as ascii art, mirrored horizontally so it reads like a
+/// mirror, with `\r\n` line endings so it displays correctly in raw mode.
+pub fn write_image_buffer(image_buffer: &GrayImage, out: &mut impl Write) -> Result<()> {
+    let bh = image_buffer.height();
+    let bw = image_buffer.width();
+    let mut buf: String = String::with_capacity(bw as usize * bh as usize + (2 * bh) as usize);
+    let chars = CharLut::new(DEFAULT_CHARSET);
+
+    for y in 0..bh {
+        // this flips the image
+        for x in (0..bw).rev() {
+            let pixel = image::ImageBuffer::get_pixel(image_buffer, x, y).0;
+            buf.push(chars.get(pixel[0]));
+        }
+        buf.push('\r');
+        buf.push('\n');
+    }
+    write!(out, "{buf}")?;
+    Ok(())
+}
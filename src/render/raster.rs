@@ -0,0 +1,78 @@
+use super::Grid;
+use font8x8::{UnicodeFonts, BASIC_FONTS, BLOCK_FONTS, BOX_FONTS, LATIN_FONTS};
+use image::{Rgb, RgbImage};
+
+/// Size of a rasterized cell in pixels, the 8x8 glyphs are doubled
+/// vertically to get the usual 1:2 terminal cell.
+pub const GLYPH_WIDTH: u32 = 8;
+pub const GLYPH_HEIGHT: u32 = 16;
+
+const DEFAULT_FG: Rgb<u8> = Rgb([255, 255, 255]);
+const DEFAULT_BG: Rgb<u8> = Rgb([0, 0, 0]);
+
+/// Draws the grid back into an image with an 8x8 bitmap font, one
+/// [`GLYPH_WIDTH`] x [`GLYPH_HEIGHT`] block per cell. Cells without colors
+/// are drawn white on black.
+pub fn rasterize(grid: &Grid) -> RgbImage {
+    let mut img = RgbImage::new(grid.width() * GLYPH_WIDTH, grid.height() * GLYPH_HEIGHT);
+
+    for (y, row) in grid.rows().enumerate() {
+        for (x, cell) in row.iter().enumerate() {
+            let bitmap = glyph(cell.glyph);
+            let fg = cell.fg.unwrap_or(DEFAULT_FG);
+            let bg = cell.bg.unwrap_or(DEFAULT_BG);
+
+            for py in 0..GLYPH_HEIGHT {
+                // bit 0 is the leftmost pixel of a row
+                let bits = bitmap[(py / 2) as usize];
+                for px in 0..GLYPH_WIDTH {
+                    let lit = bits & (1 << px) != 0;
+                    img.put_pixel(
+                        x as u32 * GLYPH_WIDTH + px,
+                        y as u32 * GLYPH_HEIGHT + py,
+                        if lit { fg } else { bg },
+                    );
+                }
+            }
+        }
+    }
+
+    img
+}
+
+/// The 8x8 bitmap for `c`, blank for chars the font doesn't have.
+fn glyph(c: char) -> [u8; 8] {
+    if let Some(dots) = braille(c) {
+        return dots;
+    }
+
+    BASIC_FONTS
+        .get(c)
+        .or_else(|| BLOCK_FONTS.get(c))
+        .or_else(|| BOX_FONTS.get(c))
+        .or_else(|| LATIN_FONTS.get(c))
+        .unwrap_or([0; 8])
+}
+
+/// Braille isn't in the font, but the pattern is simple enough to draw: two
+/// columns of four dots, each dot two pixels wide and one row high (two once
+/// the rows are doubled).
+fn braille(c: char) -> Option<[u8; 8]> {
+    let bits = (c as u32).checked_sub(0x2800).filter(|b| *b <= 0xff)?;
+
+    // bit of each dot, indexed by [row][column], see BrailleRenderer
+    const DOTS: [[u32; 2]; 4] = [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+    // pixel masks of the left and right dot columns
+    const COLUMNS: [u8; 2] = [0b0000_0110, 0b0110_0000];
+
+    let mut bitmap = [0; 8];
+    for (dy, row) in DOTS.iter().enumerate() {
+        for (dx, bit) in row.iter().enumerate() {
+            if bits & bit != 0 {
+                bitmap[dy * 2] |= COLUMNS[dx];
+            }
+        }
+    }
+
+    Some(bitmap)
+}
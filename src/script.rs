@@ -0,0 +1,219 @@
+//! Rhai scripts run on every frame, for overlays, mappings of your own and
+//! reacting to what the camera sees without forking the crate.
+//!
+//! A script defines `fn frame(luma, grid)`, called with the brightness the
+//! frame was mapped from and the grid of cells it was mapped onto. Changes
+//! to the grid are drawn, anything kept from one frame to the next goes in
+//! `this`, which starts out as an empty map:
+//!
+//! ```text
+//! fn frame(luma, grid) {
+//!     this.frames = (this.frames ?? 0) + 1;
+//!     if luma.mean() > 200 {
+//!         osd("too bright");
+//!     }
+//!     grid.print(0, grid.height - 1, `frame ${this.frames}`);
+//! }
+//! ```
+//!
+//! `luma` has `width`, `height`, `get(x, y)` from 0 to 255 and `mean()`,
+//! at the resolution the renderer works at, a few pixels per cell in the
+//! half-block and braille modes. `grid` has `width`, `height`, `get(x, y)`
+//! and `set(x, y, char)` for the character of a cell, `color(x, y, r, g, b)`
+//! for its color and `print(x, y, text)`. `print` logs a line, `osd` shows
+//! one on the top row of the viewer.
+
+use crate::render::{Cell, Grid};
+use eyre::{eyre, Result};
+use image::{GrayImage, Rgb};
+use rhai::{CallFnOptions, Dynamic, Engine, Map, Scope, AST};
+use std::env;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tracing::{debug, info};
+
+/// Where scripts given by name are looked for:
+/// `$XDG_CONFIG_HOME/asciicam/scripts`, or `~/.config/asciicam/scripts`.
+pub fn default_scripts_dir() -> PathBuf {
+    let config = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) => PathBuf::from(dir),
+        None => match env::var_os("HOME") {
+            Some(home) => PathBuf::from(home).join(".config"),
+            None => env::temp_dir(),
+        },
+    };
+
+    config.join("asciicam").join("scripts")
+}
+
+/// The brightness of a frame, as scripts see it.
+#[derive(Clone)]
+struct Luma(Arc<GrayImage>);
+
+/// The cells of a frame, shared with the script so it can change them.
+#[derive(Clone)]
+struct Cells(Arc<Mutex<Grid>>);
+
+impl Cells {
+    fn with<T>(&self, f: impl FnOnce(&mut Grid) -> T) -> T {
+        f(&mut self.0.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    /// Runs `f` on the cell at `x`, `y`, nothing happens outside the grid.
+    fn cell(&self, x: i64, y: i64, f: impl FnOnce(&mut Cell)) {
+        self.with(|grid| {
+            if (0..grid.width() as i64).contains(&x) && (0..grid.height() as i64).contains(&y) {
+                f(grid.get_mut(x as u32, y as u32));
+            }
+        })
+    }
+}
+
+/// A script with a `frame` function, see the [module docs](self).
+pub struct Script {
+    engine: Engine,
+    ast: AST,
+    scope: Scope<'static>,
+    // `this` of the frame function
+    state: Dynamic,
+    // what the script last asked to show with osd()
+    osd: Arc<Mutex<Option<String>>>,
+}
+
+impl Script {
+    /// Loads the script at `name` if that is a file, or
+    /// `<name>.rhai` in [`default_scripts_dir`] otherwise.
+    pub fn find(name: &str) -> Result<Self> {
+        let path = Path::new(name);
+        if path.is_file() {
+            return Self::load(path);
+        }
+
+        Self::load(default_scripts_dir().join(format!("{name}.rhai")))
+    }
+
+    /// Compiles the script in `path` and runs what it has outside of its
+    /// functions, once.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let osd = Arc::new(Mutex::new(None));
+        let engine = engine(&osd);
+
+        let ast = engine
+            .compile_file(path.to_path_buf())
+            .map_err(|e| eyre!("Could not load script '{}': {e}", path.display()))?;
+        if !ast
+            .iter_functions()
+            .any(|f| f.name == "frame" && f.params.len() == 2)
+        {
+            return Err(eyre!(
+                "Script '{}' has no frame(luma, grid) function",
+                path.display()
+            ));
+        }
+
+        let mut scope = Scope::new();
+        engine
+            .run_ast_with_scope(&mut scope, &ast)
+            .map_err(|e| eyre!("Script '{}' failed: {e}", path.display()))?;
+        debug!("loaded script {}", path.display());
+
+        Ok(Self {
+            engine,
+            ast,
+            scope,
+            state: Dynamic::from_map(Map::new()),
+            osd,
+        })
+    }
+
+    /// Calls the script's `frame` function with `luma` and `grid`, and
+    /// returns the grid as the script left it.
+    pub fn frame(&mut self, luma: &GrayImage, grid: Grid) -> Result<Grid> {
+        let cells = Cells(Arc::new(Mutex::new(grid)));
+        let options = CallFnOptions::new()
+            .eval_ast(false)
+            .bind_this_ptr(&mut self.state);
+        self.engine
+            .call_fn_with_options::<Dynamic>(
+                options,
+                &mut self.scope,
+                &self.ast,
+                "frame",
+                (Luma(Arc::new(luma.clone())), cells.clone()),
+            )
+            .map_err(|e| eyre!("Script failed: {e}"))?;
+
+        // the script may have held on to the grid in `this`
+        Ok(cells.with(|grid| mem::replace(grid, Grid::new(0, 0))))
+    }
+
+    /// What the script asked to show with `osd` since this was last
+    /// called.
+    pub fn take_osd(&mut self) -> Option<String> {
+        self.osd.lock().unwrap_or_else(|e| e.into_inner()).take()
+    }
+}
+
+/// An engine with the types and functions scripts get.
+fn engine(osd: &Arc<Mutex<Option<String>>>) -> Engine {
+    let mut engine = Engine::new();
+    engine.on_print(|text| info!(target: "asciicam::script", "{text}"));
+    engine.on_debug(|text, _, pos| debug!(target: "asciicam::script", "{pos}: {text}"));
+
+    let osd = osd.clone();
+    engine.register_fn("osd", move |text: &str| {
+        *osd.lock().unwrap_or_else(|e| e.into_inner()) = Some(text.to_string());
+    });
+
+    engine
+        .register_type_with_name::<Luma>("Luma")
+        .register_get("width", |luma: &mut Luma| luma.0.width() as i64)
+        .register_get("height", |luma: &mut Luma| luma.0.height() as i64)
+        .register_fn("get", |luma: &mut Luma, x: i64, y: i64| {
+            let (width, height) = luma.0.dimensions();
+            if (0..width as i64).contains(&x) && (0..height as i64).contains(&y) {
+                luma.0.get_pixel(x as u32, y as u32).0[0] as i64
+            } else {
+                0
+            }
+        })
+        .register_fn("mean", |luma: &mut Luma| {
+            let sum: u64 = luma.0.iter().map(|&v| v as u64).sum();
+            sum as f64 / luma.0.len().max(1) as f64
+        });
+
+    engine
+        .register_type_with_name::<Cells>("Grid")
+        .register_get("width", |cells: &mut Cells| {
+            cells.with(|g| g.width() as i64)
+        })
+        .register_get("height", |cells: &mut Cells| {
+            cells.with(|g| g.height() as i64)
+        })
+        .register_fn("get", |cells: &mut Cells, x: i64, y: i64| {
+            let mut glyph = ' ';
+            cells.cell(x, y, |cell| glyph = cell.glyph);
+            glyph
+        })
+        .register_fn("set", |cells: &mut Cells, x: i64, y: i64, glyph: char| {
+            cells.cell(x, y, |cell| cell.glyph = glyph)
+        })
+        .register_fn(
+            "color",
+            |cells: &mut Cells, x: i64, y: i64, r: i64, g: i64, b: i64| {
+                let channel = |v: i64| v.clamp(0, 255) as u8;
+                cells.cell(x, y, |cell| {
+                    cell.fg = Some(Rgb([channel(r), channel(g), channel(b)]))
+                })
+            },
+        )
+        .register_fn("print", |cells: &mut Cells, x: i64, y: i64, text: &str| {
+            if x >= 0 && y >= 0 {
+                cells.with(|grid| grid.print(x as u32, y as u32, text))
+            }
+        });
+
+    engine
+}
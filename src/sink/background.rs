@@ -0,0 +1,108 @@
+use super::Sink;
+use crate::render::Grid;
+use eyre::{eyre, Result};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use tracing::trace_span;
+
+#[derive(Default)]
+struct Pending {
+    grid: Option<Grid>,
+    // the error the sink last failed with
+    error: Option<String>,
+    closed: bool,
+    // frames replaced before the sink got to them
+    dropped: u64,
+    // how long the sink took to write the last frame
+    write_time: Duration,
+}
+
+/// Runs another sink on its own thread, so writing a frame out, like to a
+/// slow terminal, overlaps with capturing and rendering the next one.
+///
+/// Only the latest frame waits to be written: a frame the sink hasn't got to
+/// by the time the next one comes is stale and dropped. Errors of the sink
+/// are returned by the next [`write_frame`](Sink::write_frame).
+pub struct BackgroundSink {
+    pending: Arc<(Mutex<Pending>, Condvar)>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl BackgroundSink {
+    pub fn spawn(mut sink: impl Sink + Send + 'static) -> Self {
+        let pending = Arc::new((Mutex::new(Pending::default()), Condvar::new()));
+
+        let shared = Arc::clone(&pending);
+        let thread = thread::spawn(move || {
+            let (pending, ready) = &*shared;
+            loop {
+                let grid = {
+                    let mut pending = pending.lock().unwrap();
+                    while pending.grid.is_none() && !pending.closed {
+                        pending = ready.wait(pending).unwrap();
+                    }
+                    match pending.grid.take() {
+                        Some(grid) => grid,
+                        None => return,
+                    }
+                };
+
+                let started = Instant::now();
+                let result = trace_span!("write").in_scope(|| sink.write_frame(&grid));
+                let mut pending = pending.lock().unwrap();
+                pending.write_time = started.elapsed();
+                if let Err(e) = result {
+                    pending.error = Some(e.to_string());
+                }
+            }
+        });
+
+        Self {
+            pending,
+            thread: Some(thread),
+        }
+    }
+
+    /// Number of frames dropped so far because the sink was still busy
+    /// with the one before.
+    pub fn dropped(&self) -> u64 {
+        self.pending.0.lock().unwrap().dropped
+    }
+
+    /// How long the sink took to write the last frame it wrote.
+    pub fn write_time(&self) -> Duration {
+        self.pending.0.lock().unwrap().write_time
+    }
+}
+
+impl Sink for BackgroundSink {
+    fn write_frame(&mut self, grid: &Grid) -> Result<()> {
+        let (pending, ready) = &*self.pending;
+        let mut pending = pending.lock().unwrap();
+        if let Some(error) = pending.error.take() {
+            return Err(eyre!("{error}"));
+        }
+
+        if pending.grid.replace(grid.clone()).is_some() {
+            pending.dropped += 1;
+        }
+        ready.notify_one();
+
+        Ok(())
+    }
+}
+
+impl Drop for BackgroundSink {
+    /// Writes the last frame and waits for the thread, so nothing is drawn
+    /// after the sink is gone.
+    fn drop(&mut self) {
+        let (pending, ready) = &*self.pending;
+        pending.lock().unwrap().closed = true;
+        ready.notify_one();
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
@@ -0,0 +1,47 @@
+use super::Sink;
+use crate::broadcast::RenderSettings;
+use crate::capture::Frame;
+use crate::render::Grid;
+use eyre::Result;
+
+/// Renders captured frames at a fixed size of its own for another sink,
+/// whatever size the rest of the pipeline renders at. Records at a high
+/// cell resolution while the terminal shows whatever fits the window, for
+/// example.
+///
+/// Only the captured frame is rendered, overlays drawn by the viewer such as
+/// the status bar don't make it into these frames.
+pub struct FixedSize<S> {
+    sink: S,
+    settings: RenderSettings,
+    columns: u32,
+    rows: u32,
+}
+
+impl<S: Sink> FixedSize<S> {
+    pub fn new(sink: S, settings: RenderSettings, columns: u32, rows: u32) -> Self {
+        Self {
+            sink,
+            settings,
+            columns,
+            rows,
+        }
+    }
+
+    pub fn into_inner(self) -> S {
+        self.sink
+    }
+}
+
+impl<S: Sink> Sink for FixedSize<S> {
+    // frames rendered at the pipeline's size are ignored, ours come from the
+    // captured frame
+    fn write_frame(&mut self, _grid: &Grid) -> Result<()> {
+        Ok(())
+    }
+
+    fn write_captured(&mut self, frame: &Frame) -> Result<()> {
+        let grid = self.settings.render(frame, self.columns, self.rows)?;
+        self.sink.write_frame(&grid)
+    }
+}
@@ -0,0 +1,99 @@
+use super::Sink;
+use crate::convert::resize_rgb;
+use crate::render::{rasterize, Grid};
+use eyre::{eyre, Result};
+use image::RgbImage;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use v4l::video::Output;
+use v4l::{Device, FourCC};
+
+/// Rasterizes frames and writes them to a v4l2loopback device, so the ascii
+/// feed shows up as a webcam in other applications.
+///
+/// The device needs to exist first, e.g.
+/// `modprobe v4l2loopback exclusive_caps=1`.
+pub struct LoopbackSink {
+    // the format is kept for as long as the device is open
+    _dev: Device,
+    out: File,
+    width: u32,
+    height: u32,
+    yuyv: Vec<u8>,
+}
+
+impl LoopbackSink {
+    /// Opens `/dev/video{index}` as an output of `width` x `height` YUYV
+    /// frames. The rasterized grid is scaled to that size.
+    pub fn open(index: usize, width: u32, height: u32) -> Result<Self> {
+        if width == 0 || height == 0 || width % 2 != 0 {
+            return Err(eyre!(
+                "Invalid loopback size {width}x{height}, the width has to be even"
+            ));
+        }
+
+        let dev = match Device::new(index) {
+            Ok(dev) => dev,
+            Err(_) => {
+                return Err(eyre!(
+                    "Could not find device '{index}'. Is v4l2loopback loaded?"
+                ))
+            }
+        };
+
+        let mut fmt = Output::format(&dev)?;
+        fmt.width = width;
+        fmt.height = height;
+        fmt.fourcc = FourCC::new(b"YUYV");
+        let fmt = Output::set_format(&dev, &fmt)?;
+        if fmt.fourcc != FourCC::new(b"YUYV") || fmt.width != width || fmt.height != height {
+            return Err(eyre!(
+                "Device '{index}' doesn't accept {width}x{height} YUYV frames"
+            ));
+        }
+
+        let out = OpenOptions::new()
+            .write(true)
+            .open(format!("/dev/video{index}"))?;
+
+        Ok(Self {
+            _dev: dev,
+            out,
+            width,
+            height,
+            yuyv: vec![0; width as usize * height as usize * 2],
+        })
+    }
+}
+
+impl Sink for LoopbackSink {
+    fn write_frame(&mut self, grid: &Grid) -> Result<()> {
+        let img = resize_rgb(&rasterize(grid), self.width, self.height)?;
+        to_yuyv(&img, &mut self.yuyv);
+
+        self.out.write_all(&self.yuyv)?;
+        Ok(())
+    }
+}
+
+/// Converts to YUYV 4:2:2 with BT.601 studio range coefficients, averaging
+/// the chroma of each pair of pixels.
+fn to_yuyv(img: &RgbImage, out: &mut [u8]) {
+    let yuv = |[r, g, b]: [u8; 3]| {
+        let (r, g, b) = (r as i32, g as i32, b as i32);
+        let y = ((66 * r + 129 * g + 25 * b + 128) >> 8) + 16;
+        let u = ((-38 * r - 74 * g + 112 * b + 128) >> 8) + 128;
+        let v = ((112 * r - 94 * g - 18 * b + 128) >> 8) + 128;
+        (y, u, v)
+    };
+
+    for (pair, out) in img.as_raw().chunks_exact(6).zip(out.chunks_exact_mut(4)) {
+        let (y0, u0, v0) = yuv([pair[0], pair[1], pair[2]]);
+        let (y1, u1, v1) = yuv([pair[3], pair[4], pair[5]]);
+
+        out[0] = y0 as u8;
+        out[1] = ((u0 + u1) / 2) as u8;
+        out[2] = y1 as u8;
+        out[3] = ((v0 + v1) / 2) as u8;
+    }
+}
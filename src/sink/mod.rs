@@ -0,0 +1,142 @@
+//! Destinations for rendered frames.
+//!
+//! Every output implements [`Sink`]. A [`Fanout`] is itself a sink that
+//! forwards each frame to several others, so viewing and saving can happen
+//! from the same pipeline.
+
+mod background;
+mod fixed;
+#[cfg(target_os = "linux")]
+mod loopback;
+#[cfg(feature = "mqtt")]
+mod mqtt;
+mod record;
+#[cfg(feature = "ssh")]
+mod ssh;
+mod tcp;
+#[cfg(not(target_arch = "wasm32"))]
+mod terminal;
+#[cfg(unix)]
+mod unix;
+mod websocket;
+mod writer;
+#[cfg(feature = "zmq")]
+mod zmq;
+
+pub use background::BackgroundSink;
+pub use fixed::FixedSize;
+#[cfg(target_os = "linux")]
+pub use loopback::LoopbackSink;
+#[cfg(feature = "mqtt")]
+pub use mqtt::{MqttSettings, MqttSink, MqttTopics};
+pub use record::RecordSink;
+#[cfg(feature = "ssh")]
+pub use ssh::{SshSettings, SshSink};
+pub use tcp::TcpSink;
+#[cfg(not(target_arch = "wasm32"))]
+pub use terminal::TerminalSink;
+#[cfg(unix)]
+pub use unix::{default_socket_path, UnixSink};
+pub use websocket::{WebSocketSink, WsFormat};
+pub use writer::WriterSink;
+#[cfg(feature = "zmq")]
+pub use zmq::ZmqSink;
+
+use crate::capture::Frame;
+use crate::render::Grid;
+use eyre::Result;
+
+/// Something rendered frames can be written to.
+pub trait Sink {
+    /// Writes a single rendered frame.
+    fn write_frame(&mut self, grid: &Grid) -> Result<()>;
+
+    /// Hands over the captured frame before it is rendered, for sinks that
+    /// render frames themselves, e.g. at the size of a remote terminal.
+    /// Does nothing by default.
+    fn write_captured(&mut self, _frame: &Frame) -> Result<()> {
+        Ok(())
+    }
+
+    /// Number of clients currently connected, for sinks that serve them.
+    /// 0 by default.
+    fn clients(&self) -> usize {
+        0
+    }
+}
+
+impl<S: Sink + ?Sized> Sink for Box<S> {
+    fn write_frame(&mut self, grid: &Grid) -> Result<()> {
+        (**self).write_frame(grid)
+    }
+
+    fn write_captured(&mut self, frame: &Frame) -> Result<()> {
+        (**self).write_captured(frame)
+    }
+
+    fn clients(&self) -> usize {
+        (**self).clients()
+    }
+}
+
+/// Forwards every frame to all of its sinks.
+#[derive(Default)]
+pub struct Fanout {
+    sinks: Vec<Box<dyn Sink>>,
+}
+
+impl Fanout {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a sink to the fan-out.
+    pub fn push(&mut self, sink: impl Sink + 'static) {
+        self.sinks.push(Box::new(sink));
+    }
+
+    pub fn len(&self) -> usize {
+        self.sinks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sinks.is_empty()
+    }
+}
+
+impl Sink for Fanout {
+    /// Writes the frame to every sink. A failing sink doesn't stop the frame
+    /// from reaching the others, the first error is returned afterwards.
+    fn write_frame(&mut self, grid: &Grid) -> Result<()> {
+        let mut result = Ok(());
+
+        for sink in &mut self.sinks {
+            if let Err(e) = sink.write_frame(grid) {
+                if result.is_ok() {
+                    result = Err(e);
+                }
+            }
+        }
+
+        result
+    }
+
+    fn write_captured(&mut self, frame: &Frame) -> Result<()> {
+        let mut result = Ok(());
+
+        for sink in &mut self.sinks {
+            if let Err(e) = sink.write_captured(frame) {
+                if result.is_ok() {
+                    result = Err(e);
+                }
+            }
+        }
+
+        result
+    }
+
+    /// The clients of all the sinks.
+    fn clients(&self) -> usize {
+        self.sinks.iter().map(|sink| sink.clients()).sum()
+    }
+}
@@ -0,0 +1,239 @@
+use super::Sink;
+use crate::capture::Frame;
+use crate::motion::MotionDetector;
+use crate::render::Grid;
+use base64::Engine;
+use eyre::{eyre, Result};
+use rumqttc::{Client, Event, LastWill, MqttOptions, Packet, QoS};
+use std::process;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// motion only counts as over once the picture stayed still this long, so
+// someone pausing mid-frame doesn't end and restart the event
+const MOTION_HOLD: Duration = Duration::from_secs(2);
+// how long the connection thread waits before trying a broker that went away
+const RECONNECT_DELAY: Duration = Duration::from_secs(2);
+
+/// Topics an [`MqttSink`] publishes to.
+#[derive(Debug, Clone)]
+pub struct MqttTopics {
+    /// `{"motion":true,"changed":0.08}` when motion starts and
+    /// `{"motion":false,...}` when it stops, retained.
+    pub motion: String,
+    /// The rendered frame as base64 encoded text.
+    pub snapshot: String,
+    /// `{"uptime":..,"frames":..,"fps":..,"motion":..}` heartbeats.
+    pub health: String,
+    /// `online` while connected, `offline` once the broker loses us,
+    /// retained. Home Assistant calls this the availability topic.
+    pub status: String,
+}
+
+impl MqttTopics {
+    /// `prefix/motion`, `prefix/snapshot`, `prefix/health` and
+    /// `prefix/status`.
+    pub fn with_prefix(prefix: &str) -> Self {
+        Self {
+            motion: format!("{prefix}/motion"),
+            snapshot: format!("{prefix}/snapshot"),
+            health: format!("{prefix}/health"),
+            status: format!("{prefix}/status"),
+        }
+    }
+}
+
+/// Where and what an [`MqttSink`] publishes.
+#[derive(Debug, Clone)]
+pub struct MqttSettings {
+    pub host: String,
+    pub port: u16,
+    pub client_id: String,
+    /// User name and password for the broker.
+    pub credentials: Option<(String, String)>,
+    pub topics: MqttTopics,
+    /// How often a snapshot is published, never when `None`.
+    pub snapshot_interval: Option<Duration>,
+    pub heartbeat_interval: Duration,
+    /// Fraction of the picture that has to change between frames to count as
+    /// motion.
+    pub motion_threshold: f32,
+}
+
+impl MqttSettings {
+    /// Settings for the broker at `mqtt://[user[:password]@]host[:port]`,
+    /// publishing under `asciicam/` with a snapshot every minute and a
+    /// heartbeat every 30 seconds.
+    pub fn from_url(url: &str) -> Result<Self> {
+        let rest = match url.strip_prefix("mqtt://") {
+            Some(rest) => rest.trim_end_matches('/'),
+            None => return Err(eyre!("Expected an mqtt:// url, got '{url}'")),
+        };
+
+        let (credentials, host) = match rest.rsplit_once('@') {
+            Some((user, host)) => match user.split_once(':') {
+                Some((user, password)) => (Some((user.to_string(), password.to_string())), host),
+                None => (Some((user.to_string(), String::new())), host),
+            },
+            None => (None, rest),
+        };
+        let (host, port) = match host.rsplit_once(':') {
+            Some((host, port)) => (host, port.parse()?),
+            None => (host, 1883),
+        };
+        if host.is_empty() {
+            return Err(eyre!("'{url}' has no host"));
+        }
+
+        Ok(Self {
+            host: host.to_string(),
+            port,
+            client_id: format!("asciicam-{}", process::id()),
+            credentials,
+            topics: MqttTopics::with_prefix("asciicam"),
+            snapshot_interval: Some(Duration::from_secs(60)),
+            heartbeat_interval: Duration::from_secs(30),
+            motion_threshold: 0.02,
+        })
+    }
+}
+
+/// Publishes motion events, snapshots and heartbeats to an MQTT broker, for
+/// Home Assistant and other home automation.
+///
+/// Publishing never blocks the pipeline: messages are queued for a
+/// background connection that reconnects by itself, and dropped while the
+/// queue is full because the broker is away.
+pub struct MqttSink {
+    client: Client,
+    settings: MqttSettings,
+    detector: MotionDetector,
+    motion: bool,
+    changed: f32,
+    last_motion: Instant,
+    last_snapshot: Option<Instant>,
+    last_heartbeat: Instant,
+    started: Instant,
+    frames: u64,
+    // frames at the last heartbeat, for the frame rate since then
+    heartbeat_frames: u64,
+}
+
+impl MqttSink {
+    pub fn connect(settings: MqttSettings) -> Result<Self> {
+        let mut options = MqttOptions::new(&settings.client_id, &settings.host, settings.port);
+        options.set_keep_alive(Duration::from_secs(30));
+        options.set_last_will(LastWill::new(
+            &settings.topics.status,
+            "offline",
+            QoS::AtLeastOnce,
+            true,
+        ));
+        if let Some((user, password)) = &settings.credentials {
+            options.set_credentials(user, password);
+        }
+
+        let (client, mut connection) = Client::new(options, 64);
+
+        let status = client.clone();
+        let topic = settings.topics.status.clone();
+        thread::spawn(move || {
+            for notification in connection.iter() {
+                match notification {
+                    // announce ourselves on every (re)connect, the last will
+                    // has overwritten the status while we were gone
+                    Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                        let _ = status.try_publish(&topic, QoS::AtLeastOnce, true, "online");
+                    }
+                    Ok(_) => (),
+                    Err(_) => thread::sleep(RECONNECT_DELAY),
+                }
+            }
+        });
+
+        Ok(Self {
+            client,
+            settings,
+            detector: MotionDetector::new(),
+            motion: false,
+            changed: 0.0,
+            last_motion: Instant::now(),
+            last_snapshot: None,
+            last_heartbeat: Instant::now(),
+            started: Instant::now(),
+            frames: 0,
+            heartbeat_frames: 0,
+        })
+    }
+
+    fn publish(&self, topic: &str, retain: bool, payload: impl Into<Vec<u8>>) {
+        // a full queue means the broker is away, which shouldn't stop capture
+        let _ = self
+            .client
+            .try_publish(topic, QoS::AtLeastOnce, retain, payload);
+    }
+
+    fn publish_motion(&self) {
+        let payload = format!(
+            r#"{{"motion":{},"changed":{:.3}}}"#,
+            self.motion, self.changed
+        );
+        self.publish(&self.settings.topics.motion, true, payload);
+    }
+
+    fn publish_heartbeat(&mut self) {
+        let elapsed = self.last_heartbeat.elapsed().as_secs_f64();
+        let fps = (self.frames - self.heartbeat_frames) as f64 / elapsed;
+        self.last_heartbeat = Instant::now();
+        self.heartbeat_frames = self.frames;
+
+        let payload = format!(
+            r#"{{"uptime":{},"frames":{},"fps":{:.1},"motion":{}}}"#,
+            self.started.elapsed().as_secs(),
+            self.frames,
+            fps,
+            self.motion
+        );
+        self.publish(&self.settings.topics.health, false, payload);
+    }
+}
+
+impl Sink for MqttSink {
+    /// Publishes the frame as a snapshot if one is due.
+    fn write_frame(&mut self, grid: &Grid) -> Result<()> {
+        let interval = match self.settings.snapshot_interval {
+            Some(interval) => interval,
+            None => return Ok(()),
+        };
+        if matches!(self.last_snapshot, Some(last) if last.elapsed() < interval) {
+            return Ok(());
+        }
+        self.last_snapshot = Some(Instant::now());
+
+        let text = base64::engine::general_purpose::STANDARD.encode(grid.to_text());
+        self.publish(&self.settings.topics.snapshot, false, text);
+        Ok(())
+    }
+
+    /// Looks for motion in the frame and sends a heartbeat if one is due.
+    fn write_captured(&mut self, frame: &Frame) -> Result<()> {
+        self.frames += 1;
+        self.changed = self.detector.update(frame)?;
+
+        if self.changed >= self.settings.motion_threshold {
+            self.last_motion = Instant::now();
+            if !self.motion {
+                self.motion = true;
+                self.publish_motion();
+            }
+        } else if self.motion && self.last_motion.elapsed() > MOTION_HOLD {
+            self.motion = false;
+            self.publish_motion();
+        }
+
+        if self.last_heartbeat.elapsed() >= self.settings.heartbeat_interval {
+            self.publish_heartbeat();
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,107 @@
+use super::Sink;
+use crate::render::{write_grid_with, ColorMode, Grid};
+use eyre::Result;
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+/// Records frames in the [asciicast v2] format, so recordings play back
+/// with `asciinema play` or on a web page with asciinema-player.
+///
+/// The first frame sets the size in the header, every frame is an output
+/// event written in the color mode given, timed by when its source frame
+/// was received.
+///
+/// [asciicast v2]: https://docs.asciinema.org/manual/asciicast/v2/
+pub struct RecordSink<W: Write> {
+    out: W,
+    color_mode: ColorMode,
+    started: Option<Instant>,
+    size: Option<(u32, u32)>,
+}
+
+impl<W: Write> RecordSink<W> {
+    pub fn new(out: W, color_mode: ColorMode) -> Self {
+        Self {
+            out,
+            color_mode,
+            started: None,
+            size: None,
+        }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+
+    fn write_header(&mut self, width: u32, height: u32) -> Result<()> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |t| t.as_secs());
+
+        writeln!(
+            self.out,
+            r#"{{"version":2,"width":{width},"height":{height},"timestamp":{timestamp}}}"#
+        )?;
+        Ok(())
+    }
+}
+
+impl RecordSink<BufWriter<File>> {
+    /// Creates (or truncates) the file at `path` and records to it.
+    pub fn create(path: impl AsRef<Path>, color_mode: ColorMode) -> Result<Self> {
+        Ok(Self::new(BufWriter::new(File::create(path)?), color_mode))
+    }
+}
+
+impl<W: Write> Sink for RecordSink<W> {
+    fn write_frame(&mut self, grid: &Grid) -> Result<()> {
+        let received = grid.meta().received.unwrap_or_else(Instant::now);
+        let started = *self.started.get_or_insert(received);
+
+        let size = (grid.width(), grid.height());
+        let mut ansi = Vec::new();
+        match self.size.replace(size) {
+            None => self.write_header(size.0, size.1)?,
+            // players don't resize, clear what a bigger frame left behind
+            Some(last) if last != size => ansi.extend_from_slice(b"\x1b[2J"),
+            Some(_) => (),
+        }
+        ansi.extend_from_slice(b"\x1b[H");
+        write_grid_with(grid, self.color_mode, &mut ansi)?;
+
+        let time = received.saturating_duration_since(started).as_secs_f64();
+        writeln!(
+            self.out,
+            "[{time:.6}, \"o\", {}]",
+            json_string(&String::from_utf8_lossy(&ansi))
+        )?;
+        self.out.flush()?;
+
+        Ok(())
+    }
+}
+
+/// Quotes `s` as a json string.
+fn json_string(s: &str) -> String {
+    let mut json = String::with_capacity(s.len() + 2);
+
+    json.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            c if c.is_control() => {
+                let _ = write!(json, "\\u{:04x}", c as u32);
+            }
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+
+    json
+}
@@ -0,0 +1,258 @@
+use super::Sink;
+use crate::broadcast::{Broadcast, RenderSettings, Subscriber};
+use crate::capture::Frame;
+use crate::render::Grid;
+use async_trait::async_trait;
+use eyre::{eyre, Result};
+use russh::server::{self, Auth, Handle, Msg, Session};
+use russh::{Channel, ChannelId, CryptoVec, MethodSet, Pty};
+use russh_keys::key::{KeyPair, PublicKey};
+use std::net::{SocketAddr, TcpListener};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use tracing::{error, info};
+
+// clear the screen and hide the cursor, and the reverse on the way out
+const SESSION_SETUP: &[u8] = b"\x1b[2J\x1b[?25l";
+const SESSION_TEARDOWN: &[u8] = b"\x1b[0m\x1b[2J\x1b[H\x1b[?25h";
+
+// the size used until the client sends its pty size
+const DEFAULT_SIZE: (u32, u32) = (80, 24);
+
+/// How sessions of an [`SshSink`] are authenticated.
+#[derive(Debug, Clone)]
+pub struct SshSettings {
+    /// Password every user has to log in with, anyone can watch without one.
+    pub password: Option<String>,
+    /// OpenSSH private key to identify the server with. A new key is
+    /// generated on every start without one, so clients will warn about a
+    /// changed host key.
+    pub host_key: Option<PathBuf>,
+}
+
+/// An SSH server streaming the feed to every session, `ssh cam@host` drops
+/// straight into it. Each session is rendered at the size of its pty and
+/// follows it when the client's terminal is resized, `q` or Ctrl-C leaves.
+///
+/// A slow connection skips frames rather than falling behind or holding up
+/// capture, see [`Broadcast`].
+pub struct SshSink {
+    broadcast: Broadcast,
+    addr: SocketAddr,
+}
+
+impl SshSink {
+    /// Starts the server on `addr` in the background.
+    pub fn bind(addr: &str, settings: SshSettings, render: RenderSettings) -> Result<Self> {
+        let key = match &settings.host_key {
+            Some(path) => russh_keys::load_secret_key(path, None)
+                .map_err(|e| eyre!("Could not load host key '{}': {e}", path.display()))?,
+            None => match KeyPair::generate_ed25519() {
+                Some(key) => key,
+                None => return Err(eyre!("Could not generate a host key")),
+            },
+        };
+
+        // bind once up front so a bad address is reported here rather than
+        // from the server thread
+        let addr = TcpListener::bind(addr)?.local_addr()?;
+
+        let config = Arc::new(server::Config {
+            keys: vec![key],
+            auth_rejection_time: Duration::from_secs(1),
+            ..Default::default()
+        });
+        let broadcast = Broadcast::new(render);
+        let server = Server {
+            broadcast: broadcast.clone(),
+            settings: Arc::new(settings),
+        };
+
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()?;
+        thread::spawn(move || {
+            if let Err(e) = runtime.block_on(server::run(config, addr, server)) {
+                error!("ssh server stopped: {e}");
+            }
+        });
+
+        Ok(Self { broadcast, addr })
+    }
+
+    /// The address the server is listening on.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+}
+
+impl Sink for SshSink {
+    // sessions render captured frames at their own size instead
+    fn write_frame(&mut self, _grid: &Grid) -> Result<()> {
+        Ok(())
+    }
+
+    fn write_captured(&mut self, frame: &Frame) -> Result<()> {
+        self.broadcast.send(frame)
+    }
+
+    fn clients(&self) -> usize {
+        self.broadcast.subscribers()
+    }
+}
+
+#[derive(Clone)]
+struct Server {
+    broadcast: Broadcast,
+    settings: Arc<SshSettings>,
+}
+
+impl server::Server for Server {
+    type Handler = Client;
+
+    fn new_client(&mut self, peer: Option<SocketAddr>) -> Client {
+        info!(?peer, "ssh client connected");
+        Client {
+            server: self.clone(),
+            size: Arc::new(Mutex::new(DEFAULT_SIZE)),
+        }
+    }
+}
+
+struct Client {
+    server: Server,
+    size: Arc<Mutex<(u32, u32)>>,
+}
+
+impl Client {
+    fn auth(&self, password: Option<&str>) -> Auth {
+        match (&self.server.settings.password, password) {
+            (None, _) => Auth::Accept,
+            (Some(expected), Some(given)) if expected == given => Auth::Accept,
+            _ => Auth::Reject {
+                proceed_with_methods: Some(MethodSet::PASSWORD),
+            },
+        }
+    }
+
+    fn resize(&self, columns: u32, rows: u32) {
+        // some clients report 0x0 when they don't know
+        if columns > 0 && rows > 0 {
+            *self.size.lock().unwrap() = (columns, rows);
+        }
+    }
+}
+
+#[async_trait]
+impl server::Handler for Client {
+    type Error = russh::Error;
+
+    async fn auth_none(&mut self, _user: &str) -> Result<Auth, Self::Error> {
+        Ok(self.auth(None))
+    }
+
+    async fn auth_password(&mut self, _user: &str, password: &str) -> Result<Auth, Self::Error> {
+        Ok(self.auth(Some(password)))
+    }
+
+    async fn auth_publickey(&mut self, _user: &str, _key: &PublicKey) -> Result<Auth, Self::Error> {
+        Ok(self.auth(None))
+    }
+
+    async fn channel_open_session(
+        &mut self,
+        _channel: Channel<Msg>,
+        _session: &mut Session,
+    ) -> Result<bool, Self::Error> {
+        Ok(true)
+    }
+
+    async fn pty_request(
+        &mut self,
+        _channel: ChannelId,
+        _term: &str,
+        columns: u32,
+        rows: u32,
+        _pix_width: u32,
+        _pix_height: u32,
+        _modes: &[(Pty, u32)],
+        _session: &mut Session,
+    ) -> Result<(), Self::Error> {
+        self.resize(columns, rows);
+        Ok(())
+    }
+
+    async fn window_change_request(
+        &mut self,
+        _channel: ChannelId,
+        columns: u32,
+        rows: u32,
+        _pix_width: u32,
+        _pix_height: u32,
+        _session: &mut Session,
+    ) -> Result<(), Self::Error> {
+        self.resize(columns, rows);
+        Ok(())
+    }
+
+    async fn shell_request(
+        &mut self,
+        channel: ChannelId,
+        session: &mut Session,
+    ) -> Result<(), Self::Error> {
+        // rendering is blocking work, each session streams from a thread
+        // of its own and only goes back to the runtime to send
+        let runtime = tokio::runtime::Handle::current();
+        let handle = session.handle();
+        let subscriber = self.server.broadcast.subscribe();
+        let size = Arc::clone(&self.size);
+        thread::spawn(move || stream(runtime, handle, channel, subscriber, size));
+        Ok(())
+    }
+
+    async fn data(
+        &mut self,
+        channel: ChannelId,
+        data: &[u8],
+        session: &mut Session,
+    ) -> Result<(), Self::Error> {
+        // q or ctrl-c
+        if data.contains(&b'q') || data.contains(&3) {
+            session.data(channel, CryptoVec::from_slice(SESSION_TEARDOWN));
+            session.close(channel);
+        }
+        Ok(())
+    }
+}
+
+/// Renders every new frame at the session's current size until the
+/// session goes away.
+fn stream(
+    runtime: tokio::runtime::Handle,
+    handle: Handle,
+    channel: ChannelId,
+    subscriber: Subscriber,
+    size: Arc<Mutex<(u32, u32)>>,
+) {
+    let setup = handle.data(channel, CryptoVec::from_slice(SESSION_SETUP));
+    if runtime.block_on(setup).is_err() {
+        return;
+    }
+
+    for frame in subscriber.frames() {
+        let (columns, rows) = *size.lock().unwrap();
+        let buf = match subscriber.settings().render_ansi(&frame, columns, rows) {
+            Ok(buf) => buf,
+            Err(_) => continue,
+        };
+
+        if runtime
+            .block_on(handle.data(channel, CryptoVec::from(buf)))
+            .is_err()
+        {
+            return;
+        }
+    }
+}
@@ -0,0 +1,190 @@
+use super::Sink;
+use crate::broadcast::{Broadcast, RenderSettings, Subscriber};
+use crate::capture::Frame;
+use crate::render::Grid;
+use eyre::Result;
+use std::io::{Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use tracing::{info, warn};
+
+const IAC: u8 = 255;
+const SB: u8 = 250;
+const SE: u8 = 240;
+const NAWS: u8 = 31;
+
+// telnet negotiation asking the client to leave echo to us, to send keys as
+// they are typed and to tell us its window size (NAWS), otherwise telnet
+// clients echo typed lines over the picture
+const TELNET_HANDSHAKE: &[u8] = &[IAC, 251, 1, IAC, 251, 3, IAC, 253, NAWS];
+
+// clear the screen and hide the cursor when a client connects
+const CLIENT_SETUP: &[u8] = b"\x1b[2J\x1b[?25l";
+
+/// Streams frames as ANSI text to every client connected over TCP, like a
+/// telnet server. Connect with `telnet host port` or `nc host port`.
+///
+/// Telnet clients are rendered at their window size, clients that don't
+/// report one (like netcat) at a default size. Every client gets its own
+/// thread, and frames are dropped for clients that can't keep up, see
+/// [`Broadcast`].
+pub struct TcpSink {
+    broadcast: Broadcast,
+    addr: SocketAddr,
+}
+
+impl TcpSink {
+    /// Listens on `addr` and accepts clients in the background. `size` is
+    /// the `(columns, rows)` used for clients that don't report their own.
+    pub fn bind(
+        addr: impl ToSocketAddrs,
+        settings: RenderSettings,
+        size: (u32, u32),
+    ) -> Result<Self> {
+        Self::from_listener(TcpListener::bind(addr)?, settings, size)
+    }
+
+    /// Like [`bind`](TcpSink::bind), but accepts clients on a socket that is
+    /// already listening, e.g. one handed over by systemd.
+    pub fn from_listener(
+        listener: TcpListener,
+        settings: RenderSettings,
+        size: (u32, u32),
+    ) -> Result<Self> {
+        let addr = listener.local_addr()?;
+        let broadcast = Broadcast::new(settings);
+
+        let subscribers = broadcast.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                if let Err(e) = spawn_client(stream, subscribers.subscribe(), size) {
+                    warn!("telnet client failed to connect: {e}");
+                }
+            }
+        });
+
+        Ok(Self { broadcast, addr })
+    }
+
+    /// The address the server is listening on.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+}
+
+impl Sink for TcpSink {
+    // clients render captured frames at their own size
+    fn write_frame(&mut self, _grid: &Grid) -> Result<()> {
+        Ok(())
+    }
+
+    fn write_captured(&mut self, frame: &Frame) -> Result<()> {
+        self.broadcast.send(frame)
+    }
+
+    /// Number of clients currently connected.
+    fn clients(&self) -> usize {
+        self.broadcast.subscribers()
+    }
+}
+
+fn spawn_client(mut stream: TcpStream, subscriber: Subscriber, size: (u32, u32)) -> Result<()> {
+    let peer = stream.peer_addr()?;
+    stream.set_nodelay(true)?;
+    stream.write_all(TELNET_HANDSHAKE)?;
+    stream.write_all(CLIENT_SETUP)?;
+
+    let size = Arc::new(Mutex::new(size));
+
+    // the reader picks up window size changes, and notices the client going
+    // away before the next frame would fail to write
+    let mut reader = stream.try_clone()?;
+    let reported = Arc::clone(&size);
+    thread::spawn(move || {
+        let mut telnet = Telnet::default();
+        let mut buf = [0; 256];
+        loop {
+            let n = match reader.read(&mut buf) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => n,
+            };
+            for &byte in &buf[..n] {
+                if let Some(size) = telnet.push(byte) {
+                    *reported.lock().unwrap() = size;
+                }
+            }
+        }
+        let _ = reader.shutdown(Shutdown::Both);
+    });
+
+    info!(%peer, "telnet client connected");
+    thread::spawn(move || {
+        let mut last_size = None;
+        for frame in subscriber.frames() {
+            let (columns, rows) = *size.lock().unwrap();
+            let buf = match subscriber.settings().render_ansi(&frame, columns, rows) {
+                Ok(buf) => buf,
+                Err(_) => continue,
+            };
+            // clear what the old size left behind
+            if last_size.replace((columns, rows)) != Some((columns, rows))
+                && stream.write_all(b"\x1b[2J").is_err()
+            {
+                break;
+            }
+
+            if stream.write_all(&buf).is_err() {
+                break;
+            }
+        }
+        // dropping the subscriber here unsubscribes the client
+        let _ = stream.shutdown(Shutdown::Both);
+        info!(%peer, "telnet client disconnected");
+    });
+
+    Ok(())
+}
+
+/// Just enough of a telnet parser to pick window sizes out of `IAC SB NAWS
+/// <width> <height> IAC SE` sub-negotiations.
+#[derive(Default)]
+struct Telnet {
+    iac: bool,
+    sub: Option<Vec<u8>>,
+}
+
+impl Telnet {
+    /// Feeds one byte, returns the `(columns, rows)` when a window size
+    /// report is complete.
+    fn push(&mut self, byte: u8) -> Option<(u32, u32)> {
+        let in_sub = self.sub.is_some();
+
+        if self.iac {
+            self.iac = false;
+            match byte {
+                // an escaped 255 inside the sub-negotiation
+                IAC if in_sub => self.sub.get_or_insert_with(Vec::new).push(IAC),
+                SB if !in_sub => self.sub = Some(Vec::new()),
+                SE if in_sub => {
+                    if let Some([NAWS, w0, w1, h0, h1]) = self.sub.take().as_deref() {
+                        let columns = u16::from_be_bytes([*w0, *w1]) as u32;
+                        let rows = u16::from_be_bytes([*h0, *h1]) as u32;
+                        if columns > 0 && rows > 0 {
+                            return Some((columns, rows));
+                        }
+                    }
+                }
+                _ => (),
+            }
+            return None;
+        }
+
+        match byte {
+            IAC => self.iac = true,
+            _ if in_sub => self.sub.get_or_insert_with(Vec::new).push(byte),
+            _ => (),
+        }
+        None
+    }
+}
@@ -0,0 +1,80 @@
+use super::Sink;
+use crate::render::{ColorMode, Grid, GridWriter};
+use crossterm::{cursor, queue, terminal};
+use eyre::Result;
+use std::io::{self, stdout, Stdout, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Draws frames over the whole terminal on stdout.
+pub struct TerminalSink {
+    out: Stdout,
+    color_mode: ColorMode,
+    writer: GridWriter,
+    written: Arc<AtomicU64>,
+}
+
+impl TerminalSink {
+    pub fn new() -> Self {
+        Self::with_color_mode(ColorMode::default())
+    }
+
+    pub fn with_color_mode(color_mode: ColorMode) -> Self {
+        Self {
+            out: stdout(),
+            color_mode,
+            writer: GridWriter::default(),
+            written: Arc::default(),
+        }
+    }
+
+    /// The number of bytes written to the terminal so far, which keeps
+    /// counting once the sink moved to another thread.
+    pub fn written(&self) -> Arc<AtomicU64> {
+        Arc::clone(&self.written)
+    }
+}
+
+impl Default for TerminalSink {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Sink for TerminalSink {
+    fn write_frame(&mut self, grid: &Grid) -> Result<()> {
+        let mut out = Counting {
+            inner: &mut self.out,
+            bytes: 0,
+        };
+        queue!(
+            out,
+            terminal::Clear(terminal::ClearType::All),
+            cursor::MoveTo(0, 0)
+        )?;
+
+        self.writer.write(grid, self.color_mode, &mut out)?;
+
+        out.flush()?;
+        self.written.fetch_add(out.bytes, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+/// Counts the bytes written through it.
+struct Counting<W> {
+    inner: W,
+    bytes: u64,
+}
+
+impl<W: Write> Write for Counting<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.bytes += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
@@ -0,0 +1,165 @@
+use super::Sink;
+use crate::broadcast::{Broadcast, RenderSettings, Subscriber};
+use crate::capture::Frame;
+use crate::render::Grid;
+use eyre::{eyre, Result};
+use std::env;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::net::Shutdown;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use tracing::{info, warn};
+
+// clear the screen and hide the cursor when a client attaches
+const CLIENT_SETUP: &[u8] = b"\x1b[2J\x1b[?25l";
+
+/// Where `asciicam daemon` listens and `asciicam attach` connects when no
+/// socket is given: `$XDG_RUNTIME_DIR/asciicam.sock`, or a per user socket
+/// in the temp directory.
+pub fn default_socket_path() -> PathBuf {
+    match env::var_os("XDG_RUNTIME_DIR") {
+        Some(dir) => PathBuf::from(dir).join("asciicam.sock"),
+        None => {
+            let user = env::var("USER").unwrap_or_else(|_| "user".to_string());
+            env::temp_dir().join(format!("asciicam-{user}.sock"))
+        }
+    }
+}
+
+/// Streams frames as ANSI text to every client connected to a unix socket,
+/// which is what `asciicam attach` talks to.
+///
+/// Clients tell the sink their terminal size by sending lines like
+/// `120x40`, and are rendered at a default size until they do. Like the
+/// other network sinks every client gets its own thread and frames are
+/// dropped for clients that can't keep up, see [`Broadcast`].
+pub struct UnixSink {
+    broadcast: Broadcast,
+    path: PathBuf,
+}
+
+impl UnixSink {
+    /// Listens on the socket at `path` and accepts clients in the
+    /// background. A socket left behind by a process that didn't shut down
+    /// cleanly is replaced, one that still has a listener is an error.
+    pub fn bind(
+        path: impl AsRef<Path>,
+        settings: RenderSettings,
+        size: (u32, u32),
+    ) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        if UnixStream::connect(&path).is_ok() {
+            return Err(eyre!(
+                "Something is already listening on '{}'",
+                path.display()
+            ));
+        }
+        let _ = fs::remove_file(&path);
+
+        let listener = UnixListener::bind(&path)?;
+        let broadcast = Broadcast::new(settings);
+
+        let subscribers = broadcast.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                if let Err(e) = spawn_client(stream, subscribers.subscribe(), size) {
+                    warn!("client failed to attach: {e}");
+                }
+            }
+        });
+
+        Ok(Self { broadcast, path })
+    }
+
+    /// The path of the socket.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Sink for UnixSink {
+    // clients render captured frames at their own size
+    fn write_frame(&mut self, _grid: &Grid) -> Result<()> {
+        Ok(())
+    }
+
+    fn write_captured(&mut self, frame: &Frame) -> Result<()> {
+        self.broadcast.send(frame)
+    }
+
+    /// Number of clients currently attached.
+    fn clients(&self) -> usize {
+        self.broadcast.subscribers()
+    }
+}
+
+impl Drop for UnixSink {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+fn spawn_client(mut stream: UnixStream, subscriber: Subscriber, size: (u32, u32)) -> Result<()> {
+    stream.write_all(CLIENT_SETUP)?;
+
+    let size = Arc::new(Mutex::new(size));
+
+    // the reader picks up terminal size changes, and notices the client
+    // detaching before the next frame would fail to write
+    let reader = stream.try_clone()?;
+    let reported = Arc::clone(&size);
+    thread::spawn(move || {
+        for line in BufReader::new(&reader).lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(_) => break,
+            };
+            if let Some(size) = parse_size(&line) {
+                *reported.lock().unwrap() = size;
+            }
+        }
+        let _ = reader.shutdown(Shutdown::Both);
+    });
+
+    info!("client attached");
+    thread::spawn(move || {
+        let mut last_size = None;
+        for frame in subscriber.frames() {
+            let (columns, rows) = *size.lock().unwrap();
+            let buf = match subscriber.settings().render_ansi(&frame, columns, rows) {
+                Ok(buf) => buf,
+                Err(_) => continue,
+            };
+            // clear what the old size left behind
+            if last_size.replace((columns, rows)) != Some((columns, rows))
+                && stream.write_all(b"\x1b[2J").is_err()
+            {
+                break;
+            }
+
+            if stream.write_all(&buf).is_err() {
+                break;
+            }
+        }
+        // dropping the subscriber here unsubscribes the client
+        let _ = stream.shutdown(Shutdown::Both);
+        info!("client detached");
+    });
+
+    Ok(())
+}
+
+/// Parses a `COLUMNSxROWS` size line.
+fn parse_size(line: &str) -> Option<(u32, u32)> {
+    let (columns, rows) = line.trim().split_once('x')?;
+
+    match (columns.parse().ok()?, rows.parse().ok()?) {
+        (0, _) | (_, 0) => None,
+        // anything bigger is a mistake or an attempt to eat our memory
+        (columns, rows) if columns > 1000 || rows > 1000 => None,
+        size => Some(size),
+    }
+}
@@ -0,0 +1,405 @@
+use super::Sink;
+use crate::broadcast::{Broadcast, RenderSettings, Subscriber};
+use crate::capture::Frame;
+use crate::render::{write_grid_with, Cell, ColorMode, Grid};
+use eyre::{eyre, Result};
+use image::Rgb;
+use std::fmt::Write as _;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::str::FromStr;
+use std::sync::mpsc::RecvTimeoutError;
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+use tungstenite::{Message, WebSocket};
+
+// how long a client thread waits for a frame before checking its socket
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+const PING_INTERVAL: Duration = Duration::from_secs(10);
+// a client that hasn't sent anything, pongs included, for this long is gone
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(30);
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+// served by `bind_http`, connects back to the same address for the feed
+const VIEWER_PAGE: &str = include_str!("viewer.html");
+
+/// How frames are encoded for WebSocket clients.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WsFormat {
+    /// Text messages with the same SGR escape codes the terminal gets,
+    /// ready to write into a terminal emulator such as xterm.js.
+    #[default]
+    Ansi,
+    /// JSON text messages. The first message, and any after the size
+    /// changes, is `{"type":"frame","width":w,"height":h,"cells":[...]}`
+    /// with every cell, later ones are `{"type":"diff","cells":[...]}` with
+    /// only the cells that changed. Cells are `[x,y,"glyph",fg,bg]`, colors
+    /// `"#rrggbb"` or `null`.
+    Json,
+}
+
+impl FromStr for WsFormat {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "ansi" => Ok(WsFormat::Ansi),
+            "json" => Ok(WsFormat::Json),
+            _ => Err(eyre!(
+                "Unknown WebSocket format '{s}', expected ansi or json"
+            )),
+        }
+    }
+}
+
+/// Pushes frames to every client connected over WebSocket.
+///
+/// Every client gets its own thread and is rendered at its own size, which
+/// it can set by sending `{"type":"resize","columns":80,"rows":24}`. Frames
+/// are dropped for clients that can't keep up, either because their last
+/// frame is still queued (see [`Broadcast`]) or because the socket isn't
+/// taking any more data, so a slow client never holds up capture or the
+/// other clients. Clients are pinged every 10 seconds and dropped after 30
+/// seconds of silence.
+pub struct WebSocketSink {
+    broadcast: Broadcast,
+    addr: SocketAddr,
+}
+
+impl WebSocketSink {
+    /// Listens on `addr` and accepts clients in the background. `size` is
+    /// the `(columns, rows)` clients are rendered at until they ask for
+    /// another.
+    pub fn bind(
+        addr: impl ToSocketAddrs,
+        format: WsFormat,
+        settings: RenderSettings,
+        size: (u32, u32),
+    ) -> Result<Self> {
+        Self::listen(TcpListener::bind(addr)?, format, settings, size, false)
+    }
+
+    /// Like [`bind`](WebSocketSink::bind) with [`WsFormat::Ansi`], but plain
+    /// HTTP requests get a page showing the feed with xterm.js, so it can be
+    /// watched by opening `http://addr/` in a browser.
+    pub fn bind_http(
+        addr: impl ToSocketAddrs,
+        settings: RenderSettings,
+        size: (u32, u32),
+    ) -> Result<Self> {
+        Self::listen(
+            TcpListener::bind(addr)?,
+            WsFormat::Ansi,
+            settings,
+            size,
+            true,
+        )
+    }
+
+    /// Like [`bind`](WebSocketSink::bind), but accepts clients on a socket
+    /// that is already listening, e.g. one handed over by systemd.
+    pub fn from_listener(
+        listener: TcpListener,
+        format: WsFormat,
+        settings: RenderSettings,
+        size: (u32, u32),
+    ) -> Result<Self> {
+        Self::listen(listener, format, settings, size, false)
+    }
+
+    /// Like [`bind_http`](WebSocketSink::bind_http), but on a socket that is
+    /// already listening.
+    pub fn http_from_listener(
+        listener: TcpListener,
+        settings: RenderSettings,
+        size: (u32, u32),
+    ) -> Result<Self> {
+        Self::listen(listener, WsFormat::Ansi, settings, size, true)
+    }
+
+    fn listen(
+        listener: TcpListener,
+        format: WsFormat,
+        settings: RenderSettings,
+        size: (u32, u32),
+        page: bool,
+    ) -> Result<Self> {
+        let addr = listener.local_addr()?;
+        let broadcast = Broadcast::new(settings);
+
+        let subscribers = broadcast.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let subscribers = subscribers.clone();
+                // the handshake can take a while, don't hold up accepting
+                thread::spawn(move || {
+                    if page && !matches!(is_upgrade(&stream), Ok(true)) {
+                        let _ = serve_page(stream);
+                        return;
+                    }
+                    let peer = stream.peer_addr().ok();
+                    match handshake(stream) {
+                        Ok(ws) => {
+                            info!(?peer, "websocket client connected");
+                            serve_client(ws, subscribers.subscribe(), format, size);
+                            info!(?peer, "websocket client disconnected");
+                        }
+                        Err(e) => warn!(?peer, "{e}"),
+                    }
+                });
+            }
+        });
+
+        Ok(Self { broadcast, addr })
+    }
+
+    /// The address the server is listening on.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+}
+
+impl Sink for WebSocketSink {
+    // clients render captured frames at their own size
+    fn write_frame(&mut self, _grid: &Grid) -> Result<()> {
+        Ok(())
+    }
+
+    fn write_captured(&mut self, frame: &Frame) -> Result<()> {
+        self.broadcast.send(frame)
+    }
+
+    /// Number of clients currently connected.
+    fn clients(&self) -> usize {
+        self.broadcast.subscribers()
+    }
+}
+
+/// Whether the request waiting on `stream` asks for a WebSocket upgrade,
+/// without consuming it.
+fn is_upgrade(stream: &TcpStream) -> Result<bool> {
+    stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+
+    let mut buf = [0; 4096];
+    let started = Instant::now();
+    loop {
+        let n = stream.peek(&mut buf)?;
+        let request = String::from_utf8_lossy(&buf[..n]).to_ascii_lowercase();
+
+        if request.contains("\r\n\r\n") || n == buf.len() {
+            return Ok(request.contains("upgrade: websocket"));
+        }
+        if n == 0 || started.elapsed() > HANDSHAKE_TIMEOUT {
+            return Ok(false);
+        }
+        // the rest of the headers are still on their way
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Answers any plain HTTP request with the viewer page.
+fn serve_page(mut stream: TcpStream) -> Result<()> {
+    // the request itself doesn't matter, but has to be read so closing the
+    // socket doesn't reset the connection before the page arrives
+    let mut buf = [0; 4096];
+    let _ = stream.read(&mut buf)?;
+
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\n\
+         Content-Type: text/html; charset=utf-8\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n\r\n",
+        VIEWER_PAGE.len()
+    )?;
+    stream.write_all(VIEWER_PAGE.as_bytes())?;
+    stream.flush()?;
+
+    Ok(())
+}
+
+fn handshake(stream: TcpStream) -> Result<WebSocket<TcpStream>> {
+    stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+    stream.set_nodelay(true)?;
+
+    let ws = tungstenite::accept(stream).map_err(|e| eyre!("WebSocket handshake failed: {e}"))?;
+
+    // from here on the client thread polls, so nothing blocks on a slow
+    // client
+    ws.get_ref().set_read_timeout(None)?;
+    ws.get_ref().set_nonblocking(true)?;
+
+    Ok(ws)
+}
+
+fn serve_client(
+    mut ws: WebSocket<TcpStream>,
+    subscriber: Subscriber,
+    format: WsFormat,
+    mut size: (u32, u32),
+) {
+    let settings = subscriber.settings();
+    let mut last_sent: Option<Grid> = None;
+    // set while the socket buffer is full, frames are skipped until it drains
+    let mut blocked = false;
+    let mut last_seen = Instant::now();
+    let mut last_ping = Instant::now();
+
+    loop {
+        match subscriber.frames().recv_timeout(POLL_INTERVAL) {
+            Ok(frame) if !blocked => {
+                let grid = match settings.render(&frame, size.0, size.1) {
+                    Ok(grid) => grid,
+                    Err(_) => continue,
+                };
+                let message = match format {
+                    WsFormat::Ansi => encode_ansi(&grid, settings.color_mode),
+                    WsFormat::Json => encode_json(&grid, last_sent.as_ref()),
+                };
+                match ws.send(Message::Text(message)) {
+                    Ok(()) => (),
+                    Err(e) if would_block(&e) => blocked = true,
+                    Err(_) => return,
+                }
+                last_sent = Some(grid);
+            }
+            Ok(_) | Err(RecvTimeoutError::Timeout) => (),
+            Err(RecvTimeoutError::Disconnected) => return,
+        }
+
+        if blocked {
+            match ws.flush() {
+                Ok(()) => blocked = false,
+                Err(e) if would_block(&e) => (),
+                Err(_) => return,
+            }
+        }
+
+        // drain whatever the client sent, pongs and closes are handled by
+        // tungstenite itself
+        loop {
+            match ws.read() {
+                Ok(message) => {
+                    last_seen = Instant::now();
+                    if let Message::Text(text) = message {
+                        if let Some(requested) = parse_resize(&text) {
+                            size = requested;
+                        }
+                    }
+                }
+                Err(e) if would_block(&e) => break,
+                Err(_) => return,
+            }
+        }
+
+        if last_seen.elapsed() > CLIENT_TIMEOUT {
+            let _ = ws.close(None);
+            return;
+        }
+        if last_ping.elapsed() > PING_INTERVAL {
+            last_ping = Instant::now();
+            match ws.send(Message::Ping(Vec::new())) {
+                Ok(()) => (),
+                Err(e) if would_block(&e) => blocked = true,
+                Err(_) => return,
+            }
+        }
+    }
+}
+
+/// Picks the size out of a `{"type":"resize","columns":80,"rows":24}`
+/// message. Not a json parser, but clients only ever send this one message.
+fn parse_resize(text: &str) -> Option<(u32, u32)> {
+    if !text.contains("\"resize\"") {
+        return None;
+    }
+
+    let field = |name: &str| -> Option<u32> {
+        let start = text.find(&format!("\"{name}\""))? + name.len() + 2;
+        let value = text[start..].trim_start().strip_prefix(':')?.trim_start();
+        let end = value
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(value.len());
+        value[..end].parse().ok()
+    };
+
+    match (field("columns")?, field("rows")?) {
+        (0, _) | (_, 0) => None,
+        // anything bigger is a mistake or an attempt to eat our memory
+        (columns, rows) if columns > 1000 || rows > 1000 => None,
+        size => Some(size),
+    }
+}
+
+fn would_block(e: &tungstenite::Error) -> bool {
+    matches!(e, tungstenite::Error::Io(e) if e.kind() == ErrorKind::WouldBlock)
+}
+
+fn encode_ansi(grid: &Grid, color_mode: ColorMode) -> String {
+    let mut buf = b"\x1b[H".to_vec();
+    // writing to a vec can't fail
+    let _ = write_grid_with(grid, color_mode, &mut buf);
+
+    String::from_utf8_lossy(&buf).into_owned()
+}
+
+/// Encodes `grid` as a diff against `previous`, or in full if there is no
+/// previous grid of the same size.
+fn encode_json(grid: &Grid, previous: Option<&Grid>) -> String {
+    let previous = previous.filter(|p| p.width() == grid.width() && p.height() == grid.height());
+
+    let mut json = match previous {
+        Some(_) => String::from(r#"{"type":"diff","cells":["#),
+        None => format!(
+            r#"{{"type":"frame","width":{},"height":{},"cells":["#,
+            grid.width(),
+            grid.height()
+        ),
+    };
+
+    let mut first = true;
+    for y in 0..grid.height() {
+        for x in 0..grid.width() {
+            let cell = grid.get(x, y);
+            if previous.map(|p| p.get(x, y)) == Some(cell) {
+                continue;
+            }
+
+            if !first {
+                json.push(',');
+            }
+            first = false;
+            write_cell(&mut json, x, y, cell);
+        }
+    }
+
+    json.push_str("]}");
+    json
+}
+
+fn write_cell(json: &mut String, x: u32, y: u32, cell: &Cell) {
+    let _ = write!(json, "[{x},{y},\"");
+    match cell.glyph {
+        '"' => json.push_str("\\\""),
+        '\\' => json.push_str("\\\\"),
+        c if c.is_control() => {
+            let _ = write!(json, "\\u{:04x}", c as u32);
+        }
+        c => json.push(c),
+    }
+    json.push_str("\",");
+    write_color(json, cell.fg);
+    json.push(',');
+    write_color(json, cell.bg);
+    json.push(']');
+}
+
+fn write_color(json: &mut String, color: Option<Rgb<u8>>) {
+    match color {
+        Some(Rgb([r, g, b])) => {
+            let _ = write!(json, "\"#{r:02x}{g:02x}{b:02x}\"");
+        }
+        None => json.push_str("null"),
+    }
+}
@@ -0,0 +1,36 @@
+use super::Sink;
+use crate::render::{write_grid, Grid};
+use eyre::Result;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// Appends every frame to a writer, such as a file or a pipe.
+pub struct WriterSink<W: Write> {
+    out: W,
+}
+
+impl<W: Write> WriterSink<W> {
+    pub fn new(out: W) -> Self {
+        Self { out }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+impl WriterSink<BufWriter<File>> {
+    /// Creates (or truncates) the file at `path` and writes frames to it.
+    pub fn create(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(Self::new(BufWriter::new(File::create(path)?)))
+    }
+}
+
+impl<W: Write> Sink for WriterSink<W> {
+    fn write_frame(&mut self, grid: &Grid) -> Result<()> {
+        write_grid(grid, &mut self.out)?;
+        self.out.flush()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,81 @@
+use super::Sink;
+use crate::capture::Frame;
+use crate::convert::decode_frame;
+use crate::render::{write_grid_with, ColorMode, Grid};
+use eyre::Result;
+
+// frames queued per subscriber before zeromq drops newer ones for it, a
+// slow subscriber should see the latest frames rather than old ones
+const SEND_HIGH_WATER_MARK: i32 = 2;
+
+/// Publishes frames on a ZeroMQ PUB socket, for any number of subscribers
+/// asciicam doesn't need to know about.
+///
+/// Every frame is a multipart message whose first part is the topic, so
+/// subscribers can pick what they want with a subscription prefix:
+///
+/// - `frame`, then the rendered frame as ANSI text
+/// - `gray`, only with [`raw`](ZmqSink::raw): a json header like
+///   `{"width":640,"height":480,"sequence":42,"timestamp_us":1234}`, then
+///   the full size grayscale pixels, one byte each, row by row
+///
+/// Publishing never blocks, zeromq drops messages for subscribers that
+/// can't keep up.
+pub struct ZmqSink {
+    socket: zmq::Socket,
+    color_mode: ColorMode,
+    raw: bool,
+}
+
+impl ZmqSink {
+    /// Binds a PUB socket to `endpoint`, e.g. `tcp://*:5556`.
+    pub fn bind(endpoint: &str, color_mode: ColorMode) -> Result<Self> {
+        let socket = zmq::Context::new().socket(zmq::PUB)?;
+        socket.set_sndhwm(SEND_HIGH_WATER_MARK)?;
+        socket.bind(endpoint)?;
+
+        Ok(Self {
+            socket,
+            color_mode,
+            raw: false,
+        })
+    }
+
+    /// Also publish the captured grayscale frames under `gray`.
+    pub fn raw(mut self, raw: bool) -> Self {
+        self.raw = raw;
+        self
+    }
+}
+
+impl Sink for ZmqSink {
+    fn write_frame(&mut self, grid: &Grid) -> Result<()> {
+        let mut text = Vec::new();
+        write_grid_with(grid, self.color_mode, &mut text)?;
+
+        self.socket
+            .send_multipart([&b"frame"[..], &text[..]], zmq::DONTWAIT)?;
+        Ok(())
+    }
+
+    fn write_captured(&mut self, frame: &Frame) -> Result<()> {
+        if !self.raw {
+            return Ok(());
+        }
+
+        let img = decode_frame(frame)?;
+        let header = format!(
+            r#"{{"width":{},"height":{},"sequence":{},"timestamp_us":{}}}"#,
+            img.width(),
+            img.height(),
+            frame.meta.sequence,
+            frame.meta.timestamp.as_micros()
+        );
+
+        self.socket.send_multipart(
+            [&b"gray"[..], header.as_bytes(), img.as_raw()],
+            zmq::DONTWAIT,
+        )?;
+        Ok(())
+    }
+}
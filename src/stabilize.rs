@@ -0,0 +1,130 @@
+//! Holding the picture of a shaky camera still.
+
+use crate::convert::Resizer;
+use eyre::Result;
+use image::{GrayImage, ImageBuffer, Pixel};
+use std::mem;
+
+// frames are compared at this size, small enough to try every shift
+// within reach
+const SAMPLE_WIDTH: u32 = 64;
+const SAMPLE_HEIGHT: u32 = 48;
+// the furthest a frame is looked for from the one before, in samples
+const SEARCH: i32 = 4;
+// of every side of the picture, cropped off to have room to move it
+const MARGIN: f32 = 0.08;
+// how much of the shift is kept each frame, lets a deliberate pan through
+// while undoing the shake on top of it
+const DECAY: f32 = 0.9;
+
+/// Works out how far the picture moved between frames, by trying every
+/// shift of a small version of it, and crops the frames so that the shake
+/// is undone.
+#[derive(Default)]
+pub struct Stabilizer {
+    resizer: Resizer,
+    // the frame at the sample size, kept to resize the next one into
+    small: Vec<u8>,
+    previous: Option<GrayImage>,
+    // the sample before the previous one, filled with the next
+    spare: GrayImage,
+    // how far the crop is moved, in samples
+    offset: (f32, f32),
+}
+
+impl Stabilizer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Compares `img` with the frame before and moves the crop along with
+    /// how far the picture moved.
+    pub fn update<P>(&mut self, img: &ImageBuffer<P, Vec<u8>>) -> Result<()>
+    where
+        P: Pixel<Subpixel = u8>,
+    {
+        let mut small = mem::take(&mut self.small);
+        small.resize(
+            (SAMPLE_WIDTH * SAMPLE_HEIGHT) as usize * P::CHANNEL_COUNT as usize,
+            0,
+        );
+        let mut small = ImageBuffer::<P, Vec<u8>>::from_raw(SAMPLE_WIDTH, SAMPLE_HEIGHT, small)
+            .expect("sized for the sample");
+        self.resizer.resize(img, &mut small)?;
+
+        let mut sample = mem::take(&mut self.spare);
+        if sample.dimensions() != (SAMPLE_WIDTH, SAMPLE_HEIGHT) {
+            sample = GrayImage::new(SAMPLE_WIDTH, SAMPLE_HEIGHT);
+        }
+        for (gray, pixel) in sample.pixels_mut().zip(small.pixels()) {
+            *gray = pixel.to_luma();
+        }
+        self.small = small.into_raw();
+
+        if let Some(previous) = &self.previous {
+            let (dx, dy) = shift(previous, &sample);
+            let (max_x, max_y) = (MARGIN * SAMPLE_WIDTH as f32, MARGIN * SAMPLE_HEIGHT as f32);
+            self.offset = (
+                ((self.offset.0 + dx as f32) * DECAY).clamp(-max_x, max_x),
+                ((self.offset.1 + dy as f32) * DECAY).clamp(-max_y, max_y),
+            );
+        }
+        if let Some(previous) = self.previous.replace(sample) {
+            self.spare = previous;
+        }
+
+        Ok(())
+    }
+
+    /// The part of a `width` x `height` frame without its margins, moved by
+    /// as much as the camera shook, as (x, y, width, height).
+    pub fn region(&self, width: u32, height: u32) -> (u32, u32, u32, u32) {
+        let (margin_x, margin_y) = (
+            (width as f32 * MARGIN) as i64,
+            (height as f32 * MARGIN) as i64,
+        );
+        let x = margin_x + (self.offset.0 * width as f32 / SAMPLE_WIDTH as f32) as i64;
+        let y = margin_y + (self.offset.1 * height as f32 / SAMPLE_HEIGHT as f32) as i64;
+        let (crop_width, crop_height) = (
+            (width as i64 - 2 * margin_x).max(1) as u32,
+            (height as i64 - 2 * margin_y).max(1) as u32,
+        );
+
+        (
+            x.clamp(0, (width - crop_width) as i64) as u32,
+            y.clamp(0, (height - crop_height) as i64) as u32,
+            crop_width,
+            crop_height,
+        )
+    }
+}
+
+/// How far `current` is moved from `previous`, the shift within reach
+/// where they differ the least.
+fn shift(previous: &GrayImage, current: &GrayImage) -> (i32, i32) {
+    let (width, height) = (SAMPLE_WIDTH as i32, SAMPLE_HEIGHT as i32);
+    let mut best = ((0, 0), u32::MAX);
+
+    for dy in -SEARCH..=SEARCH {
+        for dx in -SEARCH..=SEARCH {
+            // the same middle part of every shift is compared, so all of
+            // them add up the same number of pixels
+            let mut diff = 0;
+            for y in SEARCH..height - SEARCH {
+                for x in SEARCH..width - SEARCH {
+                    let a = previous.get_pixel((x - dx) as u32, (y - dy) as u32).0[0];
+                    let b = current.get_pixel(x as u32, y as u32).0[0];
+                    diff += a.abs_diff(b) as u32;
+                }
+            }
+
+            // ties go to the smaller shift, a flat wall shouldn't drift
+            let closer = dx.abs() + dy.abs() < best.0 .0.abs() + best.0 .1.abs();
+            if diff < best.1 || (diff == best.1 && closer) {
+                best = ((dx, dy), diff);
+            }
+        }
+    }
+
+    best.0
+}
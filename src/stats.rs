@@ -0,0 +1,298 @@
+//! Counting the frames that never made it to the screen, or made it late,
+//! timing the stages the others went through, and logging how a long
+//! capture went.
+
+use crate::capture::{Frame, FrameMeta};
+use crate::motion::MotionDetector;
+use eyre::{eyre, Result};
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+// the share of a new timing mixed into the rolling average
+const AVERAGE_WEIGHT: f64 = 0.1;
+
+// how often a row is appended to a stats log
+const LOG_INTERVAL: Duration = Duration::from_secs(1);
+// the kernel reports CPU time in ticks of 1/100s to every process,
+// whatever the timer frequency inside is
+#[cfg(target_os = "linux")]
+const TICKS_PER_SECOND: f64 = 100.0;
+
+// a frame shown this long after the camera delivered it counts as late, a
+// few frames at the usual rates and about when a picture starts to feel
+// behind
+const LATE_AFTER: Duration = Duration::from_millis(150);
+
+/// Frames lost or delayed on the way from the camera to the screen.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DropStats {
+    /// Frames drawn.
+    pub shown: u64,
+    /// Frames the driver dropped, told by gaps in the sequence numbers.
+    pub driver: u64,
+    /// Frames captured but replaced by a newer one before they were drawn.
+    pub skipped: u64,
+    /// Frames drawn more than 150ms after the camera delivered them.
+    pub late: u64,
+    last_sequence: Option<u64>,
+}
+
+impl DropStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Counts the frames missing between the last frame captured and this
+    /// one.
+    pub fn captured(&mut self, meta: &FrameMeta) {
+        if let Some(last) = self.last_sequence {
+            // a sequence going backwards is a source that started over, like
+            // a reopened camera
+            if meta.sequence > last {
+                self.driver += meta.sequence - last - 1;
+            }
+        }
+        self.last_sequence = Some(meta.sequence);
+    }
+
+    /// Counts a frame drawn just now, late or not.
+    pub fn shown(&mut self, meta: &FrameMeta) {
+        self.shown += 1;
+        if meta
+            .received
+            .map_or(false, |received| received.elapsed() > LATE_AFTER)
+        {
+            self.late += 1;
+        }
+    }
+}
+
+impl fmt::Display for DropStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} shown, {} dropped by the driver, {} skipped, {} late",
+            self.shown, self.driver, self.skipped, self.late
+        )
+    }
+}
+
+/// How long each stage of getting a frame on screen took.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct StageTimings {
+    /// Waiting for the source to deliver the frame.
+    pub capture: Duration,
+    /// Decoding the JPEG, or converting the pixels for rendering.
+    pub decode: Duration,
+    /// Cropping, resizing and filtering to the size of the grid.
+    pub resize: Duration,
+    /// Mapping pixels to characters.
+    pub map: Duration,
+    /// Writing the grid to the terminal.
+    pub write: Duration,
+}
+
+impl StageTimings {
+    fn stages(&self) -> [(&'static str, Duration); 5] {
+        [
+            ("capture", self.capture),
+            ("decode", self.decode),
+            ("resize", self.resize),
+            ("map", self.map),
+            ("write", self.write),
+        ]
+    }
+}
+
+/// The timings of the last frame along with a rolling average of them.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RollingTimings {
+    pub last: StageTimings,
+    pub average: StageTimings,
+    frames: u64,
+}
+
+impl RollingTimings {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn update(&mut self, timings: StageTimings) {
+        self.average = if self.frames == 0 {
+            timings
+        } else {
+            let mix = |average: Duration, last: Duration| {
+                average.mul_f64(1.0 - AVERAGE_WEIGHT) + last.mul_f64(AVERAGE_WEIGHT)
+            };
+            StageTimings {
+                capture: mix(self.average.capture, timings.capture),
+                decode: mix(self.average.decode, timings.decode),
+                resize: mix(self.average.resize, timings.resize),
+                map: mix(self.average.map, timings.map),
+                write: mix(self.average.write, timings.write),
+            }
+        };
+        self.last = timings;
+        self.frames += 1;
+    }
+
+    /// A line per stage with both timings in microseconds, like
+    /// `decode    1520µs  avg   1498µs`.
+    pub fn lines(&self) -> Vec<String> {
+        self.last
+            .stages()
+            .iter()
+            .zip(self.average.stages())
+            .map(|((stage, last), (_, average))| {
+                format!(
+                    "{stage:<8}{:>7}µs  avg {:>7}µs",
+                    last.as_micros(),
+                    average.as_micros()
+                )
+            })
+            .collect()
+    }
+}
+
+/// Splits the time spent on a frame into stages. There is no clock in the
+/// browser, every stage takes no time there.
+pub(crate) struct Stopwatch {
+    #[cfg(not(target_arch = "wasm32"))]
+    lap: Instant,
+}
+
+impl Stopwatch {
+    pub(crate) fn start() -> Self {
+        Self {
+            #[cfg(not(target_arch = "wasm32"))]
+            lap: Instant::now(),
+        }
+    }
+
+    /// The time since the last lap, or since the start.
+    pub(crate) fn lap(&mut self) -> Duration {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let now = Instant::now();
+            let lap = now - self.lap;
+            self.lap = now;
+            lap
+        }
+        #[cfg(target_arch = "wasm32")]
+        Duration::ZERO
+    }
+}
+
+/// Appends a row a second to a CSV file, or a JSON object per line to a
+/// `.jsonl` one, for looking into long captures afterwards:
+///
+/// - `time`, RFC 3339
+/// - `fps`, frames drawn per second
+/// - `cpu`, seconds of CPU time the process used during the second, left
+///   empty (`null`) where there is no telling, which is anywhere but Linux
+/// - `terminal_bytes`, written to the terminal during the second
+/// - `motion`, the percentage of the picture that moved between frames,
+///   averaged over the second
+pub struct StatsLog {
+    out: File,
+    json: bool,
+    detector: MotionDetector,
+    since: Instant,
+    frames: u64,
+    // the sum of the fractions that moved, divided by the frames in the end
+    motion: f32,
+    // the totals when the row started
+    cpu: Option<Duration>,
+    terminal_bytes: u64,
+}
+
+impl StatsLog {
+    /// Appends to `path`, starting a CSV file with a header.
+    pub fn create(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let mut out = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| eyre!("Could not open '{}' ({e})", path.display()))?;
+        let json = path.extension().map_or(false, |ext| ext == "jsonl");
+        // a file from an earlier run already has one
+        if !json && out.metadata()?.len() == 0 {
+            writeln!(out, "time,fps,cpu,terminal_bytes,motion")?;
+        }
+
+        Ok(Self {
+            out,
+            json,
+            detector: MotionDetector::new(),
+            since: Instant::now(),
+            frames: 0,
+            motion: 0.0,
+            cpu: cpu_time(),
+            terminal_bytes: 0,
+        })
+    }
+
+    /// Counts a frame drawn, `terminal_bytes` being the total written to
+    /// the terminal so far, and appends a row when a second is up.
+    pub fn frame(&mut self, frame: &Frame, terminal_bytes: u64) -> Result<()> {
+        self.frames += 1;
+        self.motion += self.detector.update(frame)?;
+
+        let elapsed = self.since.elapsed();
+        if elapsed < LOG_INTERVAL {
+            return Ok(());
+        }
+
+        let fps = self.frames as f64 / elapsed.as_secs_f64();
+        let now = cpu_time();
+        let cpu = now
+            .zip(self.cpu)
+            .map(|(now, then)| now.saturating_sub(then).as_secs_f64());
+        let bytes = terminal_bytes.saturating_sub(self.terminal_bytes);
+        let motion = self.motion / self.frames as f32 * 100.0;
+        let time = chrono::Local::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, false);
+
+        let row = if self.json {
+            let cpu = cpu.map_or("null".to_string(), |cpu| format!("{cpu:.3}"));
+            format!(
+                r#"{{"time":"{time}","fps":{fps:.1},"cpu":{cpu},"terminal_bytes":{bytes},"motion":{motion:.1}}}"#
+            )
+        } else {
+            let cpu = cpu.map_or(String::new(), |cpu| format!("{cpu:.3}"));
+            format!("{time},{fps:.1},{cpu},{bytes},{motion:.1}")
+        };
+        writeln!(self.out, "{row}")?;
+
+        self.since = Instant::now();
+        self.frames = 0;
+        self.motion = 0.0;
+        self.cpu = now;
+        self.terminal_bytes = terminal_bytes;
+        Ok(())
+    }
+}
+
+/// The CPU time the process used so far, user and system.
+#[cfg(target_os = "linux")]
+fn cpu_time() -> Option<Duration> {
+    let stat = std::fs::read_to_string("/proc/self/stat").ok()?;
+    // the name in parentheses can have spaces, the fields after it can't.
+    // utime and stime are the 14th and 15th, the state after the name the
+    // 3rd
+    let mut fields = stat.rsplit_once(')')?.1.split_whitespace().skip(11);
+    let user: u64 = fields.next()?.parse().ok()?;
+    let system: u64 = fields.next()?.parse().ok()?;
+
+    Some(Duration::from_secs_f64(
+        (user + system) as f64 / TICKS_PER_SECOND,
+    ))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn cpu_time() -> Option<Duration> {
+    None
+}
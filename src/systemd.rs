@@ -0,0 +1,145 @@
+//! Running as a systemd service.
+//!
+//! Implements the two bits of the `sd_listen_fds(3)` and `sd_notify(3)`
+//! protocols asciicam needs without linking libsystemd: taking over
+//! listening sockets from a socket unit, and reporting readiness and
+//! liveness to the service manager. Everything here does nothing when not
+//! started by systemd.
+
+use crate::capture::Frame;
+use crate::render::Grid;
+use crate::sink::Sink;
+#[cfg(target_os = "linux")]
+use eyre::eyre;
+use eyre::Result;
+use std::env;
+use std::net::TcpListener;
+#[cfg(target_os = "linux")]
+use std::os::fd::{FromRawFd, RawFd};
+#[cfg(target_os = "linux")]
+use std::os::linux::net::SocketAddrExt;
+#[cfg(target_os = "linux")]
+use std::os::unix::net::{SocketAddr, UnixDatagram};
+use std::process;
+use std::time::{Duration, Instant};
+
+// the first socket passed by systemd, the rest follow without gaps
+#[cfg(target_os = "linux")]
+const LISTEN_FDS_START: RawFd = 3;
+
+/// Takes the listening sockets passed by a socket unit, paired with their
+/// `FileDescriptorName=`, which systemd sets to `unknown` when the unit
+/// doesn't name them. Empty when not socket activated.
+///
+/// The environment variables are removed, so the sockets are only handed
+/// out once and don't leak into child processes.
+#[cfg(target_os = "linux")]
+pub fn listeners() -> Result<Vec<(String, TcpListener)>> {
+    let pid = env::var("LISTEN_PID").ok();
+    let count = env::var("LISTEN_FDS").ok();
+    let names = env::var("LISTEN_FDNAMES").unwrap_or_default();
+    for var in ["LISTEN_PID", "LISTEN_FDS", "LISTEN_FDNAMES"] {
+        env::remove_var(var);
+    }
+
+    // the variables are meant for the process systemd started, not for
+    // anything it spawned that inherited the environment
+    let count: RawFd = match (pid, count) {
+        (Some(pid), Some(count)) if pid == process::id().to_string() => count.parse()?,
+        _ => return Ok(Vec::new()),
+    };
+    let mut names = names.split(':');
+
+    Ok((LISTEN_FDS_START..LISTEN_FDS_START + count)
+        .map(|fd| {
+            let name = names.next().filter(|name| !name.is_empty());
+            // systemd hands these over for us to own, nothing else in the
+            // process uses them
+            let listener = unsafe { TcpListener::from_raw_fd(fd) };
+            (name.unwrap_or("unknown").to_string(), listener)
+        })
+        .collect())
+}
+
+/// There is no systemd to hand over sockets off Linux.
+#[cfg(not(target_os = "linux"))]
+pub fn listeners() -> Result<Vec<(String, TcpListener)>> {
+    Ok(Vec::new())
+}
+
+/// Sends `state` to the service manager, e.g. `READY=1`. Does nothing when
+/// not running under systemd.
+#[cfg(target_os = "linux")]
+pub fn notify(state: &str) -> Result<()> {
+    let path = match env::var_os("NOTIFY_SOCKET") {
+        Some(path) => path,
+        None => return Ok(()),
+    };
+    let path = path.to_string_lossy();
+
+    // a leading @ is an abstract socket
+    let addr = match path.strip_prefix('@') {
+        Some(name) => SocketAddr::from_abstract_name(name)?,
+        None => SocketAddr::from_pathname(&*path)?,
+    };
+    UnixDatagram::unbound()?
+        .send_to_addr(state.as_bytes(), &addr)
+        .map_err(|e| eyre!("Could not notify systemd on '{path}': {e}"))?;
+
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn notify(_state: &str) -> Result<()> {
+    Ok(())
+}
+
+/// Pings the systemd watchdog for every captured frame, so a source that
+/// stops delivering, like a hung camera driver, gets the service restarted
+/// once `WatchdogSec=` passes.
+///
+/// Add it to the pipeline with [`AsciicamBuilder::sink`](crate::AsciicamBuilder::sink).
+pub struct Watchdog {
+    interval: Duration,
+    last_ping: Option<Instant>,
+}
+
+impl Watchdog {
+    /// The watchdog systemd asked for, `None` when it isn't enabled for this
+    /// process.
+    pub fn from_env() -> Option<Self> {
+        if let Ok(pid) = env::var("WATCHDOG_PID") {
+            if pid != process::id().to_string() {
+                return None;
+            }
+        }
+        let usec: u64 = env::var("WATCHDOG_USEC").ok()?.parse().ok()?;
+
+        Some(Self {
+            // pinging at half the timeout leaves room for a slow frame
+            interval: Duration::from_micros(usec) / 2,
+            last_ping: None,
+        })
+    }
+
+    /// Pings the watchdog unless it was pinged less than half the timeout
+    /// ago.
+    pub fn ping(&mut self) -> Result<()> {
+        if matches!(self.last_ping, Some(last) if last.elapsed() < self.interval) {
+            return Ok(());
+        }
+        self.last_ping = Some(Instant::now());
+
+        notify("WATCHDOG=1")
+    }
+}
+
+impl Sink for Watchdog {
+    fn write_frame(&mut self, _grid: &Grid) -> Result<()> {
+        Ok(())
+    }
+
+    fn write_captured(&mut self, _frame: &Frame) -> Result<()> {
+        self.ping()
+    }
+}
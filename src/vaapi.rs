@@ -0,0 +1,170 @@
+//! Decoding MJPEG on the GPU through VA-API, with GStreamer's va plugin.
+//!
+//! Every thread that decodes gets a pipeline of its own the first time it
+//! decodes a frame. When there is no VA-API JPEG decoder, or one stops
+//! working, the thread goes back to mozjpeg for good.
+
+use crate::convert::unpad_rows;
+use eyre::{eyre, Result};
+use gstreamer as gst;
+use gstreamer::prelude::*;
+use gstreamer_app::{AppSink, AppSrc};
+use gstreamer_video::VideoInfo;
+use std::cell::RefCell;
+use tracing::{info, warn};
+
+// the decoder of the va plugin, and the one of the older vaapi plugin
+const DECODERS: [&str; 2] = ["vajpegdec", "vaapijpegdec"];
+// a frame the GPU hasn't decoded by then won't be
+const DECODE_TIMEOUT_MS: u64 = 1000;
+
+/// What frames are decoded to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Output {
+    Rgb,
+    Gray,
+}
+
+impl Output {
+    fn caps(self) -> &'static str {
+        match self {
+            Output::Rgb => "RGB",
+            Output::Gray => "GRAY8",
+        }
+    }
+
+    fn channels(self) -> usize {
+        match self {
+            Output::Rgb => 3,
+            Output::Gray => 1,
+        }
+    }
+}
+
+enum State {
+    Untried,
+    Ready(Decoder),
+    Unavailable,
+}
+
+thread_local! {
+    // one per output
+    static DECODER: RefCell<[State; 2]> = const { RefCell::new([State::Untried, State::Untried]) };
+}
+
+/// Decodes the JPEG in `buf` into `data` and returns its size, or `None`
+/// when it should be decoded in software instead.
+pub(crate) fn decode_into(buf: &[u8], output: Output, data: &mut Vec<u8>) -> Option<(u32, u32)> {
+    DECODER.with(|states| {
+        let state = &mut states.borrow_mut()[output as usize];
+        if let State::Untried = state {
+            *state = match Decoder::new(output) {
+                Ok(decoder) => State::Ready(decoder),
+                Err(e) => {
+                    info!("decoding MJPEG in software: {e}");
+                    State::Unavailable
+                }
+            };
+        }
+
+        let decoder = match state {
+            State::Ready(decoder) => decoder,
+            _ => return None,
+        };
+        match decoder.decode(buf, data) {
+            Ok(size) => Some(size),
+            Err(e) => {
+                warn!("VA-API stopped decoding, going back to software: {e}");
+                *state = State::Unavailable;
+                None
+            }
+        }
+    })
+}
+
+/// A pipeline taking in JPEGs and handing out raw frames.
+struct Decoder {
+    pipeline: gst::Element,
+    src: AppSrc,
+    sink: AppSink,
+    output: Output,
+}
+
+impl Decoder {
+    fn new(output: Output) -> Result<Self> {
+        gst::init()?;
+        let decoder = match DECODERS
+            .iter()
+            .find(|name| gst::ElementFactory::find(name).is_some())
+        {
+            Some(decoder) => decoder,
+            None => {
+                return Err(eyre!(
+                    "no VA-API JPEG decoder, is gstreamer's va plugin installed?"
+                ))
+            }
+        };
+
+        let pipeline = gst::parse_launch(&format!(
+            "appsrc name=src caps=image/jpeg ! jpegparse ! {decoder} ! videoconvert ! \
+             video/x-raw,format={} ! appsink name=sink sync=false",
+            output.caps()
+        ))?;
+        let bin = pipeline.downcast_ref::<gst::Bin>();
+        let (src, sink) = match (
+            bin.and_then(|bin| bin.by_name("src"))
+                .and_then(|src| src.downcast::<AppSrc>().ok()),
+            bin.and_then(|bin| bin.by_name("sink"))
+                .and_then(|sink| sink.downcast::<AppSink>().ok()),
+        ) {
+            (Some(src), Some(sink)) => (src, sink),
+            _ => return Err(eyre!("could not set up {decoder}")),
+        };
+        pipeline.set_state(gst::State::Playing)?;
+        info!("decoding MJPEG with {decoder}");
+
+        Ok(Self {
+            pipeline,
+            src,
+            sink,
+            output,
+        })
+    }
+
+    fn decode(&mut self, buf: &[u8], data: &mut Vec<u8>) -> Result<(u32, u32)> {
+        self.src
+            .push_buffer(gst::Buffer::from_slice(buf.to_vec()))
+            .map_err(|e| eyre!("the decoder took no frame ({e})"))?;
+        let sample = match self
+            .sink
+            .try_pull_sample(gst::ClockTime::from_mseconds(DECODE_TIMEOUT_MS))
+        {
+            Some(sample) => sample,
+            None => return Err(eyre!("the decoder sent nothing back")),
+        };
+
+        let info = match sample.caps() {
+            Some(caps) => VideoInfo::from_caps(caps)?,
+            None => return Err(eyre!("the decoder sent a frame without a format")),
+        };
+        let buffer = match sample.buffer() {
+            Some(buffer) => buffer,
+            None => return Err(eyre!("the decoder sent an empty frame")),
+        };
+        let map = buffer.map_readable()?;
+
+        // rows are padded to what the GPU aligns them to
+        let (width, height) = (info.width(), info.height());
+        let stride = info.stride()[0] as usize;
+        let row = width as usize * self.output.channels();
+        *data = unpad_rows(map.as_slice(), stride, row, height);
+
+        Ok((width, height))
+    }
+}
+
+impl Drop for Decoder {
+    fn drop(&mut self) {
+        let _ = self.pipeline.set_state(gst::State::Null);
+    }
+}
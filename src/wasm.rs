@@ -0,0 +1,124 @@
+//! Rendering in the browser, enabled with the `wasm` feature and built with
+//! `wasm-pack build --target web --features wasm`.
+//!
+//! There is no camera to capture from in WebAssembly, the page gets the
+//! frames from `getUserMedia`, draws them onto a canvas and hands its pixels
+//! to [`Asciicam::render`], whose output goes to an xterm.js terminal. See
+//! `web/` for such a page.
+
+use crate::capture::{Frame, FrameMeta, PixelFormat};
+use crate::render::{
+    parse_charset, render_frame, write_grid_with, ColorMode, RenderMode, RenderOptions, Renderer,
+    DEFAULT_CHARSET,
+};
+use wasm_bindgen::prelude::*;
+
+/// The conversion pipeline with the settings the page picked.
+#[wasm_bindgen]
+pub struct Asciicam {
+    render_mode: RenderMode,
+    charset: Vec<char>,
+    renderer: Box<dyn Renderer>,
+    color_mode: ColorMode,
+    options: RenderOptions,
+    sequence: u64,
+}
+
+#[wasm_bindgen]
+impl Asciicam {
+    /// Renders in color with the default charset, mirrored like the viewer.
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Asciicam {
+        let render_mode = RenderMode::Color;
+        let charset = DEFAULT_CHARSET.to_vec();
+
+        Asciicam {
+            renderer: render_mode.renderer(&charset),
+            render_mode,
+            charset,
+            color_mode: ColorMode::TrueColor,
+            options: RenderOptions::default(),
+            sequence: 0,
+        }
+    }
+
+    /// One of `ascii`, `half-block`, `braille` or `color`.
+    #[wasm_bindgen(js_name = setRenderMode)]
+    pub fn set_render_mode(&mut self, mode: &str) -> Result<(), JsError> {
+        self.render_mode = mode.parse().map_err(to_js_error)?;
+        self.renderer = self.render_mode.renderer(&self.charset);
+        Ok(())
+    }
+
+    /// Characters ordered from dark to bright.
+    #[wasm_bindgen(js_name = setCharset)]
+    pub fn set_charset(&mut self, charset: &str) -> Result<(), JsError> {
+        self.charset = parse_charset(charset).map_err(to_js_error)?;
+        self.renderer = self.render_mode.renderer(&self.charset);
+        Ok(())
+    }
+
+    /// One of `mono`, `256` or `truecolor`.
+    #[wasm_bindgen(js_name = setColorMode)]
+    pub fn set_color_mode(&mut self, mode: &str) -> Result<(), JsError> {
+        self.color_mode = mode.parse().map_err(to_js_error)?;
+        self.options.color = self.color_mode != ColorMode::Mono;
+        Ok(())
+    }
+
+    #[wasm_bindgen(js_name = setMirror)]
+    pub fn set_mirror(&mut self, mirror: bool) {
+        self.options.mirror = mirror;
+    }
+
+    /// Renders `width` x `height` pixels of RGBA, as in the `ImageData` of a
+    /// canvas, into `columns` x `rows` cells. Returns the escape codes that
+    /// draw them over the whole terminal, for `Terminal.write`.
+    pub fn render(
+        &mut self,
+        rgba: &[u8],
+        width: u32,
+        height: u32,
+        columns: u32,
+        rows: u32,
+    ) -> Result<String, JsError> {
+        self.sequence += 1;
+        let frame = Frame {
+            // canvases always have an alpha channel, the camera has no use
+            // for it
+            data: rgba
+                .chunks_exact(4)
+                .flat_map(|pixel| [pixel[0], pixel[1], pixel[2]])
+                .collect(),
+            width,
+            height,
+            pixel_format: PixelFormat::Rgb8,
+            meta: FrameMeta {
+                sequence: self.sequence,
+                ..FrameMeta::default()
+            },
+        };
+
+        let grid = render_frame(&frame, &*self.renderer, columns, rows, &self.options)
+            .map_err(to_js_error)?;
+        // home instead of clearing, which would flicker
+        let mut out = b"\x1b[H".to_vec();
+        write_grid_with(&grid, self.color_mode, &mut out).map_err(to_js_error)?;
+        // the line break after the last row would scroll the terminal
+        if out.ends_with(b"\r\n") {
+            out.truncate(out.len() - 2);
+        }
+
+        Ok(String::from_utf8_lossy(&out).into_owned())
+    }
+}
+
+impl Default for Asciicam {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn to_js_error(e: eyre::Report) -> JsError {
+    JsError::new(&format!("{e:#}"))
+}
@@ -0,0 +1,169 @@
+//! Counts the allocations made by the parts of rendering that run every
+//! frame, which should make none once they have seen a frame of the size.
+//!
+//! Only allocations on the test's own thread are counted, rayon's threads
+//! and the other tests running alongside make their own.
+
+use asciicam::capture::PixelFormat;
+use asciicam::convert::Resizer;
+use asciicam::filter::Filter;
+use asciicam::render::{
+    render_frame_with, AsciiRenderer, ColorMode, ColorRenderer, FitMode, Grid, GridWriter,
+    RenderBuffers, RenderOptions, Renderer, DEFAULT_CHARSET,
+};
+use asciicam::{Frame, FrameMeta};
+use image::{GrayImage, Luma, Rgb, RgbImage};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+struct Counting;
+
+thread_local! {
+    // None while not counting
+    static ALLOCATIONS: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get().map(|n| n + 1)));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+fn allocations(f: impl FnOnce()) -> usize {
+    ALLOCATIONS.with(|count| count.set(Some(0)));
+    f();
+    ALLOCATIONS.with(|count| count.take()).unwrap()
+}
+
+#[test]
+fn resizing_again_doesnt_allocate() {
+    let src = GrayImage::from_fn(640, 480, |x, y| Luma([(x ^ y) as u8]));
+    let mut dst = GrayImage::new(80, 24);
+    let mut resizer = Resizer::default();
+    resizer.resize(&src, &mut dst).unwrap();
+
+    assert_eq!(allocations(|| resizer.resize(&src, &mut dst).unwrap()), 0);
+
+    let src = RgbImage::from_fn(640, 480, |x, y| Rgb([x as u8, y as u8, (x + y) as u8]));
+    let mut dst = RgbImage::new(80, 24);
+    resizer.resize(&src, &mut dst).unwrap();
+
+    assert_eq!(allocations(|| resizer.resize(&src, &mut dst).unwrap()), 0);
+}
+
+#[test]
+fn writing_a_grid_again_doesnt_allocate() {
+    let mut grid = Grid::new(80, 24);
+    for y in 0..24 {
+        for x in 0..80 {
+            let cell = grid.get_mut(x, y);
+            cell.glyph = if (x + y) % 2 == 0 { '#' } else { '.' };
+            cell.fg = Some(Rgb([x as u8 * 3, y as u8 * 10, 128]));
+        }
+    }
+
+    let mut writer = GridWriter::default();
+    let mut out = Vec::new();
+    // the first grid sizes the buffers and starts rayon's threads
+    writer.write(&grid, ColorMode::TrueColor, &mut out).unwrap();
+    out.clear();
+
+    let count = allocations(|| writer.write(&grid, ColorMode::TrueColor, &mut out).unwrap());
+    assert_eq!(count, 0);
+}
+
+/// A `width` x `height` frame of `pixel_format` with something in it.
+fn frame(pixel_format: PixelFormat, channels: usize) -> Frame {
+    let (width, height) = (640, 480);
+    Frame {
+        data: (0..width * height * channels)
+            .map(|i| (i * 7 % 251) as u8)
+            .collect(),
+        width: width as u32,
+        height: height as u32,
+        pixel_format,
+        meta: FrameMeta::default(),
+    }
+}
+
+/// Renders and writes `frame` like the viewer does, handing the grid back.
+fn cycle(
+    frame: &Frame,
+    renderer: &dyn Renderer,
+    options: &RenderOptions,
+    buffers: &mut RenderBuffers,
+    writer: &mut GridWriter,
+    out: &mut Vec<u8>,
+) {
+    let grid = render_frame_with(frame, renderer, 80, 24, options, buffers).unwrap();
+    out.clear();
+    writer.write(&grid, ColorMode::TrueColor, out).unwrap();
+    buffers.recycle(grid);
+}
+
+#[test]
+fn rendering_and_writing_a_frame_again_doesnt_allocate() {
+    let gray = frame(PixelFormat::Gray8, 1);
+    let rgb = frame(PixelFormat::Rgb8, 3);
+    let ascii = AsciiRenderer::new(DEFAULT_CHARSET);
+    let color = ColorRenderer::new(DEFAULT_CHARSET);
+    // everything that crops: the stabilizer, the zoom and the layout
+    let cropped = RenderOptions {
+        fit: FitMode::Fill,
+        zoom: 2.0,
+        stabilize: true,
+        mirror: true,
+        filters: vec![Filter::Invert],
+        ..RenderOptions::default()
+    };
+    let colored = RenderOptions {
+        fit: FitMode::Fit,
+        color: true,
+        area: true,
+        ..RenderOptions::default()
+    };
+
+    let runs: [(&Frame, &dyn Renderer, &RenderOptions); 4] = [
+        (&gray, &ascii, &cropped),
+        (&rgb, &ascii, &cropped),
+        (&gray, &color, &colored),
+        (&rgb, &color, &colored),
+    ];
+    for (frame, renderer, options) in runs {
+        let mut buffers = RenderBuffers::default();
+        let mut writer = GridWriter::default();
+        let mut out = Vec::new();
+        // the grid rendered into and the one handed back take two frames
+        // to be sized
+        for _ in 0..2 {
+            cycle(
+                frame,
+                renderer,
+                options,
+                &mut buffers,
+                &mut writer,
+                &mut out,
+            );
+        }
+
+        let count = allocations(|| {
+            cycle(
+                frame,
+                renderer,
+                options,
+                &mut buffers,
+                &mut writer,
+                &mut out,
+            )
+        });
+        assert_eq!(count, 0);
+    }
+}
@@ -0,0 +1,59 @@
+//! The characters pixels map to are the same however a row is mapped, on
+//! the cpu this runs on, and hold still on the edge between two of them.
+
+use asciicam::render::{parse_charset, Cell, CHARSETS, DEFAULT_CHARSET};
+use asciicam::CharLut;
+
+#[test]
+fn mapping_a_row_matches_the_scalar_loop_for_every_brightness() {
+    let pixels: Vec<u8> = (0..=255).collect();
+    let mut charsets = vec![DEFAULT_CHARSET.to_vec()];
+    for charset in CHARSETS {
+        charsets.push(parse_charset(charset).unwrap());
+    }
+
+    for charset in &charsets {
+        let chars = CharLut::new(charset);
+        // rows that end partway through eight pixels and start at every
+        // brightness, so every value goes through both paths
+        for start in 0..8 {
+            for len in [0, 1, 7, 8, 9, 31, 256 - start] {
+                let pixels = &pixels[start..start + len];
+                let mut fast = vec![Cell::default(); len];
+                let mut scalar = vec![Cell::default(); len];
+                chars.map_row(pixels, &mut fast);
+                chars.map_row_scalar(pixels, &mut scalar);
+
+                assert_eq!(fast, scalar);
+                for (cell, &pixel) in fast.iter().zip(pixels) {
+                    assert_eq!(cell.glyph, chars.get(pixel));
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn a_cell_keeps_its_char_until_it_leaves_it_by_more_than_the_margin() {
+    // ' ' up to 127, '#' from 128
+    let chars = CharLut::new(&parse_charset(" #").unwrap());
+    let mut shown = Vec::new();
+    let mut glyph = |v: u8| {
+        let mut luma = [v];
+        chars.hold(&mut luma, &mut shown, 6);
+        chars.get(luma[0])
+    };
+
+    assert_eq!(glyph(120), ' ');
+    // across the edge, but not by more than the margin
+    assert_eq!(glyph(130), ' ');
+    assert_eq!(glyph(133), ' ');
+    assert_eq!(glyph(125), ' ');
+    assert_eq!(glyph(134), '#');
+    // and back the other way
+    assert_eq!(glyph(127), '#');
+    assert_eq!(glyph(122), '#');
+    assert_eq!(glyph(130), '#');
+    assert_eq!(glyph(121), ' ');
+    assert_eq!(glyph(128), ' ');
+}
@@ -0,0 +1,17 @@
+//! Cameras without v4l controls, like the nokhwa ones on windows and macos or
+//! termux on android, have to open without them.
+
+use asciicam::capture::Controls;
+
+// no system has this many cameras, and off linux there is no v4l at all
+const MISSING: usize = 999;
+
+#[test]
+fn a_camera_without_controls_opens_without_them() {
+    assert!(Controls::open_optional(MISSING, false).unwrap().is_none());
+}
+
+#[test]
+fn asking_for_controls_a_camera_doesnt_have_fails() {
+    assert!(Controls::open_optional(MISSING, true).is_err());
+}
@@ -0,0 +1,56 @@
+//! Raw Bayer frames turn into one color per 2x2 cell, whatever the padding
+//! at the end of their rows.
+
+use asciicam::convert::{black_levels, demosaic, BayerPattern};
+
+// a 4x4 RGGB mosaic with two bytes of padding after every row
+const STRIDE: usize = 6;
+#[rustfmt::skip]
+const RAW: [u8; 24] = [
+    200, 100,  10,  30, 0xee, 0xee,
+     60,  20,  50, 250, 0xee, 0xee,
+     90,  90,   0, 255, 0xee, 0xee,
+     90,  90, 255,   0, 0xee, 0xee,
+];
+
+// every pixel of a 4x4 frame in the color of its 2x2 cell
+fn cells(colors: [[u8; 3]; 4]) -> Vec<u8> {
+    let mut rgb = Vec::new();
+    for y in 0..4 {
+        for x in 0..4 {
+            rgb.extend_from_slice(&colors[y / 2 * 2 + x / 2]);
+        }
+    }
+    rgb
+}
+
+fn demosaiced(raw: &[u8], pattern: BayerPattern) -> eyre::Result<Vec<u8>> {
+    // left over from a bigger frame, which mustn't show through
+    let mut out = vec![0xaa; 100];
+    demosaic(raw, 4, 4, STRIDE, pattern, &black_levels(0), &mut out)?;
+    Ok(out)
+}
+
+#[test]
+fn every_cell_gets_its_red_blue_and_average_green() {
+    let rggb = cells([[200, 80, 20], [10, 40, 250], [90, 90, 90], [0, 255, 0]]);
+    assert_eq!(demosaiced(&RAW, BayerPattern::Rggb).unwrap(), rggb);
+
+    // the same mosaic read the other way round swaps red and blue
+    let bggr = cells([[20, 80, 200], [250, 40, 10], [90, 90, 90], [0, 255, 0]]);
+    assert_eq!(demosaiced(&RAW, BayerPattern::Bggr).unwrap(), bggr);
+}
+
+#[test]
+fn the_last_row_doesnt_need_its_padding() {
+    assert!(demosaiced(&RAW[..3 * STRIDE + 4], BayerPattern::Rggb).is_ok());
+    assert!(demosaiced(&RAW[..3 * STRIDE + 3], BayerPattern::Rggb).is_err());
+}
+
+#[test]
+fn the_black_level_is_taken_off_and_the_rest_stretched() {
+    let levels = black_levels(16);
+    assert!(levels[..=16].iter().all(|&v| v == 0));
+    assert_eq!(levels[255], 255);
+    assert!(levels.windows(2).all(|pair| pair[0] <= pair[1]));
+}
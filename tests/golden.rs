@@ -0,0 +1,149 @@
+//! Renders synthetic frames and compares them against the grids checked in
+//! under `tests/golden`.
+//!
+//! Run with `ASCIICAM_BLESS=1` to rewrite the goldens after an intended
+//! change to the output, and review the diff before committing it.
+
+use asciicam::capture::SyntheticSource;
+use asciicam::filter::Filter;
+use asciicam::render::{
+    parse_charset, render_frame, AsciiRenderer, BrailleRenderer, HalfBlockRenderer, RenderOptions,
+    Renderer, DEFAULT_CHARSET,
+};
+use std::fs;
+use std::path::PathBuf;
+
+struct Case {
+    name: &'static str,
+    seed: u64,
+    frame: u64,
+    columns: u32,
+    rows: u32,
+    renderer: Box<dyn Renderer>,
+    options: RenderOptions,
+}
+
+fn options() -> RenderOptions {
+    RenderOptions {
+        mirror: false,
+        color: false,
+        ..RenderOptions::default()
+    }
+}
+
+fn check(case: Case) {
+    let (cell_width, cell_height) = case.renderer.cell_size();
+    let source = SyntheticSource::new(
+        case.columns * cell_width,
+        case.rows * cell_height,
+        case.seed,
+    );
+    let frame = source.frame_at(case.frame);
+
+    let grid = render_frame(
+        &frame,
+        &*case.renderer,
+        case.columns,
+        case.rows,
+        &case.options,
+    )
+    .unwrap();
+    let actual = grid.to_text();
+
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(format!("{}.txt", case.name));
+
+    if std::env::var_os("ASCIICAM_BLESS").is_some() {
+        fs::write(&path, &actual).unwrap();
+        return;
+    }
+
+    let expected = fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("Could not read {}: {e}", path.display()));
+    assert!(
+        actual == expected,
+        "{} doesn't match its golden\n--- expected\n{expected}--- actual\n{actual}",
+        case.name
+    );
+}
+
+#[test]
+fn ascii_default_charset() {
+    check(Case {
+        name: "ascii_default_charset",
+        seed: 1,
+        frame: 0,
+        columns: 32,
+        rows: 16,
+        renderer: Box::new(AsciiRenderer::new(DEFAULT_CHARSET)),
+        options: options(),
+    });
+}
+
+#[test]
+fn ascii_mirrored() {
+    check(Case {
+        name: "ascii_mirrored",
+        seed: 1,
+        frame: 5,
+        columns: 32,
+        rows: 16,
+        renderer: Box::new(AsciiRenderer::new(&parse_charset(" .:-=+*#%@").unwrap())),
+        options: RenderOptions {
+            mirror: true,
+            ..options()
+        },
+    });
+}
+
+#[test]
+fn ascii_inverted() {
+    check(Case {
+        name: "ascii_inverted",
+        seed: 7,
+        frame: 2,
+        columns: 32,
+        rows: 16,
+        renderer: Box::new(AsciiRenderer::new(DEFAULT_CHARSET)),
+        options: RenderOptions {
+            filters: vec![Filter::Invert],
+            ..options()
+        },
+    });
+}
+
+#[test]
+fn braille() {
+    check(Case {
+        name: "braille",
+        seed: 3,
+        frame: 0,
+        columns: 16,
+        rows: 8,
+        renderer: Box::new(BrailleRenderer::default()),
+        options: options(),
+    });
+}
+
+#[test]
+fn half_block() {
+    check(Case {
+        name: "half_block",
+        seed: 4,
+        frame: 1,
+        columns: 32,
+        rows: 8,
+        renderer: Box::new(HalfBlockRenderer),
+        options: options(),
+    });
+}
+
+#[test]
+fn synthetic_frames_are_deterministic() {
+    let a = SyntheticSource::new(64, 48, 42);
+    let b = SyntheticSource::new(64, 48, 42);
+
+    assert_eq!(a.frame_at(9).data, b.frame_at(9).data);
+    assert_ne!(a.frame_at(9).data, a.frame_at(10).data);
+}
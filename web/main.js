@@ -0,0 +1,60 @@
+// asciicam in the browser: getUserMedia frames rendered by the wasm build
+// into an xterm.js terminal. build the package into ../pkg first with
+//
+//   wasm-pack build --target web --features wasm
+//
+// and serve the repository root, getUserMedia needs localhost or https
+import init, { Asciicam } from "../pkg/asciicam.js";
+
+// frames are rendered this small at most, the terminal has far fewer cells
+const CAPTURE_WIDTH = 320;
+
+await init();
+const asciicam = new Asciicam();
+
+const terminal = new Terminal({ cursorBlink: false, disableStdin: true });
+const fit = new FitAddon.FitAddon();
+terminal.loadAddon(fit);
+terminal.open(document.getElementById("terminal"));
+fit.fit();
+window.addEventListener("resize", () => fit.fit());
+// hide the cursor
+terminal.write("\x1b[?25l");
+
+const video = document.getElementById("video");
+const canvas = document.getElementById("canvas");
+const context = canvas.getContext("2d", { willReadFrequently: true });
+
+try {
+  video.srcObject = await navigator.mediaDevices.getUserMedia({ video: true });
+  await video.play();
+} catch (e) {
+  terminal.write(`Could not open the camera (${e.message})\r\n`);
+  throw e;
+}
+
+// m cycles render modes like the viewer does
+const modes = ["ascii", "half-block", "braille", "color"];
+let mode = modes.indexOf("color");
+window.addEventListener("keydown", (event) => {
+  if (event.key === "m") {
+    mode = (mode + 1) % modes.length;
+    asciicam.setRenderMode(modes[mode]);
+  }
+});
+
+function frame() {
+  if (video.videoWidth > 0) {
+    const scale = Math.min(1, CAPTURE_WIDTH / video.videoWidth);
+    canvas.width = Math.round(video.videoWidth * scale);
+    canvas.height = Math.round(video.videoHeight * scale);
+    context.drawImage(video, 0, 0, canvas.width, canvas.height);
+
+    const pixels = context.getImageData(0, 0, canvas.width, canvas.height);
+    terminal.write(
+      asciicam.render(pixels.data, canvas.width, canvas.height, terminal.cols, terminal.rows),
+    );
+  }
+  requestAnimationFrame(frame);
+}
+requestAnimationFrame(frame);